@@ -4,7 +4,7 @@ mod wire {
     use smoltcp::phy::ChecksumCapabilities;
     use smoltcp::wire::{IpAddress, IpProtocol};
     #[cfg(feature = "proto-ipv4")]
-    use smoltcp::wire::{Ipv4Address, Ipv4Packet, Ipv4Repr};
+    use smoltcp::wire::{Ipv4Address, Ipv4Options, Ipv4Packet, Ipv4Repr};
     #[cfg(feature = "proto-ipv6")]
     use smoltcp::wire::{Ipv6Address, Ipv6Packet, Ipv6Repr};
     use smoltcp::wire::{TcpControl, TcpPacket, TcpRepr, TcpSeqNumber};
@@ -88,6 +88,9 @@ mod wire {
             next_header: IpProtocol::Tcp,
             payload_len: 100,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
         };
         let mut bytes = vec![0xa5; repr.buffer_len()];
 
@@ -106,6 +109,8 @@ mod wire {
             next_header: IpProtocol::Tcp,
             payload_len: 100,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
         };
         let mut bytes = vec![0xa5; repr.buffer_len()];
 
@@ -8,9 +8,18 @@ static CONFIGS: &[(&str, usize)] = &[
     // Generated by gen_config.py. DO NOT EDIT.
     ("IFACE_MAX_ADDR_COUNT", 2),
     ("IFACE_MAX_MULTICAST_GROUP_COUNT", 4),
+    ("IFACE_MAX_MULTICAST_SOURCE_COUNT", 4),
+    ("IFACE_MAX_MULTICAST_LISTENER_COUNT", 8),
     ("IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT", 4),
     ("IFACE_NEIGHBOR_CACHE_COUNT", 4),
+    ("IFACE_DESTINATION_CACHE_COUNT", 4),
     ("IFACE_MAX_ROUTE_COUNT", 2),
+    ("IFACE_MAX_ROUTE_RULE_COUNT", 2),
+    ("IFACE_MAX_ADDRESS_POLICY_COUNT", 9),
+    ("IFACE_EGRESS_CLASS_COUNT", 4),
+    ("IFACE_LOOPBACK_BUFFER_SIZE", 1536),
+    ("ROUTER_MAX_IFACE_COUNT", 2),
+    ("ROUTER_MAX_ROUTE_COUNT", 4),
     ("FRAGMENTATION_BUFFER_SIZE", 1500),
     ("ASSEMBLER_MAX_SEGMENT_COUNT", 4),
     ("REASSEMBLY_BUFFER_SIZE", 1500),
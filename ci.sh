@@ -19,10 +19,13 @@ FEATURES_TEST=(
     "std,medium-ethernet,phy-tuntap_interface,proto-ipv6,socket-udp"
     "std,medium-ethernet,proto-ipv4,proto-ipv4-fragmentation,socket-raw,socket-dns"
     "std,medium-ethernet,proto-ipv4,proto-igmp,socket-raw,socket-dns"
+    "std,medium-ethernet,proto-ipv4,proto-ipv6,proto-multicast-querier,socket-udp"
     "std,medium-ethernet,proto-ipv4,socket-udp,socket-tcp,socket-dns"
     "std,medium-ethernet,proto-ipv4,proto-dhcpv4,socket-udp"
+    "std,medium-ethernet,proto-ipv4,proto-ipv4-link-local,socket-udp"
     "std,medium-ethernet,medium-ip,medium-ieee802154,proto-ipv6,socket-udp,socket-dns"
     "std,medium-ethernet,proto-ipv6,socket-tcp"
+    "std,medium-ip,proto-ipv6,proto-ipv6-temporary-addresses,socket-udp"
     "std,medium-ethernet,medium-ip,proto-ipv4,socket-icmp,socket-tcp"
     "std,medium-ip,proto-ipv6,socket-icmp,socket-tcp"
     "std,medium-ieee802154,proto-sixlowpan,socket-udp"
@@ -32,6 +35,8 @@ FEATURES_TEST=(
     "std,medium-ethernet,medium-ip,medium-ieee802154,proto-ipv4,proto-ipv6,socket-raw,socket-udp,socket-tcp,socket-icmp,socket-dns,async"
     "std,medium-ieee802154,medium-ip,proto-ipv4,socket-raw"
     "std,medium-ethernet,proto-ipv4,proto-ipsec,socket-raw"
+    "std,medium-ethernet,proto-ipv6,socket-ethernet"
+    "std,medium-ethernet,proto-ipv4,socket-udp,packetmeta-mark"
 )
 
 FEATURES_TEST_NIGHTLY=(
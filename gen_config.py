@@ -29,9 +29,18 @@ def feature(name, default, min, max, pow2=None):
 
 feature("iface_max_addr_count", default=2, min=1, max=8)
 feature("iface_max_multicast_group_count", default=4, min=1, max=1024, pow2=8)
+feature("iface_max_multicast_source_count", default=4, min=1, max=1024, pow2=8)
+feature("iface_max_multicast_listener_count", default=8, min=1, max=1024, pow2=8)
 feature("iface_max_sixlowpan_address_context_count", default=4, min=1, max=1024, pow2=8)
 feature("iface_neighbor_cache_count", default=4, min=1, max=1024, pow2=8)
+feature("iface_destination_cache_count", default=4, min=1, max=1024, pow2=8)
 feature("iface_max_route_count", default=2, min=1, max=1024, pow2=8)
+feature("iface_max_route_rule_count", default=2, min=1, max=1024, pow2=8)
+feature("iface_max_address_policy_count", default=9, min=1, max=1024, pow2=8)
+feature("iface_egress_class_count", default=4, min=1, max=8)
+feature("iface_loopback_buffer_size", default=1536, min=256, max=65536, pow2=True)
+feature("router_max_iface_count", default=2, min=1, max=8)
+feature("router_max_route_count", default=4, min=1, max=1024, pow2=8)
 feature("fragmentation_buffer_size", default=1500, min=256, max=65536, pow2=True)
 feature("assembler_max_segment_count", default=4, min=1, max=32, pow2=4)
 feature("reassembly_buffer_size", default=1500, min=256, max=65536, pow2=True)
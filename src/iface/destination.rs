@@ -0,0 +1,132 @@
+// Heads up! Before working on this file you should read, at least, the parts of
+// RFC 4861 that discuss the destination cache and redirects, and RFC 1122 § 3.2.2.2.
+
+use heapless::LinearMap;
+
+use crate::config::IFACE_DESTINATION_CACHE_COUNT;
+use crate::time::{Duration, Instant};
+use crate::wire::IpAddress;
+
+/// A cached destination.
+///
+/// A destination mapping translates from a destination address to the next hop
+/// that packets to it are sent to, overriding the route table, and contains the
+/// timestamp past which the mapping should be discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct Destination {
+    pub(crate) next_hop: IpAddress,
+    pub(crate) expires_at: Instant,
+}
+
+/// A destination cache backed by a map, holding the host routes learnt from redirects.
+#[derive(Debug)]
+pub(crate) struct Cache {
+    storage: LinearMap<IpAddress, Destination, IFACE_DESTINATION_CACHE_COUNT>,
+}
+
+impl Cache {
+    /// Destination entry lifetime, in milliseconds.
+    pub(crate) const ENTRY_LIFETIME: Duration = Duration::from_millis(600_000);
+
+    /// Create a cache.
+    pub(crate) fn new() -> Self {
+        Self {
+            storage: LinearMap::new(),
+        }
+    }
+
+    pub(crate) fn fill(&mut self, dst_addr: IpAddress, next_hop: IpAddress, timestamp: Instant) {
+        debug_assert!(dst_addr.is_unicast());
+        debug_assert!(next_hop.is_unicast());
+
+        let destination = Destination {
+            next_hop,
+            expires_at: timestamp + Self::ENTRY_LIFETIME,
+        };
+        if let Err((dst_addr, destination)) = self.storage.insert(dst_addr, destination) {
+            // The cache is full, evict the entry that expires first.
+            let old_dst_addr = *self
+                .storage
+                .iter()
+                .min_by_key(|(_, destination)| destination.expires_at)
+                .expect("empty destination cache storage")
+                .0;
+            self.storage.remove(&old_dst_addr);
+            net_trace!("evicted destination {}", old_dst_addr);
+
+            // We've just made room for the new entry.
+            let _ = self.storage.insert(dst_addr, destination);
+        }
+        net_trace!("filled destination {} => {}", dst_addr, next_hop);
+    }
+
+    pub(crate) fn lookup(&self, dst_addr: &IpAddress, timestamp: Instant) -> Option<Destination> {
+        self.storage
+            .get(dst_addr)
+            .filter(|destination| timestamp < destination.expires_at)
+            .copied()
+    }
+
+    pub(crate) fn flush(&mut self) {
+        self.storage.clear()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(all(feature = "proto-ipv4", not(feature = "proto-ipv6")))]
+    use crate::wire::ipv4::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2, MOCK_IP_ADDR_3, MOCK_IP_ADDR_4};
+    #[cfg(feature = "proto-ipv6")]
+    use crate::wire::ipv6::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2, MOCK_IP_ADDR_3, MOCK_IP_ADDR_4};
+
+    #[test]
+    fn test_fill() {
+        let mut cache = Cache::new();
+        let next_hop = MOCK_IP_ADDR_4.into();
+
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), Instant::ZERO), None);
+
+        cache.fill(MOCK_IP_ADDR_1.into(), next_hop, Instant::ZERO);
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_1.into(), Instant::ZERO),
+            Some(Destination {
+                next_hop,
+                expires_at: Instant::ZERO + Cache::ENTRY_LIFETIME,
+            })
+        );
+        assert_eq!(
+            cache.lookup(
+                &MOCK_IP_ADDR_1.into(),
+                Instant::ZERO + Cache::ENTRY_LIFETIME
+            ),
+            None
+        );
+
+        cache.flush();
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), Instant::ZERO), None);
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut cache = Cache::new();
+        let next_hop = MOCK_IP_ADDR_4.into();
+
+        cache.fill(MOCK_IP_ADDR_1.into(), next_hop, Instant::from_millis(100));
+        cache.fill(MOCK_IP_ADDR_2.into(), next_hop, Instant::from_millis(50));
+        cache.fill(MOCK_IP_ADDR_3.into(), next_hop, Instant::from_millis(200));
+        assert!(cache
+            .lookup(&MOCK_IP_ADDR_2.into(), Instant::from_millis(1000))
+            .is_some());
+
+        // The entry expiring first makes room for the new one.
+        cache.fill(MOCK_IP_ADDR_4.into(), next_hop, Instant::from_millis(300));
+        assert!(cache
+            .lookup(&MOCK_IP_ADDR_2.into(), Instant::from_millis(1000))
+            .is_none());
+        assert!(cache
+            .lookup(&MOCK_IP_ADDR_4.into(), Instant::from_millis(1000))
+            .is_some());
+    }
+}
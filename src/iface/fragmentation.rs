@@ -180,6 +180,11 @@ impl<K> PacketAssembler<K> {
         self.total_size == Some(self.assembler.peek_front())
     }
 
+    /// Returns `true` when any of the octets in the given range have already been received.
+    pub(crate) fn overlaps(&self, offset: usize, len: usize) -> bool {
+        self.assembler.overlaps(offset, len)
+    }
+
     /// Returns `true` when the packet assembler is free to use.
     fn is_free(&self) -> bool {
         self.key.is_none()
@@ -248,6 +253,8 @@ pub(crate) const MAX_DECOMPRESSED_LEN: usize = 1500;
 pub(crate) enum FragKey {
     #[cfg(feature = "proto-ipv4-fragmentation")]
     Ipv4(Ipv4FragKey),
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    Ipv6(Ipv6FragKey),
     #[cfg(feature = "proto-sixlowpan-fragmentation")]
     Sixlowpan(SixlowpanFragKey),
 }
@@ -284,6 +291,8 @@ pub(crate) struct Fragmenter {
 
     #[cfg(feature = "proto-ipv4-fragmentation")]
     pub ipv4: Ipv4Fragmenter,
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    pub ipv6: Ipv6Fragmenter,
     #[cfg(feature = "proto-sixlowpan-fragmentation")]
     pub sixlowpan: SixlowpanFragmenter,
 }
@@ -301,6 +310,20 @@ pub(crate) struct Ipv4Fragmenter {
     pub ident: u16,
 }
 
+#[cfg(feature = "proto-ipv6-fragmentation")]
+pub(crate) struct Ipv6Fragmenter {
+    /// The length of the Unfragmentable Part, i.e. the IPv6 header and the extension headers
+    /// that have to be repeated in every fragment.
+    pub unfragmentable_len: usize,
+    /// The Next Header value of the first header of the Fragmentable Part.
+    pub next_header: IpProtocol,
+    /// The destination hardware address.
+    #[cfg(feature = "medium-ethernet")]
+    pub dst_hardware_addr: EthernetAddress,
+    /// The identifier of the stream.
+    pub ident: u32,
+}
+
 #[cfg(feature = "proto-sixlowpan-fragmentation")]
 pub(crate) struct SixlowpanFragmenter {
     /// The datagram size that is used for the fragmentation headers.
@@ -334,6 +357,9 @@ impl Fragmenter {
                     next_header: IpProtocol::Unknown(0),
                     payload_len: 0,
                     hop_limit: 0,
+                    dscp: 0,
+                    ecn: 0,
+                    options: Ipv4Options::EMPTY,
                 },
                 #[cfg(feature = "medium-ethernet")]
                 dst_hardware_addr: EthernetAddress::default(),
@@ -341,6 +367,15 @@ impl Fragmenter {
                 ident: 0,
             },
 
+            #[cfg(feature = "proto-ipv6-fragmentation")]
+            ipv6: Ipv6Fragmenter {
+                unfragmentable_len: 0,
+                next_header: IpProtocol::Unknown(0),
+                #[cfg(feature = "medium-ethernet")]
+                dst_hardware_addr: EthernetAddress::default(),
+                ident: 0,
+            },
+
             #[cfg(feature = "proto-sixlowpan-fragmentation")]
             sixlowpan: SixlowpanFragmenter {
                 datagram_size: 0,
@@ -378,6 +413,9 @@ impl Fragmenter {
                 next_header: IpProtocol::Unknown(0),
                 payload_len: 0,
                 hop_limit: 0,
+                dscp: 0,
+                ecn: 0,
+                options: Ipv4Options::EMPTY,
             };
             #[cfg(feature = "medium-ethernet")]
             {
@@ -385,6 +423,16 @@ impl Fragmenter {
             }
         }
 
+        #[cfg(feature = "proto-ipv6-fragmentation")]
+        {
+            self.ipv6.unfragmentable_len = 0;
+            self.ipv6.next_header = IpProtocol::Unknown(0);
+            #[cfg(feature = "medium-ethernet")]
+            {
+                self.ipv6.dst_hardware_addr = EthernetAddress::default();
+            }
+        }
+
         #[cfg(feature = "proto-sixlowpan-fragmentation")]
         {
             self.sixlowpan.datagram_size = 0;
@@ -0,0 +1,246 @@
+use super::*;
+
+/// Delay between two rounds of announcements, see `RETRANS_TIMER` in RFC 4861 § 10.
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default amount of announcements of an address, see `ANNOUNCE_NUM` in RFC 5227 § 1.1.
+const ANNOUNCE_NUM: u8 = 2;
+
+/// Announcements of our addresses to the link, using gratuitous ARP requests for IPv4
+/// and unsolicited Neighbor Advertisements for IPv6.
+#[derive(Debug)]
+pub(crate) struct Announcer {
+    /// How many times each address is announced.
+    count: u8,
+    /// Addresses that are waiting to be announced.
+    addrs: Vec<IpAddress, IFACE_MAX_ADDR_COUNT>,
+    /// Rounds of announcements left for `addrs`.
+    rounds_left: u8,
+    /// Index in `addrs` of the next address to announce in this round.
+    next_index: usize,
+    /// When to send the next announcement.
+    timeout: Instant,
+}
+
+impl Announcer {
+    pub(crate) const fn new() -> Self {
+        Self {
+            count: ANNOUNCE_NUM,
+            addrs: Vec::new(),
+            rounds_left: 0,
+            next_index: 0,
+            timeout: Instant::ZERO,
+        }
+    }
+
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        if self.rounds_left > 0 {
+            Some(self.timeout)
+        } else {
+            None
+        }
+    }
+}
+
+impl Interface {
+    /// Get how many times an address is announced to the link.
+    ///
+    /// See [`set_announce_count`](Self::set_announce_count) for details.
+    pub fn announce_count(&self) -> u8 {
+        self.inner.announcer.count
+    }
+
+    /// Set how many times an address is announced to the link.
+    ///
+    /// When addresses are added with [`update_ip_addrs`](Self::update_ip_addrs), or the hardware
+    /// address is changed with [`set_hardware_addr`](Self::set_hardware_addr), the interface
+    /// announces the affected addresses so that peers update their neighbor caches right away
+    /// instead of waiting for stale entries to expire. IPv4 addresses are announced with
+    /// gratuitous ARP requests, IPv6 addresses with unsolicited Neighbor Advertisements that
+    /// have the override flag set. Repeated announcements are one second apart.
+    ///
+    /// The default is 2, as in RFC 5227. Setting the count to 0 disables announcements.
+    pub fn set_announce_count(&mut self, count: u8) {
+        self.inner.announcer.count = count;
+        if count == 0 {
+            self.inner.announcer.rounds_left = 0;
+        }
+    }
+
+    /// Announce all addresses of the interface to the link.
+    ///
+    /// This can be used after a failover, when the addresses of this interface were
+    /// previously owned by another host. The announcements are sent by [`poll`](Self::poll),
+    /// as many times as configured with [`set_announce_count`](Self::set_announce_count).
+    pub fn announce_ip_addrs(&mut self) {
+        let addrs: Vec<IpAddress, IFACE_MAX_ADDR_COUNT> = self
+            .inner
+            .ip_addrs
+            .iter()
+            .map(|cidr| cidr.address())
+            .collect();
+        self.inner.schedule_announcements(&addrs);
+    }
+
+    /// Send the announcements that are due.
+    pub(crate) fn announce_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let announcer = &mut self.inner.announcer;
+        if announcer.rounds_left == 0 || self.inner.now < announcer.timeout {
+            return false;
+        }
+
+        let addr = match announcer.addrs.get(announcer.next_index) {
+            Some(addr) => *addr,
+            None => {
+                // This round is complete, schedule the next one.
+                announcer.rounds_left -= 1;
+                announcer.next_index = 0;
+                announcer.timeout = self.inner.now + ANNOUNCE_INTERVAL;
+                if announcer.rounds_left == 0 {
+                    announcer.addrs.clear();
+                }
+                return false;
+            }
+        };
+
+        // The address may have been removed since the announcement was scheduled.
+        if self.inner.has_ip_addr(addr) {
+            let tx_token = match device.transmit(self.inner.now) {
+                Some(tx_token) => tx_token,
+                None => return false,
+            };
+
+            // NOTE(unwrap): announcements are sent to broadcast or multicast addresses,
+            // which never require neighbor discovery.
+            match addr {
+                #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+                IpAddress::Ipv4(addr) => {
+                    net_debug!("announcing {} with a gratuitous ARP request", addr);
+                    let arp_repr = self.inner.gratuitous_arp(addr);
+                    self.inner
+                        .dispatch(
+                            tx_token,
+                            EthernetPacket::Arp(arp_repr),
+                            &mut self.fragmenter,
+                        )
+                        .unwrap();
+                }
+                #[cfg(feature = "proto-ipv6")]
+                IpAddress::Ipv6(addr) => {
+                    net_debug!("announcing {} with an unsolicited NA", addr);
+                    let packet = self.inner.unsolicited_neighbor_advert(addr);
+                    self.inner
+                        .dispatch_ip(
+                            tx_token,
+                            PacketMeta::default(),
+                            packet,
+                            &mut self.fragmenter,
+                        )
+                        .unwrap();
+                }
+                #[allow(unreachable_patterns)]
+                _ => (),
+            }
+        }
+
+        self.inner.announcer.next_index += 1;
+        true
+    }
+}
+
+impl InterfaceInner {
+    /// Schedule announcements for the given addresses, skipping those that
+    /// can not be announced on this medium.
+    pub(super) fn schedule_announcements(&mut self, addrs: &[IpAddress]) {
+        if self.announcer.count == 0 {
+            return;
+        }
+
+        let mut scheduled = false;
+        for addr in addrs {
+            if !self.can_announce(addr) {
+                continue;
+            }
+            if !self.announcer.addrs.contains(addr) && self.announcer.addrs.push(*addr).is_err() {
+                net_debug!("too many addresses to announce, dropping {}", addr);
+                continue;
+            }
+            scheduled = true;
+        }
+
+        if scheduled {
+            self.announcer.rounds_left = self.announcer.count;
+            self.announcer.next_index = 0;
+            self.announcer.timeout = self.now;
+        }
+    }
+
+    /// Cancel the announcements for addresses that were removed from the interface.
+    pub(super) fn cancel_stale_announcements(&mut self) {
+        let ip_addrs = &self.ip_addrs;
+        let announcer = &mut self.announcer;
+
+        announcer
+            .addrs
+            .retain(|addr| ip_addrs.iter().any(|cidr| cidr.address() == *addr));
+        announcer.next_index = announcer.next_index.min(announcer.addrs.len());
+
+        if announcer.addrs.is_empty() {
+            announcer.rounds_left = 0;
+        }
+    }
+
+    fn can_announce(&self, addr: &IpAddress) -> bool {
+        if !addr.is_unicast() || addr.is_loopback() {
+            return false;
+        }
+
+        match (addr, self.caps.medium) {
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+            (IpAddress::Ipv4(_), Medium::Ethernet) => true,
+            #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv6"))]
+            (IpAddress::Ipv6(_), Medium::Ethernet) => true,
+            #[cfg(all(feature = "medium-ieee802154", feature = "proto-ipv6"))]
+            (IpAddress::Ipv6(_), Medium::Ieee802154) => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// A gratuitous ARP request announcing `addr`, see RFC 5227 § 2.3.
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    pub(super) fn gratuitous_arp(&self, addr: Ipv4Address) -> ArpRepr {
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: self.hardware_addr.ethernet_or_panic(),
+            source_protocol_addr: addr,
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: addr,
+        }
+    }
+
+    /// An unsolicited Neighbor Advertisement announcing `addr`, see RFC 4861 § 7.2.6.
+    #[cfg(feature = "proto-ipv6")]
+    fn unsolicited_neighbor_advert<'a>(&self, addr: Ipv6Address) -> Packet<'a> {
+        let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+            flags: NdiscNeighborFlags::OVERRIDE,
+            target_addr: addr,
+            lladdr: Some(self.hardware_addr.into()),
+        });
+        Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: addr,
+                dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: advert.buffer_len(),
+                hop_limit: 0xff,
+                dscp: 0,
+                ecn: 0,
+            },
+            IpPayload::Icmpv6(advert),
+        )
+    }
+}
@@ -18,6 +18,17 @@ impl InterfaceInner {
             return None;
         }
 
+        // Pass a copy of the frame to the Ethernet sockets, the stack still handles it.
+        #[cfg(feature = "socket-ethernet")]
+        for ethernet_socket in sockets
+            .items_mut()
+            .filter_map(|i| crate::socket::ethernet::Socket::downcast_mut(&mut i.socket))
+        {
+            if ethernet_socket.accepts(&eth_frame) {
+                ethernet_socket.process(self, &eth_frame);
+            }
+        }
+
         match eth_frame.ethertype() {
             #[cfg(feature = "proto-ipv4")]
             EthernetProtocol::Arp => self.process_arp(self.now, &eth_frame),
@@ -25,13 +36,13 @@ impl InterfaceInner {
             EthernetProtocol::Ipv4 => {
                 let ipv4_packet = check!(Ipv4Packet::new_checked(eth_frame.payload()));
 
-                self.process_ipv4(sockets, meta, &ipv4_packet, fragments)
+                self.process_ipv4(sockets, meta, &ipv4_packet, fragments, false)
                     .map(EthernetPacket::Ip)
             }
             #[cfg(feature = "proto-ipv6")]
             EthernetProtocol::Ipv6 => {
                 let ipv6_packet = check!(Ipv6Packet::new_checked(eth_frame.payload()));
-                self.process_ipv6(sockets, meta, &ipv6_packet)
+                self.process_ipv6(sockets, meta, &ipv6_packet, Some(fragments), false)
                     .map(EthernetPacket::Ip)
             }
             // Drop all other traffic.
@@ -0,0 +1,710 @@
+use super::*;
+
+use crate::iface::router::{InterfaceId, Router};
+
+/// How long a forwarded packet may wait for the discovery of its next hop.
+const NEIGHBOR_WAIT: Duration = Duration::from_secs(3);
+
+/// Delay between two attempts to send a packet whose next hop is not discovered yet.
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+const NEIGHBOR_RETRY: Duration = Duration::from_secs(1);
+
+/// Reasons to send an ICMP error about a packet that can not be forwarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ForwardError {
+    /// The TTL or hop limit of the packet expired.
+    TimeExceeded,
+    /// There is no route towards the destination of the packet.
+    NoRoute,
+    /// The destination of the packet is covered by an unreachable route.
+    Unreachable,
+    /// The packet is larger than the MTU of the outgoing interface.
+    TooBig(usize),
+}
+
+impl Interface {
+    /// Transmit packets queued in the given sockets, receive packets queued in the device,
+    /// and forward packets between the interfaces of `router`.
+    ///
+    /// This is [`poll`](Self::poll) for an interface that was attached to `router` with
+    /// [`Router::add_interface`]. Received packets that are not addressed to this interface
+    /// are handed to the outgoing interface picked by the router, and the packets handed to
+    /// this interface by the other ones are sent. Since packets move between interfaces, all
+    /// interfaces of a router should be polled together. [`Router::poll_at`] tells when
+    /// forwarded packets are waiting to be sent.
+    ///
+    /// # Panics
+    /// This function panics if the interface is not attached to a router.
+    pub fn poll_router<D>(
+        &mut self,
+        timestamp: Instant,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        router: &mut Router<'_>,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let iface_id = self
+            .inner
+            .iface_id
+            .expect("interface is not attached to a router");
+        router.update_interface(iface_id, self);
+
+        self.poll_inner(timestamp, device, sockets, Some(router))
+    }
+
+    /// Send the next packet that was forwarded out of this interface.
+    pub(super) fn forward_egress<D>(&mut self, device: &mut D, router: &mut Router<'_>) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let iface_id = match self.inner.iface_id {
+            Some(iface_id) => iface_id,
+            None => return false,
+        };
+        let now = self.inner.now;
+
+        let port = router.port_mut(iface_id);
+        let (queued_at, packet) = match port.queue.peek() {
+            Ok((queued_at, packet)) => (*queued_at, packet),
+            Err(_) => return false,
+        };
+
+        // Packets whose next hop is being discovered wait at the tail of the queue.
+        let ready = self.inner.has_forwarded_next_hop(packet);
+        if !ready && now >= queued_at + NEIGHBOR_WAIT {
+            net_debug!("next hop of forwarded packet not discovered, dropping packet");
+        } else if !ready && now < port.retry_at {
+            return port.park();
+        } else {
+            let tx_token = match device.transmit(now) {
+                Some(tx_token) => tx_token,
+                None => return false,
+            };
+
+            match self
+                .inner
+                .dispatch_forwarded(tx_token, packet, &mut self.fragmenter)
+            {
+                #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+                Err(DispatchError::NeighborPending) => {
+                    port.retry_at = now + NEIGHBOR_RETRY;
+                    port.parked = 0;
+                    // Sending the neighbor request used the token.
+                    port.park();
+                    return true;
+                }
+                Err(err) => net_debug!("failed to forward packet: {:?}", err),
+                Ok(()) => (),
+            }
+        }
+
+        let _ = port.queue.dequeue();
+        port.parked = 0;
+        true
+    }
+}
+
+impl InterfaceInner {
+    pub(crate) fn set_iface_id(&mut self, iface_id: Option<InterfaceId>) {
+        self.iface_id = iface_id;
+    }
+
+    /// Return the largest IP packet that can be forwarded out of the interface.
+    pub(crate) fn forwarding_mtu(&self) -> usize {
+        match self.caps.medium {
+            // 6LoWPAN fragments IPv6 packets up to the minimum MTU at the adaptation layer.
+            #[cfg(feature = "medium-ieee802154")]
+            Medium::Ieee802154 => IPV6_MIN_MTU,
+            #[allow(unreachable_patterns)]
+            _ => self.ip_mtu(),
+        }
+    }
+
+    /// Check whether a received IPv4 packet is to be forwarded to another host.
+    #[cfg(feature = "proto-ipv4")]
+    pub(super) fn should_forward_ipv4(&self, ipv4_repr: &Ipv4Repr) -> bool {
+        let (src_addr, dst_addr) = (ipv4_repr.src_addr, ipv4_repr.dst_addr);
+        if self.iface_id.is_none()
+            || !dst_addr.is_unicast()
+            || self.has_ip_addr(dst_addr)
+            || self.is_broadcast_v4(dst_addr)
+            || src_addr.is_unspecified()
+        {
+            return false;
+        }
+        // Link-local and loopback addresses must not leave their link, see RFC 3927 § 2.7
+        // and RFC 1812 § 5.3.7.
+        if src_addr.is_link_local()
+            || dst_addr.is_link_local()
+            || src_addr.is_loopback()
+            || dst_addr.is_loopback()
+        {
+            return false;
+        }
+        // With AnyIP, packets routed to one of our own addresses are ours.
+        !(self.any_ip
+            && self
+                .routes
+                .lookup(&IpAddress::Ipv4(dst_addr), self.now)
+                .map_or(false, |router_addr| self.has_ip_addr(router_addr)))
+    }
+
+    /// Check whether a received IPv6 packet is to be forwarded to another host.
+    #[cfg(feature = "proto-ipv6")]
+    pub(super) fn should_forward_ipv6(&self, ipv6_repr: &Ipv6Repr) -> bool {
+        let (src_addr, dst_addr) = (ipv6_repr.src_addr, ipv6_repr.dst_addr);
+        self.iface_id.is_some()
+            && dst_addr.is_unicast()
+            && !self.has_ip_addr(dst_addr)
+            // Link-local addresses must not leave their link, see RFC 4291 § 2.5.6.
+            && !src_addr.is_link_local()
+            && !dst_addr.is_link_local()
+            && !src_addr.is_loopback()
+            && !dst_addr.is_loopback()
+    }
+
+    /// Hand a packet that is addressed to another host to the router, unless its TTL or hop
+    /// limit expires.
+    pub(super) fn forward_ip<'frame>(
+        &self,
+        ip_repr: IpRepr,
+        packet: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
+        if ip_repr.hop_limit() <= 1 {
+            net_debug!(
+                "hop limit exceeded, not forwarding packet to {}",
+                ip_repr.dst_addr()
+            );
+            return self.forward_error(&ip_repr, packet, ForwardError::TimeExceeded);
+        }
+
+        Some(Packet::new(ip_repr, IpPayload::Forward(packet)))
+    }
+
+    /// Queue a received packet on the outgoing interface picked by the router.
+    ///
+    /// Packets that are not to be forwarded are returned unchanged. For forwarded
+    /// packets, an ICMP error or redirect for the source of the packet is returned.
+    pub(super) fn forward<'frame>(
+        &mut self,
+        router: Option<&mut Router<'_>>,
+        packet: Packet<'frame>,
+    ) -> Option<Packet<'frame>> {
+        let raw = match packet.payload() {
+            IpPayload::Forward(raw) => *raw,
+            _ => return Some(packet),
+        };
+        let (router, iface_id) = match (router, self.iface_id) {
+            (Some(router), Some(iface_id)) => (router, iface_id),
+            _ => {
+                net_debug!("forwarding requires Interface::poll_router, dropping packet");
+                return None;
+            }
+        };
+        let ip_repr = packet.ip_repr();
+
+        let out_id = match router.lookup(&ip_repr.dst_addr()) {
+            Some(out_id) => out_id,
+            None => {
+                net_debug!("no route to forward packet to {}", ip_repr.dst_addr());
+                return self.forward_error(&ip_repr, raw, ForwardError::NoRoute);
+            }
+        };
+
+        let (src_addr, dst_addr) = (ip_repr.src_addr(), ip_repr.dst_addr());
+        match router.dropping_route(out_id, &src_addr, &dst_addr, self.now) {
+            Some(RouteKind::Unreachable) => {
+                net_debug!("unreachable route, not forwarding packet to {}", dst_addr);
+                return self.forward_error(&ip_repr, raw, ForwardError::Unreachable);
+            }
+            Some(_) => {
+                net_debug!("blackhole route, not forwarding packet to {}", dst_addr);
+                return None;
+            }
+            None => (),
+        }
+
+        let mtu = router.ip_mtu(out_id);
+        if raw.len() > mtu {
+            match ip_repr {
+                #[cfg(feature = "proto-ipv4")]
+                IpRepr::Ipv4(_) => {
+                    let ipv4_packet = Ipv4Packet::new_unchecked(raw);
+                    if ipv4_packet.dont_frag() {
+                        net_debug!("packet too big to forward, mtu is {}", mtu);
+                        return self.forward_error(&ip_repr, raw, ForwardError::TooBig(mtu));
+                    }
+                    if ipv4_packet.more_frags() || ipv4_packet.frag_offset() != 0 {
+                        net_debug!("fragment too big to forward, mtu is {}", mtu);
+                        return None;
+                    }
+                    // The outgoing interface fragments the packet.
+                }
+                #[cfg(feature = "proto-ipv6")]
+                IpRepr::Ipv6(_) => {
+                    net_debug!("packet too big to forward, mtu is {}", mtu);
+                    return self.forward_error(&ip_repr, raw, ForwardError::TooBig(mtu));
+                }
+            }
+        }
+
+        // A packet that leaves through the interface it came from has a better first hop
+        // on the link of its source, see RFC 1812 § 5.2.7.2 and RFC 4861 § 8.2.
+        let redirect = if out_id == iface_id {
+            self.forward_redirect(&ip_repr, raw)
+        } else {
+            None
+        };
+
+        let port = router.port_mut(out_id);
+        match port.queue.enqueue(raw.len(), self.now) {
+            Ok(buf) => {
+                buf.copy_from_slice(raw);
+                // The new packet may be sent before the parked ones.
+                port.parked = 0;
+            }
+            Err(_) => net_debug!("forwarding queue full, dropping packet"),
+        }
+
+        redirect
+    }
+
+    /// Build an ICMP error about a packet that can not be forwarded.
+    pub(super) fn forward_error<'frame>(
+        &self,
+        ip_repr: &IpRepr,
+        packet: &'frame [u8],
+        error: ForwardError,
+    ) -> Option<Packet<'frame>> {
+        if !may_send_icmp_error(packet) {
+            return None;
+        }
+
+        match *ip_repr {
+            #[cfg(feature = "proto-ipv4")]
+            IpRepr::Ipv4(header) => {
+                let payload = Ipv4Packet::new_unchecked(packet).payload();
+                let data_len =
+                    icmp_reply_payload_len(payload.len(), IPV4_MIN_MTU, header.buffer_len());
+                let data = &payload[..data_len];
+                let icmp_repr = match error {
+                    ForwardError::TimeExceeded => Icmpv4Repr::TimeExceeded {
+                        reason: Icmpv4TimeExceeded::TtlExpired,
+                        header,
+                        data,
+                        extensions: IcmpExtensions::EMPTY,
+                    },
+                    ForwardError::NoRoute => Icmpv4Repr::DstUnreachable {
+                        reason: Icmpv4DstUnreachable::NetUnreachable,
+                        header,
+                        data,
+                        extensions: IcmpExtensions::EMPTY,
+                    },
+                    ForwardError::Unreachable => Icmpv4Repr::DstUnreachable {
+                        reason: Icmpv4DstUnreachable::HostUnreachable,
+                        header,
+                        data,
+                        extensions: IcmpExtensions::EMPTY,
+                    },
+                    ForwardError::TooBig(mtu) => Icmpv4Repr::FragRequired {
+                        mtu: mtu as u16,
+                        header,
+                        data,
+                        extensions: IcmpExtensions::EMPTY,
+                    },
+                };
+                self.icmpv4_error(header, icmp_repr)
+            }
+            #[cfg(feature = "proto-ipv6")]
+            IpRepr::Ipv6(header) => {
+                let payload = &packet[header.buffer_len()..];
+                let data_len =
+                    icmp_reply_payload_len(payload.len(), IPV6_MIN_MTU, header.buffer_len());
+                let data = &payload[..data_len];
+                let icmp_repr = match error {
+                    ForwardError::TimeExceeded => Icmpv6Repr::TimeExceeded {
+                        reason: Icmpv6TimeExceeded::HopLimitExceeded,
+                        header,
+                        data,
+                        extensions: IcmpExtensions::EMPTY,
+                    },
+                    ForwardError::NoRoute | ForwardError::Unreachable => {
+                        Icmpv6Repr::DstUnreachable {
+                            reason: Icmpv6DstUnreachable::NoRoute,
+                            header,
+                            data,
+                            extensions: IcmpExtensions::EMPTY,
+                        }
+                    }
+                    ForwardError::TooBig(mtu) => Icmpv6Repr::PktTooBig {
+                        mtu: mtu as u32,
+                        header,
+                        data,
+                    },
+                };
+                self.icmpv6_error(header, icmp_repr)
+            }
+        }
+    }
+
+    /// Build a redirect telling the source of a packet, which is on our link, to send
+    /// packets for its destination to the next hop directly.
+    fn forward_redirect<'frame>(
+        &self,
+        ip_repr: &IpRepr,
+        packet: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
+        let src_addr = ip_repr.src_addr();
+        let dst_addr = ip_repr.dst_addr();
+
+        if !self.in_same_network(&src_addr) || !may_send_icmp_error(packet) {
+            return None;
+        }
+        let next_hop = self.route(&dst_addr, self.now)?;
+        if next_hop == src_addr {
+            return None;
+        }
+
+        match (ip_repr, next_hop) {
+            #[cfg(feature = "proto-ipv4")]
+            (&IpRepr::Ipv4(header), IpAddress::Ipv4(gateway)) => {
+                let payload = Ipv4Packet::new_unchecked(packet).payload();
+                let data_len =
+                    icmp_reply_payload_len(payload.len(), IPV4_MIN_MTU, header.buffer_len());
+                let icmp_repr = Icmpv4Repr::Redirect {
+                    reason: Icmpv4Redirect::Host,
+                    gateway,
+                    header,
+                    data: &payload[..data_len],
+                };
+                self.icmpv4_error(header, icmp_repr)
+            }
+            #[cfg(all(
+                feature = "proto-ipv6",
+                any(feature = "medium-ethernet", feature = "medium-ieee802154")
+            ))]
+            (&IpRepr::Ipv6(header), IpAddress::Ipv6(target_addr)) => {
+                // Redirects are sent from a link-local address, see RFC 4861 § 8.2.
+                let src_addr = self.ip_addrs.iter().find_map(|cidr| match cidr {
+                    IpCidr::Ipv6(cidr) if cidr.address().is_link_local() => Some(cidr.address()),
+                    _ => None,
+                })?;
+                let lladdr = match self.neighbor_cache.lookup(&next_hop, self.now) {
+                    NeighborAnswer::Found(hardware_addr) => Some(hardware_addr.into()),
+                    _ => None,
+                };
+
+                let redirect = |data| {
+                    Icmpv6Repr::Ndisc(NdiscRepr::Redirect {
+                        target_addr,
+                        dest_addr: header.dst_addr,
+                        lladdr,
+                        redirected_hdr: Some(NdiscRedirectedHeader {
+                            header: Ipv6Repr {
+                                payload_len: <[u8]>::len(data),
+                                ..header
+                            },
+                            data,
+                        }),
+                    })
+                };
+
+                // Quote as much of the packet as fits in the minimum MTU, in multiples of 8
+                // octets to avoid padding the option.
+                let payload = &packet[header.buffer_len()..];
+                let room = IPV6_MIN_MTU - header.buffer_len() - redirect(&[]).buffer_len();
+                let data_len = payload.len().min(room) / 8 * 8;
+                let icmp_repr = redirect(&payload[..data_len]);
+
+                Some(Packet::new_ipv6(
+                    Ipv6Repr {
+                        src_addr,
+                        dst_addr: header.src_addr,
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: icmp_repr.buffer_len(),
+                        hop_limit: 0xff,
+                        dscp: 0,
+                        ecn: 0,
+                    },
+                    IpPayload::Icmpv6(icmp_repr),
+                ))
+            }
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Return the next hop of a packet that was queued by the router on this interface.
+    fn forwarded_next_hop(&mut self, packet: &[u8]) -> Option<IpAddress> {
+        let (src_addr, dst_addr) = forwarded_addrs(packet)?;
+        self.with_rule_key(forwarded_rule_key(packet), |inner| {
+            inner.route_from(Some(&src_addr), &dst_addr, inner.now)
+        })
+    }
+
+    /// Return the hardware address of the next hop of a packet that was queued by the
+    /// router on this interface, or start discovering it.
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    fn forwarded_hardware_addr<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        packet: &[u8],
+        frag: &mut Fragmenter,
+    ) -> Result<(HardwareAddress, Tx), DispatchError> {
+        let next_hop = self
+            .forwarded_next_hop(packet)
+            .ok_or(DispatchError::NoRoute)?;
+        // Neighbor discovery is done from our own address, not the one of the source.
+        let src_addr = self
+            .get_source_address(&next_hop)
+            .ok_or(DispatchError::NoRoute)?;
+        self.lookup_hardware_addr(tx_token, &src_addr, &next_hop, frag)
+    }
+
+    /// Check whether a packet that was queued by the router on this interface can be sent
+    /// without discovering its next hop first.
+    fn has_forwarded_next_hop(&mut self, packet: &[u8]) -> bool {
+        let _next_hop = match self.forwarded_next_hop(packet) {
+            Some(next_hop) => next_hop,
+            // Sending the packet reports the missing route.
+            None => return true,
+        };
+        match self.caps.medium {
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => self.neighbor_cache.lookup(&_next_hop, self.now).found(),
+            #[cfg(feature = "medium-ieee802154")]
+            Medium::Ieee802154 => self.neighbor_cache.lookup(&_next_hop, self.now).found(),
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip => true,
+        }
+    }
+
+    /// Send a packet that was queued by the router on this interface.
+    fn dispatch_forwarded<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        packet: &[u8],
+        frag: &mut Fragmenter,
+    ) -> Result<(), DispatchError> {
+        // Packets that do not fit the MTU (which were let through by the router), or that
+        // are sent over 6LoWPAN, are emitted again from their representation.
+        #[allow(unused_mut)]
+        let mut emit_repr = packet.len() > self.ip_mtu();
+        #[cfg(feature = "medium-ieee802154")]
+        if matches!(self.caps.medium, Medium::Ieee802154) {
+            emit_repr = true;
+        }
+
+        if emit_repr {
+            // Discover the next hop first, which `dispatch_ip` would do from the address
+            // of the source.
+            #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+            let tx_token = match self.caps.medium {
+                #[cfg(feature = "medium-ip")]
+                Medium::Ip => tx_token,
+                _ => self.forwarded_hardware_addr(tx_token, packet, frag)?.1,
+            };
+            let rule_key = forwarded_rule_key(packet);
+
+            let packet = match IpVersion::of_packet(packet) {
+                #[cfg(feature = "proto-ipv4")]
+                Ok(IpVersion::Ipv4) => {
+                    let ipv4_packet = Ipv4Packet::new_unchecked(packet);
+                    // The fragmenter repeats the options with the copied flag in every
+                    // fragment, and sends all of them in the first one.
+                    let options = match Ipv4Options::new(ipv4_packet.options()) {
+                        Ok(options) => options,
+                        Err(_) => {
+                            net_debug!("malformed options, not fragmenting forwarded packet");
+                            return Ok(());
+                        }
+                    };
+                    let repr = Ipv4Repr {
+                        src_addr: ipv4_packet.src_addr(),
+                        dst_addr: ipv4_packet.dst_addr(),
+                        next_header: ipv4_packet.next_header(),
+                        payload_len: ipv4_packet.payload().len(),
+                        hop_limit: ipv4_packet.hop_limit() - 1,
+                        dscp: ipv4_packet.dscp(),
+                        ecn: ipv4_packet.ecn(),
+                        options,
+                    };
+                    Packet::new_ipv4(repr, IpPayload::Raw(ipv4_packet.payload()))
+                }
+                #[cfg(feature = "proto-ipv6")]
+                Ok(IpVersion::Ipv6) => {
+                    let ipv6_packet = Ipv6Packet::new_unchecked(packet);
+                    let repr = Ipv6Repr {
+                        src_addr: ipv6_packet.src_addr(),
+                        dst_addr: ipv6_packet.dst_addr(),
+                        next_header: ipv6_packet.next_header(),
+                        payload_len: ipv6_packet.payload().len(),
+                        hop_limit: ipv6_packet.hop_limit() - 1,
+                        dscp: ipv6_packet.traffic_class() >> 2,
+                        ecn: ipv6_packet.traffic_class() & 0x03,
+                    };
+                    Packet::new_ipv6(repr, IpPayload::Raw(ipv6_packet.payload()))
+                }
+                _ => return Ok(()),
+            };
+            return self.with_rule_key(rule_key, |inner| {
+                inner.dispatch_ip(tx_token, PacketMeta::default(), packet, frag)
+            });
+        }
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ip"))]
+        let checksum_caps = self.caps.checksum.clone();
+
+        match self.caps.medium {
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => {
+                let dst_addr = match forwarded_dst_addr(packet) {
+                    Some(dst_addr) => dst_addr,
+                    None => return Ok(()),
+                };
+                let (dst_hardware_addr, tx_token) =
+                    self.forwarded_hardware_addr(tx_token, packet, frag)?;
+
+                self.dispatch_ethernet(tx_token, packet.len(), |mut frame| {
+                    frame.set_dst_addr(dst_hardware_addr.ethernet_or_panic());
+                    frame.set_ethertype(match dst_addr {
+                        #[cfg(feature = "proto-ipv4")]
+                        IpAddress::Ipv4(_) => EthernetProtocol::Ipv4,
+                        #[cfg(feature = "proto-ipv6")]
+                        IpAddress::Ipv6(_) => EthernetProtocol::Ipv6,
+                    });
+                    let buffer = frame.payload_mut();
+                    buffer.copy_from_slice(packet);
+                    decrement_hop_limit(buffer, &checksum_caps);
+                })
+            }
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip => tx_token.consume(packet.len(), |buffer| {
+                buffer.copy_from_slice(packet);
+                decrement_hop_limit(buffer, &checksum_caps);
+                Ok(())
+            }),
+            #[cfg(feature = "medium-ieee802154")]
+            Medium::Ieee802154 => unreachable!(),
+        }
+    }
+}
+
+/// Return the source and destination addresses of a forwarded packet.
+fn forwarded_addrs(packet: &[u8]) -> Option<(IpAddress, IpAddress)> {
+    match IpVersion::of_packet(packet) {
+        #[cfg(feature = "proto-ipv4")]
+        Ok(IpVersion::Ipv4) => {
+            let ipv4_packet = Ipv4Packet::new_unchecked(packet);
+            Some((ipv4_packet.src_addr().into(), ipv4_packet.dst_addr().into()))
+        }
+        #[cfg(feature = "proto-ipv6")]
+        Ok(IpVersion::Ipv6) => {
+            let ipv6_packet = Ipv6Packet::new_unchecked(packet);
+            Some((ipv6_packet.src_addr().into(), ipv6_packet.dst_addr().into()))
+        }
+        _ => None,
+    }
+}
+
+/// Return the destination address of a forwarded packet.
+#[cfg(feature = "medium-ethernet")]
+fn forwarded_dst_addr(packet: &[u8]) -> Option<IpAddress> {
+    forwarded_addrs(packet).map(|(_, dst_addr)| dst_addr)
+}
+
+/// Return the properties of a forwarded packet that routing rules match. Forwarded
+/// packets only match routing rules on their addresses, and their protocol and ports
+/// pick one of several equal-cost routes.
+fn forwarded_rule_key(packet: &[u8]) -> RuleKey {
+    let (protocol, payload) = match IpVersion::of_packet(packet) {
+        #[cfg(feature = "proto-ipv4")]
+        Ok(IpVersion::Ipv4) => {
+            let ipv4_packet = Ipv4Packet::new_unchecked(packet);
+            // The fragments of a packet take the same path, whether they hold the ports
+            // or not.
+            let fragment = ipv4_packet.more_frags() || ipv4_packet.frag_offset() != 0;
+            let payload = if fragment {
+                &[][..]
+            } else {
+                ipv4_packet.payload()
+            };
+            (ipv4_packet.next_header(), payload)
+        }
+        #[cfg(feature = "proto-ipv6")]
+        Ok(IpVersion::Ipv6) => {
+            let ipv6_packet = Ipv6Packet::new_unchecked(packet);
+            (ipv6_packet.next_header(), ipv6_packet.payload())
+        }
+        _ => return RuleKey::default(),
+    };
+    RuleKey {
+        protocol: Some(protocol),
+        ports: transport_ports(protocol, payload),
+        ..RuleKey::default()
+    }
+}
+
+/// Decrement the TTL or hop limit of a forwarded packet.
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ip"))]
+fn decrement_hop_limit(packet: &mut [u8], _checksum_caps: &ChecksumCapabilities) {
+    match IpVersion::of_packet(packet) {
+        #[cfg(feature = "proto-ipv4")]
+        Ok(IpVersion::Ipv4) => {
+            let mut ipv4_packet = Ipv4Packet::new_unchecked(packet);
+            ipv4_packet.set_hop_limit(ipv4_packet.hop_limit() - 1);
+            if _checksum_caps.ipv4.tx() {
+                ipv4_packet.fill_checksum();
+            } else {
+                ipv4_packet.set_checksum(0);
+            }
+        }
+        #[cfg(feature = "proto-ipv6")]
+        Ok(IpVersion::Ipv6) => {
+            let mut ipv6_packet = Ipv6Packet::new_unchecked(packet);
+            ipv6_packet.set_hop_limit(ipv6_packet.hop_limit() - 1);
+        }
+        _ => (),
+    }
+}
+
+/// Check that an ICMP error may be sent about a packet. Errors are only sent about the first
+/// fragment of a datagram, and never about ICMP errors, see RFC 1812 § 4.3.2.7 and
+/// RFC 4443 § 2.4.
+fn may_send_icmp_error(packet: &[u8]) -> bool {
+    match IpVersion::of_packet(packet) {
+        #[cfg(feature = "proto-ipv4")]
+        Ok(IpVersion::Ipv4) => {
+            let ipv4_packet = Ipv4Packet::new_unchecked(packet);
+            if ipv4_packet.frag_offset() != 0 {
+                return false;
+            }
+            if ipv4_packet.next_header() != IpProtocol::Icmp {
+                return true;
+            }
+            !matches!(
+                Icmpv4Packet::new_checked(ipv4_packet.payload()).map(|icmp| icmp.msg_type()),
+                Ok(Icmpv4Message::DstUnreachable
+                    | Icmpv4Message::Redirect
+                    | Icmpv4Message::TimeExceeded
+                    | Icmpv4Message::ParamProblem)
+                    | Err(_)
+            )
+        }
+        #[cfg(feature = "proto-ipv6")]
+        Ok(IpVersion::Ipv6) => {
+            let ipv6_packet = Ipv6Packet::new_unchecked(packet);
+            if ipv6_packet.next_header() != IpProtocol::Icmpv6 {
+                return true;
+            }
+            !matches!(
+                Icmpv6Packet::new_checked(ipv6_packet.payload())
+                    .map(|icmp| icmp.msg_type().is_error()),
+                Ok(true) | Err(_)
+            )
+        }
+        _ => false,
+    }
+}
@@ -1,5 +1,15 @@
 use super::*;
 
+use crate::config::IFACE_MAX_MULTICAST_SOURCE_COUNT;
+use crate::wire::{IgmpGroupRecord, IgmpRecordType};
+
+/// How long the interface keeps sending the messages of an older IGMP version after hearing
+/// a query of that version, see RFC 3376 § 8.12.
+const OLDER_VERSION_QUERIER_PRESENT_TIMEOUT: Duration = Duration::from_secs(260);
+
+/// Max length of a group record holding all the sources of a multicast filter.
+const GROUP_RECORD_MAX_LEN: usize = 8 + 4 * IFACE_MAX_MULTICAST_SOURCE_COUNT;
+
 /// Error type for `join_multicast_group`, `leave_multicast_group`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -8,6 +18,8 @@ pub enum MulticastError {
     Exhausted,
     /// The table of joined multicast groups is already full.
     GroupTableFull,
+    /// The source list of the multicast group is already full.
+    SourceTableFull,
     /// IPv6 multicast is not yet supported.
     Ipv6NotSupported,
 }
@@ -17,6 +29,7 @@ impl core::fmt::Display for MulticastError {
         match self {
             MulticastError::Exhausted => write!(f, "Exhausted"),
             MulticastError::GroupTableFull => write!(f, "GroupTableFull"),
+            MulticastError::SourceTableFull => write!(f, "SourceTableFull"),
             MulticastError::Ipv6NotSupported => write!(f, "Ipv6NotSupported"),
         }
     }
@@ -25,9 +38,81 @@ impl core::fmt::Display for MulticastError {
 #[cfg(feature = "std")]
 impl std::error::Error for MulticastError {}
 
+/// Filter mode of the source list of a multicast group, see RFC 3376 § 3.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MulticastFilterMode {
+    /// Only receive the packets sent by the sources of the list.
+    Include,
+    /// Receive the packets sent by all the sources, except the ones of the list.
+    Exclude,
+}
+
+/// Reception state of a multicast group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MulticastFilter {
+    mode: MulticastFilterMode,
+    sources: Vec<Ipv4Address, IFACE_MAX_MULTICAST_SOURCE_COUNT>,
+}
+
+impl MulticastFilter {
+    /// The filter of a group that is not joined.
+    const NONE: Self = Self {
+        mode: MulticastFilterMode::Include,
+        sources: Vec::new(),
+    };
+
+    fn new(mode: MulticastFilterMode, sources: &[Ipv4Address]) -> Result<Self, MulticastError> {
+        let mut filter = Self {
+            mode,
+            sources: Vec::new(),
+        };
+        for source in sources {
+            filter.insert(*source)?;
+        }
+        Ok(filter)
+    }
+
+    fn insert(&mut self, source: Ipv4Address) -> Result<(), MulticastError> {
+        if !self.sources.contains(&source) {
+            self.sources
+                .push(source)
+                .map_err(|_| MulticastError::SourceTableFull)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, source: Ipv4Address) {
+        self.sources.retain(|probe| *probe != source);
+    }
+
+    /// Return whether packets to the group are received from any source.
+    fn is_member(&self) -> bool {
+        self.mode == MulticastFilterMode::Exclude || !self.sources.is_empty()
+    }
+
+    /// Return whether packets to the group are received from `source`.
+    fn accepts(&self, source: Ipv4Address) -> bool {
+        self.sources.contains(&source) == (self.mode == MulticastFilterMode::Include)
+    }
+
+    /// Return the sources of `self` that are not in `other`.
+    fn difference(&self, other: &Self) -> Vec<Ipv4Address, IFACE_MAX_MULTICAST_SOURCE_COUNT> {
+        self.sources
+            .iter()
+            .filter(|source| !other.sources.contains(source))
+            .cloned()
+            .collect()
+    }
+}
+
 impl Interface {
     /// Add an address to a list of subscribed multicast IP addresses.
     ///
+    /// Packets to an IPv4 group are received from all the sources, use
+    /// [`set_multicast_filter`](Self::set_multicast_filter) to receive them from specific
+    /// sources only.
+    ///
     /// Returns `Ok(announce_sent)` if the address was added successfully, where `announce_sent`
     /// indicates whether an initial immediate announcement has been sent.
     pub fn join_multicast_group<D, T: Into<IpAddress>>(
@@ -43,30 +128,11 @@ impl Interface {
 
         match addr.into() {
             IpAddress::Ipv4(addr) => {
-                let is_not_new = self
-                    .inner
-                    .ipv4_multicast_groups
-                    .insert(addr, ())
-                    .map_err(|_| MulticastError::GroupTableFull)?
-                    .is_some();
-                if is_not_new {
-                    Ok(false)
-                } else if let Some(pkt) = self.inner.igmp_report_packet(IgmpVersion::Version2, addr)
-                {
-                    // Send initial membership report
-                    let tx_token = device
-                        .transmit(timestamp)
-                        .ok_or(MulticastError::Exhausted)?;
-
-                    // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
-                    self.inner
-                        .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
-                        .unwrap();
-
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
+                let filter = MulticastFilter {
+                    mode: MulticastFilterMode::Exclude,
+                    sources: Vec::new(),
+                };
+                self.update_multicast_filter(device, addr, filter)
             }
             // Multicast is not yet implemented for other address families
             #[allow(unreachable_patterns)]
@@ -91,24 +157,7 @@ impl Interface {
 
         match addr.into() {
             IpAddress::Ipv4(addr) => {
-                let was_not_present = self.inner.ipv4_multicast_groups.remove(&addr).is_none();
-                if was_not_present {
-                    Ok(false)
-                } else if let Some(pkt) = self.inner.igmp_leave_packet(addr) {
-                    // Send group leave packet
-                    let tx_token = device
-                        .transmit(timestamp)
-                        .ok_or(MulticastError::Exhausted)?;
-
-                    // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
-                    self.inner
-                        .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
-                        .unwrap();
-
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
+                self.update_multicast_filter(device, addr, MulticastFilter::NONE)
             }
             // Multicast is not yet implemented for other address families
             #[allow(unreachable_patterns)]
@@ -116,24 +165,141 @@ impl Interface {
         }
     }
 
+    /// Set the sources an IPv4 multicast group is received from.
+    ///
+    /// With [`MulticastFilterMode::Include`], packets to the group are only received from
+    /// `sources`, and an empty list leaves the group. With [`MulticastFilterMode::Exclude`],
+    /// they are received from all the sources except `sources`.
+    ///
+    /// Returns `Ok(announce_sent)` if the filter was set successfully, where `announce_sent`
+    /// indicates whether an immediate state change report has been sent.
+    pub fn set_multicast_filter<D>(
+        &mut self,
+        device: &mut D,
+        group: Ipv4Address,
+        mode: MulticastFilterMode,
+        sources: &[Ipv4Address],
+        timestamp: Instant,
+    ) -> Result<bool, MulticastError>
+    where
+        D: Device + ?Sized,
+    {
+        self.inner.now = timestamp;
+
+        let filter = MulticastFilter::new(mode, sources)?;
+        self.update_multicast_filter(device, group, filter)
+    }
+
+    /// Start receiving the packets sent by `source` to an IPv4 multicast group, joining the
+    /// group for that source if it was not joined.
+    ///
+    /// Returns `Ok(announce_sent)` if the source was added successfully, where `announce_sent`
+    /// indicates whether an immediate state change report has been sent.
+    pub fn join_multicast_source<D>(
+        &mut self,
+        device: &mut D,
+        group: Ipv4Address,
+        source: Ipv4Address,
+        timestamp: Instant,
+    ) -> Result<bool, MulticastError>
+    where
+        D: Device + ?Sized,
+    {
+        self.inner.now = timestamp;
+
+        let mut filter = self.inner.multicast_filter(group);
+        match filter.mode {
+            MulticastFilterMode::Include => filter.insert(source)?,
+            MulticastFilterMode::Exclude => filter.remove(source),
+        }
+        self.update_multicast_filter(device, group, filter)
+    }
+
+    /// Stop receiving the packets sent by `source` to an IPv4 multicast group, leaving the
+    /// group if no source is left.
+    ///
+    /// Returns `Ok(announce_sent)` if the source was removed successfully, where
+    /// `announce_sent` indicates whether an immediate state change report has been sent.
+    pub fn leave_multicast_source<D>(
+        &mut self,
+        device: &mut D,
+        group: Ipv4Address,
+        source: Ipv4Address,
+        timestamp: Instant,
+    ) -> Result<bool, MulticastError>
+    where
+        D: Device + ?Sized,
+    {
+        self.inner.now = timestamp;
+
+        let mut filter = self.inner.multicast_filter(group);
+        match filter.mode {
+            MulticastFilterMode::Include => filter.remove(source),
+            MulticastFilterMode::Exclude => filter.insert(source)?,
+        }
+        self.update_multicast_filter(device, group, filter)
+    }
+
     /// Check whether the interface listens to given destination multicast IP address.
     pub fn has_multicast_group<T: Into<IpAddress>>(&self, addr: T) -> bool {
         self.inner.has_multicast_group(addr)
     }
 
+    /// Replace the filter of a multicast group, and report the change.
+    fn update_multicast_filter<D>(
+        &mut self,
+        device: &mut D,
+        group: Ipv4Address,
+        filter: MulticastFilter,
+    ) -> Result<bool, MulticastError>
+    where
+        D: Device + ?Sized,
+    {
+        let old_filter = self.inner.multicast_filter(group);
+        if old_filter == filter {
+            return Ok(false);
+        }
+
+        if filter.is_member() {
+            self.inner
+                .ipv4_multicast_groups
+                .insert(group, filter.clone())
+                .map_err(|_| MulticastError::GroupTableFull)?;
+        } else {
+            self.inner.ipv4_multicast_groups.remove(&group);
+        }
+
+        let mut buffer = [0; 2 * GROUP_RECORD_MAX_LEN];
+        if let Some(pkt) =
+            self.inner
+                .igmp_state_change_packet(&mut buffer, group, &old_filter, &filter)
+        {
+            // Send state change report
+            let tx_token = device
+                .transmit(self.inner.now)
+                .ok_or(MulticastError::Exhausted)?;
+
+            // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+            self.inner
+                .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                .unwrap();
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     /// Depending on `igmp_report_state` and the therein contained
     /// timeouts, send IGMP membership reports.
     pub(crate) fn igmp_egress<D>(&mut self, device: &mut D) -> bool
     where
         D: Device + ?Sized,
     {
+        let mut buffer = [0; GROUP_RECORD_MAX_LEN];
         match self.inner.igmp_report_state {
-            IgmpReportState::ToSpecificQuery {
-                version,
-                timeout,
-                group,
-            } if self.inner.now >= timeout => {
-                if let Some(pkt) = self.inner.igmp_report_packet(version, group) {
+            IgmpReportState::ToSpecificQuery { timeout, group } if self.inner.now >= timeout => {
+                if let Some(pkt) = self.inner.igmp_current_state_packet(&mut buffer, group) {
                     // Send initial membership report
                     if let Some(tx_token) = device.transmit(self.inner.now) {
                         // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
@@ -149,7 +315,6 @@ impl Interface {
                 true
             }
             IgmpReportState::ToGeneralQuery {
-                version,
                 timeout,
                 interval,
                 next_index,
@@ -159,11 +324,11 @@ impl Interface {
                     .ipv4_multicast_groups
                     .iter()
                     .nth(next_index)
-                    .map(|(addr, ())| *addr);
+                    .map(|(addr, _)| *addr);
 
                 match addr {
                     Some(addr) => {
-                        if let Some(pkt) = self.inner.igmp_report_packet(version, addr) {
+                        if let Some(pkt) = self.inner.igmp_current_state_packet(&mut buffer, addr) {
                             // Send initial membership report
                             if let Some(tx_token) = device.transmit(self.inner.now) {
                                 // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
@@ -182,7 +347,6 @@ impl Interface {
 
                         let next_timeout = (timeout + interval).max(self.inner.now);
                         self.inner.igmp_report_state = IgmpReportState::ToGeneralQuery {
-                            version,
                             timeout: next_timeout,
                             interval,
                             next_index: next_index + 1,
@@ -202,7 +366,8 @@ impl Interface {
 }
 
 impl InterfaceInner {
-    /// Host duties of the **IGMPv2** protocol.
+    /// Host duties of the **IGMPv3** protocol, falling back to **IGMPv1/v2** when queriers
+    /// of these versions are present.
     ///
     /// Sets up `igmp_report_state` for responding to IGMP general/specific membership queries.
     /// Membership must not be reported immediately in order to avoid flooding the network
@@ -215,55 +380,185 @@ impl InterfaceInner {
         let igmp_packet = check!(IgmpPacket::new_checked(ip_payload));
         let igmp_repr = check!(IgmpRepr::parse(&igmp_packet));
 
+        #[cfg(feature = "proto-multicast-querier")]
+        self.multicast_querier_process_igmp(&ipv4_repr, &igmp_repr);
+
         // FIXME: report membership after a delay
-        match igmp_repr {
+        let (group_addr, max_resp_time) = match igmp_repr {
             IgmpRepr::MembershipQuery {
                 group_addr,
                 version,
                 max_resp_time,
             } => {
-                // General query
-                if group_addr.is_unspecified()
-                    && ipv4_repr.dst_addr == Ipv4Address::MULTICAST_ALL_SYSTEMS
-                {
-                    // Are we member in any groups?
-                    if self.ipv4_multicast_groups.iter().next().is_some() {
-                        let interval = match version {
-                            IgmpVersion::Version1 => Duration::from_millis(100),
-                            IgmpVersion::Version2 => {
-                                // No dependence on a random generator
-                                // (see [#24](https://github.com/m-labs/smoltcp/issues/24))
-                                // but at least spread reports evenly across max_resp_time.
-                                let intervals = self.ipv4_multicast_groups.len() as u32 + 1;
-                                max_resp_time / intervals
-                            }
-                        };
-                        self.igmp_report_state = IgmpReportState::ToGeneralQuery {
-                            version,
-                            timeout: self.now + interval,
-                            interval,
-                            next_index: 0,
-                        };
-                    }
-                } else {
-                    // Group-specific query
-                    if self.has_multicast_group(group_addr) && ipv4_repr.dst_addr == group_addr {
-                        // Don't respond immediately
-                        let timeout = max_resp_time / 4;
-                        self.igmp_report_state = IgmpReportState::ToSpecificQuery {
-                            version,
-                            timeout: self.now + timeout,
-                            group: group_addr,
-                        };
-                    }
+                let timeout = Some(self.now + OLDER_VERSION_QUERIER_PRESENT_TIMEOUT);
+                match version {
+                    IgmpVersion::Version1 => self.igmp_v1_querier_present = timeout,
+                    IgmpVersion::Version2 => self.igmp_v2_querier_present = timeout,
                 }
+                (group_addr, max_resp_time)
             }
+            // Group-and-source-specific queries are answered with the whole state of the
+            // group, which is a superset of the requested sources.
+            IgmpRepr::MembershipQueryV3 {
+                group_addr,
+                max_resp_time,
+                ..
+            } => (group_addr, max_resp_time),
             // Ignore membership reports
-            IgmpRepr::MembershipReport { .. } => (),
+            IgmpRepr::MembershipReport { .. } | IgmpRepr::MembershipReportV3 { .. } => return None,
             // Ignore hosts leaving groups
-            IgmpRepr::LeaveGroup { .. } => (),
+            IgmpRepr::LeaveGroup { .. } => return None,
+        };
+
+        // General query
+        if group_addr.is_unspecified() && ipv4_repr.dst_addr == Ipv4Address::MULTICAST_ALL_SYSTEMS {
+            // Are we member in any groups?
+            if self.ipv4_multicast_groups.iter().next().is_some() {
+                // IGMPv1 queries have no maximum response time.
+                let interval = if max_resp_time == Duration::ZERO {
+                    Duration::from_millis(100)
+                } else {
+                    // No dependence on a random generator
+                    // (see [#24](https://github.com/m-labs/smoltcp/issues/24))
+                    // but at least spread reports evenly across max_resp_time.
+                    let intervals = self.ipv4_multicast_groups.len() as u32 + 1;
+                    max_resp_time / intervals
+                };
+                self.igmp_report_state = IgmpReportState::ToGeneralQuery {
+                    timeout: self.now + interval,
+                    interval,
+                    next_index: 0,
+                };
+            }
+        } else {
+            // Group-specific query
+            if self.has_multicast_group(group_addr) && ipv4_repr.dst_addr == group_addr {
+                // Don't respond immediately
+                let timeout = max_resp_time / 4;
+                self.igmp_report_state = IgmpReportState::ToSpecificQuery {
+                    timeout: self.now + timeout,
+                    group: group_addr,
+                };
+            }
         }
 
         None
     }
+
+    /// Check whether the filter of a joined multicast group accepts packets from `source`.
+    pub(super) fn accepts_multicast_source(&self, group: Ipv4Address, source: Ipv4Address) -> bool {
+        self.ipv4_multicast_groups
+            .get(&group)
+            .map_or(true, |filter| filter.accepts(source))
+    }
+
+    fn multicast_filter(&self, group: Ipv4Address) -> MulticastFilter {
+        self.ipv4_multicast_groups
+            .get(&group)
+            .cloned()
+            .unwrap_or(MulticastFilter::NONE)
+    }
+
+    /// Return the older IGMP version the interface falls back to while queriers of that
+    /// version are present on the network, see RFC 3376 § 7.2.1.
+    fn igmp_older_version(&self) -> Option<IgmpVersion> {
+        let is_present = |timeout: Option<Instant>| timeout.map_or(false, |t| self.now < t);
+        if is_present(self.igmp_v1_querier_present) {
+            Some(IgmpVersion::Version1)
+        } else if is_present(self.igmp_v2_querier_present) {
+            Some(IgmpVersion::Version2)
+        } else {
+            None
+        }
+    }
+
+    /// Return the report of the current state of a joined group, answering a query.
+    fn igmp_current_state_packet<'a>(
+        &self,
+        buffer: &'a mut [u8],
+        group: Ipv4Address,
+    ) -> Option<Packet<'a>> {
+        let filter = self.ipv4_multicast_groups.get(&group)?;
+        match self.igmp_older_version() {
+            Some(version) => self.igmp_report_packet(version, group),
+            None => {
+                let record_type = match filter.mode {
+                    MulticastFilterMode::Include => IgmpRecordType::ModeIsInclude,
+                    MulticastFilterMode::Exclude => IgmpRecordType::ModeIsExclude,
+                };
+                let len = emit_group_record(buffer, record_type, group, &filter.sources);
+                self.igmpv3_report_packet(1, &buffer[..len])
+            }
+        }
+    }
+
+    /// Return the report of a change of the filter of a group from `old` to `new`, see
+    /// RFC 3376 § 5.1.
+    fn igmp_state_change_packet<'a>(
+        &self,
+        buffer: &'a mut [u8],
+        group: Ipv4Address,
+        old: &MulticastFilter,
+        new: &MulticastFilter,
+    ) -> Option<Packet<'a>> {
+        match self.igmp_older_version() {
+            // Older routers only know about the groups, not their sources.
+            Some(version) if new.is_member() && !old.is_member() => {
+                self.igmp_report_packet(version, group)
+            }
+            Some(IgmpVersion::Version2) if !new.is_member() => self.igmp_leave_packet(group),
+            Some(_) => None,
+            None => {
+                let mut len = 0;
+                let mut nr_group_rcrds = 0;
+                let mut push = |record_type, sources: &[Ipv4Address]| {
+                    len += emit_group_record(&mut buffer[len..], record_type, group, sources);
+                    nr_group_rcrds += 1;
+                };
+
+                if old.mode != new.mode {
+                    let record_type = match new.mode {
+                        MulticastFilterMode::Include => IgmpRecordType::ChangeToInclude,
+                        MulticastFilterMode::Exclude => IgmpRecordType::ChangeToExclude,
+                    };
+                    push(record_type, &new.sources);
+                } else {
+                    // Sources become allowed when they are added to an include list, or
+                    // removed from an exclude list.
+                    let (allowed, blocked) = match new.mode {
+                        MulticastFilterMode::Include => (new, old),
+                        MulticastFilterMode::Exclude => (old, new),
+                    };
+                    let allow = allowed.difference(blocked);
+                    if !allow.is_empty() {
+                        push(IgmpRecordType::AllowNewSources, &allow);
+                    }
+                    let block = blocked.difference(allowed);
+                    if !block.is_empty() {
+                        push(IgmpRecordType::BlockOldSources, &block);
+                    }
+                }
+
+                self.igmpv3_report_packet(nr_group_rcrds, &buffer[..len])
+            }
+        }
+    }
+}
+
+/// Emit a group record into `buffer`, returning its length.
+fn emit_group_record(
+    buffer: &mut [u8],
+    record_type: IgmpRecordType,
+    group: Ipv4Address,
+    sources: &[Ipv4Address],
+) -> usize {
+    let mut record = IgmpGroupRecord::new_unchecked(buffer);
+    record.set_record_type(record_type);
+    record.set_aux_data_len(0);
+    record.set_num_srcs(sources.len() as u16);
+    record.set_group_addr(group);
+    for (field, source) in record.payload_mut().chunks_exact_mut(4).zip(sources) {
+        field.copy_from_slice(source.as_bytes());
+    }
+    record.record_len()
 }
@@ -21,6 +21,10 @@ impl Interface {
         }
 
         let pkt = &self.fragmenter;
+        if IpVersion::of_packet(&pkt.buffer) != Ok(IpVersion::Ipv4) {
+            return false;
+        }
+
         if pkt.packet_len > pkt.sent_bytes {
             if let Some(tx_token) = device.transmit(self.inner.now) {
                 self.inner
@@ -93,6 +97,7 @@ impl InterfaceInner {
         meta: PacketMeta,
         ipv4_packet: &Ipv4Packet<&'a [u8]>,
         frag: &'a mut FragmentsBuffer,
+        loopback: bool,
     ) -> Option<Packet<'a>> {
         let ipv4_repr = check!(Ipv4Repr::parse(ipv4_packet, &self.caps.checksum));
         if !self.is_unicast_v4(ipv4_repr.src_addr) && !ipv4_repr.src_addr.is_unspecified() {
@@ -101,6 +106,44 @@ impl InterfaceInner {
             return None;
         }
 
+        // Source routing is not supported, see RFC 7126 § 4.3 and § 4.4: packets with
+        // addresses left to visit in their route are dropped. Completed routes are ignored,
+        // and replies are not source routed.
+        if ipv4_repr.options.has_pending_source_route() {
+            net_debug!("ipv4: dropping source routed packet");
+            if ipv4_packet.frag_offset() != 0
+                || !(self.has_ip_addr(ipv4_repr.dst_addr) || self.should_forward_ipv4(&ipv4_repr))
+            {
+                return None;
+            }
+            let payload = ipv4_packet.payload();
+            let payload_len =
+                icmp_reply_payload_len(payload.len(), IPV4_MIN_MTU, ipv4_repr.buffer_len());
+            let icmp_repr = Icmpv4Repr::DstUnreachable {
+                reason: Icmpv4DstUnreachable::SrcRouteFailed,
+                header: ipv4_repr,
+                data: &payload[..payload_len],
+                extensions: IcmpExtensions::EMPTY,
+            };
+            return self.icmpv4_error(ipv4_repr, icmp_repr);
+        }
+
+        if self.should_forward_ipv4(&ipv4_repr) {
+            // Packets with a Router Alert option are also shown to the raw sockets, which
+            // may belong to e.g. a multicast routing daemon, see RFC 2113.
+            #[cfg(feature = "socket-raw")]
+            if ipv4_repr.options.router_alert().is_some()
+                && !ipv4_packet.more_frags()
+                && ipv4_packet.frag_offset() == 0
+            {
+                self.raw_socket_filter(sockets, &IpRepr::Ipv4(ipv4_repr), ipv4_packet.payload());
+            }
+
+            let packet = ipv4_packet.clone().into_inner();
+            let packet = &packet[..ipv4_packet.total_len() as usize];
+            return self.forward_ip(IpRepr::Ipv4(ipv4_repr), packet);
+        }
+
         #[cfg(feature = "proto-ipv4-fragmentation")]
         let ip_payload = {
             if ipv4_packet.more_frags() || ipv4_packet.frag_offset() != 0 {
@@ -179,9 +222,20 @@ impl InterfaceInner {
             }
         }
 
+        #[cfg(feature = "proto-multicast-querier")]
+        let for_querier = self.is_for_igmp_querier(&ipv4_repr);
+        #[cfg(not(feature = "proto-multicast-querier"))]
+        let for_querier = false;
+
+        // Loopback destinations only come from the loopback buffer, the device must never
+        // deliver them, see RFC 1122 § 3.2.1.3.
+        let for_loopback = loopback && ipv4_repr.dst_addr.is_loopback();
+
         if !self.has_ip_addr(ipv4_repr.dst_addr)
             && !self.has_multicast_group(ipv4_repr.dst_addr)
             && !self.is_broadcast_v4(ipv4_repr.dst_addr)
+            && !for_loopback
+            && !for_querier
         {
             // Ignore IP packets not directed at us, or broadcast, or any of the multicast groups.
             // If AnyIP is enabled, also check if the packet is routed locally.
@@ -196,6 +250,21 @@ impl InterfaceInner {
             }
         }
 
+        // Drop the packets from the sources filtered out of the multicast group, queries
+        // from the routers are always processed.
+        #[cfg(feature = "proto-igmp")]
+        if ipv4_repr.dst_addr.is_multicast()
+            && ipv4_repr.next_header != IpProtocol::Igmp
+            && !self.accepts_multicast_source(ipv4_repr.dst_addr, ipv4_repr.src_addr)
+        {
+            net_trace!(
+                "multicast source {} filtered out of {}",
+                ipv4_repr.src_addr,
+                ipv4_repr.dst_addr
+            );
+            return None;
+        }
+
         match ipv4_repr.next_header {
             IpProtocol::Icmp => self.process_icmpv4(sockets, ipv4_repr, ip_payload),
 
@@ -207,6 +276,11 @@ impl InterfaceInner {
                 self.process_udp(sockets, meta, handled_by_raw_socket, ip_repr, ip_payload)
             }
 
+            #[cfg(feature = "socket-udp")]
+            IpProtocol::UdpLite => {
+                self.process_udp_lite(sockets, meta, handled_by_raw_socket, ip_repr, ip_payload)
+            }
+
             #[cfg(feature = "socket-tcp")]
             IpProtocol::Tcp => self.process_tcp(sockets, ip_repr, ip_payload),
 
@@ -220,6 +294,7 @@ impl InterfaceInner {
                     reason: Icmpv4DstUnreachable::ProtoUnreachable,
                     header: ipv4_repr,
                     data: &ip_payload[0..payload_len],
+                    extensions: IcmpExtensions::EMPTY,
                 };
                 self.icmpv4_reply(ipv4_repr, icmp_reply_repr)
             }
@@ -235,6 +310,11 @@ impl InterfaceInner {
         let arp_packet = check!(ArpPacket::new_checked(eth_frame.payload()));
         let arp_repr = check!(ArpRepr::parse(&arp_packet));
 
+        #[cfg(feature = "proto-ipv4-link-local")]
+        if let Some(response) = self.ipv4_link_local_process_arp(&arp_repr) {
+            return Some(EthernetPacket::Arp(response));
+        }
+
         match arp_repr {
             ArpRepr::EthernetIpv4 {
                 operation,
@@ -243,11 +323,6 @@ impl InterfaceInner {
                 target_protocol_addr,
                 ..
             } => {
-                // Only process ARP packets for us.
-                if !self.has_ip_addr(target_protocol_addr) && !self.any_ip {
-                    return None;
-                }
-
                 // Only process REQUEST and RESPONSE.
                 if let ArpOperation::Unknown(_) = operation {
                     net_debug!("arp: unknown operation code");
@@ -260,6 +335,24 @@ impl InterfaceInner {
                     return None;
                 }
 
+                // Only process ARP packets for us. Packets for other hosts are still used to
+                // update an existing cache entry (the "merge" step of RFC 826), such that
+                // gratuitous ARP announcements of a new hardware address take effect.
+                if !self.has_ip_addr(target_protocol_addr) && !self.any_ip {
+                    if self
+                        .neighbor_cache
+                        .lookup(&source_protocol_addr.into(), timestamp)
+                        .found()
+                    {
+                        self.neighbor_cache.fill(
+                            source_protocol_addr.into(),
+                            source_hardware_addr.into(),
+                            timestamp,
+                        );
+                    }
+                    return None;
+                }
+
                 if !self.in_same_network(&IpAddress::Ipv4(source_protocol_addr)) {
                     net_debug!("arp: source IP address not in same network as us");
                     return None;
@@ -310,7 +403,9 @@ impl InterfaceInner {
             .filter_map(|i| icmp::Socket::downcast_mut(&mut i.socket))
         {
             if icmp_socket.accepts_v4(self, &ip_repr, &icmp_repr) {
-                icmp_socket.process_v4(self, &ip_repr, &icmp_repr);
+                if icmp_socket.passes_filter(ip_payload) {
+                    icmp_socket.process_v4(self, &ip_repr, &icmp_repr);
+                }
                 handled_by_icmp_socket = true;
             }
         }
@@ -334,6 +429,24 @@ impl InterfaceInner {
             // Ignore any echo replies.
             Icmpv4Repr::EchoReply { .. } => None,
 
+            // Learn host routes from redirects. Network redirects are treated as host
+            // redirects, as recommended by RFC 1122 § 3.2.2.2.
+            #[cfg(feature = "medium-ethernet")]
+            Icmpv4Repr::Redirect {
+                gateway, header, ..
+            } if self.caps.medium == Medium::Ethernet => {
+                if !self.in_same_network(&gateway.into()) {
+                    net_debug!("redirect: gateway {} is not on-link", gateway);
+                    return None;
+                }
+                self.process_redirect(
+                    ip_repr.src_addr.into(),
+                    header.dst_addr.into(),
+                    gateway.into(),
+                );
+                None
+            }
+
             // Don't report an error if a packet with unknown type
             // has been handled by an ICMP socket
             #[cfg(feature = "socket-icmp")]
@@ -360,6 +473,9 @@ impl InterfaceInner {
                 next_header: IpProtocol::Icmp,
                 payload_len: icmp_repr.buffer_len(),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
+                options: Ipv4Options::EMPTY,
             };
             Some(Packet::new_ipv4(
                 ipv4_reply_repr,
@@ -376,6 +492,9 @@ impl InterfaceInner {
                             next_header: IpProtocol::Icmp,
                             payload_len: icmp_repr.buffer_len(),
                             hop_limit: 64,
+                            dscp: 0,
+                            ecn: 0,
+                            options: Ipv4Options::EMPTY,
                         };
                         Some(Packet::new_ipv4(
                             ipv4_reply_repr,
@@ -391,15 +510,48 @@ impl InterfaceInner {
         }
     }
 
+    /// Build an ICMP error about a packet that was not addressed to us, sent from
+    /// our own address.
+    pub(super) fn icmpv4_error<'frame, 'icmp: 'frame>(
+        &self,
+        ipv4_repr: Ipv4Repr,
+        icmp_repr: Icmpv4Repr<'icmp>,
+    ) -> Option<Packet<'frame>> {
+        if !self.is_unicast_v4(ipv4_repr.src_addr) {
+            return None;
+        }
+
+        let src_addr = self.get_source_address_ipv4(&ipv4_repr.src_addr)?;
+        let ipv4_reply_repr = Ipv4Repr {
+            src_addr,
+            dst_addr: ipv4_repr.src_addr,
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
+        };
+        Some(Packet::new_ipv4(
+            ipv4_reply_repr,
+            IpPayload::Icmpv4(icmp_repr),
+        ))
+    }
+
     #[cfg(feature = "proto-ipv4-fragmentation")]
     pub(super) fn dispatch_ipv4_frag<Tx: TxToken>(&mut self, tx_token: Tx, frag: &mut Fragmenter) {
         let caps = self.caps.clone();
 
         let mtu_max = self.ip_mtu();
-        let ip_len = (frag.packet_len - frag.sent_bytes + frag.ipv4.repr.buffer_len()).min(mtu_max);
-        let payload_len = ip_len - frag.ipv4.repr.buffer_len();
+        let header_len = frag.ipv4.repr.buffer_len();
+        let mut payload_len = (frag.packet_len - frag.sent_bytes).min(mtu_max - header_len);
 
         let more_frags = (frag.packet_len - frag.sent_bytes) != payload_len;
+        if more_frags {
+            // The payload of all fragments but the last is a multiple of 8 octets.
+            payload_len &= !7;
+        }
+        let ip_len = header_len + payload_len;
         frag.ipv4.repr.payload_len = payload_len;
         frag.sent_bytes += payload_len;
 
@@ -445,8 +597,10 @@ impl InterfaceInner {
                 packet.fill_checksum();
             }
 
-            tx_buffer[frag.ipv4.repr.buffer_len()..][..payload_len].copy_from_slice(
-                &frag.buffer[frag.ipv4.frag_offset as usize + frag.ipv4.repr.buffer_len()..]
+            // The header of the first fragment is still in front of the payload.
+            let first_header_len = Ipv4Packet::new_unchecked(&frag.buffer[..]).header_len();
+            tx_buffer[header_len..][..payload_len].copy_from_slice(
+                &frag.buffer[frag.ipv4.frag_offset as usize + first_header_len as usize..]
                     [..payload_len],
             );
 
@@ -474,7 +628,36 @@ impl InterfaceInner {
                 next_header: IpProtocol::Igmp,
                 payload_len: igmp_repr.buffer_len(),
                 hop_limit: 1,
-                // [#183](https://github.com/m-labs/smoltcp/issues/183).
+                dscp: 0,
+                ecn: 0,
+                options: Self::igmp_options(),
+            },
+            IpPayload::Igmp(igmp_repr),
+        );
+        Some(pkt)
+    }
+
+    #[cfg(feature = "proto-igmp")]
+    pub(super) fn igmpv3_report_packet<'a>(
+        &self,
+        nr_group_rcrds: u16,
+        data: &'a [u8],
+    ) -> Option<Packet<'a>> {
+        let iface_addr = self.ipv4_addr()?;
+        let igmp_repr = IgmpRepr::MembershipReportV3 {
+            nr_group_rcrds,
+            data,
+        };
+        let pkt = Packet::new_ipv4(
+            Ipv4Repr {
+                src_addr: iface_addr,
+                dst_addr: Ipv4Address::MULTICAST_ALL_IGMPV3_ROUTERS,
+                next_header: IpProtocol::Igmp,
+                payload_len: igmp_repr.buffer_len(),
+                hop_limit: 1,
+                dscp: 0,
+                ecn: 0,
+                options: Self::igmp_options(),
             },
             IpPayload::Igmp(igmp_repr),
         );
@@ -492,9 +675,63 @@ impl InterfaceInner {
                     next_header: IpProtocol::Igmp,
                     payload_len: igmp_repr.buffer_len(),
                     hop_limit: 1,
+                    dscp: 0,
+                    ecn: 0,
+                    options: Self::igmp_options(),
                 },
                 IpPayload::Igmp(igmp_repr),
             )
         })
     }
+
+    /// Return an IGMPv3 query, general if `group_addr` is unspecified and group-specific
+    /// otherwise.
+    #[cfg(feature = "proto-multicast-querier")]
+    pub(super) fn igmp_query_packet<'any>(
+        &self,
+        group_addr: Ipv4Address,
+        max_resp_time: Duration,
+        qrv: u8,
+        qqic: u8,
+    ) -> Option<Packet<'any>> {
+        let iface_addr = self.ipv4_addr()?;
+        let igmp_repr = IgmpRepr::MembershipQueryV3 {
+            max_resp_time,
+            group_addr,
+            s_flag: false,
+            qrv,
+            qqic,
+            num_srcs: 0,
+            data: &[],
+        };
+        let dst_addr = if group_addr.is_unspecified() {
+            Ipv4Address::MULTICAST_ALL_SYSTEMS
+        } else {
+            group_addr
+        };
+        let pkt = Packet::new_ipv4(
+            Ipv4Repr {
+                src_addr: iface_addr,
+                dst_addr,
+                next_header: IpProtocol::Igmp,
+                payload_len: igmp_repr.buffer_len(),
+                hop_limit: 1,
+                dscp: 0,
+                ecn: 0,
+                options: Self::igmp_options(),
+            },
+            IpPayload::Igmp(igmp_repr),
+        );
+        Some(pkt)
+    }
+
+    /// Return the options of the IGMP messages, which carry a Router Alert option, see
+    /// RFC 2236 § 2.
+    #[cfg(feature = "proto-igmp")]
+    fn igmp_options() -> Ipv4Options {
+        let mut options = Ipv4Options::EMPTY;
+        // NOTE(unwrap): the option fits in the options of an IPv4 header.
+        options.push(&Ipv4OptionRepr::RouterAlert(0)).unwrap();
+        options
+    }
 }
@@ -0,0 +1,340 @@
+use super::*;
+
+// Timing constants, see RFC 5227 § 1.1 and RFC 3927 § 9.
+const PROBE_WAIT: Duration = Duration::from_secs(1);
+const PROBE_NUM: u8 = 3;
+const PROBE_MIN: Duration = Duration::from_secs(1);
+const PROBE_MAX: Duration = Duration::from_secs(2);
+const ANNOUNCE_WAIT: Duration = Duration::from_secs(2);
+const ANNOUNCE_NUM: u8 = 2;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_CONFLICTS: u8 = 10;
+const RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+const DEFEND_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Prefix length of the IPv4 link-local network 169.254.0.0/16.
+const LINK_LOCAL_PREFIX_LEN: u8 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum State {
+    /// Link-local autoconfiguration is not running.
+    Disabled,
+    /// Checking that `addr` is not in use by sending ARP probes.
+    Probing {
+        addr: Ipv4Address,
+        probes_sent: u8,
+        timeout: Instant,
+    },
+    /// `addr` has been claimed, and is being announced with gratuitous ARP requests.
+    Announcing {
+        addr: Ipv4Address,
+        announcements_sent: u8,
+        timeout: Instant,
+    },
+    /// `addr` has been claimed and announced, and is being defended.
+    Bound { addr: Ipv4Address },
+}
+
+/// State of the IPv4 link-local address autoconfiguration (RFC 3927), including
+/// the Address Conflict Detection (RFC 5227) for the selected address.
+#[derive(Debug)]
+pub(crate) struct Ipv4LinkLocal {
+    state: State,
+    /// Number of conflicts encountered while trying to claim an address.
+    conflicts: u8,
+    /// When we last defended our address against a conflicting host.
+    last_defense: Option<Instant>,
+}
+
+impl Ipv4LinkLocal {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: State::Disabled,
+            conflicts: 0,
+            last_defense: None,
+        }
+    }
+
+    /// Return the claimed link-local address, if any.
+    fn claimed_addr(&self) -> Option<Ipv4Address> {
+        match self.state {
+            State::Announcing { addr, .. } | State::Bound { addr } => Some(addr),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        match self.state {
+            State::Probing { timeout, .. } | State::Announcing { timeout, .. } => Some(timeout),
+            _ => None,
+        }
+    }
+}
+
+impl Interface {
+    /// Start IPv4 link-local address autoconfiguration.
+    ///
+    /// The interface picks a random address in `169.254.0.0/16` as described in RFC 3927,
+    /// probes the link with ARP to make sure it is not in use and then claims it by adding it
+    /// to the [`ip_addrs`](Self::ip_addrs) of the interface. Once claimed, the address is
+    /// defended against conflicting hosts, as described in RFC 5227. If the address is
+    /// lost to another host, a new address is selected and the process starts over.
+    ///
+    /// This can be used as a fallback while a DHCP client is still discovering a server,
+    /// calling [`disable_ipv4_link_local`](Self::disable_ipv4_link_local) once a lease
+    /// has been obtained. Calling this while autoconfiguration is already running has
+    /// no effect.
+    ///
+    /// # Panics
+    /// This function panics if the medium is not Ethernet.
+    pub fn enable_ipv4_link_local(&mut self) {
+        assert!(self.inner.caps.medium == Medium::Ethernet);
+
+        if self.inner.ipv4_link_local.state == State::Disabled {
+            self.inner.ipv4_link_local.conflicts = 0;
+            self.inner.ipv4_link_local_restart(None);
+        }
+    }
+
+    /// Stop IPv4 link-local address autoconfiguration.
+    ///
+    /// If an address was claimed, it is removed from the addresses of the interface.
+    pub fn disable_ipv4_link_local(&mut self) {
+        self.inner.ipv4_link_local_release();
+        self.inner.ipv4_link_local.state = State::Disabled;
+    }
+
+    /// Get the IPv4 link-local address claimed by the interface, if any.
+    pub fn ipv4_link_local_addr(&self) -> Option<Ipv4Address> {
+        self.inner.ipv4_link_local.claimed_addr()
+    }
+
+    /// Send the ARP probes and announcements that are due.
+    pub(crate) fn ipv4_link_local_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        let (arp_repr, next_state) = match self.inner.ipv4_link_local.state {
+            State::Probing {
+                addr,
+                probes_sent,
+                timeout,
+            } if now >= timeout => {
+                if probes_sent == PROBE_NUM {
+                    // No conflicting host answered our probes, claim the address.
+                    if !self.inner.ipv4_link_local_claim(addr) {
+                        self.inner.ipv4_link_local.state = State::Disabled;
+                        return false;
+                    }
+                    self.inner.ipv4_link_local.state = State::Announcing {
+                        addr,
+                        announcements_sent: 0,
+                        timeout: now,
+                    };
+                    return true;
+                }
+
+                let probes_sent = probes_sent + 1;
+                let delay = if probes_sent == PROBE_NUM {
+                    ANNOUNCE_WAIT
+                } else {
+                    self.inner.random_delay(PROBE_MIN, PROBE_MAX)
+                };
+                (
+                    self.inner.ipv4_link_local_probe(addr),
+                    State::Probing {
+                        addr,
+                        probes_sent,
+                        timeout: now + delay,
+                    },
+                )
+            }
+            State::Announcing {
+                addr,
+                announcements_sent,
+                timeout,
+            } if now >= timeout => {
+                let announcements_sent = announcements_sent + 1;
+                let next_state = if announcements_sent == ANNOUNCE_NUM {
+                    // Claiming the address was successful.
+                    self.inner.ipv4_link_local.conflicts = 0;
+                    State::Bound { addr }
+                } else {
+                    State::Announcing {
+                        addr,
+                        announcements_sent,
+                        timeout: now + ANNOUNCE_INTERVAL,
+                    }
+                };
+                (self.inner.gratuitous_arp(addr), next_state)
+            }
+            _ => return false,
+        };
+
+        if let Some(tx_token) = device.transmit(now) {
+            // NOTE(unwrap): ARP packets are sent to the broadcast address and never require
+            // neighbor discovery.
+            self.inner
+                .dispatch(
+                    tx_token,
+                    EthernetPacket::Arp(arp_repr),
+                    &mut self.fragmenter,
+                )
+                .unwrap();
+        } else {
+            return false;
+        }
+
+        self.inner.ipv4_link_local.state = next_state;
+        true
+    }
+}
+
+impl InterfaceInner {
+    /// Inspect an incoming ARP packet for conflicts with the address we are probing for or
+    /// have claimed, as described in RFC 5227 § 2.1.1 and § 2.4.
+    ///
+    /// Returns an ARP packet that has to be sent in response, if any.
+    pub(super) fn ipv4_link_local_process_arp(&mut self, arp_repr: &ArpRepr) -> Option<ArpRepr> {
+        let ArpRepr::EthernetIpv4 {
+            operation,
+            source_hardware_addr,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        } = *arp_repr;
+
+        // Ignore our own packets, in case they are looped back to us.
+        if HardwareAddress::Ethernet(source_hardware_addr) == self.hardware_addr {
+            return None;
+        }
+
+        match self.ipv4_link_local.state {
+            State::Probing { addr, .. } => {
+                // Another host uses the address, or is probing for it at the same time.
+                let probe_conflict = operation == ArpOperation::Request
+                    && source_protocol_addr.is_unspecified()
+                    && target_protocol_addr == addr;
+                if source_protocol_addr == addr || probe_conflict {
+                    net_debug!("ipv4 link-local: conflict while probing for {}", addr);
+                    self.ipv4_link_local_conflict();
+                }
+                None
+            }
+            State::Announcing { addr, .. } | State::Bound { addr } => {
+                if source_protocol_addr == addr {
+                    let defended_recently = self
+                        .ipv4_link_local
+                        .last_defense
+                        .map_or(false, |t| self.now < t + DEFEND_INTERVAL);
+
+                    if defended_recently {
+                        // RFC 5227 § 2.4 (b): we already defended our address, give it up.
+                        net_debug!("ipv4 link-local: lost address {} to another host", addr);
+                        self.ipv4_link_local_release();
+                        self.ipv4_link_local_conflict();
+                        None
+                    } else {
+                        net_debug!("ipv4 link-local: defending address {}", addr);
+                        self.ipv4_link_local.last_defense = Some(self.now);
+                        Some(self.gratuitous_arp(addr))
+                    }
+                } else if operation == ArpOperation::Request
+                    && source_protocol_addr.is_unspecified()
+                    && target_protocol_addr == addr
+                {
+                    // Another host is probing for our address, tell it that it is in use.
+                    Some(ArpRepr::EthernetIpv4 {
+                        operation: ArpOperation::Reply,
+                        source_hardware_addr: self.hardware_addr.ethernet_or_panic(),
+                        source_protocol_addr: addr,
+                        target_hardware_addr: source_hardware_addr,
+                        target_protocol_addr: source_protocol_addr,
+                    })
+                } else {
+                    None
+                }
+            }
+            State::Disabled => None,
+        }
+    }
+
+    /// Restart probing with a newly selected address, waiting at least `delay`.
+    fn ipv4_link_local_restart(&mut self, delay: Option<Duration>) {
+        let addr = self.ipv4_link_local_select_addr();
+        let delay = delay.unwrap_or_else(|| self.random_delay(Duration::ZERO, PROBE_WAIT));
+        self.ipv4_link_local.state = State::Probing {
+            addr,
+            probes_sent: 0,
+            timeout: self.now + delay,
+        };
+        self.ipv4_link_local.last_defense = None;
+    }
+
+    /// Handle a conflict for the address that we are probing for or have lost.
+    fn ipv4_link_local_conflict(&mut self) {
+        self.ipv4_link_local.conflicts = self.ipv4_link_local.conflicts.saturating_add(1);
+
+        // RFC 5227 § 2.1.1: after too many conflicts, limit the rate of probing.
+        let delay = if self.ipv4_link_local.conflicts >= MAX_CONFLICTS {
+            Some(RATE_LIMIT_INTERVAL)
+        } else {
+            None
+        };
+        self.ipv4_link_local_restart(delay);
+    }
+
+    /// Add the claimed address to the interface addresses.
+    fn ipv4_link_local_claim(&mut self, addr: Ipv4Address) -> bool {
+        let cidr = IpCidr::Ipv4(Ipv4Cidr::new(addr, LINK_LOCAL_PREFIX_LEN));
+        if self.ip_addrs.push(cidr).is_err() {
+            net_debug!("ipv4 link-local: no space left for address {}", addr);
+            return false;
+        }
+        net_debug!("ipv4 link-local: claimed address {}", addr);
+        true
+    }
+
+    /// Remove the claimed address from the interface addresses, if any.
+    fn ipv4_link_local_release(&mut self) {
+        if let Some(addr) = self.ipv4_link_local.claimed_addr() {
+            self.ip_addrs
+                .retain(|cidr| cidr.address() != IpAddress::Ipv4(addr));
+            self.flush_neighbor_cache();
+        }
+    }
+
+    /// Select a pseudo-random address in the 169.254.1.0 to 169.254.254.255 range,
+    /// see RFC 3927 § 2.1.
+    fn ipv4_link_local_select_addr(&mut self) -> Ipv4Address {
+        // The first address is derived from the hardware address, such that the same
+        // address is selected after a reboot, as recommended by RFC 3927.
+        let seed = if self.ipv4_link_local.conflicts == 0 {
+            let hw = self.hardware_addr.ethernet_or_panic();
+            u16::from_be_bytes([hw.0[4], hw.0[5]]) ^ u16::from_be_bytes([hw.0[2], hw.0[3]])
+        } else {
+            self.rand.rand_u16()
+        };
+
+        let host = seed % (254 * 256);
+        Ipv4Address::new(169, 254, (host >> 8) as u8 + 1, host as u8)
+    }
+
+    fn random_delay(&mut self, min: Duration, max: Duration) -> Duration {
+        let range = (max - min).total_millis();
+        min + Duration::from_millis(self.rand.rand_u32() as u64 % (range + 1))
+    }
+
+    /// An ARP probe, see RFC 5227 § 2.1.1.
+    fn ipv4_link_local_probe(&self, addr: Ipv4Address) -> ArpRepr {
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: self.hardware_addr.ethernet_or_panic(),
+            source_protocol_addr: Ipv4Address::UNSPECIFIED,
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: addr,
+        }
+    }
+}
@@ -1,10 +1,10 @@
 use super::*;
 
-/// Enum used for the process_hopbyhop function. In some cases, when discarding a packet, an ICMMP
-/// parameter problem message needs to be transmitted to the source of the address. In other cases,
-/// the processing of the IP packet can continue.
+/// Enum used for processing IPv6 extension headers. In some cases, when discarding a packet, an
+/// ICMMP parameter problem message needs to be transmitted to the source of the address. In other
+/// cases, the processing of the IP packet can continue.
 #[allow(clippy::large_enum_variant)]
-enum HopByHopResponse<'frame> {
+enum ExtHeaderResponse<'frame> {
     /// Continue processing the IPv6 packet.
     Continue((IpProtocol, &'frame [u8])),
     /// Discard the packet and maybe send back an ICMPv6 packet.
@@ -12,13 +12,60 @@ enum HopByHopResponse<'frame> {
 }
 
 // We implement `Default` such that we can use the check! macro.
-impl Default for HopByHopResponse<'_> {
+impl Default for ExtHeaderResponse<'_> {
     fn default() -> Self {
         Self::Discard(None)
     }
 }
 
+impl Interface {
+    /// Process fragments that still need to be sent for IPv6 packets.
+    ///
+    /// This function returns a boolean value indicating whether any packets were
+    /// processed or emitted, and thus, whether the readiness of any socket might
+    /// have changed.
+    #[cfg(all(
+        feature = "proto-ipv6-fragmentation",
+        any(feature = "medium-ethernet", feature = "medium-ip")
+    ))]
+    pub(super) fn ipv6_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        // Reset the buffer when we transmitted everything.
+        if self.fragmenter.finished() {
+            self.fragmenter.reset();
+        }
+
+        if self.fragmenter.is_empty() {
+            return false;
+        }
+
+        let pkt = &self.fragmenter;
+        if IpVersion::of_packet(&pkt.buffer) != Ok(IpVersion::Ipv6) {
+            return false;
+        }
+
+        if pkt.packet_len > pkt.sent_bytes {
+            if let Some(tx_token) = device.transmit(self.inner.now) {
+                self.inner
+                    .dispatch_ipv6_frag(tx_token, &mut self.fragmenter);
+                return true;
+            }
+        }
+        false
+    }
+}
+
 impl InterfaceInner {
+    /// Get the next IPv6 fragment identifier.
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    pub(super) fn next_ipv6_frag_ident(&mut self) -> u32 {
+        let ident = self.ipv6_frag_ident;
+        self.ipv6_frag_ident = self.ipv6_frag_ident.wrapping_add(1);
+        ident
+    }
+
     /// Return the IPv6 address that is a candidate source address for the given destination
     /// address, based on RFC 6724.
     ///
@@ -51,92 +98,111 @@ impl InterfaceInner {
                 return false;
             }
 
-            true
-        }
-
-        // See RFC 6724 Section 2.2: Common Prefix Length
-        fn common_prefix_length(dst_addr: &Ipv6Cidr, src_addr: &Ipv6Address) -> usize {
-            let addr = dst_addr.address();
-            let mut bits = 0;
-            for (l, r) in addr.as_bytes().iter().zip(src_addr.as_bytes().iter()) {
-                if l == r {
-                    bits += 8;
-                } else {
-                    bits += (l ^ r).leading_zeros();
-                    break;
-                }
+            // The loopback address is not assigned to the outgoing interface.
+            if src_addr.is_loopback() {
+                return false;
             }
 
-            bits = bits.min(dst_addr.prefix_len() as u32);
-
-            bits as usize
-        }
-
-        // If the destination address is a loopback address, or when there are no IPv6 addresses in
-        // the interface, then the loopback address is the only candidate source address.
-        if dst_addr.is_loopback()
-            || self
-                .ip_addrs
-                .iter()
-                .filter(|a| matches!(a, IpCidr::Ipv6(_)))
-                .count()
-                == 0
-        {
-            return Ipv6Address::LOOPBACK;
+            true
         }
 
-        let mut candidate = self
-            .ip_addrs
-            .iter()
-            .find_map(|a| match a {
-                #[cfg(feature = "proto-ipv4")]
-                IpCidr::Ipv4(_) => None,
-                IpCidr::Ipv6(a) => Some(a),
-            })
-            .unwrap(); // NOTE: we check above that there is at least one IPv6 address.
-
-        for addr in self.ip_addrs.iter().filter_map(|a| match a {
+        let addrs = self.ip_addrs.iter().filter_map(|a| match a {
             #[cfg(feature = "proto-ipv4")]
             IpCidr::Ipv4(_) => None,
-            #[cfg(feature = "proto-ipv6")]
             IpCidr::Ipv6(a) => Some(a),
-        }) {
-            if !is_candidate_source_address(dst_addr, &addr.address()) {
-                continue;
-            }
+        });
 
-            // Rule 1: prefer the address that is the same as the output destination address.
-            if candidate.address() != *dst_addr && addr.address() == *dst_addr {
-                candidate = addr;
-            }
+        // If the destination address is a loopback address, or when there are no IPv6 addresses in
+        // the interface, then the loopback address is the only candidate source address.
+        let first = match addrs.clone().next() {
+            Some(first) if !dst_addr.is_loopback() => first,
+            _ => return Ipv6Address::LOOPBACK,
+        };
 
-            // Rule 2: prefer appropriate scope.
-            if (candidate.address().multicast_scope() as u8)
-                < (addr.address().multicast_scope() as u8)
-            {
-                if (candidate.address().multicast_scope() as u8)
-                    < (dst_addr.multicast_scope() as u8)
+        let mut candidate: Option<&Ipv6Cidr> = None;
+        for addr in addrs.filter(|a| is_candidate_source_address(dst_addr, &a.address())) {
+            candidate = match candidate {
+                Some(candidate)
+                    if self.compare_source_addrs(dst_addr, candidate, addr) != Ordering::Less =>
                 {
-                    candidate = addr;
+                    Some(candidate)
                 }
-            } else if (addr.address().multicast_scope() as u8) > (dst_addr.multicast_scope() as u8)
-            {
-                candidate = addr;
+                _ => Some(addr),
+            };
+        }
+
+        candidate.unwrap_or(first).address()
+    }
+
+    /// Compare two candidate source addresses for `dst_addr`, using the rules of
+    /// RFC 6724 § 5. `Ordering::Greater` means that `a` is preferred over `b`.
+    fn compare_source_addrs(&self, dst_addr: &Ipv6Address, a: &Ipv6Cidr, b: &Ipv6Cidr) -> Ordering {
+        let dst = IpAddress::Ipv6(*dst_addr);
+        let (sa, sb) = (IpAddress::Ipv6(a.address()), IpAddress::Ipv6(b.address()));
+
+        // Rule 1: prefer the address that is the same as the destination address.
+        if (sa == dst) != (sb == dst) {
+            return (sa == dst).cmp(&(sb == dst));
+        }
+
+        // Rule 2: prefer appropriate scope.
+        let (scope_a, scope_b, scope_d) =
+            (policy::scope(&sa), policy::scope(&sb), policy::scope(&dst));
+        if scope_a < scope_b {
+            return if scope_a < scope_d {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            };
+        }
+        if scope_b < scope_a {
+            return if scope_b < scope_d {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        // Rule 3: avoid deprecated addresses.
+        #[cfg(feature = "proto-ipv6-temporary-addresses")]
+        {
+            let deprecated_a = self.is_ipv6_deprecated(&a.address());
+            let deprecated_b = self.is_ipv6_deprecated(&b.address());
+            if deprecated_a != deprecated_b {
+                return deprecated_b.cmp(&deprecated_a);
             }
+        }
 
-            // Rule 3: avoid deprecated addresses (TODO)
-            // Rule 4: prefer home addresses (TODO)
-            // Rule 5: prefer outgoing interfaces (TODO)
-            // Rule 5.5: prefer addresses in a prefix advertises by the next-hop (TODO).
-            // Rule 6: prefer matching label (TODO)
-            // Rule 7: prefer temporary addresses (TODO)
-            // Rule 8: use longest matching prefix
-            if common_prefix_length(candidate, dst_addr) < common_prefix_length(addr, dst_addr) {
-                candidate = addr;
+        // Rule 4: prefer home addresses. Mobile IPv6 is not supported, so there are no home
+        // or care-of addresses.
+        // Rule 5: prefer outgoing interface. All the candidates belong to this interface.
+        // Rule 5.5: prefer addresses in a prefix advertised by the next-hop. Router
+        // advertisements are not processed, so this is not known.
+
+        // Rule 6: prefer matching label.
+        let label_d = self.policy_table.label(&dst);
+        let matching_a = self.policy_table.label(&sa) == label_d;
+        let matching_b = self.policy_table.label(&sb) == label_d;
+        if matching_a != matching_b {
+            return matching_a.cmp(&matching_b);
+        }
+
+        // Rule 7: prefer temporary addresses.
+        #[cfg(feature = "proto-ipv6-temporary-addresses")]
+        {
+            let temporary_a = self.is_ipv6_temporary(&a.address());
+            let temporary_b = self.is_ipv6_temporary(&b.address());
+            if temporary_a != temporary_b {
+                return temporary_a.cmp(&temporary_b);
             }
         }
 
-        candidate.address()
+        // Rule 8: use longest matching prefix.
+        policy::common_prefix_len(&sa, &dst, a.prefix_len()).cmp(&policy::common_prefix_len(
+            &sb,
+            &dst,
+            b.prefix_len(),
+        ))
     }
 
     /// Determine if the given `Ipv6Address` is the solicited node
@@ -171,6 +237,8 @@ impl InterfaceInner {
         sockets: &mut SocketSet,
         meta: PacketMeta,
         ipv6_packet: &Ipv6Packet<&'frame [u8]>,
+        frag: Option<&'frame mut FragmentsBuffer>,
+        loopback: bool,
     ) -> Option<Packet<'frame>> {
         let ipv6_repr = check!(Ipv6Repr::parse(ipv6_packet));
 
@@ -182,21 +250,50 @@ impl InterfaceInner {
 
         let (next_header, ip_payload) = if ipv6_repr.next_header == IpProtocol::HopByHop {
             match self.process_hopbyhop(ipv6_repr, ipv6_packet.payload()) {
-                HopByHopResponse::Discard(e) => return e,
-                HopByHopResponse::Continue(next) => next,
+                ExtHeaderResponse::Discard(e) => return e,
+                ExtHeaderResponse::Continue(next) => next,
             }
         } else {
             (ipv6_repr.next_header, ipv6_packet.payload())
         };
 
+        if self.should_forward_ipv6(&ipv6_repr) {
+            let packet = ipv6_packet.clone().into_inner();
+            let packet = &packet[..ipv6_packet.total_len()];
+            return self.forward_ip(IpRepr::Ipv6(ipv6_repr), packet);
+        }
+
+        #[cfg(feature = "proto-multicast-querier")]
+        let for_querier = self.is_for_mld_querier(&ipv6_repr, next_header, ip_payload);
+        #[cfg(not(feature = "proto-multicast-querier"))]
+        let for_querier = false;
+
+        // Loopback destinations only come from the loopback buffer, the device must never
+        // deliver them, see RFC 4291 § 2.5.3.
+        let for_loopback = loopback && ipv6_repr.dst_addr.is_loopback();
+
         if !self.has_ip_addr(ipv6_repr.dst_addr)
             && !self.has_multicast_group(ipv6_repr.dst_addr)
-            && !ipv6_repr.dst_addr.is_loopback()
+            && !for_loopback
+            && !for_querier
         {
             net_trace!("packet IP address not for this interface");
             return None;
         }
 
+        #[cfg(feature = "proto-ipv6-fragmentation")]
+        let (next_header, ip_payload) = if next_header == IpProtocol::Ipv6Frag {
+            match self.process_ipv6_fragment(ipv6_repr, ipv6_packet.payload(), ip_payload, frag) {
+                ExtHeaderResponse::Discard(e) => return e,
+                ExtHeaderResponse::Continue(next) => next,
+            }
+        } else {
+            (next_header, ip_payload)
+        };
+
+        #[cfg(not(feature = "proto-ipv6-fragmentation"))]
+        let _ = frag;
+
         #[cfg(feature = "socket-raw")]
         let handled_by_raw_socket = self.raw_socket_filter(sockets, &ipv6_repr.into(), ip_payload);
         #[cfg(not(feature = "socket-raw"))]
@@ -216,7 +313,7 @@ impl InterfaceInner {
         &mut self,
         ipv6_repr: Ipv6Repr,
         ip_payload: &'frame [u8],
-    ) -> HopByHopResponse<'frame> {
+    ) -> ExtHeaderResponse<'frame> {
         let param_problem = || {
             let payload_len =
                 icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
@@ -239,6 +336,8 @@ impl InterfaceInner {
         for opt_repr in &hbh_repr.options {
             match opt_repr {
                 Ipv6OptionRepr::Pad1 | Ipv6OptionRepr::PadN(_) => (),
+                // The packets asking routers to look at them are processed like the others.
+                Ipv6OptionRepr::RouterAlert(_) => (),
                 #[cfg(feature = "proto-rpl")]
                 Ipv6OptionRepr::Rpl(_) => {}
 
@@ -246,15 +345,15 @@ impl InterfaceInner {
                     match Ipv6OptionFailureType::from(*type_) {
                         Ipv6OptionFailureType::Skip => (),
                         Ipv6OptionFailureType::Discard => {
-                            return HopByHopResponse::Discard(None);
+                            return ExtHeaderResponse::Discard(None);
                         }
                         Ipv6OptionFailureType::DiscardSendAll => {
-                            return HopByHopResponse::Discard(param_problem());
+                            return ExtHeaderResponse::Discard(param_problem());
                         }
                         Ipv6OptionFailureType::DiscardSendUnicast
                             if !ipv6_repr.dst_addr.is_multicast() =>
                         {
-                            return HopByHopResponse::Discard(param_problem());
+                            return ExtHeaderResponse::Discard(param_problem());
                         }
                         _ => unreachable!(),
                     }
@@ -262,12 +361,222 @@ impl InterfaceInner {
             }
         }
 
-        HopByHopResponse::Continue((
+        ExtHeaderResponse::Continue((
             ext_repr.next_header,
             &ip_payload[ext_repr.header_len() + ext_repr.data.len()..],
         ))
     }
 
+    /// Process an IPv6 Fragment header, reassembling the packet when all the fragments were
+    /// received ([RFC 8200 § 4.5]).
+    ///
+    /// `ipv6_payload` is the payload of the IPv6 packet, while `ip_payload` starts at the
+    /// Fragment header. Overlapping fragments discard the whole packet, as required by
+    /// [RFC 5722].
+    ///
+    /// [RFC 8200 § 4.5]: https://www.rfc-editor.org/rfc/rfc8200#section-4.5
+    /// [RFC 5722]: https://www.rfc-editor.org/rfc/rfc5722
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    fn process_ipv6_fragment<'frame>(
+        &mut self,
+        ipv6_repr: Ipv6Repr,
+        ipv6_payload: &'frame [u8],
+        ip_payload: &'frame [u8],
+        frag: Option<&'frame mut FragmentsBuffer>,
+    ) -> ExtHeaderResponse<'frame> {
+        // The offset of the Fragment header in the IPv6 payload.
+        let header_offset = ipv6_payload.len() - ip_payload.len();
+        let param_problem = |pointer: usize| {
+            if ipv6_repr.dst_addr.is_multicast() {
+                return None;
+            }
+            let payload_len =
+                icmp_reply_payload_len(ipv6_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
+            self.icmpv6_reply(
+                ipv6_repr,
+                Icmpv6Repr::ParamProblem {
+                    reason: Icmpv6ParamProblem::ErroneousHdrField,
+                    pointer: pointer as u32,
+                    header: ipv6_repr,
+                    data: &ipv6_payload[..payload_len],
+                },
+            )
+        };
+
+        let ext_hdr = check!(Ipv6ExtHeader::new_checked(ip_payload));
+        let ext_repr = check!(Ipv6ExtHeaderRepr::parse(&ext_hdr));
+        let frag_hdr = check!(Ipv6FragmentHeader::new_checked(ext_repr.data));
+        let frag_repr = check!(Ipv6FragmentRepr::parse(&frag_hdr));
+        let payload = &ip_payload[ext_repr.header_len() + ext_repr.data.len()..];
+
+        // An atomic fragment is processed as if it was not fragmented (RFC 6946).
+        if frag_repr.frag_offset == 0 && !frag_repr.more_frags {
+            return ExtHeaderResponse::Continue((ext_repr.next_header, payload));
+        }
+
+        // Only the last fragment may have a length that is not a multiple of 8 octets.
+        if frag_repr.more_frags && payload.len() % 8 != 0 {
+            // Point to the Payload Length field of the IPv6 header.
+            return ExtHeaderResponse::Discard(param_problem(4));
+        }
+
+        let offset = frag_repr.frag_offset as usize * 8;
+        if offset + payload.len() > u16::MAX as usize {
+            // Point to the Fragment Offset field of the Fragment header.
+            return ExtHeaderResponse::Discard(param_problem(
+                ipv6_repr.buffer_len() + header_offset + 2,
+            ));
+        }
+
+        let frag = match frag {
+            Some(frag) => frag,
+            None => {
+                net_debug!("IPv6 fragments cannot be reassembled on this medium");
+                return ExtHeaderResponse::Discard(None);
+            }
+        };
+
+        let key = FragKey::Ipv6(frag_hdr.get_key(&ipv6_repr));
+        let f = match frag.assembler.get(&key, self.now + frag.reassembly_timeout) {
+            Ok(f) => f,
+            Err(_) => {
+                net_debug!("No available packet assembler for fragmented packet");
+                return ExtHeaderResponse::Discard(None);
+            }
+        };
+
+        // The packet is reassembled behind an IPv6 header, which holds the Next Header value of
+        // the first fragment. The fragmentable part starts right after it.
+        let offset = IPV6_HEADER_LEN + offset;
+
+        if f.overlaps(offset, payload.len()) {
+            net_debug!("overlapping IPv6 fragment, dropping the packet");
+            f.reset();
+            return ExtHeaderResponse::Discard(None);
+        }
+
+        if !frag_repr.more_frags {
+            // This is the last fragment, so we know the total size.
+            if let Err(e) = f.set_total_size(offset + payload.len()) {
+                net_debug!("fragmentation error: {:?}", e);
+                f.reset();
+                return ExtHeaderResponse::Discard(None);
+            }
+        }
+
+        if frag_repr.frag_offset == 0 {
+            let mut header = [0u8; IPV6_HEADER_LEN];
+            Ipv6Repr {
+                next_header: ext_repr.next_header,
+                // NOTE: the payload length is not used after the packet is reassembled.
+                payload_len: 0,
+                ..ipv6_repr
+            }
+            .emit(&mut Ipv6Packet::new_unchecked(&mut header[..]));
+
+            if let Err(e) = f.add(&header, 0) {
+                net_debug!("fragmentation error: {:?}", e);
+                f.reset();
+                return ExtHeaderResponse::Discard(None);
+            }
+        }
+
+        if let Err(e) = f.add(payload, offset) {
+            net_debug!("fragmentation error: {:?}", e);
+            f.reset();
+            return ExtHeaderResponse::Discard(None);
+        }
+
+        match f.assemble() {
+            Some(packet) => {
+                net_trace!("IPv6: fragmented packet now complete");
+                ExtHeaderResponse::Continue((
+                    Ipv6Packet::new_unchecked(packet).next_header(),
+                    &packet[IPV6_HEADER_LEN..],
+                ))
+            }
+            None => ExtHeaderResponse::Discard(None),
+        }
+    }
+
+    /// Split the Unfragmentable Part off an emitted IPv6 packet ([RFC 8200 § 4.5]).
+    ///
+    /// The Next Header field of the last header of the Unfragmentable Part is changed to point
+    /// to a Fragment header. The length of the Unfragmentable Part and the Next Header value of
+    /// the Fragmentable Part are returned, or `None` if the packet cannot be fragmented.
+    ///
+    /// [RFC 8200 § 4.5]: https://www.rfc-editor.org/rfc/rfc8200#section-4.5
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    pub(super) fn split_ipv6_unfragmentable(packet: &mut [u8]) -> Option<(usize, IpProtocol)> {
+        // The offset of the Next Header field that is to be changed.
+        let mut next_header_offset = 6;
+        let mut next_header = IpProtocol::from(packet[next_header_offset]);
+        let mut len = IPV6_HEADER_LEN;
+
+        while matches!(next_header, IpProtocol::HopByHop | IpProtocol::Ipv6Route) {
+            let ext_hdr = Ipv6ExtHeader::new_checked(&packet[len..]).ok()?;
+            next_header_offset = len;
+            next_header = ext_hdr.next_header();
+            len += (ext_hdr.header_len() as usize + 1) * 8;
+        }
+
+        packet[next_header_offset] = IpProtocol::Ipv6Frag.into();
+        Some((len, next_header))
+    }
+
+    /// Transmit the next fragment of the IPv6 packet held by the fragmenter.
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    pub(super) fn dispatch_ipv6_frag<Tx: TxToken>(&mut self, tx_token: Tx, frag: &mut Fragmenter) {
+        let unfragmentable_len = frag.ipv6.unfragmentable_len;
+        let header_len = unfragmentable_len + 8;
+
+        // Every fragment, except the last one, carries a multiple of 8 octets.
+        let max_payload_len = (self.ip_mtu() - header_len) & !7;
+        let payload_len = (frag.packet_len - frag.sent_bytes).min(max_payload_len);
+        let more_frags = (frag.packet_len - frag.sent_bytes) != payload_len;
+        let frag_offset = frag.sent_bytes - unfragmentable_len;
+        let ip_len = header_len + payload_len;
+
+        #[allow(unused_mut)]
+        let mut tx_len = ip_len;
+        #[cfg(feature = "medium-ethernet")]
+        if matches!(self.caps.medium, Medium::Ethernet) {
+            tx_len += EthernetFrame::<&[u8]>::header_len();
+        }
+
+        tx_token.consume(tx_len, |#[allow(unused_mut)] mut tx_buffer| {
+            #[cfg(feature = "medium-ethernet")]
+            if matches!(self.caps.medium, Medium::Ethernet) {
+                let mut frame = EthernetFrame::new_unchecked(&mut tx_buffer[..]);
+                frame.set_src_addr(self.hardware_addr.ethernet_or_panic());
+                frame.set_dst_addr(frag.ipv6.dst_hardware_addr);
+                frame.set_ethertype(EthernetProtocol::Ipv6);
+                tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
+            }
+
+            // Copy the Unfragmentable Part and fix up the payload length.
+            tx_buffer[..unfragmentable_len].copy_from_slice(&frag.buffer[..unfragmentable_len]);
+            Ipv6Packet::new_unchecked(&mut tx_buffer[..])
+                .set_payload_len((ip_len - IPV6_HEADER_LEN) as u16);
+
+            let mut ext_hdr = Ipv6ExtHeader::new_unchecked(&mut tx_buffer[unfragmentable_len..]);
+            ext_hdr.set_next_header(frag.ipv6.next_header);
+            ext_hdr.set_header_len(0);
+            Ipv6FragmentRepr {
+                frag_offset: (frag_offset / 8) as u16,
+                more_frags,
+                ident: frag.ipv6.ident,
+            }
+            .emit(&mut Ipv6FragmentHeader::new_unchecked(
+                &mut tx_buffer[unfragmentable_len + 2..header_len],
+            ));
+
+            tx_buffer[header_len..ip_len]
+                .copy_from_slice(&frag.buffer[frag.sent_bytes..][..payload_len]);
+            frag.sent_bytes += payload_len;
+        })
+    }
+
     /// Given the next header value forward the payload onto the correct process
     /// function.
     fn process_nxt_hdr<'frame>(
@@ -291,6 +600,15 @@ impl InterfaceInner {
                 ip_payload,
             ),
 
+            #[cfg(feature = "socket-udp")]
+            IpProtocol::UdpLite => self.process_udp_lite(
+                sockets,
+                meta,
+                handled_by_raw_socket,
+                ipv6_repr.into(),
+                ip_payload,
+            ),
+
             #[cfg(feature = "socket-tcp")]
             IpProtocol::Tcp => self.process_tcp(sockets, ipv6_repr.into(), ip_payload),
 
@@ -338,7 +656,9 @@ impl InterfaceInner {
                 .filter_map(|i| IcmpSocket::downcast_mut(&mut i.socket))
             {
                 if icmp_socket.accepts_v6(self, &ip_repr, &icmp_repr) {
-                    icmp_socket.process_v6(self, &ip_repr, &icmp_repr);
+                    if icmp_socket.passes_filter(ip_payload) {
+                        icmp_socket.process_v6(self, &ip_repr, &icmp_repr);
+                    }
                     handled_by_icmp_socket = true;
                 }
             }
@@ -373,6 +693,13 @@ impl InterfaceInner {
                 Medium::Ip => None,
             },
 
+            // Keep track of the listeners of the link if we are a querier
+            #[cfg(feature = "proto-multicast-querier")]
+            Icmpv6Repr::Mld(repr) => {
+                self.multicast_querier_process_mld(&ip_repr, &repr);
+                None
+            }
+
             // Don't report an error if a packet with unknown type
             // has been handled by an ICMP socket
             #[cfg(feature = "socket-icmp")]
@@ -435,6 +762,8 @@ impl InterfaceInner {
                         dst_addr: ip_repr.src_addr,
                         next_header: IpProtocol::Icmpv6,
                         hop_limit: 0xff,
+                        dscp: 0,
+                        ecn: 0,
                         payload_len: advert.buffer_len(),
                     };
                     Some(Packet::new_ipv6(ip_repr, IpPayload::Icmpv6(advert)))
@@ -442,6 +771,40 @@ impl InterfaceInner {
                     None
                 }
             }
+            NdiscRepr::Redirect {
+                target_addr,
+                dest_addr,
+                lladdr,
+                ..
+            } => {
+                // Validate the redirect according to RFC 4861 § 8.1. The hop limit was already
+                // checked by the caller.
+                if !ip_repr.src_addr.is_link_local() {
+                    net_debug!("redirect: source {} is not link-local", ip_repr.src_addr);
+                    return None;
+                }
+                if !target_addr.is_link_local() && target_addr != dest_addr {
+                    net_debug!("redirect: invalid target {}", target_addr);
+                    return None;
+                }
+
+                if !self.process_redirect(
+                    ip_repr.src_addr.into(),
+                    dest_addr.into(),
+                    target_addr.into(),
+                ) {
+                    return None;
+                }
+
+                if let Some(lladdr) = lladdr {
+                    let lladdr = check!(lladdr.parse(self.caps.medium));
+                    if lladdr.is_unicast() {
+                        self.neighbor_cache
+                            .fill(target_addr.into(), lladdr, self.now);
+                    }
+                }
+                None
+            }
             _ => None,
         }
     }
@@ -466,10 +829,112 @@ impl InterfaceInner {
             next_header: IpProtocol::Icmpv6,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
         };
         Some(Packet::new_ipv6(
             ipv6_reply_repr,
             IpPayload::Icmpv6(icmp_repr),
         ))
     }
+
+    /// Build an ICMPv6 error about a packet that was not addressed to us, sent from
+    /// our own address.
+    pub(super) fn icmpv6_error<'frame, 'icmp: 'frame>(
+        &self,
+        ipv6_repr: Ipv6Repr,
+        icmp_repr: Icmpv6Repr<'icmp>,
+    ) -> Option<Packet<'frame>> {
+        if !ipv6_repr.src_addr.is_unicast() {
+            return None;
+        }
+
+        let ipv6_reply_repr = Ipv6Repr {
+            src_addr: self.get_source_address_ipv6(&ipv6_repr.src_addr),
+            dst_addr: ipv6_repr.src_addr,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+        };
+        Some(Packet::new_ipv6(
+            ipv6_reply_repr,
+            IpPayload::Icmpv6(icmp_repr),
+        ))
+    }
+
+    /// Return an MLDv2 query, general if `group_addr` is unspecified and
+    /// multicast-address-specific otherwise.
+    ///
+    /// The query carries a Router Alert option in a Hop-by-Hop header, see RFC 3810 § 5,
+    /// so it is emitted in `buffer` as a whole.
+    #[cfg(feature = "proto-multicast-querier")]
+    pub(super) fn mld_query_packet<'a>(
+        &self,
+        buffer: &'a mut [u8],
+        group_addr: Ipv6Address,
+        max_resp_time: Duration,
+        qrv: u8,
+        qqic: u8,
+    ) -> Option<Packet<'a>> {
+        let src_addr = self.ipv6_link_local_addr()?;
+        let dst_addr = if group_addr.is_unspecified() {
+            Ipv6Address::LINK_LOCAL_ALL_NODES
+        } else {
+            group_addr
+        };
+
+        let (hbh_buffer, icmp_buffer) = buffer.split_at_mut(8);
+        let hbh_repr = Ipv6ExtHeaderRepr {
+            next_header: IpProtocol::Icmpv6,
+            length: 0,
+            data: &[],
+        };
+        hbh_repr.emit(&mut Ipv6ExtHeader::new_unchecked(&mut *hbh_buffer));
+        let mut options = &mut hbh_buffer[hbh_repr.header_len()..];
+        for option in [Ipv6OptionRepr::RouterAlert(0), Ipv6OptionRepr::PadN(0)] {
+            let (option_buffer, rest) = options.split_at_mut(option.buffer_len());
+            option.emit(&mut Ipv6Option::new_unchecked(option_buffer));
+            options = rest;
+        }
+
+        let icmp_repr = Icmpv6Repr::Mld(MldRepr::Query {
+            // Codes below 32768 are the maximum response time in milliseconds.
+            max_resp_code: max_resp_time.total_millis().min(0x7fff) as u16,
+            mcast_addr: group_addr,
+            s_flag: false,
+            qrv,
+            qqic,
+            num_srcs: 0,
+            data: &[],
+        });
+        icmp_repr.emit(
+            &src_addr,
+            &dst_addr,
+            &mut Icmpv6Packet::new_unchecked(&mut icmp_buffer[..icmp_repr.buffer_len()]),
+            &self.caps.checksum,
+        );
+
+        let len = hbh_buffer.len() + icmp_repr.buffer_len();
+        let ipv6_repr = Ipv6Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::HopByHop,
+            payload_len: len,
+            hop_limit: 1,
+            dscp: 0,
+            ecn: 0,
+        };
+        Some(Packet::new_ipv6(ipv6_repr, IpPayload::Raw(&buffer[..len])))
+    }
+
+    /// Get the first link-local IPv6 address of the interface.
+    #[cfg(feature = "proto-multicast-querier")]
+    pub(super) fn ipv6_link_local_addr(&self) -> Option<Ipv6Address> {
+        self.ip_addrs.iter().find_map(|addr| match *addr {
+            IpCidr::Ipv6(cidr) if cidr.address().is_link_local() => Some(cidr.address()),
+            _ => None,
+        })
+    }
 }
@@ -0,0 +1,282 @@
+use super::*;
+
+// Default lifetimes and timing constants, see RFC 8981 § 3.8.
+const TEMP_VALID_LIFETIME: Duration = Duration::from_secs(2 * 24 * 60 * 60);
+const TEMP_PREFERRED_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long before the deprecation of a temporary address a new one is generated.
+/// smoltcp does not run Duplicate Address Detection, so this is only the 2 seconds
+/// the RFC adds on top of the time needed for it.
+const REGEN_ADVANCE: Duration = Duration::from_secs(2);
+const TEMP_IDGEN_RETRIES: u8 = 3;
+
+/// Prefix length of the prefixes temporary addresses are generated for.
+const PREFIX_LEN: u8 = 64;
+
+/// A temporary address assigned to the interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct TemporaryAddress {
+    addr: Ipv6Address,
+    /// The address is deprecated from this point on.
+    preferred_until: Instant,
+    /// The address is removed from the interface at this point.
+    valid_until: Instant,
+    /// Whether a new temporary address has already been generated to replace this one.
+    regenerated: bool,
+}
+
+/// State of the IPv6 temporary address generation (RFC 8981).
+#[derive(Debug)]
+pub(crate) struct Ipv6Temporary {
+    enabled: bool,
+    valid_lifetime: Duration,
+    preferred_lifetime: Duration,
+    // Every temporary address is also in `ip_addrs`, so there is always enough room.
+    addrs: Vec<TemporaryAddress, IFACE_MAX_ADDR_COUNT>,
+}
+
+impl Ipv6Temporary {
+    pub(crate) const fn new() -> Self {
+        Self {
+            enabled: false,
+            valid_lifetime: TEMP_VALID_LIFETIME,
+            preferred_lifetime: TEMP_PREFERRED_LIFETIME,
+            addrs: Vec::new(),
+        }
+    }
+
+    fn get(&self, addr: &Ipv6Address) -> Option<&TemporaryAddress> {
+        self.addrs.iter().find(|temp| temp.addr == *addr)
+    }
+
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        self.addrs
+            .iter()
+            .map(|temp| {
+                if temp.regenerated {
+                    temp.valid_until
+                } else {
+                    temp.valid_until.min(temp.preferred_until - REGEN_ADVANCE)
+                }
+            })
+            .min()
+    }
+}
+
+impl Interface {
+    /// Start generating temporary IPv6 addresses, as described in RFC 8981.
+    ///
+    /// For every global or unique local `/64` address of the interface, a temporary address
+    /// with a random interface identifier is added to the [`ip_addrs`](Self::ip_addrs) of
+    /// the interface. Temporary addresses are preferred over the other addresses when
+    /// selecting the source address of outgoing packets, so that the stable interface
+    /// identifier is not exposed to the destinations.
+    ///
+    /// Before a temporary address reaches the end of its preferred lifetime, a new one is
+    /// generated and the old one is deprecated: it is no longer used for new connections,
+    /// but is kept until the end of its valid lifetime for the existing ones.
+    pub fn enable_ipv6_temporary_addresses(&mut self) {
+        self.inner.ipv6_temporary.enabled = true;
+        self.inner.ipv6_temporary_update();
+    }
+
+    /// Stop generating temporary IPv6 addresses, and remove the existing ones from the
+    /// addresses of the interface.
+    pub fn disable_ipv6_temporary_addresses(&mut self) {
+        self.inner.ipv6_temporary.enabled = false;
+        self.inner.ipv6_temporary_update();
+    }
+
+    /// Set the valid and preferred lifetimes of the temporary addresses generated from now on.
+    ///
+    /// The defaults are 2 days and 1 day, see RFC 8981 § 3.8.
+    ///
+    /// # Panics
+    /// This function panics if the preferred lifetime is longer than the valid lifetime, or
+    /// too short for new addresses to be generated before the old ones are deprecated.
+    pub fn set_ipv6_temporary_lifetimes(
+        &mut self,
+        valid_lifetime: Duration,
+        preferred_lifetime: Duration,
+    ) {
+        assert!(preferred_lifetime <= valid_lifetime);
+        // The preferred lifetime of an address is reduced by up to 40%, see
+        // `ipv6_temporary_generate`.
+        assert!(preferred_lifetime * 3 / 5 > REGEN_ADVANCE);
+
+        self.inner.ipv6_temporary.valid_lifetime = valid_lifetime;
+        self.inner.ipv6_temporary.preferred_lifetime = preferred_lifetime;
+    }
+
+    /// Get the temporary IPv6 addresses of the interface, including the deprecated ones.
+    pub fn ipv6_temporary_addrs(&self) -> impl Iterator<Item = Ipv6Address> + '_ {
+        self.inner.ipv6_temporary.addrs.iter().map(|temp| temp.addr)
+    }
+}
+
+impl InterfaceInner {
+    /// Check whether `addr` is a temporary address of the interface.
+    pub(super) fn is_ipv6_temporary(&self, addr: &Ipv6Address) -> bool {
+        self.ipv6_temporary.get(addr).is_some()
+    }
+
+    /// Check whether `addr` is a temporary address that reached the end of its preferred
+    /// lifetime.
+    pub(super) fn is_ipv6_deprecated(&self, addr: &Ipv6Address) -> bool {
+        self.ipv6_temporary
+            .get(addr)
+            .map_or(false, |temp| self.now >= temp.preferred_until)
+    }
+
+    /// Remove the expired temporary addresses, and generate the missing ones.
+    pub(super) fn ipv6_temporary_update(&mut self) {
+        let mut removed: Vec<Ipv6Address, IFACE_MAX_ADDR_COUNT> = Vec::new();
+        let public_prefixes = self.ipv6_public_prefixes();
+        let now = self.now;
+        let enabled = self.ipv6_temporary.enabled;
+        let ip_addrs = &self.ip_addrs;
+
+        // Forget the addresses that were removed by the user, and the ones that expired or
+        // whose prefix is gone.
+        self.ipv6_temporary.addrs.retain(|temp| {
+            let assigned = ip_addrs
+                .iter()
+                .any(|cidr| cidr.address() == temp.addr.into());
+            let keep = assigned
+                && enabled
+                && now < temp.valid_until
+                && public_prefixes.iter().any(|p| p.contains_addr(&temp.addr));
+            if assigned && !keep {
+                // NOTE(unwrap): there are never more temporary addresses than addresses.
+                removed.push(temp.addr).unwrap();
+            }
+            keep
+        });
+
+        if !removed.is_empty() {
+            self.ip_addrs
+                .retain(|cidr| !removed.iter().any(|addr| cidr.address() == (*addr).into()));
+            self.flush_neighbor_cache();
+            #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+            self.cancel_stale_announcements();
+        }
+
+        if !enabled {
+            return;
+        }
+
+        let mut added: Vec<IpAddress, IFACE_MAX_ADDR_COUNT> = Vec::new();
+        for prefix in public_prefixes {
+            let mut needed = true;
+            for temp in self.ipv6_temporary.addrs.iter_mut() {
+                if !prefix.contains_addr(&temp.addr) || temp.regenerated {
+                    continue;
+                }
+                if now >= temp.preferred_until - REGEN_ADVANCE {
+                    // Even if generating its replacement fails, only try once.
+                    temp.regenerated = true;
+                } else {
+                    needed = false;
+                }
+            }
+
+            if needed {
+                if let Some(addr) = self.ipv6_temporary_generate(&prefix) {
+                    // NOTE(unwrap): there are never more new addresses than addresses.
+                    added.push(addr.into()).unwrap();
+                }
+            }
+        }
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        self.schedule_announcements(&added);
+    }
+
+    /// Generate a temporary address in `prefix`, see RFC 8981 § 3.3 and § 3.4.
+    fn ipv6_temporary_generate(&mut self, prefix: &Ipv6Cidr) -> Option<Ipv6Address> {
+        let mut addr = None;
+        for _ in 0..TEMP_IDGEN_RETRIES {
+            let mut bytes = [0; 16];
+            bytes[..8].copy_from_slice(&prefix.address().as_bytes()[..8]);
+            bytes[8..12].copy_from_slice(&self.rand.rand_u32().to_be_bytes());
+            bytes[12..].copy_from_slice(&self.rand.rand_u32().to_be_bytes());
+            let candidate = Ipv6Address::from_bytes(&bytes);
+
+            if !Self::is_reserved_interface_id(&bytes[8..]) && !self.has_ip_addr(candidate) {
+                addr = Some(candidate);
+                break;
+            }
+        }
+        let addr = match addr {
+            Some(addr) => addr,
+            None => {
+                net_debug!(
+                    "ipv6 temporary: no interface identifier found in {}",
+                    prefix
+                );
+                return None;
+            }
+        };
+
+        if self
+            .ip_addrs
+            .push(IpCidr::Ipv6(Ipv6Cidr::new(addr, PREFIX_LEN)))
+            .is_err()
+        {
+            net_debug!("ipv6 temporary: no space left for an address in {}", prefix);
+            return None;
+        }
+
+        // Spread the regeneration of the addresses of the hosts of the link, by reducing
+        // the preferred lifetime by a random factor of up to 40%.
+        let valid_lifetime = self.ipv6_temporary.valid_lifetime;
+        let preferred_lifetime = self.ipv6_temporary.preferred_lifetime;
+        let max_desync = (preferred_lifetime * 2 / 5).total_millis();
+        let desync = Duration::from_millis(self.rand.rand_u32() as u64 % (max_desync + 1));
+
+        // NOTE(unwrap): there are never more temporary addresses than addresses.
+        self.ipv6_temporary
+            .addrs
+            .push(TemporaryAddress {
+                addr,
+                preferred_until: self.now + preferred_lifetime - desync,
+                valid_until: self.now + valid_lifetime,
+                regenerated: false,
+            })
+            .unwrap();
+        net_debug!("ipv6 temporary: added address {}", addr);
+        Some(addr)
+    }
+
+    /// Return the prefixes of the global and unique local `/64` addresses of the interface
+    /// that are not temporary themselves.
+    fn ipv6_public_prefixes(&self) -> Vec<Ipv6Cidr, IFACE_MAX_ADDR_COUNT> {
+        let mut prefixes: Vec<Ipv6Cidr, IFACE_MAX_ADDR_COUNT> = Vec::new();
+        for cidr in self.ip_addrs.iter() {
+            let cidr = match cidr {
+                IpCidr::Ipv6(cidr) if cidr.prefix_len() == PREFIX_LEN => cidr,
+                #[allow(unreachable_patterns)]
+                _ => continue,
+            };
+            let addr = cidr.address();
+            if !(addr.is_global_unicast() || addr.is_unique_local())
+                || self.is_ipv6_temporary(&addr)
+            {
+                continue;
+            }
+
+            if !prefixes.iter().any(|prefix| prefix.contains_addr(&addr)) {
+                // NOTE(unwrap): there are never more prefixes than addresses.
+                prefixes.push(*cidr).unwrap();
+            }
+        }
+        prefixes
+    }
+
+    /// Check whether an interface identifier is reserved, see RFC 5453 and RFC 8981 § 3.3.1.
+    fn is_reserved_interface_id(iid: &[u8]) -> bool {
+        // The Subnet-Router anycast identifier, and the reserved subnet anycast identifiers.
+        iid.iter().all(|b| *b == 0)
+            || (iid[..7] == [0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff] && iid[7] >= 0x80)
+    }
+}
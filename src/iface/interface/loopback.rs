@@ -0,0 +1,148 @@
+use super::*;
+
+use crate::config::IFACE_LOOPBACK_BUFFER_SIZE;
+
+/// Length of the prefix stored in front of every packet of the loopback buffer.
+const LEN_PREFIX: usize = 2;
+
+/// Packets sent to the interface itself, waiting to be processed as received packets.
+///
+/// The packets are stored back to back, each one behind its length as a big endian `u16`.
+pub(crate) struct Loopback {
+    buffer: [u8; IFACE_LOOPBACK_BUFFER_SIZE],
+    len: usize,
+}
+
+impl Loopback {
+    pub(crate) const fn new() -> Self {
+        Self {
+            buffer: [0; IFACE_LOOPBACK_BUFFER_SIZE],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Queue a packet, returning `false` if there is no room left for it until the queued
+    /// packets are processed. Packets larger than the whole buffer are dropped, TCP sockets
+    /// avoid sending them by sizing their segments with `ip_mtu_to`.
+    pub(crate) fn enqueue(&mut self, inner: &InterfaceInner, packet: Packet) -> bool {
+        let packet_len = LEN_PREFIX + packet.ip_repr().buffer_len();
+        if packet_len > self.buffer.len() {
+            net_debug!(
+                "loopback: packet to {} too large, dropping",
+                packet.ip_repr().dst_addr()
+            );
+            return true;
+        }
+        if packet_len > self.buffer.len() - self.len {
+            return false;
+        }
+
+        self.len += inner.loopback_emit(&mut self.buffer[self.len..], packet);
+        true
+    }
+}
+
+impl Interface {
+    /// Process the oldest packet of the loopback buffer, if any.
+    pub(super) fn loopback_ingress<D>(
+        &mut self,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        if self.loopback.is_empty() {
+            return false;
+        }
+
+        let (queued, free) = self.loopback.buffer.split_at_mut(self.loopback.len);
+        let packet_len = u16::from_be_bytes([queued[0], queued[1]]) as usize;
+        let packet = &queued[LEN_PREFIX..LEN_PREFIX + packet_len];
+
+        // Responses to the interface itself are queued behind the packets already there.
+        let mut queued_len = 0;
+        if let Some(response) = self.inner.process_ip(
+            sockets,
+            PacketMeta::default(),
+            packet,
+            &mut self.fragments,
+            true,
+        ) {
+            if self
+                .inner
+                .is_loopback_destination(&response.ip_repr().dst_addr())
+            {
+                queued_len = self.inner.loopback_emit(free, response);
+            } else if let Some(tx_token) = device.transmit(self.inner.now) {
+                if let Err(err) = self.inner.dispatch_ip(
+                    tx_token,
+                    PacketMeta::default(),
+                    response,
+                    &mut self.fragmenter,
+                ) {
+                    net_debug!("Failed to send response: {:?}", err);
+                }
+            } else {
+                net_debug!("failed to transmit IP: device exhausted");
+            }
+        }
+
+        let end = self.loopback.len + queued_len;
+        self.loopback
+            .buffer
+            .copy_within(LEN_PREFIX + packet_len..end, 0);
+        self.loopback.len = end - LEN_PREFIX - packet_len;
+        true
+    }
+}
+
+impl InterfaceInner {
+    /// Check whether packets to `addr` are delivered through the loopback buffer, instead
+    /// of being transmitted by the device.
+    pub(crate) fn is_loopback_destination(&self, addr: &IpAddress) -> bool {
+        addr.is_loopback() || self.has_ip_addr(*addr)
+    }
+
+    /// Return the largest IP packet that can be sent to `addr`, which is limited by the size
+    /// of the loopback buffer for the packets delivered through it.
+    #[cfg(feature = "socket-tcp")]
+    pub(crate) fn ip_mtu_to(&self, addr: &IpAddress) -> usize {
+        if self.is_loopback_destination(addr) {
+            self.ip_mtu().min(IFACE_LOOPBACK_BUFFER_SIZE - LEN_PREFIX)
+        } else {
+            self.ip_mtu()
+        }
+    }
+
+    /// Emit an IP packet with its length prefix into `buffer`, returning the amount of bytes
+    /// written, or 0 if the packet does not fit.
+    fn loopback_emit(&self, buffer: &mut [u8], packet: Packet) -> usize {
+        let ip_repr = packet.ip_repr();
+        let packet_len = ip_repr.buffer_len();
+        if packet_len > u16::MAX as usize || buffer.len() < LEN_PREFIX + packet_len {
+            net_debug!(
+                "loopback: buffer full, dropping packet to {}",
+                ip_repr.dst_addr()
+            );
+            return 0;
+        }
+
+        // The checksums are verified when the packet is processed, so they are always
+        // computed, whatever the device offloads.
+        let mut caps = self.caps.clone();
+        caps.checksum = ChecksumCapabilities::default();
+
+        buffer[..LEN_PREFIX].copy_from_slice(&(packet_len as u16).to_be_bytes());
+        let buffer = &mut buffer[LEN_PREFIX..LEN_PREFIX + packet_len];
+        ip_repr.emit(&mut *buffer, &caps.checksum);
+        packet.emit_payload(&ip_repr, &mut buffer[ip_repr.header_len()..], &caps);
+
+        net_trace!("loopback: queued packet to {}", ip_repr.dst_addr());
+        LEN_PREFIX + packet_len
+    }
+}
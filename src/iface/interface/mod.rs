@@ -5,15 +5,25 @@
 #[cfg(test)]
 mod tests;
 
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+mod announce;
 #[cfg(feature = "medium-ethernet")]
 mod ethernet;
+mod forward;
 #[cfg(feature = "medium-ieee802154")]
 mod ieee802154;
 
 #[cfg(feature = "proto-ipv4")]
 mod ipv4;
+#[cfg(feature = "proto-ipv4-link-local")]
+mod ipv4_link_local;
 #[cfg(feature = "proto-ipv6")]
 mod ipv6;
+#[cfg(feature = "proto-ipv6-temporary-addresses")]
+mod ipv6_temporary;
+mod loopback;
+#[cfg(feature = "proto-multicast-querier")]
+mod querier;
 #[cfg(feature = "proto-sixlowpan")]
 mod sixlowpan;
 
@@ -25,27 +35,47 @@ mod tcp;
 mod udp;
 
 #[cfg(feature = "proto-igmp")]
-pub use igmp::MulticastError;
+use igmp::MulticastFilter;
+#[cfg(feature = "proto-igmp")]
+pub use igmp::{MulticastError, MulticastFilterMode};
+
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+use announce::Announcer;
+#[cfg(feature = "proto-ipv4-link-local")]
+use ipv4_link_local::Ipv4LinkLocal;
+#[cfg(feature = "proto-ipv6-temporary-addresses")]
+use ipv6_temporary::Ipv6Temporary;
+use loopback::Loopback;
+#[cfg(feature = "proto-multicast-querier")]
+use querier::MulticastQuerier;
 
 use super::packet::*;
 
+#[cfg(feature = "proto-ipv6")]
+use core::cmp::Ordering;
 use core::result::Result;
 use heapless::{LinearMap, Vec};
 
 #[cfg(feature = "_proto-fragmentation")]
 use super::fragmentation::FragKey;
-#[cfg(any(feature = "proto-ipv4", feature = "proto-sixlowpan"))]
+#[cfg(feature = "_proto-fragmentation")]
 use super::fragmentation::PacketAssemblerSet;
 use super::fragmentation::{Fragmenter, FragmentsBuffer};
 
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+use super::destination::Cache as DestinationCache;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
 use super::neighbor::{Answer as NeighborAnswer, Cache as NeighborCache};
-use super::socket_set::SocketSet;
+#[cfg(feature = "proto-ipv6")]
+use super::policy::{self, PolicyTable};
+use super::route::{transport_ports, RuleKey};
+use super::router::{InterfaceId, Router};
+use super::socket_set::{Item, SocketSet};
 use crate::config::{
     IFACE_MAX_ADDR_COUNT, IFACE_MAX_MULTICAST_GROUP_COUNT,
     IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT,
 };
-use crate::iface::Routes;
+use crate::iface::{EgressScheduler, Route, RouteKind, Routes};
 use crate::phy::PacketMeta;
 use crate::phy::{ChecksumCapabilities, Device, DeviceCapabilities, Medium, RxToken, TxToken};
 use crate::rand::Rand;
@@ -80,8 +110,13 @@ pub struct Interface {
     pub(crate) inner: InterfaceInner,
     fragments: FragmentsBuffer,
     fragmenter: Fragmenter,
+    loopback: Loopback,
+    scheduler: EgressScheduler,
 }
 
+/// The device cannot transmit more packets for now.
+struct DeviceExhausted;
+
 /// The device independent part of an Ethernet network interface.
 ///
 /// Separating the device from the data required for processing and dispatching makes
@@ -96,6 +131,10 @@ pub struct InterfaceInner {
 
     #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
     neighbor_cache: NeighborCache,
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    destination_cache: DestinationCache,
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    accept_redirects: bool,
     hardware_addr: HardwareAddress,
     #[cfg(feature = "medium-ieee802154")]
     sequence_no: u8,
@@ -103,6 +142,8 @@ pub struct InterfaceInner {
     pan_id: Option<Ieee802154Pan>,
     #[cfg(feature = "proto-ipv4-fragmentation")]
     ipv4_id: u16,
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    ipv6_frag_ident: u32,
     #[cfg(feature = "proto-sixlowpan")]
     sixlowpan_address_context:
         Vec<SixlowpanAddressContext, IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT>,
@@ -112,11 +153,30 @@ pub struct InterfaceInner {
     #[cfg(feature = "proto-ipv4")]
     any_ip: bool,
     routes: Routes,
+    #[cfg(feature = "proto-ipv6")]
+    policy_table: PolicyTable,
     #[cfg(feature = "proto-igmp")]
-    ipv4_multicast_groups: LinearMap<Ipv4Address, (), IFACE_MAX_MULTICAST_GROUP_COUNT>,
+    ipv4_multicast_groups: LinearMap<Ipv4Address, MulticastFilter, IFACE_MAX_MULTICAST_GROUP_COUNT>,
     /// When to report for (all or) the next multicast group membership via IGMP
     #[cfg(feature = "proto-igmp")]
     igmp_report_state: IgmpReportState,
+    /// Until when IGMPv1 and IGMPv2 queriers are considered present on the network.
+    #[cfg(feature = "proto-igmp")]
+    igmp_v1_querier_present: Option<Instant>,
+    #[cfg(feature = "proto-igmp")]
+    igmp_v2_querier_present: Option<Instant>,
+    #[cfg(feature = "proto-multicast-querier")]
+    multicast_querier: MulticastQuerier,
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    announcer: Announcer,
+    #[cfg(feature = "proto-ipv4-link-local")]
+    ipv4_link_local: Ipv4LinkLocal,
+    #[cfg(feature = "proto-ipv6-temporary-addresses")]
+    ipv6_temporary: Ipv6Temporary,
+    /// The identifier of the interface in the router it is attached to, if any.
+    iface_id: Option<InterfaceId>,
+    /// What routing rules match for the packet being dispatched.
+    rule_key: RuleKey,
 }
 
 /// Configuration structure used for creating a network interface.
@@ -205,6 +265,9 @@ impl Interface {
             }
         }
 
+        #[cfg(feature = "proto-ipv6-fragmentation")]
+        let ipv6_frag_ident = rand.rand_u32();
+
         Interface {
             fragments: FragmentsBuffer {
                 #[cfg(feature = "proto-sixlowpan")]
@@ -216,6 +279,8 @@ impl Interface {
                 reassembly_timeout: Duration::from_secs(60),
             },
             fragmenter: Fragmenter::new(),
+            loopback: Loopback::new(),
+            scheduler: EgressScheduler::new(),
             inner: InterfaceInner {
                 now,
                 caps,
@@ -224,12 +289,32 @@ impl Interface {
                 #[cfg(feature = "proto-ipv4")]
                 any_ip: false,
                 routes: Routes::new(),
+                #[cfg(feature = "proto-ipv6")]
+                policy_table: PolicyTable::default(),
                 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
                 neighbor_cache: NeighborCache::new(),
+                #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+                destination_cache: DestinationCache::new(),
+                #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+                accept_redirects: true,
                 #[cfg(feature = "proto-igmp")]
                 ipv4_multicast_groups: LinearMap::new(),
                 #[cfg(feature = "proto-igmp")]
                 igmp_report_state: IgmpReportState::Inactive,
+                #[cfg(feature = "proto-igmp")]
+                igmp_v1_querier_present: None,
+                #[cfg(feature = "proto-igmp")]
+                igmp_v2_querier_present: None,
+                #[cfg(feature = "proto-multicast-querier")]
+                multicast_querier: MulticastQuerier::new(),
+                #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+                announcer: Announcer::new(),
+                #[cfg(feature = "proto-ipv4-link-local")]
+                ipv4_link_local: Ipv4LinkLocal::new(),
+                #[cfg(feature = "proto-ipv6-temporary-addresses")]
+                ipv6_temporary: Ipv6Temporary::new(),
+                iface_id: None,
+                rule_key: RuleKey::default(),
                 #[cfg(feature = "medium-ieee802154")]
                 sequence_no,
                 #[cfg(feature = "medium-ieee802154")]
@@ -238,6 +323,8 @@ impl Interface {
                 tag,
                 #[cfg(feature = "proto-ipv4-fragmentation")]
                 ipv4_id,
+                #[cfg(feature = "proto-ipv6-fragmentation")]
+                ipv6_frag_ident,
                 #[cfg(feature = "proto-sixlowpan")]
                 sixlowpan_address_context: Vec::new(),
                 rand,
@@ -292,6 +379,9 @@ impl Interface {
 
         InterfaceInner::check_hardware_addr(&addr);
         self.inner.hardware_addr = addr;
+
+        // Let our peers know about the new hardware address.
+        self.announce_ip_addrs();
     }
 
     /// Get the IP addresses of the interface.
@@ -334,12 +424,34 @@ impl Interface {
 
     /// Update the IP addresses of the interface.
     ///
+    /// Newly added addresses are announced to the link, see
+    /// [`set_announce_count`](Self::set_announce_count).
+    ///
     /// # Panics
     /// This function panics if any of the addresses are not unicast.
     pub fn update_ip_addrs<F: FnOnce(&mut Vec<IpCidr, IFACE_MAX_ADDR_COUNT>)>(&mut self, f: F) {
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        let old_addrs = self.inner.ip_addrs.clone();
+
         f(&mut self.inner.ip_addrs);
         InterfaceInner::flush_neighbor_cache(&mut self.inner);
-        InterfaceInner::check_ip_addrs(&self.inner.ip_addrs)
+        InterfaceInner::check_ip_addrs(&self.inner.ip_addrs);
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        {
+            let new_addrs: Vec<IpAddress, IFACE_MAX_ADDR_COUNT> = self
+                .inner
+                .ip_addrs
+                .iter()
+                .map(|cidr| cidr.address())
+                .filter(|addr| !old_addrs.iter().any(|cidr| cidr.address() == *addr))
+                .collect();
+            self.inner.cancel_stale_announcements();
+            self.inner.schedule_announcements(&new_addrs);
+        }
+
+        #[cfg(feature = "proto-ipv6-temporary-addresses")]
+        self.inner.ipv6_temporary_update();
     }
 
     /// Check whether the interface has the given IP address assigned.
@@ -351,10 +463,125 @@ impl Interface {
         &self.inner.routes
     }
 
+    /// Return the route packets for `dst_addr` would use, if any.
+    ///
+    /// Destinations in the network of one of the addresses of the interface use an on-link
+    /// route for that network. Destinations that were redirected use a host route, which
+    /// expires with the redirect. Other destinations use the route picked by
+    /// [`Routes::lookup_route`] for packets sent from the source address that
+    /// [`get_source_address`](Self::get_source_address) selects.
+    pub fn lookup_route(&self, dst_addr: &IpAddress) -> Option<Route> {
+        if let Some(cidr) = self
+            .inner
+            .ip_addrs
+            .iter()
+            .find(|cidr| cidr.contains_addr(dst_addr))
+        {
+            return Some(Route::new_without_gateway(*cidr, RouteKind::OnLink));
+        }
+        if !dst_addr.is_unicast() {
+            return None;
+        }
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        if let Some(destination) = self
+            .inner
+            .destination_cache
+            .lookup(dst_addr, self.inner.now)
+        {
+            let prefix_len = match dst_addr {
+                #[cfg(feature = "proto-ipv4")]
+                IpAddress::Ipv4(_) => 32,
+                #[cfg(feature = "proto-ipv6")]
+                IpAddress::Ipv6(_) => 128,
+            };
+            let cidr = IpCidr::new(*dst_addr, prefix_len);
+            let mut route = if destination.next_hop == *dst_addr {
+                Route::new_without_gateway(cidr, RouteKind::OnLink)
+            } else {
+                Route::new_gateway(cidr, destination.next_hop)
+            };
+            route.expires_at = Some(destination.expires_at);
+            return Some(route);
+        }
+
+        let src_addr = self.inner.get_source_address(dst_addr);
+        self.inner
+            .routes
+            .lookup_route(dst_addr, src_addr.as_ref(), self.inner.now)
+            .copied()
+    }
+
+    /// Return a mutable reference to the route table.
+    ///
+    /// This forgets the routes learnt from redirects.
     pub fn routes_mut(&mut self) -> &mut Routes {
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        self.inner.destination_cache.flush();
         &mut self.inner.routes
     }
 
+    /// Return the policy table used for source and destination address selection.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn policy_table(&self) -> &PolicyTable {
+        &self.inner.policy_table
+    }
+
+    /// Return a mutable reference to the policy table used for source and destination
+    /// address selection.
+    ///
+    /// The table initially holds the default policies of RFC 6724 § 2.1.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn policy_table_mut(&mut self) -> &mut PolicyTable {
+        &mut self.inner.policy_table
+    }
+
+    /// Return the egress scheduler, which orders and rate limits the packets of the sockets.
+    pub fn egress_scheduler(&self) -> &EgressScheduler {
+        &self.scheduler
+    }
+
+    /// Return a mutable reference to the egress scheduler.
+    pub fn egress_scheduler_mut(&mut self) -> &mut EgressScheduler {
+        &mut self.scheduler
+    }
+
+    /// Sort destination addresses, such as the addresses a DNS query resolved to, in the
+    /// order they should be tried in, as described in RFC 6724 § 6.
+    ///
+    /// Destinations that can not be reached come last. The relative order of destinations
+    /// that are equally preferred is kept.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn sort_destination_addrs(&self, addrs: &mut [IpAddress]) {
+        self.inner.sort_destination_addrs(addrs)
+    }
+
+    /// Enable or disable accepting ICMP and ICMPv6 Redirect messages.
+    ///
+    /// When enabled, a redirect received from the router that is the current next hop
+    /// for a destination installs a host route to that destination via the advertised
+    /// router, or directly on the link. These routes are kept in a destination cache
+    /// that is consulted before the route table, and expire after 10 minutes.
+    ///
+    /// Redirects are never accepted by interfaces attached to a [`Router`].
+    ///
+    /// Redirects are accepted by default, disable them for hardened deployments.
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    pub fn set_accept_redirects(&mut self, accept_redirects: bool) {
+        self.inner.accept_redirects = accept_redirects;
+        if !accept_redirects {
+            self.inner.destination_cache.flush();
+        }
+    }
+
+    /// Get whether ICMP and ICMPv6 Redirect messages are accepted.
+    ///
+    /// See [`set_accept_redirects`](Self::set_accept_redirects) for details.
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    pub fn accept_redirects(&self) -> bool {
+        self.inner.accept_redirects
+    }
+
     /// Enable or disable the AnyIP capability.
     ///
     /// AnyIP allowins packets to be received
@@ -407,6 +634,19 @@ impl Interface {
         device: &mut D,
         sockets: &mut SocketSet<'_>,
     ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        self.poll_inner(timestamp, device, sockets, None)
+    }
+
+    fn poll_inner<D>(
+        &mut self,
+        timestamp: Instant,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        mut router: Option<&mut Router<'_>>,
+    ) -> bool
     where
         D: Device + ?Sized,
     {
@@ -415,6 +655,9 @@ impl Interface {
         #[cfg(feature = "_proto-fragmentation")]
         self.fragments.assembler.remove_expired(timestamp);
 
+        #[cfg(feature = "proto-ipv6-temporary-addresses")]
+        self.inner.ipv6_temporary_update();
+
         match self.inner.caps.medium {
             #[cfg(feature = "medium-ieee802154")]
             Medium::Ieee802154 =>
@@ -425,12 +668,15 @@ impl Interface {
                 }
             }
             #[cfg(any(feature = "medium-ethernet", feature = "medium-ip"))]
-            _ =>
-            {
+            _ => {
                 #[cfg(feature = "proto-ipv4-fragmentation")]
                 if self.ipv4_egress(device) {
                     return true;
                 }
+                #[cfg(feature = "proto-ipv6-fragmentation")]
+                if self.ipv6_egress(device) {
+                    return true;
+                }
             }
         }
 
@@ -438,14 +684,34 @@ impl Interface {
 
         loop {
             let mut did_something = false;
-            did_something |= self.socket_ingress(device, sockets);
+            did_something |= self.socket_ingress(device, sockets, router.as_deref_mut());
             did_something |= self.socket_egress(device, sockets);
+            did_something |= self.loopback_ingress(device, sockets);
+
+            if let Some(router) = router.as_deref_mut() {
+                did_something |= self.forward_egress(device, router);
+            }
 
             #[cfg(feature = "proto-igmp")]
             {
                 did_something |= self.igmp_egress(device);
             }
 
+            #[cfg(feature = "proto-multicast-querier")]
+            {
+                did_something |= self.multicast_querier_egress(device);
+            }
+
+            #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+            {
+                did_something |= self.announce_egress(device);
+            }
+
+            #[cfg(feature = "proto-ipv4-link-local")]
+            {
+                did_something |= self.ipv4_link_local_egress(device);
+            }
+
             if did_something {
                 readiness_may_have_changed = true;
             } else {
@@ -472,21 +738,35 @@ impl Interface {
             return Some(Instant::from_millis(0));
         }
 
+        if !self.loopback.is_empty() {
+            return Some(Instant::from_millis(0));
+        }
+
         let inner = &mut self.inner;
+        let scheduler = &self.scheduler;
+
+        let iface_poll_at = inner.poll_at();
 
         sockets
             .items()
             .filter_map(move |item| {
                 let socket_poll_at = item.socket.poll_at(inner);
-                match item
+                let poll_at = match item
                     .meta
                     .poll_at(socket_poll_at, |ip_addr| inner.has_neighbor(&ip_addr))
                 {
                     PollAt::Ingress => None,
                     PollAt::Time(instant) => Some(instant),
                     PollAt::Now => Some(Instant::from_millis(0)),
+                };
+                // Sockets of a rate limited class wait for its token bucket to refill.
+                let class = EgressScheduler::clamp_class(item.meta.egress_class);
+                match scheduler.ready_at(class) {
+                    Some(ready_at) => poll_at.map(|poll_at| poll_at.max(ready_at)),
+                    None => poll_at,
                 }
             })
+            .chain(iface_poll_at)
             .min()
     }
 
@@ -506,7 +786,12 @@ impl Interface {
         }
     }
 
-    fn socket_ingress<D>(&mut self, device: &mut D, sockets: &mut SocketSet<'_>) -> bool
+    fn socket_ingress<D>(
+        &mut self,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        mut router: Option<&mut Router<'_>>,
+    ) -> bool
     where
         D: Device + ?Sized,
     {
@@ -528,6 +813,16 @@ impl Interface {
                             frame,
                             &mut self.fragments,
                         ) {
+                            let packet = match packet {
+                                EthernetPacket::Ip(packet) => {
+                                    match self.inner.forward(router.as_deref_mut(), packet) {
+                                        Some(packet) => EthernetPacket::Ip(packet),
+                                        None => return,
+                                    }
+                                }
+                                #[cfg(feature = "proto-ipv4")]
+                                packet => packet,
+                            };
                             if let Err(err) =
                                 self.inner.dispatch(tx_token, packet, &mut self.fragmenter)
                             {
@@ -537,9 +832,10 @@ impl Interface {
                     }
                     #[cfg(feature = "medium-ip")]
                     Medium::Ip => {
-                        if let Some(packet) =
-                            self.inner
-                                .process_ip(sockets, rx_meta, frame, &mut self.fragments)
+                        if let Some(packet) = self
+                            .inner
+                            .process_ip(sockets, rx_meta, frame, &mut self.fragments, false)
+                            .and_then(|packet| self.inner.forward(router.as_deref_mut(), packet))
                         {
                             if let Err(err) = self.inner.dispatch_ip(
                                 tx_token,
@@ -553,12 +849,11 @@ impl Interface {
                     }
                     #[cfg(feature = "medium-ieee802154")]
                     Medium::Ieee802154 => {
-                        if let Some(packet) = self.inner.process_ieee802154(
-                            sockets,
-                            rx_meta,
-                            frame,
-                            &mut self.fragments,
-                        ) {
+                        if let Some(packet) = self
+                            .inner
+                            .process_ieee802154(sockets, rx_meta, frame, &mut self.fragments)
+                            .and_then(|packet| self.inner.forward(router.as_deref_mut(), packet))
+                        {
                             if let Err(err) = self.inner.dispatch_ip(
                                 tx_token,
                                 PacketMeta::default(),
@@ -583,115 +878,241 @@ impl Interface {
     {
         let _caps = device.capabilities();
 
-        enum EgressError {
-            Exhausted,
-            Dispatch(DispatchError),
+        if self.scheduler.is_enabled() {
+            return self.scheduled_socket_egress(device, sockets);
         }
 
         let mut emitted_any = false;
         for item in sockets.items_mut() {
-            if !item
-                .meta
-                .egress_permitted(self.inner.now, |ip_addr| self.inner.has_neighbor(&ip_addr))
-            {
+            match self.socket_dispatch(device, item) {
+                Ok(emitted) => emitted_any |= emitted.is_some(),
+                Err(DeviceExhausted) => break,
+            }
+        }
+        emitted_any
+    }
+
+    /// Offer the sockets of each traffic class to send, in the order chosen by the egress
+    /// scheduler, until the sockets of one class emitted packets.
+    fn scheduled_socket_egress<D>(&mut self, device: &mut D, sockets: &mut SocketSet<'_>) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        self.scheduler.refill(self.inner.now);
+
+        let order = self.scheduler.class_order();
+        for (i, &class) in order.iter().enumerate() {
+            if !self.scheduler.may_send(class) {
                 continue;
             }
 
-            let mut neighbor_addr = None;
-            let mut respond = |inner: &mut InterfaceInner, meta: PacketMeta, response: Packet| {
-                neighbor_addr = Some(response.ip_repr().dst_addr());
-                let t = device.transmit(inner.now).ok_or_else(|| {
-                    net_debug!("failed to transmit IP: device exhausted");
-                    EgressError::Exhausted
-                })?;
+            let mut emitted_any = false;
+            let mut exhausted = false;
+            for item in sockets
+                .items_mut()
+                .filter(|item| EgressScheduler::clamp_class(item.meta.egress_class) == class)
+            {
+                match self.socket_dispatch(device, item) {
+                    Ok(Some(len)) => {
+                        emitted_any = true;
+                        self.scheduler.sent(class, len);
+                    }
+                    Ok(None) => {}
+                    Err(DeviceExhausted) => {
+                        exhausted = true;
+                        break;
+                    }
+                }
+                if !self.scheduler.may_send(class) {
+                    break;
+                }
+            }
 
-                inner
-                    .dispatch_ip(t, meta, response, &mut self.fragmenter)
-                    .map_err(EgressError::Dispatch)?;
+            if emitted_any {
+                for &skipped in &order[..i] {
+                    self.scheduler.idle(skipped, class);
+                }
+                return true;
+            }
+            if exhausted {
+                break;
+            }
+        }
+        false
+    }
 
-                emitted_any = true;
+    /// Let a socket emit a packet, returning the amount of octets sent to the device if
+    /// it did.
+    fn socket_dispatch<D>(
+        &mut self,
+        device: &mut D,
+        item: &mut Item<'_>,
+    ) -> Result<Option<usize>, DeviceExhausted>
+    where
+        D: Device + ?Sized,
+    {
+        enum EgressError {
+            Exhausted,
+            Dispatch(DispatchError),
+        }
 
-                Ok(())
-            };
+        self.inner.rule_key = RuleKey {
+            socket: Some(item.meta.handle),
+            ..RuleKey::default()
+        };
+        if !item
+            .meta
+            .egress_permitted(self.inner.now, |ip_addr| self.inner.has_neighbor(&ip_addr))
+        {
+            self.inner.rule_key = RuleKey::default();
+            return Ok(None);
+        }
 
-            let result = match &mut item.socket {
-                #[cfg(feature = "socket-raw")]
-                Socket::Raw(socket) => socket.dispatch(&mut self.inner, |inner, (ip, raw)| {
-                    respond(
-                        inner,
-                        PacketMeta::default(),
-                        Packet::new(ip, IpPayload::Raw(raw)),
-                    )
-                }),
-                #[cfg(feature = "socket-icmp")]
-                Socket::Icmp(socket) => {
-                    socket.dispatch(&mut self.inner, |inner, response| match response {
-                        #[cfg(feature = "proto-ipv4")]
-                        (IpRepr::Ipv4(ipv4_repr), IcmpRepr::Ipv4(icmpv4_repr)) => respond(
-                            inner,
-                            PacketMeta::default(),
-                            Packet::new_ipv4(ipv4_repr, IpPayload::Icmpv4(icmpv4_repr)),
-                        ),
-                        #[cfg(feature = "proto-ipv6")]
-                        (IpRepr::Ipv6(ipv6_repr), IcmpRepr::Ipv6(icmpv6_repr)) => respond(
-                            inner,
-                            PacketMeta::default(),
-                            Packet::new_ipv6(ipv6_repr, IpPayload::Icmpv6(icmpv6_repr)),
-                        ),
-                        #[allow(unreachable_patterns)]
-                        _ => unreachable!(),
-                    })
-                }
-                #[cfg(feature = "socket-udp")]
-                Socket::Udp(socket) => {
-                    socket.dispatch(&mut self.inner, |inner, meta, (ip, udp, payload)| {
-                        respond(inner, meta, Packet::new(ip, IpPayload::Udp(udp, payload)))
-                    })
+        let mut neighbor_addr = None;
+        let mut emitted = None;
+        #[allow(unused)] // unused depending on which sockets are enabled
+        let mut respond = |inner: &mut InterfaceInner, meta: PacketMeta, response: Packet| {
+            neighbor_addr = Some(response.ip_repr().dst_addr());
+
+            // Packets to the interface itself never reach the device.
+            if inner.is_loopback_destination(&response.ip_repr().dst_addr()) {
+                if !self.loopback.enqueue(inner, response) {
+                    return Err(EgressError::Exhausted);
                 }
-                #[cfg(feature = "socket-tcp")]
-                Socket::Tcp(socket) => socket.dispatch(&mut self.inner, |inner, (ip, tcp)| {
-                    respond(
+                emitted = Some(0);
+                return Ok(());
+            }
+
+            let t = device.transmit(inner.now).ok_or_else(|| {
+                net_debug!("failed to transmit IP: device exhausted");
+                EgressError::Exhausted
+            })?;
+
+            let len = response.ip_repr().buffer_len();
+            inner
+                .dispatch_ip(t, meta, response, &mut self.fragmenter)
+                .map_err(EgressError::Dispatch)?;
+
+            emitted = Some(len);
+
+            Ok(())
+        };
+
+        let result = match &mut item.socket {
+            #[cfg(feature = "socket-raw")]
+            Socket::Raw(socket) => socket.dispatch(&mut self.inner, |inner, (ip, raw)| {
+                respond(
+                    inner,
+                    PacketMeta::default(),
+                    Packet::new(ip, IpPayload::Raw(raw)),
+                )
+            }),
+            #[cfg(feature = "socket-icmp")]
+            Socket::Icmp(socket) => {
+                socket.dispatch(&mut self.inner, |inner, response| match response {
+                    #[cfg(feature = "proto-ipv4")]
+                    (IpRepr::Ipv4(ipv4_repr), IcmpRepr::Ipv4(icmpv4_repr)) => respond(
                         inner,
                         PacketMeta::default(),
-                        Packet::new(ip, IpPayload::Tcp(tcp)),
-                    )
-                }),
-                #[cfg(feature = "socket-dhcpv4")]
-                Socket::Dhcpv4(socket) => {
-                    socket.dispatch(&mut self.inner, |inner, (ip, udp, dhcp)| {
-                        respond(
-                            inner,
-                            PacketMeta::default(),
-                            Packet::new_ipv4(ip, IpPayload::Dhcpv4(udp, dhcp)),
-                        )
-                    })
-                }
-                #[cfg(feature = "socket-dns")]
-                Socket::Dns(socket) => socket.dispatch(&mut self.inner, |inner, (ip, udp, dns)| {
-                    respond(
+                        Packet::new_ipv4(ipv4_repr, IpPayload::Icmpv4(icmpv4_repr)),
+                    ),
+                    #[cfg(feature = "proto-ipv6")]
+                    (IpRepr::Ipv6(ipv6_repr), IcmpRepr::Ipv6(icmpv6_repr)) => respond(
                         inner,
                         PacketMeta::default(),
-                        Packet::new(ip, IpPayload::Udp(udp, dns)),
-                    )
-                }),
-            };
-
-            match result {
-                Err(EgressError::Exhausted) => break, // Device buffer full.
-                Err(EgressError::Dispatch(_)) => {
-                    // `NeighborCache` already takes care of rate limiting the neighbor discovery
-                    // requests from the socket. However, without an additional rate limiting
-                    // mechanism, we would spin on every socket that has yet to discover its
-                    // neighbor.
-                    item.meta.neighbor_missing(
-                        self.inner.now,
-                        neighbor_addr.expect("non-IP response packet"),
-                    );
+                        Packet::new_ipv6(ipv6_repr, IpPayload::Icmpv6(icmpv6_repr)),
+                    ),
+                    #[allow(unreachable_patterns)]
+                    _ => unreachable!(),
+                })
+            }
+            #[cfg(feature = "socket-udp")]
+            Socket::Udp(socket) => {
+                let checksum_coverage = socket.checksum_coverage();
+                socket.dispatch(&mut self.inner, |inner, meta, (ip, udp, payload)| {
+                    let payload = match checksum_coverage {
+                        Some(coverage) => {
+                            // A coverage beyond the datagram covers all of it.
+                            let len = udp.header_len() + payload.len();
+                            let udp = UdpLiteRepr {
+                                src_port: udp.src_port,
+                                dst_port: udp.dst_port,
+                                checksum_coverage: if coverage as usize >= len {
+                                    0
+                                } else {
+                                    coverage
+                                },
+                            };
+                            IpPayload::UdpLite(udp, payload)
+                        }
+                        None => IpPayload::Udp(udp, payload),
+                    };
+                    respond(inner, meta, Packet::new(ip, payload))
+                })
+            }
+            #[cfg(feature = "socket-tcp")]
+            Socket::Tcp(socket) => socket.dispatch(&mut self.inner, |inner, (ip, tcp)| {
+                respond(
+                    inner,
+                    PacketMeta::default(),
+                    Packet::new(ip, IpPayload::Tcp(tcp)),
+                )
+            }),
+            #[cfg(feature = "socket-dhcpv4")]
+            Socket::Dhcpv4(socket) => socket.dispatch(&mut self.inner, |inner, (ip, udp, dhcp)| {
+                respond(
+                    inner,
+                    PacketMeta::default(),
+                    Packet::new_ipv4(ip, IpPayload::Dhcpv4(udp, dhcp)),
+                )
+            }),
+            #[cfg(feature = "socket-dns")]
+            Socket::Dns(socket) => socket.dispatch(&mut self.inner, |inner, (ip, udp, dns)| {
+                respond(
+                    inner,
+                    PacketMeta::default(),
+                    Packet::new(ip, IpPayload::Udp(udp, dns)),
+                )
+            }),
+            #[cfg(feature = "socket-ethernet")]
+            Socket::Ethernet(socket) => socket.dispatch(&mut self.inner, |inner, frame| {
+                if inner.caps.medium != Medium::Ethernet {
+                    net_debug!("ethernet: interface medium is not Ethernet, dropping frame");
+                    return Ok(());
                 }
-                Ok(()) => {}
+                if frame.len() > inner.caps.max_transmission_unit {
+                    net_debug!("ethernet: frame exceeds the MTU, dropping");
+                    return Ok(());
+                }
+
+                let t = device.transmit(inner.now).ok_or_else(|| {
+                    net_debug!("failed to transmit frame: device exhausted");
+                    EgressError::Exhausted
+                })?;
+                t.consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+
+                emitted = Some(frame.len());
+                Ok(())
+            }),
+        };
+
+        self.inner.rule_key = RuleKey::default();
+        match result {
+            Err(EgressError::Exhausted) => return Err(DeviceExhausted), // Device buffer full.
+            Err(EgressError::Dispatch(_)) => {
+                // `NeighborCache` already takes care of rate limiting the neighbor discovery
+                // requests from the socket. However, without an additional rate limiting
+                // mechanism, we would spin on every socket that has yet to discover its
+                // neighbor.
+                item.meta.neighbor_missing(
+                    self.inner.now,
+                    neighbor_addr.expect("non-IP response packet"),
+                );
             }
+            Ok(()) => {}
         }
-        emitted_any
+
+        Ok(emitted)
     }
 }
 
@@ -712,6 +1133,11 @@ impl InterfaceInner {
         self.caps.checksum.clone()
     }
 
+    #[cfg(feature = "socket-udp")]
+    pub(crate) fn udp_segmentation_offload(&self) -> bool {
+        self.caps.udp_segmentation_offload
+    }
+
     #[allow(unused)] // unused depending on which sockets are enabled
     pub(crate) fn ip_mtu(&self) -> usize {
         self.caps.ip_mtu()
@@ -722,6 +1148,22 @@ impl InterfaceInner {
         &mut self.rand
     }
 
+    /// Return the earliest time at which the interface itself, rather than one of
+    /// the sockets, has something to send.
+    fn poll_at(&self) -> Option<Instant> {
+        let timers = core::iter::empty::<Option<Instant>>();
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        let timers = timers.chain([self.announcer.poll_at()]);
+        #[cfg(feature = "proto-ipv4-link-local")]
+        let timers = timers.chain([self.ipv4_link_local.poll_at()]);
+        #[cfg(feature = "proto-ipv6-temporary-addresses")]
+        let timers = timers.chain([self.ipv6_temporary.poll_at()]);
+        #[cfg(feature = "proto-multicast-querier")]
+        let timers = timers.chain([self.multicast_querier_poll_at()]);
+
+        timers.flatten().min()
+    }
+
     #[allow(unused)] // unused depending on which sockets are enabled
     pub(crate) fn get_source_address(&self, dst_addr: &IpAddress) -> Option<IpAddress> {
         match dst_addr {
@@ -732,6 +1174,98 @@ impl InterfaceInner {
         }
     }
 
+    /// Sort destination addresses using the rules of RFC 6724 § 6.
+    #[cfg(feature = "proto-ipv6")]
+    pub(crate) fn sort_destination_addrs(&self, addrs: &mut [IpAddress]) {
+        // A stable insertion sort, as Rule 10 requires keeping the order of equally
+        // preferred destinations, and there are only a few of them.
+        for i in 1..addrs.len() {
+            let mut j = i;
+            while j > 0
+                && self.compare_destination_addrs(&addrs[j], &addrs[j - 1]) == Ordering::Greater
+            {
+                addrs.swap(j, j - 1);
+                j -= 1;
+            }
+        }
+    }
+
+    /// Compare two destination addresses, using the rules of RFC 6724 § 6.
+    /// `Ordering::Greater` means that `a` is preferred over `b`.
+    #[cfg(feature = "proto-ipv6")]
+    fn compare_destination_addrs(&self, a: &IpAddress, b: &IpAddress) -> Ordering {
+        // The source address used for a destination, if the destination is usable.
+        let source = |dst_addr: &IpAddress| {
+            let src_addr = self.get_source_address(dst_addr)?;
+            self.route(dst_addr, self.now)?;
+            self.ip_addrs
+                .iter()
+                .find(|cidr| cidr.address() == src_addr)
+                .copied()
+        };
+
+        // Rule 1: avoid unusable destinations.
+        let (src_a, src_b) = match (source(a), source(b)) {
+            (Some(src_a), Some(src_b)) => (src_a, src_b),
+            (src_a, src_b) => return src_a.is_some().cmp(&src_b.is_some()),
+        };
+
+        // Rule 2: prefer matching scope.
+        let matching_a = policy::scope(a) == policy::scope(&src_a.address());
+        let matching_b = policy::scope(b) == policy::scope(&src_b.address());
+        if matching_a != matching_b {
+            return matching_a.cmp(&matching_b);
+        }
+
+        // Rule 3: avoid deprecated addresses.
+        #[cfg(feature = "proto-ipv6-temporary-addresses")]
+        {
+            let deprecated = |src: &IpCidr| match src {
+                IpCidr::Ipv6(src) => self.is_ipv6_deprecated(&src.address()),
+                #[allow(unreachable_patterns)]
+                _ => false,
+            };
+            let (deprecated_a, deprecated_b) = (deprecated(&src_a), deprecated(&src_b));
+            if deprecated_a != deprecated_b {
+                return deprecated_b.cmp(&deprecated_a);
+            }
+        }
+
+        // Rule 4: prefer home addresses. Mobile IPv6 is not supported.
+
+        // Rule 5: prefer matching label.
+        let table = &self.policy_table;
+        let matching_a = table.label(&src_a.address()) == table.label(a);
+        let matching_b = table.label(&src_b.address()) == table.label(b);
+        if matching_a != matching_b {
+            return matching_a.cmp(&matching_b);
+        }
+
+        // Rule 6: prefer higher precedence.
+        let (precedence_a, precedence_b) = (table.precedence(a), table.precedence(b));
+        if precedence_a != precedence_b {
+            return precedence_a.cmp(&precedence_b);
+        }
+
+        // Rule 7: prefer native transport. Tunnels are not supported, so all are native.
+
+        // Rule 8: prefer smaller scope.
+        let (scope_a, scope_b) = (policy::scope(a), policy::scope(b));
+        if scope_a != scope_b {
+            return scope_b.cmp(&scope_a);
+        }
+
+        // Rule 9: use longest matching prefix, for destinations of the same family.
+        if a.version() == b.version() {
+            let prefix_len_a = policy::common_prefix_len(a, &src_a.address(), src_a.prefix_len());
+            let prefix_len_b = policy::common_prefix_len(b, &src_b.address(), src_b.prefix_len());
+            return prefix_len_a.cmp(&prefix_len_b);
+        }
+
+        // Rule 10: otherwise, leave the order unchanged.
+        Ordering::Equal
+    }
+
     #[cfg(test)]
     #[allow(unused)] // unused depending on which sockets are enabled
     pub(crate) fn set_now(&mut self, now: Instant) {
@@ -754,11 +1288,40 @@ impl InterfaceInner {
     }
 
     /// Check whether the interface has the given IP address assigned.
-    fn has_ip_addr<T: Into<IpAddress>>(&self, addr: T) -> bool {
+    pub(crate) fn has_ip_addr<T: Into<IpAddress>>(&self, addr: T) -> bool {
         let addr = addr.into();
         self.ip_addrs.iter().any(|probe| probe.address() == addr)
     }
 
+    /// Check whether `src_addr` may be the source address of a packet to `dst_addr`: it
+    /// must be assigned to the interface, and reach as far as the destination. Loopback
+    /// addresses are only the source of packets to loopback addresses.
+    #[cfg(feature = "socket-udp")]
+    pub(crate) fn is_source_address_for(&self, src_addr: &IpAddress, dst_addr: &IpAddress) -> bool {
+        if !self.has_ip_addr(*src_addr) {
+            return false;
+        }
+        if dst_addr.is_loopback() {
+            return src_addr.version() == dst_addr.version();
+        }
+
+        match (src_addr, dst_addr) {
+            #[cfg(feature = "proto-ipv4")]
+            (IpAddress::Ipv4(src_addr), IpAddress::Ipv4(dst_addr)) => {
+                !src_addr.is_loopback()
+                    && (!src_addr.is_link_local()
+                        || dst_addr.is_link_local()
+                        || !dst_addr.is_unicast())
+            }
+            #[cfg(feature = "proto-ipv6")]
+            (IpAddress::Ipv6(src), IpAddress::Ipv6(_)) => {
+                !src.is_loopback() && policy::scope(src_addr) >= policy::scope(dst_addr)
+            }
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
     /// Check whether the interface listens to given destination multicast IP address.
     ///
     /// If built without feature `proto-igmp` this function will
@@ -781,25 +1344,25 @@ impl InterfaceInner {
         }
     }
 
-    #[cfg(feature = "medium-ip")]
     fn process_ip<'frame>(
         &mut self,
         sockets: &mut SocketSet,
         meta: PacketMeta,
         ip_payload: &'frame [u8],
         frag: &'frame mut FragmentsBuffer,
+        loopback: bool,
     ) -> Option<Packet<'frame>> {
         match IpVersion::of_packet(ip_payload) {
             #[cfg(feature = "proto-ipv4")]
             Ok(IpVersion::Ipv4) => {
                 let ipv4_packet = check!(Ipv4Packet::new_checked(ip_payload));
 
-                self.process_ipv4(sockets, meta, &ipv4_packet, frag)
+                self.process_ipv4(sockets, meta, &ipv4_packet, frag, loopback)
             }
             #[cfg(feature = "proto-ipv6")]
             Ok(IpVersion::Ipv6) => {
                 let ipv6_packet = check!(Ipv6Packet::new_checked(ip_payload));
-                self.process_ipv6(sockets, meta, &ipv6_packet)
+                self.process_ipv6(sockets, meta, &ipv6_packet, Some(frag), loopback)
             }
             // Drop all other traffic.
             _ => None,
@@ -820,14 +1383,53 @@ impl InterfaceInner {
             .items_mut()
             .filter_map(|i| raw::Socket::downcast_mut(&mut i.socket))
         {
-            if raw_socket.accepts(ip_repr) {
-                raw_socket.process(self, ip_repr, ip_payload);
+            if !raw_socket.unclaimed_only() && raw_socket.accepts(ip_repr) {
+                if raw_socket.passes_filter(self, ip_repr, ip_payload) {
+                    raw_socket.process(self, ip_repr, ip_payload);
+                }
+                handled_by_raw_socket = true;
+            }
+        }
+        if handled_by_raw_socket || self.handles_protocol(ip_repr.next_header()) {
+            return handled_by_raw_socket;
+        }
+
+        // The packets no one claimed go to the sockets asking for them.
+        for raw_socket in sockets
+            .items_mut()
+            .filter_map(|i| raw::Socket::downcast_mut(&mut i.socket))
+        {
+            if raw_socket.unclaimed_only() && raw_socket.accepts(ip_repr) {
+                if raw_socket.passes_filter(self, ip_repr, ip_payload) {
+                    raw_socket.process(self, ip_repr, ip_payload);
+                }
                 handled_by_raw_socket = true;
             }
         }
         handled_by_raw_socket
     }
 
+    /// Checks if the interface processes the packets of a protocol itself.
+    #[cfg(feature = "socket-raw")]
+    fn handles_protocol(&self, protocol: IpProtocol) -> bool {
+        match protocol {
+            IpProtocol::Icmp | IpProtocol::Icmpv6 => true,
+            #[cfg(feature = "proto-igmp")]
+            IpProtocol::Igmp => true,
+            #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
+            IpProtocol::Udp => true,
+            #[cfg(feature = "socket-udp")]
+            IpProtocol::UdpLite => true,
+            #[cfg(feature = "socket-tcp")]
+            IpProtocol::Tcp => true,
+            #[cfg(feature = "proto-ipv6")]
+            IpProtocol::HopByHop => true,
+            #[cfg(feature = "proto-ipv6-fragmentation")]
+            IpProtocol::Ipv6Frag => true,
+            _ => false,
+        }
+    }
+
     /// Checks if an address is broadcast, taking into account ipv4 subnet-local
     /// broadcast addresses.
     pub(crate) fn is_broadcast(&self, address: &IpAddress) -> bool {
@@ -853,6 +1455,12 @@ impl InterfaceInner {
             #[cfg(feature = "proto-ipv4")]
             EthernetPacket::Arp(arp_repr) => {
                 let dst_hardware_addr = match arp_repr {
+                    // ARP probes and announcements leave the target hardware address
+                    // zeroed, they are broadcast to the whole link.
+                    ArpRepr::EthernetIpv4 {
+                        target_hardware_addr: EthernetAddress([0, 0, 0, 0, 0, 0]),
+                        ..
+                    } => EthernetAddress::BROADCAST,
                     ArpRepr::EthernetIpv4 {
                         target_hardware_addr,
                         ..
@@ -878,6 +1486,17 @@ impl InterfaceInner {
     }
 
     fn route(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
+        self.route_from(self.get_source_address(addr).as_ref(), addr, timestamp)
+    }
+
+    /// Return the next hop for packets from `src_addr` to `addr`, or `None` if they
+    /// are not routed or are dropped.
+    fn route_from(
+        &self,
+        src_addr: Option<&IpAddress>,
+        addr: &IpAddress,
+        timestamp: Instant,
+    ) -> Option<IpAddress> {
         // Send directly.
         // note: no need to use `self.is_broadcast()` to check for subnet-local broadcast addrs
         //       here because `in_same_network` will already return true.
@@ -885,8 +1504,16 @@ impl InterfaceInner {
             return Some(*addr);
         }
 
+        // Send as redirected.
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        if let Some(destination) = self.destination_cache.lookup(addr, timestamp) {
+            return Some(destination.next_hop);
+        }
+
         // Route via a router.
-        self.routes.lookup(addr, timestamp)
+        self.routes
+            .lookup_route_with(addr, src_addr, &self.rule_key, timestamp)?
+            .next_hop(addr)
     }
 
     fn has_neighbor(&self, addr: &IpAddress) -> bool {
@@ -966,7 +1593,7 @@ impl InterfaceInner {
         }
 
         let dst_addr = self
-            .route(dst_addr, self.now)
+            .route_from(Some(src_addr), dst_addr, self.now)
             .ok_or(DispatchError::NoRoute)?;
 
         match self.neighbor_cache.lookup(&dst_addr, self.now) {
@@ -1026,6 +1653,8 @@ impl InterfaceInner {
                         next_header: IpProtocol::Icmpv6,
                         payload_len: solicit.buffer_len(),
                         hop_limit: 0xff,
+                        dscp: 0,
+                        ecn: 0,
                     },
                     IpPayload::Icmpv6(solicit),
                 );
@@ -1049,10 +1678,86 @@ impl InterfaceInner {
 
     fn flush_neighbor_cache(&mut self) {
         #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
-        self.neighbor_cache.flush()
+        {
+            self.neighbor_cache.flush();
+            self.destination_cache.flush();
+        }
+    }
+
+    /// Process a redirect from `router`, telling that packets to `dst_addr` are better
+    /// sent to `target_addr`. Returns whether the redirect was accepted.
+    ///
+    /// The redirect is only accepted when `router` is the current next hop for
+    /// `dst_addr`, as required by [RFC 1122 § 3.2.2.2] and [RFC 4861 § 8.1].
+    ///
+    /// [RFC 1122 § 3.2.2.2]: https://www.rfc-editor.org/rfc/rfc1122#section-3.2.2.2
+    /// [RFC 4861 § 8.1]: https://www.rfc-editor.org/rfc/rfc4861#section-8.1
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    fn process_redirect(
+        &mut self,
+        router: IpAddress,
+        dst_addr: IpAddress,
+        target_addr: IpAddress,
+    ) -> bool {
+        if !self.accept_redirects || self.iface_id.is_some() {
+            net_debug!("redirect from {}: redirects are not accepted", router);
+            return false;
+        }
+        if !dst_addr.is_unicast() || self.has_ip_addr(dst_addr) || self.in_same_network(&dst_addr) {
+            net_debug!("redirect from {}: invalid destination {}", router, dst_addr);
+            return false;
+        }
+        if !target_addr.is_unicast() || self.has_ip_addr(target_addr) {
+            net_debug!("redirect from {}: invalid target {}", router, target_addr);
+            return false;
+        }
+        if self.route(&dst_addr, self.now) != Some(router) {
+            net_debug!(
+                "redirect from {}: not the next hop for {}",
+                router,
+                dst_addr
+            );
+            return false;
+        }
+
+        net_debug!("redirect: {} via {}", dst_addr, target_addr);
+        self.destination_cache.fill(dst_addr, target_addr, self.now);
+        true
+    }
+
+    /// Run `f` with routing rules matching `rule_key`, and restore the previous key after.
+    fn with_rule_key<R>(&mut self, rule_key: RuleKey, f: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = core::mem::replace(&mut self.rule_key, rule_key);
+        let result = f(self);
+        self.rule_key = previous;
+        result
     }
 
     fn dispatch_ip<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        meta: PacketMeta,
+        packet: Packet,
+        frag: &mut Fragmenter,
+    ) -> Result<(), DispatchError> {
+        // Routing rules match the packet while it is dispatched, unless the key was already
+        // set for it, as for forwarded packets.
+        let mut rule_key = self.rule_key;
+        if rule_key.protocol.is_none() {
+            #[cfg(feature = "packetmeta-mark")]
+            {
+                rule_key.mark = meta.mark;
+            }
+            let protocol = packet.ip_repr().next_header();
+            rule_key.protocol = Some(protocol);
+            rule_key.ports = packet.payload().ports(protocol);
+        }
+        self.with_rule_key(rule_key, |inner| {
+            inner.dispatch_ip_impl(tx_token, meta, packet, frag)
+        })
+    }
+
+    fn dispatch_ip_impl<Tx: TxToken>(
         &mut self,
         // NOTE(unused_mut): tx_token isn't always mutated, depending on
         // the feature set that is used.
@@ -1082,6 +1787,29 @@ impl InterfaceInner {
 
         // Dispatch IP/Ethernet:
 
+        // Without neighbors, packets are sent as is, unless a route drops them.
+        #[cfg(feature = "medium-ip")]
+        if matches!(self.caps.medium, Medium::Ip) {
+            let dst_addr = ip_repr.dst_addr();
+            if dst_addr.is_unicast() && !self.in_same_network(&dst_addr) {
+                if let Some(route) = self.routes.lookup_route_with(
+                    &dst_addr,
+                    Some(&ip_repr.src_addr()),
+                    &self.rule_key,
+                    self.now,
+                ) {
+                    if route.next_hop(&dst_addr).is_none() {
+                        net_debug!(
+                            "packets to {} are dropped by route {}",
+                            dst_addr,
+                            route.cidr
+                        );
+                        return Err(DispatchError::NoRoute);
+                    }
+                }
+            }
+        }
+
         let caps = self.caps.clone();
 
         #[cfg(feature = "proto-ipv4-fragmentation")]
@@ -1142,20 +1870,24 @@ impl InterfaceInner {
 
         let total_ip_len = ip_repr.buffer_len();
 
+        // Packets the device splits into several datagrams are never fragmented.
+        let fragment = meta.segment_size.is_none();
+
         match &mut ip_repr {
             #[cfg(feature = "proto-ipv4")]
             IpRepr::Ipv4(repr) => {
                 // If we have an IPv4 packet, then we need to check if we need to fragment it.
-                if total_ip_len > self.caps.max_transmission_unit {
+                if fragment && total_ip_len > self.caps.max_transmission_unit {
                     #[cfg(feature = "proto-ipv4-fragmentation")]
                     {
                         net_debug!("start fragmentation");
 
                         // Calculate how much we will send now (including the Ethernet header).
-                        let tx_len = self.caps.max_transmission_unit;
-
+                        // The payload of the fragment is a multiple of 8 octets.
                         let ip_header_len = repr.buffer_len();
-                        let first_frag_ip_len = self.caps.ip_mtu();
+                        let first_frag_ip_len =
+                            ip_header_len + ((self.caps.ip_mtu() - ip_header_len) & !7);
+                        let tx_len = total_len - total_ip_len + first_frag_ip_len;
 
                         if frag.buffer.len() < total_ip_len {
                             net_debug!(
@@ -1174,8 +1906,12 @@ impl InterfaceInner {
                         // IP header).
                         frag.packet_len = total_ip_len;
 
-                        // Save the IP header for other fragments.
-                        frag.ipv4.repr = *repr;
+                        // Save the IP header for other fragments, which only repeat the
+                        // options with the copied flag.
+                        frag.ipv4.repr = Ipv4Repr {
+                            options: repr.options.copied(),
+                            ..*repr
+                        };
 
                         // Save how much bytes we will send now.
                         frag.sent_bytes = first_frag_ip_len;
@@ -1184,7 +1920,7 @@ impl InterfaceInner {
                         repr.payload_len = first_frag_ip_len - repr.buffer_len();
 
                         // Emit the IP header to the buffer.
-                        emit_ip(&ip_repr, &mut frag.buffer);
+                        emit_ip(&ip_repr, &mut frag.buffer[..total_ip_len]);
 
                         let mut ipv4_packet = Ipv4Packet::new_unchecked(&mut frag.buffer[..]);
                         frag.ipv4.ident = ipv4_id;
@@ -1237,18 +1973,71 @@ impl InterfaceInner {
                     })
                 }
             }
-            // We don't support IPv6 fragmentation yet.
             #[cfg(feature = "proto-ipv6")]
-            IpRepr::Ipv6(_) => tx_token.consume(total_len, |mut tx_buffer| {
-                #[cfg(feature = "medium-ethernet")]
-                if matches!(self.caps.medium, Medium::Ethernet) {
-                    emit_ethernet(&ip_repr, tx_buffer)?;
-                    tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
-                }
+            IpRepr::Ipv6(_) => {
+                // If we have an IPv6 packet, then we need to check if we need to fragment it.
+                if fragment && total_ip_len > self.ip_mtu() {
+                    #[cfg(feature = "proto-ipv6-fragmentation")]
+                    {
+                        net_debug!("start fragmentation");
 
-                emit_ip(&ip_repr, tx_buffer);
-                Ok(())
-            }),
+                        if frag.buffer.len() < total_ip_len {
+                            net_debug!(
+                                "Fragmentation buffer is too small, at least {} needed. Dropping",
+                                total_ip_len
+                            );
+                            return Ok(());
+                        }
+
+                        // Emit the whole packet to the buffer, the fragments are cut from it.
+                        emit_ip(&ip_repr, &mut frag.buffer[..total_ip_len]);
+
+                        let (unfragmentable_len, next_header) =
+                            match Self::split_ipv6_unfragmentable(&mut frag.buffer[..total_ip_len])
+                            {
+                                Some(split) => split,
+                                None => {
+                                    net_debug!("cannot fragment IPv6 packet, dropping");
+                                    return Ok(());
+                                }
+                            };
+
+                        #[cfg(feature = "medium-ethernet")]
+                        {
+                            frag.ipv6.dst_hardware_addr = dst_hardware_addr;
+                        }
+
+                        frag.packet_len = total_ip_len;
+                        frag.sent_bytes = unfragmentable_len;
+                        frag.ipv6.unfragmentable_len = unfragmentable_len;
+                        frag.ipv6.next_header = next_header;
+                        frag.ipv6.ident = self.next_ipv6_frag_ident();
+
+                        // Transmit the first fragment.
+                        self.dispatch_ipv6_frag(tx_token, frag);
+                        Ok(())
+                    }
+
+                    #[cfg(not(feature = "proto-ipv6-fragmentation"))]
+                    {
+                        net_debug!("Enable the `proto-ipv6-fragmentation` feature for fragmentation support.");
+                        Ok(())
+                    }
+                } else {
+                    tx_token.set_meta(meta);
+
+                    tx_token.consume(total_len, |mut tx_buffer| {
+                        #[cfg(feature = "medium-ethernet")]
+                        if matches!(self.caps.medium, Medium::Ethernet) {
+                            emit_ethernet(&ip_repr, tx_buffer)?;
+                            tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
+                        }
+
+                        emit_ip(&ip_repr, tx_buffer);
+                        Ok(())
+                    })
+                }
+            }
         }
     }
 }
@@ -1262,5 +2051,6 @@ enum DispatchError {
     /// We do have a route to dispatch this packet, but we haven't discovered
     /// the neighbor for it yet. Discovery has been initiated, dispatch
     /// should be retried later.
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
     NeighborPending,
 }
@@ -0,0 +1,596 @@
+use super::*;
+
+use crate::config::IFACE_MAX_MULTICAST_LISTENER_COUNT;
+use crate::wire::{IgmpGroupRecord, IgmpRecordType};
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::{MldAddressRecord, MldRecordType};
+
+// Default timers and counters, see RFC 3376 § 8 and RFC 3810 § 9.
+const ROBUSTNESS: u8 = 2;
+const DEFAULT_QUERY_INTERVAL: Duration = Duration::from_secs(125);
+const QUERY_RESPONSE_INTERVAL: Duration = Duration::from_secs(10);
+const LAST_MEMBER_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Length of an MLDv2 general or multicast-address-specific query, Hop-by-Hop header
+/// included.
+#[cfg(feature = "proto-ipv6")]
+pub(super) const MLD_QUERY_LEN: usize = 36;
+
+/// Listening state of a multicast group on the link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Listener {
+    /// The group has no listener left from this point on, unless a report refreshes it.
+    expires_at: Instant,
+    /// How many group-specific queries are left to send after a listener left the group.
+    queries_left: u8,
+    /// When to send the next group-specific query.
+    next_query: Instant,
+}
+
+/// Querier state of one of IGMP and MLD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct QuerierState {
+    /// When to send the next general query.
+    next_query: Instant,
+    /// How many general queries of the startup sequence are left to send.
+    startup_queries_left: u8,
+    /// Until when a querier with a lower address is present on the link.
+    other_querier_until: Option<Instant>,
+}
+
+impl QuerierState {
+    const fn new() -> Self {
+        Self {
+            next_query: Instant::ZERO,
+            startup_queries_left: ROBUSTNESS,
+            other_querier_until: None,
+        }
+    }
+
+    fn is_querier(&self, now: Instant) -> bool {
+        self.other_querier_until.map_or(true, |until| now >= until)
+    }
+
+    fn should_query(&self, now: Instant) -> bool {
+        self.is_querier(now) && now >= self.next_query
+    }
+
+    /// Schedule the next general query, the first ones being sent at a quarter of the
+    /// query interval.
+    fn query_sent(&mut self, now: Instant, query_interval: Duration) {
+        self.startup_queries_left = self.startup_queries_left.saturating_sub(1);
+        self.next_query = if self.startup_queries_left > 0 {
+            now + query_interval / 4
+        } else {
+            now + query_interval
+        };
+    }
+
+    fn poll_at(&self) -> Instant {
+        match self.other_querier_until {
+            Some(until) if until > self.next_query => until,
+            _ => self.next_query,
+        }
+    }
+}
+
+/// State of the IGMP and MLD querier (RFC 3376 § 6, RFC 3810 § 7).
+#[derive(Debug)]
+pub(crate) struct MulticastQuerier {
+    enabled: bool,
+    query_interval: Duration,
+    igmp: QuerierState,
+    #[cfg(feature = "proto-ipv6")]
+    mld: QuerierState,
+    listeners: LinearMap<IpAddress, Listener, IFACE_MAX_MULTICAST_LISTENER_COUNT>,
+}
+
+impl MulticastQuerier {
+    pub(crate) const fn new() -> Self {
+        Self {
+            enabled: false,
+            query_interval: DEFAULT_QUERY_INTERVAL,
+            igmp: QuerierState::new(),
+            #[cfg(feature = "proto-ipv6")]
+            mld: QuerierState::new(),
+            listeners: LinearMap::new(),
+        }
+    }
+
+    /// How long a group keeps listeners without hearing a report about it.
+    fn group_membership_interval(&self) -> Duration {
+        self.query_interval * ROBUSTNESS as u32 + QUERY_RESPONSE_INTERVAL
+    }
+
+    /// How long a querier with a lower address is considered present after its last query.
+    fn other_querier_present_interval(&self) -> Duration {
+        self.query_interval * ROBUSTNESS as u32 + QUERY_RESPONSE_INTERVAL / 2
+    }
+
+    /// The Querier's Query Interval Code advertised in the queries, see RFC 3376 § 4.1.7.
+    fn qqic(&self) -> u8 {
+        let secs = self.query_interval.secs();
+        if secs < 128 {
+            secs as u8
+        } else if secs < 31744 {
+            let mut mant = secs >> 3;
+            let mut exp = 0u8;
+            while mant > 0x1F {
+                mant >>= 1;
+                exp += 1;
+            }
+            0x80 | (exp << 4) | (mant as u8 & 0xF)
+        } else {
+            0xFF
+        }
+    }
+
+    /// A report shows that `group` has listeners on the link.
+    fn listener_reported(&mut self, group: IpAddress, now: Instant) {
+        let listener = Listener {
+            expires_at: now + self.group_membership_interval(),
+            queries_left: 0,
+            next_query: now,
+        };
+        if self.listeners.insert(group, listener).is_err() {
+            net_debug!("multicast listener table full, not tracking {}", group);
+        }
+    }
+
+    /// A listener left `group`, query the remaining ones if we are the querier, see
+    /// RFC 3376 § 6.6.3.1.
+    fn listener_left(&mut self, group: IpAddress, now: Instant, is_querier: bool) {
+        if let Some(listener) = self.listeners.get_mut(&group) {
+            if is_querier {
+                let last_member_query_time = LAST_MEMBER_QUERY_INTERVAL * ROBUSTNESS as u32;
+                listener.expires_at = listener.expires_at.min(now + last_member_query_time);
+                listener.queries_left = ROBUSTNESS;
+                listener.next_query = now;
+            }
+        }
+    }
+
+    /// Another querier asked whether listeners of `group` are left, see RFC 3376 § 6.6.1.
+    fn group_queried(&mut self, group: IpAddress, now: Instant) {
+        if let Some(listener) = self.listeners.get_mut(&group) {
+            let last_member_query_time = LAST_MEMBER_QUERY_INTERVAL * ROBUSTNESS as u32;
+            listener.expires_at = listener.expires_at.min(now + last_member_query_time);
+        }
+    }
+
+    /// Forget the groups whose listeners all left.
+    fn expire_listeners(&mut self, now: Instant) {
+        while let Some(group) = self
+            .listeners
+            .iter()
+            .find(|(_, listener)| listener.expires_at <= now)
+            .map(|(group, _)| *group)
+        {
+            self.listeners.remove(&group);
+        }
+    }
+}
+
+impl Interface {
+    /// Start acting as the IGMP and MLD querier of the link, as described in RFC 3376 § 6
+    /// and RFC 3810 § 7.
+    ///
+    /// Hosts only report the multicast groups they listen to when a querier asks them to,
+    /// which is usually the job of a multicast router. On a small isolated network without
+    /// one, the interface can take that role: it sends general queries every query
+    /// interval, and keeps track of the groups that have listeners on the link, see
+    /// [`multicast_listeners`](Self::multicast_listeners).
+    ///
+    /// When several queriers are present, the one with the lowest address sends the queries,
+    /// and the others only listen to the reports. IGMP queries are sent from the first IPv4
+    /// address of the interface, and MLD queries from its first link-local IPv6 address; MLD
+    /// is not available on IEEE 802.15.4 interfaces. Only the groups are tracked, not the
+    /// sources of IGMPv3 and MLDv2 reports.
+    pub fn enable_multicast_querier(&mut self) {
+        let query_interval = self.inner.multicast_querier.query_interval;
+        self.inner.multicast_querier = MulticastQuerier {
+            enabled: true,
+            query_interval,
+            ..MulticastQuerier::new()
+        };
+    }
+
+    /// Stop acting as a querier, and forget the groups that have listeners on the link.
+    pub fn disable_multicast_querier(&mut self) {
+        let query_interval = self.inner.multicast_querier.query_interval;
+        self.inner.multicast_querier = MulticastQuerier {
+            query_interval,
+            ..MulticastQuerier::new()
+        };
+    }
+
+    /// Set the interval between two general queries. The default is 125 seconds.
+    ///
+    /// # Panics
+    /// This function panics if the interval is not longer than the maximum response time of
+    /// the queries, which is 10 seconds.
+    pub fn set_multicast_query_interval(&mut self, interval: Duration) {
+        assert!(interval > QUERY_RESPONSE_INTERVAL);
+        self.inner.multicast_querier.query_interval = interval;
+    }
+
+    /// Check whether the interface is currently the IGMP querier of the link.
+    pub fn is_igmp_querier(&self) -> bool {
+        self.inner.igmp_querier_addr().is_some()
+            && self.inner.multicast_querier.igmp.is_querier(self.inner.now)
+    }
+
+    /// Check whether the interface is currently the MLD querier of the link.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn is_mld_querier(&self) -> bool {
+        self.inner.mld_querier_addr().is_some()
+            && self.inner.multicast_querier.mld.is_querier(self.inner.now)
+    }
+
+    /// Get the multicast groups that have listeners on the link, as learnt by the querier.
+    pub fn multicast_listeners(&self) -> impl Iterator<Item = IpAddress> + '_ {
+        let now = self.inner.now;
+        self.inner
+            .multicast_querier
+            .listeners
+            .iter()
+            .filter(move |(_, listener)| listener.expires_at > now)
+            .map(|(group, _)| *group)
+    }
+
+    /// Check whether a multicast group has listeners on the link, as learnt by the querier.
+    pub fn has_multicast_listeners<T: Into<IpAddress>>(&self, group: T) -> bool {
+        self.inner
+            .multicast_querier
+            .listeners
+            .get(&group.into())
+            .map_or(false, |listener| listener.expires_at > self.inner.now)
+    }
+
+    /// Send the general queries, and the group-specific queries following a leave, that are
+    /// due.
+    pub(crate) fn multicast_querier_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        if !self.inner.multicast_querier.enabled {
+            return false;
+        }
+
+        let now = self.inner.now;
+        self.inner.multicast_querier.expire_listeners(now);
+
+        let querier = &self.inner.multicast_querier;
+        let qqic = querier.qqic();
+
+        if self.inner.igmp_querier_addr().is_some() && querier.igmp.should_query(now) {
+            let pkt = self.inner.igmp_query_packet(
+                Ipv4Address::UNSPECIFIED,
+                QUERY_RESPONSE_INTERVAL,
+                ROBUSTNESS,
+                qqic,
+            );
+            if !self.multicast_querier_dispatch(device, pkt) {
+                return false;
+            }
+
+            let query_interval = self.inner.multicast_querier.query_interval;
+            self.inner
+                .multicast_querier
+                .igmp
+                .query_sent(now, query_interval);
+            return true;
+        }
+
+        #[cfg(feature = "proto-ipv6")]
+        if self.inner.mld_querier_addr().is_some() && querier.mld.should_query(now) {
+            let mut buffer = [0; MLD_QUERY_LEN];
+            let pkt = self.inner.mld_query_packet(
+                &mut buffer,
+                Ipv6Address::UNSPECIFIED,
+                QUERY_RESPONSE_INTERVAL,
+                ROBUSTNESS,
+                qqic,
+            );
+            if !self.multicast_querier_dispatch(device, pkt) {
+                return false;
+            }
+
+            let query_interval = self.inner.multicast_querier.query_interval;
+            self.inner
+                .multicast_querier
+                .mld
+                .query_sent(now, query_interval);
+            return true;
+        }
+
+        let group = querier
+            .listeners
+            .iter()
+            .find(|(_, listener)| listener.queries_left > 0 && now >= listener.next_query)
+            .map(|(group, _)| *group);
+
+        if let Some(group) = group {
+            let sent = match group {
+                IpAddress::Ipv4(group) if self.is_igmp_querier() => {
+                    let pkt = self.inner.igmp_query_packet(
+                        group,
+                        LAST_MEMBER_QUERY_INTERVAL,
+                        ROBUSTNESS,
+                        qqic,
+                    );
+                    Some(self.multicast_querier_dispatch(device, pkt))
+                }
+                #[cfg(feature = "proto-ipv6")]
+                IpAddress::Ipv6(group) if self.is_mld_querier() => {
+                    let mut buffer = [0; MLD_QUERY_LEN];
+                    let pkt = self.inner.mld_query_packet(
+                        &mut buffer,
+                        group,
+                        LAST_MEMBER_QUERY_INTERVAL,
+                        ROBUSTNESS,
+                        qqic,
+                    );
+                    Some(self.multicast_querier_dispatch(device, pkt))
+                }
+                // Another querier took over, and queries the group itself.
+                _ => None,
+            };
+
+            // NOTE(unwrap): the group was found in the listeners above.
+            let listener = self
+                .inner
+                .multicast_querier
+                .listeners
+                .get_mut(&group)
+                .unwrap();
+            match sent {
+                Some(false) => return false,
+                Some(true) => {
+                    listener.queries_left -= 1;
+                    listener.next_query = now + LAST_MEMBER_QUERY_INTERVAL;
+                }
+                None => listener.queries_left = 0,
+            }
+            return true;
+        }
+
+        false
+    }
+
+    fn multicast_querier_dispatch<D>(&mut self, device: &mut D, pkt: Option<Packet>) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let pkt = match pkt {
+            Some(pkt) => pkt,
+            None => return true,
+        };
+
+        if let Some(tx_token) = device.transmit(self.inner.now) {
+            // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+            self.inner
+                .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                .unwrap();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl InterfaceInner {
+    pub(super) fn multicast_querier_poll_at(&self) -> Option<Instant> {
+        let querier = &self.multicast_querier;
+        if !querier.enabled {
+            return None;
+        }
+
+        let timers = querier.listeners.values().filter_map(|listener| {
+            if listener.queries_left > 0 {
+                Some(listener.next_query)
+            } else {
+                None
+            }
+        });
+        let timers = timers.chain(self.igmp_querier_addr().map(|_| querier.igmp.poll_at()));
+        #[cfg(feature = "proto-ipv6")]
+        let timers = timers.chain(self.mld_querier_addr().map(|_| querier.mld.poll_at()));
+
+        timers.min()
+    }
+
+    /// Return the address the IGMP queries are sent from, if the querier is enabled.
+    fn igmp_querier_addr(&self) -> Option<Ipv4Address> {
+        if !self.multicast_querier.enabled {
+            return None;
+        }
+        self.ipv4_addr()
+    }
+
+    /// Return the address the MLD queries are sent from, if the querier is enabled.
+    #[cfg(feature = "proto-ipv6")]
+    fn mld_querier_addr(&self) -> Option<Ipv6Address> {
+        if !self.multicast_querier.enabled {
+            return None;
+        }
+        // 6LoWPAN networks don't use MLD, and the queries can't be compressed.
+        #[cfg(feature = "medium-ieee802154")]
+        if self.caps.medium == Medium::Ieee802154 {
+            return None;
+        }
+        self.ipv6_link_local_addr()
+    }
+
+    /// Check whether a packet to a multicast group that was not joined is an IGMP message
+    /// for the querier.
+    pub(super) fn is_for_igmp_querier(&self, ipv4_repr: &Ipv4Repr) -> bool {
+        self.multicast_querier.enabled
+            && ipv4_repr.dst_addr.is_multicast()
+            && ipv4_repr.next_header == IpProtocol::Igmp
+    }
+
+    /// Check whether a packet to a multicast group that was not joined is an MLD message
+    /// for the querier.
+    #[cfg(feature = "proto-ipv6")]
+    pub(super) fn is_for_mld_querier(
+        &self,
+        ipv6_repr: &Ipv6Repr,
+        next_header: IpProtocol,
+        ip_payload: &[u8],
+    ) -> bool {
+        self.multicast_querier.enabled
+            && ipv6_repr.dst_addr.is_multicast()
+            && next_header == IpProtocol::Icmpv6
+            && Icmpv6Packet::new_checked(ip_payload).map_or(false, |icmp| icmp.msg_type().is_mld())
+    }
+
+    /// Router duties of the **IGMP** protocol: querier election and tracking of the groups
+    /// with listeners, see RFC 3376 § 6.
+    pub(super) fn multicast_querier_process_igmp(
+        &mut self,
+        ipv4_repr: &Ipv4Repr,
+        igmp_repr: &IgmpRepr,
+    ) {
+        if !self.multicast_querier.enabled {
+            return;
+        }
+
+        let now = self.now;
+        let is_querier =
+            self.igmp_querier_addr().is_some() && self.multicast_querier.igmp.is_querier(now);
+
+        match *igmp_repr {
+            IgmpRepr::MembershipQuery { group_addr, .. }
+            | IgmpRepr::MembershipQueryV3 { group_addr, .. } => {
+                let own_addr = match self.igmp_querier_addr() {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                if ipv4_repr.src_addr == own_addr {
+                    return;
+                }
+
+                if ipv4_repr.src_addr < own_addr {
+                    net_debug!("IGMP querier {} present", ipv4_repr.src_addr);
+                    let interval = self.multicast_querier.other_querier_present_interval();
+                    self.multicast_querier.igmp.other_querier_until = Some(now + interval);
+                }
+                if !group_addr.is_unspecified() {
+                    self.multicast_querier.group_queried(group_addr.into(), now);
+                }
+            }
+            IgmpRepr::MembershipReport { group_addr, .. } => {
+                self.multicast_querier
+                    .listener_reported(group_addr.into(), now);
+            }
+            IgmpRepr::LeaveGroup { group_addr } => {
+                self.multicast_querier
+                    .listener_left(group_addr.into(), now, is_querier);
+            }
+            IgmpRepr::MembershipReportV3 {
+                nr_group_rcrds,
+                mut data,
+            } => {
+                for _ in 0..nr_group_rcrds {
+                    let record = check!(IgmpGroupRecord::new_checked(data));
+                    let group = IpAddress::Ipv4(record.group_addr());
+                    match record.record_type() {
+                        IgmpRecordType::ModeIsExclude | IgmpRecordType::ChangeToExclude => {
+                            self.multicast_querier.listener_reported(group, now)
+                        }
+                        IgmpRecordType::ModeIsInclude
+                        | IgmpRecordType::ChangeToInclude
+                        | IgmpRecordType::AllowNewSources
+                            if record.num_srcs() > 0 =>
+                        {
+                            self.multicast_querier.listener_reported(group, now)
+                        }
+                        IgmpRecordType::ChangeToInclude => {
+                            self.multicast_querier.listener_left(group, now, is_querier)
+                        }
+                        _ => (),
+                    }
+                    data = &data[record.record_len()..];
+                }
+            }
+        }
+    }
+
+    /// Router duties of the **MLDv2** protocol: querier election and tracking of the groups
+    /// with listeners, see RFC 3810 § 7.
+    #[cfg(feature = "proto-ipv6")]
+    pub(super) fn multicast_querier_process_mld(
+        &mut self,
+        ipv6_repr: &Ipv6Repr,
+        mld_repr: &MldRepr,
+    ) {
+        if !self.multicast_querier.enabled {
+            return;
+        }
+
+        // MLD messages are only valid on the link, see RFC 3810 § 5.1.14 and § 5.2.13.
+        if !ipv6_repr.src_addr.is_link_local() || ipv6_repr.hop_limit != 1 {
+            net_debug!("MLD message with invalid source or hop limit");
+            return;
+        }
+
+        let now = self.now;
+        let is_querier =
+            self.mld_querier_addr().is_some() && self.multicast_querier.mld.is_querier(now);
+
+        match *mld_repr {
+            MldRepr::Query { mcast_addr, .. } => {
+                let own_addr = match self.mld_querier_addr() {
+                    Some(addr) => addr,
+                    None => return,
+                };
+                if ipv6_repr.src_addr == own_addr {
+                    return;
+                }
+
+                if ipv6_repr.src_addr < own_addr {
+                    net_debug!("MLD querier {} present", ipv6_repr.src_addr);
+                    let interval = self.multicast_querier.other_querier_present_interval();
+                    self.multicast_querier.mld.other_querier_until = Some(now + interval);
+                }
+                if !mcast_addr.is_unspecified() {
+                    self.multicast_querier.group_queried(mcast_addr.into(), now);
+                }
+            }
+            MldRepr::Report {
+                nr_mcast_addr_rcrds,
+                mut data,
+            } => {
+                for _ in 0..nr_mcast_addr_rcrds {
+                    let record = check!(MldAddressRecord::new_checked(data));
+                    if data.len() < record.record_len() {
+                        net_debug!("MLD report with truncated address record");
+                        return;
+                    }
+
+                    let group = IpAddress::Ipv6(record.mcast_addr());
+                    match record.record_type() {
+                        MldRecordType::ModeIsExclude | MldRecordType::ChangeToExclude => {
+                            self.multicast_querier.listener_reported(group, now)
+                        }
+                        MldRecordType::ModeIsInclude
+                        | MldRecordType::ChangeToInclude
+                        | MldRecordType::AllowNewSources
+                            if record.num_srcs() > 0 =>
+                        {
+                            self.multicast_querier.listener_reported(group, now)
+                        }
+                        MldRecordType::ChangeToInclude => {
+                            self.multicast_querier.listener_left(group, now, is_querier)
+                        }
+                        _ => (),
+                    }
+                    data = &data[record.record_len()..];
+                }
+            }
+        }
+    }
+}
@@ -99,7 +99,15 @@ impl InterfaceInner {
             }
         };
 
-        self.process_ipv6(sockets, meta, &check!(Ipv6Packet::new_checked(payload)))
+        // The 6LoWPAN adaptation layer already reassembled the packet, the fragments buffer is
+        // still borrowed by it.
+        self.process_ipv6(
+            sockets,
+            meta,
+            &check!(Ipv6Packet::new_checked(payload)),
+            None,
+            false,
+        )
     }
 
     #[cfg(feature = "proto-sixlowpan-fragmentation")]
@@ -251,7 +259,10 @@ impl InterfaceInner {
                 SixlowpanNextHeader::Uncompressed(proto) => {
                     // We have a 6LoWPAN uncompressed header.
                     match proto {
-                        IpProtocol::Tcp | IpProtocol::Udp | IpProtocol::Icmpv6 => {
+                        IpProtocol::Tcp
+                        | IpProtocol::Udp
+                        | IpProtocol::UdpLite
+                        | IpProtocol::Icmpv6 => {
                             // There can be no protocol after this one, so we can just copy the
                             // rest of the data buffer. There is also no length field in the UDP
                             // header that we need to correct as this header was not changed by the
@@ -279,6 +290,8 @@ impl InterfaceInner {
             next_header: decompress_next_header(iphc_repr.next_header, iphc.payload())?,
             payload_len: total_len.unwrap_or(payload_len) - 40,
             hop_limit: iphc_repr.hop_limit,
+            dscp: 0,
+            ecn: 0,
         };
         ipv6_repr.emit(&mut ipv6_header);
 
@@ -420,7 +433,7 @@ impl InterfaceInner {
         ieee_repr: &Ieee802154Repr,
         mut buffer: &mut [u8],
     ) {
-        let last_header = packet.payload.as_sixlowpan_next_header();
+        let last_header = packet.payload_sixlowpan_next_header();
         let next_header = last_header;
 
         #[cfg(feature = "proto-ipv6-hbh")]
@@ -528,6 +541,17 @@ impl InterfaceInner {
                     checksum_caps,
                 );
             }
+            #[cfg(feature = "socket-udp")]
+            IpPayload::UdpLite(udp_repr, payload) => {
+                udp_repr.emit(
+                    &mut UdpLitePacket::new_unchecked(
+                        &mut buffer[..udp_repr.header_len() + payload.len()],
+                    ),
+                    &packet.header.src_addr.into(),
+                    &packet.header.dst_addr.into(),
+                    |buf| buf.copy_from_slice(payload),
+                );
+            }
             #[cfg(feature = "socket-tcp")]
             IpPayload::Tcp(tcp_repr) => {
                 tcp_repr.emit(
@@ -537,8 +561,9 @@ impl InterfaceInner {
                     checksum_caps,
                 );
             }
-            #[cfg(feature = "socket-raw")]
-            IpPayload::Raw(_raw) => todo!(),
+            IpPayload::Raw(raw) => {
+                buffer[..raw.len()].copy_from_slice(raw);
+            }
 
             #[allow(unreachable_patterns)]
             _ => unreachable!(),
@@ -554,7 +579,7 @@ impl InterfaceInner {
         packet: &PacketV6,
         ieee_repr: &Ieee802154Repr,
     ) -> (usize, usize, usize) {
-        let last_header = packet.payload.as_sixlowpan_next_header();
+        let last_header = packet.payload_sixlowpan_next_header();
         let next_header = last_header;
 
         #[cfg(feature = "proto-ipv6-hbh")]
@@ -843,6 +868,8 @@ mod tests {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 66,
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
             },
             #[cfg(feature = "proto-ipv6-hbh")]
             hop_by_hop: None,
@@ -922,6 +949,8 @@ mod tests {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 66,
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
             },
             #[cfg(feature = "proto-ipv6-hbh")]
             hop_by_hop: Some(Ipv6HopByHopRepr {
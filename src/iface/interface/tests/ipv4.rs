@@ -77,6 +77,9 @@ fn test_no_icmp_no_unicast(#[case] medium: Medium) {
         next_header: IpProtocol::Unknown(0x0c),
         payload_len: 0,
         hop_limit: 0x40,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     });
 
     let mut bytes = vec![0u8; 54];
@@ -92,7 +95,8 @@ fn test_no_icmp_no_unicast(#[case] medium: Medium) {
             &mut sockets,
             PacketMeta::default(),
             &frame,
-            &mut iface.fragments
+            &mut iface.fragments,
+            false
         ),
         None
     );
@@ -114,6 +118,9 @@ fn test_icmp_error_no_payload(#[case] medium: Medium) {
         next_header: IpProtocol::Unknown(0x0c),
         payload_len: 0,
         hop_limit: 0x40,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     });
 
     let mut bytes = vec![0u8; 34];
@@ -130,8 +137,12 @@ fn test_icmp_error_no_payload(#[case] medium: Medium) {
             next_header: IpProtocol::Unknown(12),
             payload_len: 0,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
         },
         data: &NO_BYTES,
+        extensions: IcmpExtensions::EMPTY,
     };
 
     let expected_repr = Packet::new_ipv4(
@@ -141,6 +152,9 @@ fn test_icmp_error_no_payload(#[case] medium: Medium) {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
         },
         IpPayload::Icmpv4(icmp_repr),
     );
@@ -153,7 +167,8 @@ fn test_icmp_error_no_payload(#[case] medium: Medium) {
             &mut sockets,
             PacketMeta::default(),
             &frame,
-            &mut iface.fragments
+            &mut iface.fragments,
+            false
         ),
         Some(expected_repr)
     );
@@ -253,6 +268,9 @@ fn test_icmp_error_port_unreachable(#[case] medium: Medium) {
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     });
 
     // Emit the representations to a packet
@@ -277,8 +295,12 @@ fn test_icmp_error_port_unreachable(#[case] medium: Medium) {
             next_header: IpProtocol::Udp,
             payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
         },
         data,
+        extensions: IcmpExtensions::EMPTY,
     };
     let expected_repr = Packet::new_ipv4(
         Ipv4Repr {
@@ -287,6 +309,9 @@ fn test_icmp_error_port_unreachable(#[case] medium: Medium) {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
         },
         IpPayload::Icmpv4(icmp_repr),
     );
@@ -306,6 +331,9 @@ fn test_icmp_error_port_unreachable(#[case] medium: Medium) {
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     });
 
     // Emit the representations to a packet
@@ -360,7 +388,10 @@ fn test_handle_ipv4_broadcast(#[case] medium: Medium) {
         dst_addr: Ipv4Address::BROADCAST,
         next_header: IpProtocol::Icmp,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
         payload_len: icmpv4_repr.buffer_len(),
+        options: Ipv4Options::EMPTY,
     };
 
     // Emit to ip frame
@@ -388,7 +419,10 @@ fn test_handle_ipv4_broadcast(#[case] medium: Medium) {
         dst_addr: src_ipv4_addr,
         next_header: IpProtocol::Icmp,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
         payload_len: expected_icmpv4_repr.buffer_len(),
+        options: Ipv4Options::EMPTY,
     };
     let expected_packet =
         Packet::new_ipv4(expected_ipv4_repr, IpPayload::Icmpv4(expected_icmpv4_repr));
@@ -398,7 +432,8 @@ fn test_handle_ipv4_broadcast(#[case] medium: Medium) {
             &mut sockets,
             PacketMeta::default(),
             &frame,
-            &mut iface.fragments
+            &mut iface.fragments,
+            false
         ),
         Some(expected_packet)
     );
@@ -622,6 +657,9 @@ fn test_icmpv4_socket(#[case] medium: Medium) {
         next_header: IpProtocol::Icmp,
         payload_len: 24,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     };
 
     // Open a socket and ensure the packet is handled due to the listening
@@ -657,41 +695,130 @@ fn test_icmpv4_socket(#[case] medium: Medium) {
     );
 }
 
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "socket-icmp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "socket-icmp", feature = "medium-ethernet"))]
+fn test_icmpv4_socket_filter(#[case] medium: Medium) {
+    use crate::socket::filter::{Instruction, Program};
+    use crate::wire::Icmpv4Packet;
+
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let rx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY; 2], vec![0; 48]);
+    let tx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 24]);
+    let mut icmpv4_socket = icmp::Socket::new(rx_buffer, tx_buffer);
+    assert_eq!(icmpv4_socket.bind(icmp::Endpoint::Ident(0x1234)), Ok(()));
+
+    // Only receive the messages with an odd sequence number.
+    const ODD_SEQ_NO: [Instruction; 4] = [
+        Instruction::new(0x30, 0, 0, 7), // ldb [7]
+        Instruction::new(0x45, 0, 1, 1), // jset #1, accept, drop
+        Instruction::new(0x06, 0, 0, 1), // accept: ret #1
+        Instruction::new(0x06, 0, 0, 0), // drop: ret #0
+    ];
+    icmpv4_socket.set_filter(Some(Program::new(&ODD_SEQ_NO).unwrap()));
+    let socket_handle = sockets.add(icmpv4_socket);
+
+    let ipv4_repr = Ipv4Repr {
+        src_addr: Ipv4Address::new(0x7f, 0x00, 0x00, 0x02),
+        dst_addr: Ipv4Address::new(0x7f, 0x00, 0x00, 0x01),
+        next_header: IpProtocol::Icmp,
+        payload_len: 12,
+        hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
+    };
+    for seq_no in [2, 3] {
+        let mut bytes = [0; 12];
+        Icmpv4Repr::EchoReply {
+            ident: 0x1234,
+            seq_no,
+            data: &[0xff; 4],
+        }
+        .emit(
+            &mut Icmpv4Packet::new_unchecked(&mut bytes[..]),
+            &ChecksumCapabilities::default(),
+        );
+        assert_eq!(
+            iface.inner.process_icmpv4(&mut sockets, ipv4_repr, &bytes),
+            None
+        );
+    }
+
+    let socket = sockets.get_mut::<icmp::Socket>(socket_handle);
+    let (payload, _) = socket.recv().unwrap();
+    assert_eq!(Icmpv4Packet::new_unchecked(payload).echo_seq_no(), 3);
+    assert!(!socket.can_recv());
+}
+
+/// Receive the IGMP messages sent by the interface, with their IPv4 header.
+#[cfg(feature = "proto-igmp")]
+#[allow(unused)]
+fn recv_igmp(
+    device: &mut crate::tests::TestingDevice,
+    timestamp: Instant,
+) -> Vec<(Ipv4Repr, Vec<u8>)> {
+    let caps = device.capabilities();
+    let checksum_caps = &caps.checksum;
+    recv_all(device, timestamp)
+        .iter()
+        .filter_map(|frame| {
+            let ipv4_packet = match caps.medium {
+                #[cfg(feature = "medium-ethernet")]
+                Medium::Ethernet => {
+                    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+                    Ipv4Packet::new_checked(eth_frame.payload()).ok()?
+                }
+                #[cfg(feature = "medium-ip")]
+                Medium::Ip => Ipv4Packet::new_checked(&frame[..]).ok()?,
+                #[cfg(feature = "medium-ieee802154")]
+                Medium::Ieee802154 => todo!(),
+            };
+            let ipv4_repr = Ipv4Repr::parse(&ipv4_packet, checksum_caps).ok()?;
+            if ipv4_repr.next_header != IpProtocol::Igmp {
+                return None;
+            }
+            Some((ipv4_repr, ipv4_packet.payload().to_vec()))
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Parse the group records of an IGMPv3 report.
+#[cfg(feature = "proto-igmp")]
+#[allow(unused)]
+fn igmpv3_records(igmp: &[u8]) -> Vec<(IgmpRecordType, Ipv4Address, Vec<Ipv4Address>)> {
+    let igmp_packet = IgmpPacket::new_checked(igmp).unwrap();
+    let (nr_group_rcrds, mut data) = match IgmpRepr::parse(&igmp_packet).unwrap() {
+        IgmpRepr::MembershipReportV3 {
+            nr_group_rcrds,
+            data,
+        } => (nr_group_rcrds, data),
+        repr => panic!("unexpected IGMP message {repr}"),
+    };
+
+    let mut records = Vec::new();
+    for _ in 0..nr_group_rcrds {
+        let record = IgmpGroupRecord::new_checked(data).unwrap();
+        records.push((
+            record.record_type(),
+            record.group_addr(),
+            record.sources().collect(),
+        ));
+        data = &data[record.record_len()..];
+    }
+    assert!(data.is_empty());
+    records
+}
+
 #[rstest]
 #[case(Medium::Ip)]
 #[cfg(all(feature = "proto-igmp", feature = "medium-ip"))]
 #[case(Medium::Ethernet)]
 #[cfg(all(feature = "proto-igmp", feature = "medium-ethernet"))]
 fn test_handle_igmp(#[case] medium: Medium) {
-    fn recv_igmp(
-        device: &mut crate::tests::TestingDevice,
-        timestamp: Instant,
-    ) -> Vec<(Ipv4Repr, IgmpRepr)> {
-        let caps = device.capabilities();
-        let checksum_caps = &caps.checksum;
-        recv_all(device, timestamp)
-            .iter()
-            .filter_map(|frame| {
-                let ipv4_packet = match caps.medium {
-                    #[cfg(feature = "medium-ethernet")]
-                    Medium::Ethernet => {
-                        let eth_frame = EthernetFrame::new_checked(frame).ok()?;
-                        Ipv4Packet::new_checked(eth_frame.payload()).ok()?
-                    }
-                    #[cfg(feature = "medium-ip")]
-                    Medium::Ip => Ipv4Packet::new_checked(&frame[..]).ok()?,
-                    #[cfg(feature = "medium-ieee802154")]
-                    Medium::Ieee802154 => todo!(),
-                };
-                let ipv4_repr = Ipv4Repr::parse(&ipv4_packet, checksum_caps).ok()?;
-                let ip_payload = ipv4_packet.payload();
-                let igmp_packet = IgmpPacket::new_checked(ip_payload).ok()?;
-                let igmp_repr = IgmpRepr::parse(&igmp_packet).ok()?;
-                Some((ipv4_repr, igmp_repr))
-            })
-            .collect::<Vec<_>>()
-    }
-
     let groups = [
         Ipv4Address::new(224, 0, 0, 22),
         Ipv4Address::new(224, 0, 0, 56),
@@ -711,13 +838,14 @@ fn test_handle_igmp(#[case] medium: Medium) {
     assert_eq!(reports.len(), 2);
     for (i, group_addr) in groups.iter().enumerate() {
         assert_eq!(reports[i].0.next_header, IpProtocol::Igmp);
-        assert_eq!(reports[i].0.dst_addr, *group_addr);
         assert_eq!(
-            reports[i].1,
-            IgmpRepr::MembershipReport {
-                group_addr: *group_addr,
-                version: IgmpVersion::Version2,
-            }
+            reports[i].0.dst_addr,
+            Ipv4Address::MULTICAST_ALL_IGMPV3_ROUTERS
+        );
+        assert_eq!(reports[i].0.options.router_alert(), Some(0));
+        assert_eq!(
+            igmpv3_records(&reports[i].1),
+            vec![(IgmpRecordType::ChangeToExclude, *group_addr, vec![])]
         );
     }
 
@@ -729,21 +857,32 @@ fn test_handle_igmp(#[case] medium: Medium) {
         0x00, 0x00, 0x02, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x00,
     ];
-    {
-        // Transmit GENERAL_QUERY_BYTES into loopback
-        let tx_token = device.transmit(timestamp).unwrap();
-        tx_token.consume(GENERAL_QUERY_BYTES.len(), |buffer| {
-            buffer.copy_from_slice(GENERAL_QUERY_BYTES);
-        });
+    iface.inner.now = timestamp;
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(GENERAL_QUERY_BYTES).unwrap(),
+            &mut iface.fragments,
+            false
+        ),
+        None
+    );
+
+    // The current state of the groups is reported, spread over the maximum response time.
+    let timestamp = Instant::from_secs(10);
+    iface.inner.now = timestamp;
+    while iface.igmp_egress(&mut device) {}
+    let reports = recv_igmp(&mut device, timestamp);
+    assert_eq!(reports.len(), 2);
+    for (i, group_addr) in groups.iter().enumerate() {
+        assert_eq!(
+            igmpv3_records(&reports[i].1),
+            vec![(IgmpRecordType::ModeIsExclude, *group_addr, vec![])]
+        );
     }
-    // Trigger processing until all packets received through the
-    // loopback have been processed, including responses to
-    // GENERAL_QUERY_BYTES. Therefore `recv_all()` would return 0
-    // pkts that could be checked.
-    iface.socket_ingress(&mut device, &mut sockets);
 
     // Leave multicast groups
-    let timestamp = Instant::ZERO;
     for group in &groups {
         iface
             .leave_multicast_group(&mut device, *group, timestamp)
@@ -754,9 +893,433 @@ fn test_handle_igmp(#[case] medium: Medium) {
     assert_eq!(leaves.len(), 2);
     for (i, group_addr) in groups.iter().cloned().enumerate() {
         assert_eq!(leaves[i].0.next_header, IpProtocol::Igmp);
-        assert_eq!(leaves[i].0.dst_addr, Ipv4Address::MULTICAST_ALL_ROUTERS);
-        assert_eq!(leaves[i].1, IgmpRepr::LeaveGroup { group_addr });
+        assert_eq!(
+            leaves[i].0.dst_addr,
+            Ipv4Address::MULTICAST_ALL_IGMPV3_ROUTERS
+        );
+        assert_eq!(
+            igmpv3_records(&leaves[i].1),
+            vec![(IgmpRecordType::ChangeToInclude, group_addr, vec![])]
+        );
+    }
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "proto-igmp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "proto-igmp", feature = "medium-ethernet"))]
+fn test_igmp_older_querier(#[case] medium: Medium) {
+    let groups = [
+        Ipv4Address::new(224, 0, 0, 56),
+        Ipv4Address::new(224, 0, 0, 57),
+    ];
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    // IGMPv2 general query
+    let query_repr = IgmpRepr::MembershipQuery {
+        max_resp_time: Duration::from_secs(10),
+        group_addr: Ipv4Address::UNSPECIFIED,
+        version: IgmpVersion::Version2,
+    };
+    let ipv4_repr = Ipv4Repr {
+        src_addr: Ipv4Address::new(192, 168, 1, 2),
+        dst_addr: Ipv4Address::MULTICAST_ALL_SYSTEMS,
+        next_header: IpProtocol::Igmp,
+        payload_len: query_repr.buffer_len(),
+        hop_limit: 1,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
+    };
+    let mut bytes = vec![0; ipv4_repr.buffer_len() + query_repr.buffer_len()];
+    ipv4_repr.emit(
+        &mut Ipv4Packet::new_unchecked(&mut bytes[..]),
+        &ChecksumCapabilities::default(),
+    );
+    query_repr.emit(&mut IgmpPacket::new_unchecked(
+        &mut bytes[ipv4_repr.buffer_len()..],
+    ));
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments,
+            false
+        ),
+        None
+    );
+
+    // Groups are reported with IGMPv2 messages while the IGMPv2 querier is present
+    let timestamp = Instant::from_secs(1);
+    iface
+        .join_multicast_group(&mut device, groups[0], timestamp)
+        .unwrap();
+    let reports = recv_igmp(&mut device, timestamp);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].0.dst_addr, groups[0]);
+    assert_eq!(
+        IgmpRepr::parse(&IgmpPacket::new_checked(&reports[0].1[..]).unwrap()),
+        Ok(IgmpRepr::MembershipReport {
+            group_addr: groups[0],
+            version: IgmpVersion::Version2,
+        })
+    );
+
+    // Source filters are not reported to IGMPv2 routers
+    let source = Ipv4Address::new(10, 0, 0, 1);
+    assert_eq!(
+        iface.leave_multicast_source(&mut device, groups[0], source, timestamp),
+        Ok(false)
+    );
+
+    // Back to IGMPv3 once the IGMPv2 querier is gone
+    let timestamp = Instant::from_secs(300);
+    iface
+        .join_multicast_group(&mut device, groups[1], timestamp)
+        .unwrap();
+    let reports = recv_igmp(&mut device, timestamp);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(
+        reports[0].0.dst_addr,
+        Ipv4Address::MULTICAST_ALL_IGMPV3_ROUTERS
+    );
+    assert_eq!(
+        igmpv3_records(&reports[0].1),
+        vec![(IgmpRecordType::ChangeToExclude, groups[1], vec![])]
+    );
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "proto-igmp", feature = "socket-udp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(
+    feature = "proto-igmp",
+    feature = "socket-udp",
+    feature = "medium-ethernet"
+))]
+fn test_igmp_source_filter(#[case] medium: Medium) {
+    use crate::socket::udp;
+    use crate::wire::{UdpPacket, UdpRepr};
+
+    static UDP_PAYLOAD: [u8; 5] = [0x48, 0x65, 0x6c, 0x6c, 0x6f];
+
+    let group = Ipv4Address::new(232, 1, 1, 1);
+    let sources = [Ipv4Address::new(10, 0, 0, 1), Ipv4Address::new(10, 0, 0, 2)];
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    let udp_rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 15]);
+    let udp_tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 15]);
+    let mut udp_socket = udp::Socket::new(udp_rx_buffer, udp_tx_buffer);
+    udp_socket.bind(5000).unwrap();
+    let udp_socket_handle = sockets.add(udp_socket);
+
+    // Subscribe to the group for both sources, one at a time
+    let timestamp = Instant::ZERO;
+    for source in &sources {
+        assert_eq!(
+            iface.join_multicast_source(&mut device, group, *source, timestamp),
+            Ok(true)
+        );
+        let reports = recv_igmp(&mut device, timestamp);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(
+            igmpv3_records(&reports[0].1),
+            vec![(IgmpRecordType::AllowNewSources, group, vec![*source])]
+        );
+    }
+    assert!(iface.has_multicast_group(group));
+
+    // Unsubscribe from the first source
+    assert_eq!(
+        iface.leave_multicast_source(&mut device, group, sources[0], timestamp),
+        Ok(true)
+    );
+    let reports = recv_igmp(&mut device, timestamp);
+    assert_eq!(
+        igmpv3_records(&reports[0].1),
+        vec![(IgmpRecordType::BlockOldSources, group, vec![sources[0]])]
+    );
+
+    // Only the datagrams of the remaining source are received
+    let udp_datagram = |src_addr: Ipv4Address| {
+        let udp_repr = UdpRepr {
+            src_port: 5000,
+            dst_port: 5000,
+        };
+        let ipv4_repr = Ipv4Repr {
+            src_addr,
+            dst_addr: group,
+            next_header: IpProtocol::Udp,
+            hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
+            options: Ipv4Options::EMPTY,
+        };
+        let mut bytes = vec![0u8; ipv4_repr.buffer_len() + ipv4_repr.payload_len];
+        ipv4_repr.emit(
+            &mut Ipv4Packet::new_unchecked(&mut bytes),
+            &ChecksumCapabilities::default(),
+        );
+        udp_repr.emit(
+            &mut UdpPacket::new_unchecked(&mut bytes[ipv4_repr.buffer_len()..]),
+            &src_addr.into(),
+            &group.into(),
+            UDP_PAYLOAD.len(),
+            |buf| buf.copy_from_slice(&UDP_PAYLOAD),
+            &ChecksumCapabilities::default(),
+        );
+        bytes
+    };
+
+    for source in &sources {
+        let bytes = udp_datagram(*source);
+        assert_eq!(
+            iface.inner.process_ipv4(
+                &mut sockets,
+                PacketMeta::default(),
+                &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+                &mut iface.fragments,
+                false
+            ),
+            None
+        );
     }
+
+    let socket = sockets.get_mut::<udp::Socket>(udp_socket_handle);
+    assert_eq!(
+        socket.recv(),
+        Ok((
+            &UDP_PAYLOAD[..],
+            udp::UdpMetadata {
+                local_address: Some(group.into()),
+                ..IpEndpoint::new(sources[1].into(), 5000).into()
+            }
+        ))
+    );
+    assert!(!socket.can_recv());
+
+    // Receive from all the sources except the second one
+    assert_eq!(
+        iface.set_multicast_filter(
+            &mut device,
+            group,
+            MulticastFilterMode::Exclude,
+            &sources[1..],
+            timestamp
+        ),
+        Ok(true)
+    );
+    let reports = recv_igmp(&mut device, timestamp);
+    assert_eq!(
+        igmpv3_records(&reports[0].1),
+        vec![(IgmpRecordType::ChangeToExclude, group, vec![sources[1]])]
+    );
+
+    // Leaving an exclude mode group removes it
+    iface
+        .leave_multicast_group(&mut device, group, timestamp)
+        .unwrap();
+    let reports = recv_igmp(&mut device, timestamp);
+    assert_eq!(
+        igmpv3_records(&reports[0].1),
+        vec![(IgmpRecordType::ChangeToInclude, group, vec![])]
+    );
+    assert!(!iface.has_multicast_group(group));
+}
+
+/// Build an IPv4 packet carrying an IGMP message, as sent by another host of the link.
+#[cfg(feature = "proto-multicast-querier")]
+#[allow(unused)]
+fn igmp_packet(src_addr: Ipv4Address, dst_addr: Ipv4Address, igmp_repr: IgmpRepr) -> Vec<u8> {
+    let ipv4_repr = Ipv4Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Igmp,
+        payload_len: igmp_repr.buffer_len(),
+        hop_limit: 1,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
+    };
+    let mut bytes = vec![0; ipv4_repr.buffer_len() + igmp_repr.buffer_len()];
+    ipv4_repr.emit(
+        &mut Ipv4Packet::new_unchecked(&mut bytes[..]),
+        &ChecksumCapabilities::default(),
+    );
+    igmp_repr.emit(&mut IgmpPacket::new_unchecked(
+        &mut bytes[ipv4_repr.buffer_len()..],
+    ));
+    bytes
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "proto-multicast-querier", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "proto-multicast-querier", feature = "medium-ethernet"))]
+fn test_igmp_querier(#[case] medium: Medium) {
+    let group = Ipv4Address::new(224, 0, 0, 56);
+    let host = Ipv4Address::new(192, 168, 1, 2);
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.enable_multicast_querier();
+
+    let mut recv_queries = |iface: &mut Interface, timestamp: Instant| {
+        iface.inner.now = timestamp;
+        while iface.multicast_querier_egress(&mut device) {}
+        recv_igmp(&mut device, timestamp)
+            .into_iter()
+            .map(|(ipv4_repr, igmp)| {
+                assert_eq!(ipv4_repr.src_addr, Ipv4Address::new(192, 168, 1, 1));
+                assert_eq!(ipv4_repr.hop_limit, 1);
+                assert_eq!(ipv4_repr.options.router_alert(), Some(0));
+                let igmp_repr = IgmpRepr::parse(&IgmpPacket::new_checked(&igmp[..]).unwrap());
+                match igmp_repr.unwrap() {
+                    IgmpRepr::MembershipQueryV3 {
+                        group_addr,
+                        max_resp_time,
+                        qrv,
+                        qqic,
+                        ..
+                    } => {
+                        assert_eq!((qrv, qqic), (2, 125));
+                        (ipv4_repr.dst_addr, group_addr, max_resp_time)
+                    }
+                    repr => panic!("unexpected IGMP message {repr}"),
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    let general_query = (
+        Ipv4Address::MULTICAST_ALL_SYSTEMS,
+        Ipv4Address::UNSPECIFIED,
+        Duration::from_secs(10),
+    );
+
+    // The startup queries are sent at a quarter of the query interval
+    assert_eq!(recv_queries(&mut iface, Instant::ZERO), vec![general_query]);
+    assert!(iface.is_igmp_querier());
+    assert_eq!(recv_queries(&mut iface, Instant::from_secs(31)), vec![]);
+    assert_eq!(
+        recv_queries(&mut iface, Instant::from_millis(31_250)),
+        vec![general_query]
+    );
+    assert_eq!(
+        iface.inner.multicast_querier_poll_at(),
+        Some(Instant::from_millis(31_250 + 125_000))
+    );
+
+    // A host reports a group
+    let timestamp = Instant::from_secs(40);
+    iface.inner.now = timestamp;
+    let report = igmp_packet(
+        host,
+        group,
+        IgmpRepr::MembershipReport {
+            group_addr: group,
+            version: IgmpVersion::Version2,
+        },
+    );
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&report[..]).unwrap(),
+            &mut iface.fragments,
+            false
+        ),
+        None
+    );
+    assert!(iface.has_multicast_listeners(group));
+    assert_eq!(
+        iface.multicast_listeners().collect::<Vec<_>>(),
+        vec![IpAddress::Ipv4(group)]
+    );
+
+    // The host leaves the group, which is queried twice before being forgotten
+    let leave = igmp_packet(
+        host,
+        Ipv4Address::MULTICAST_ALL_ROUTERS,
+        IgmpRepr::LeaveGroup { group_addr: group },
+    );
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&leave[..]).unwrap(),
+            &mut iface.fragments,
+            false
+        ),
+        None
+    );
+    let group_query = (group, group, Duration::from_secs(1));
+    assert_eq!(recv_queries(&mut iface, timestamp), vec![group_query]);
+    assert_eq!(
+        recv_queries(&mut iface, Instant::from_secs(41)),
+        vec![group_query]
+    );
+    assert!(iface.has_multicast_listeners(group));
+    assert_eq!(recv_queries(&mut iface, Instant::from_secs(42)), vec![]);
+    assert!(!iface.has_multicast_listeners(group));
+
+    // No more queries once disabled
+    iface.disable_multicast_querier();
+    assert_eq!(recv_queries(&mut iface, Instant::from_secs(1000)), vec![]);
+    assert_eq!(iface.inner.multicast_querier_poll_at(), None);
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "proto-multicast-querier", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "proto-multicast-querier", feature = "medium-ethernet"))]
+fn test_igmp_querier_election(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.enable_multicast_querier();
+    while iface.multicast_querier_egress(&mut device) {}
+    assert_eq!(recv_igmp(&mut device, Instant::ZERO).len(), 1);
+
+    let mut process_query = |iface: &mut Interface, src_addr: Ipv4Address| {
+        let query = igmp_packet(
+            src_addr,
+            Ipv4Address::MULTICAST_ALL_SYSTEMS,
+            IgmpRepr::MembershipQuery {
+                max_resp_time: Duration::from_secs(10),
+                group_addr: Ipv4Address::UNSPECIFIED,
+                version: IgmpVersion::Version2,
+            },
+        );
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&query[..]).unwrap(),
+            &mut iface.fragments,
+            false,
+        );
+    };
+
+    // A querier with a higher address doesn't stop us
+    process_query(&mut iface, Ipv4Address::new(192, 168, 1, 3));
+    assert!(iface.is_igmp_querier());
+
+    // A querier with a lower address wins the election
+    process_query(&mut iface, Ipv4Address::new(192, 168, 1, 0));
+    assert!(!iface.is_igmp_querier());
+    iface.inner.now = Instant::from_secs(100);
+    while iface.multicast_querier_egress(&mut device) {}
+    assert!(recv_igmp(&mut device, Instant::from_secs(100)).is_empty());
+
+    // We take over again once it stays silent for the other querier present interval
+    iface.inner.now = Instant::from_secs(255);
+    assert!(iface.is_igmp_querier());
+    while iface.multicast_querier_egress(&mut device) {}
+    assert_eq!(recv_igmp(&mut device, Instant::from_secs(255)).len(), 1);
 }
 
 #[rstest]
@@ -776,7 +1339,12 @@ fn test_raw_socket_no_reply(#[case] medium: Medium) {
         vec![raw::PacketMetadata::EMPTY; packets],
         vec![0; 48 * packets],
     );
-    let raw_socket = raw::Socket::new(IpVersion::Ipv4, IpProtocol::Udp, rx_buffer, tx_buffer);
+    let raw_socket = raw::Socket::new(
+        Some(IpVersion::Ipv4),
+        Some(IpProtocol::Udp),
+        rx_buffer,
+        tx_buffer,
+    );
     sockets.add(raw_socket);
 
     let src_addr = Ipv4Address([127, 0, 0, 2]);
@@ -803,7 +1371,10 @@ fn test_raw_socket_no_reply(#[case] medium: Medium) {
         dst_addr,
         next_header: IpProtocol::Udp,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
         payload_len: udp_repr.header_len() + PAYLOAD_LEN,
+        options: Ipv4Options::EMPTY,
     };
 
     // Emit to frame
@@ -829,10 +1400,111 @@ fn test_raw_socket_no_reply(#[case] medium: Medium) {
             &mut sockets,
             PacketMeta::default(),
             &frame,
-            &mut iface.fragments
+            &mut iface.fragments,
+            false
+        ),
+        None
+    );
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "socket-raw", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "socket-raw", feature = "medium-ethernet"))]
+fn test_raw_socket_unclaimed(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _) = setup(medium);
+
+    let raw_buffer = || raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 2], vec![0; 64]);
+    let mut wildcard_socket = raw::Socket::new(None, None, raw_buffer(), raw_buffer());
+    wildcard_socket.set_unclaimed_only(true);
+    let wildcard_handle = sockets.add(wildcard_socket);
+    let protocol_socket = raw::Socket::new(
+        None,
+        Some(IpProtocol::Unknown(0x0d)),
+        raw_buffer(),
+        raw_buffer(),
+    );
+    let protocol_handle = sockets.add(protocol_socket);
+
+    let packet = |next_header, payload: &[u8]| {
+        let ipv4_repr = Ipv4Repr {
+            src_addr: Ipv4Address([192, 168, 1, 2]),
+            dst_addr: Ipv4Address([192, 168, 1, 1]),
+            next_header,
+            payload_len: payload.len(),
+            hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
+        };
+        let mut bytes = vec![0; ipv4_repr.buffer_len() + payload.len()];
+        ipv4_repr.emit(
+            &mut Ipv4Packet::new_unchecked(&mut bytes[..]),
+            &ChecksumCapabilities::default(),
+        );
+        bytes[ipv4_repr.buffer_len()..].copy_from_slice(payload);
+        bytes
+    };
+
+    // A packet claimed by another raw socket.
+    let claimed = packet(IpProtocol::Unknown(0x0d), &[0x2a; 4]);
+    let frame = Ipv4Packet::new_unchecked(&claimed[..]);
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &frame,
+            &mut iface.fragments,
+            false
+        ),
+        None
+    );
+    assert!(sockets.get_mut::<raw::Socket>(protocol_handle).can_recv());
+    assert!(!sockets.get_mut::<raw::Socket>(wildcard_handle).can_recv());
+
+    // A packet handled by the interface itself.
+    let mut echo_bytes = [0; 12];
+    Icmpv4Repr::EchoRequest {
+        ident: 0x1234,
+        seq_no: 1,
+        data: &[0x2a; 4],
+    }
+    .emit(
+        &mut Icmpv4Packet::new_unchecked(&mut echo_bytes[..]),
+        &ChecksumCapabilities::default(),
+    );
+    let handled = packet(IpProtocol::Icmp, &echo_bytes);
+    let frame = Ipv4Packet::new_unchecked(&handled[..]);
+    assert!(iface
+        .inner
+        .process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &frame,
+            &mut iface.fragments,
+            false
+        )
+        .is_some());
+    assert!(!sockets.get_mut::<raw::Socket>(wildcard_handle).can_recv());
+
+    // A packet of an unknown protocol is not answered with a Protocol Unreachable message.
+    let unclaimed = packet(IpProtocol::Unknown(0x0c), &[0x2a; 4]);
+    let frame = Ipv4Packet::new_unchecked(&unclaimed[..]);
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &frame,
+            &mut iface.fragments,
+            false
         ),
         None
     );
+    let socket = sockets.get_mut::<raw::Socket>(wildcard_handle);
+    let mut expected = unclaimed.clone();
+    Ipv4Packet::new_unchecked(&mut expected[..]).fill_checksum();
+    assert_eq!(socket.recv(), Ok(&expected[..]));
 }
 
 #[rstest]
@@ -871,8 +1543,8 @@ fn test_raw_socket_with_udp_socket(#[case] medium: Medium) {
         vec![0; 48 * packets],
     );
     let raw_socket = raw::Socket::new(
-        IpVersion::Ipv4,
-        IpProtocol::Udp,
+        Some(IpVersion::Ipv4),
+        Some(IpProtocol::Udp),
         raw_rx_buffer,
         raw_tx_buffer,
     );
@@ -900,7 +1572,10 @@ fn test_raw_socket_with_udp_socket(#[case] medium: Medium) {
         dst_addr,
         next_header: IpProtocol::Udp,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
+        options: Ipv4Options::EMPTY,
     };
 
     // Emit to frame
@@ -926,7 +1601,8 @@ fn test_raw_socket_with_udp_socket(#[case] medium: Medium) {
             &mut sockets,
             PacketMeta::default(),
             &frame,
-            &mut iface.fragments
+            &mut iface.fragments,
+            false
         ),
         None
     );
@@ -938,7 +1614,10 @@ fn test_raw_socket_with_udp_socket(#[case] medium: Medium) {
         socket.recv(),
         Ok((
             &UDP_PAYLOAD[..],
-            IpEndpoint::new(src_addr.into(), 67).into()
+            udp::UdpMetadata {
+                local_address: Some(dst_addr.into()),
+                ..IpEndpoint::new(src_addr.into(), 67).into()
+            }
         ))
     );
 }
@@ -979,7 +1658,10 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         dst_addr,
         next_header: IpProtocol::Udp,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
         payload_len: udp_repr.header_len() + MAX_PAYLOAD_LEN,
+        options: Ipv4Options::EMPTY,
     };
     let payload = packet.into_inner();
 
@@ -987,6 +1669,7 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         reason: Icmpv4DstUnreachable::PortUnreachable,
         header: ip_repr,
         data: &payload[..MAX_PAYLOAD_LEN],
+        extensions: IcmpExtensions::EMPTY,
     };
 
     let expected_ip_repr = Ipv4Repr {
@@ -994,7 +1677,10 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         dst_addr: src_addr,
         next_header: IpProtocol::Icmp,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
         payload_len: expected_icmp_repr.buffer_len(),
+        options: Ipv4Options::EMPTY,
     };
 
     assert_eq!(
@@ -1016,3 +1702,1929 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         ))
     );
 }
+
+#[cfg(feature = "proto-ipv4-link-local")]
+fn arp_frame(repr: &ArpRepr) -> std::vec::Vec<u8> {
+    let ArpRepr::EthernetIpv4 {
+        source_hardware_addr,
+        ..
+    } = *repr;
+
+    let mut eth_bytes = vec![0u8; EthernetFrame::<&[u8]>::buffer_len(repr.buffer_len())];
+    let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+    frame.set_dst_addr(EthernetAddress::BROADCAST);
+    frame.set_src_addr(source_hardware_addr);
+    frame.set_ethertype(EthernetProtocol::Arp);
+    repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+    eth_bytes
+}
+
+/// Run the link-local autoconfiguration until `until`, returning the ARP packets that were sent.
+#[cfg(feature = "proto-ipv4-link-local")]
+fn link_local_egress(
+    iface: &mut Interface,
+    device: &mut crate::tests::TestingDevice,
+    until: Instant,
+) -> std::vec::Vec<ArpRepr> {
+    let mut sent = std::vec::Vec::new();
+    while iface.inner.now < until {
+        iface.inner.now += Duration::from_millis(100);
+        iface.ipv4_link_local_egress(device);
+
+        while let Some(frame) = device.queue.pop_front() {
+            let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+            assert_eq!(frame.dst_addr(), EthernetAddress::BROADCAST);
+            let packet = ArpPacket::new_checked(frame.payload()).unwrap();
+            sent.push(ArpRepr::parse(&packet).unwrap());
+        }
+    }
+    sent
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "proto-ipv4-link-local")]
+fn test_ipv4_link_local_claim(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    iface.update_ip_addrs(|addrs| addrs.clear());
+
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+
+    iface.enable_ipv4_link_local();
+    assert_eq!(iface.ipv4_link_local_addr(), None);
+
+    // Three probes are sent, then the address is claimed and announced twice.
+    let sent = link_local_egress(&mut iface, &mut device, Instant::from_secs(20));
+    assert_eq!(sent.len(), 5);
+
+    let addr = iface.ipv4_link_local_addr().unwrap();
+    assert!(addr.is_link_local());
+    assert!(iface.has_ip_addr(addr));
+
+    for probe in &sent[..3] {
+        assert_eq!(
+            *probe,
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Request,
+                source_hardware_addr: local_hw_addr,
+                source_protocol_addr: Ipv4Address::UNSPECIFIED,
+                target_hardware_addr: EthernetAddress([0; 6]),
+                target_protocol_addr: addr,
+            }
+        );
+    }
+    for announcement in &sent[3..] {
+        assert_eq!(
+            *announcement,
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Request,
+                source_hardware_addr: local_hw_addr,
+                source_protocol_addr: addr,
+                target_hardware_addr: EthernetAddress([0; 6]),
+                target_protocol_addr: addr,
+            }
+        );
+    }
+
+    // Nothing more to send, and no need to be polled.
+    assert_eq!(
+        iface.poll_at(iface.inner.now, &SocketSet::new(vec![])),
+        None
+    );
+
+    iface.disable_ipv4_link_local();
+    assert_eq!(iface.ipv4_link_local_addr(), None);
+    assert!(!iface.has_ip_addr(addr));
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "proto-ipv4-link-local")]
+fn test_ipv4_link_local_conflict_while_probing(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.update_ip_addrs(|addrs| addrs.clear());
+
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    iface.enable_ipv4_link_local();
+
+    // Send the first probe.
+    let sent = link_local_egress(&mut iface, &mut device, Instant::from_millis(1100));
+    let ArpRepr::EthernetIpv4 {
+        target_protocol_addr: candidate,
+        ..
+    } = sent[0];
+
+    // Another host is using the candidate address.
+    let frame = arp_frame(&ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Reply,
+        source_hardware_addr: remote_hw_addr,
+        source_protocol_addr: candidate,
+        target_hardware_addr: EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]),
+        target_protocol_addr: Ipv4Address::UNSPECIFIED,
+    });
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            PacketMeta::default(),
+            &frame,
+            &mut iface.fragments
+        ),
+        None
+    );
+
+    // Probing restarts with a different address.
+    link_local_egress(&mut iface, &mut device, Instant::from_secs(20));
+    let addr = iface.ipv4_link_local_addr().unwrap();
+    assert_ne!(addr, candidate);
+    assert!(!iface.has_ip_addr(candidate));
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "proto-ipv4-link-local")]
+fn test_ipv4_link_local_defend(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.update_ip_addrs(|addrs| addrs.clear());
+
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    iface.enable_ipv4_link_local();
+    link_local_egress(&mut iface, &mut device, Instant::from_secs(20));
+    let addr = iface.ipv4_link_local_addr().unwrap();
+
+    // Another host probing for our address gets a reply.
+    let frame = arp_frame(&ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr: remote_hw_addr,
+        source_protocol_addr: Ipv4Address::UNSPECIFIED,
+        target_hardware_addr: EthernetAddress([0; 6]),
+        target_protocol_addr: addr,
+    });
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            PacketMeta::default(),
+            &frame,
+            &mut iface.fragments
+        ),
+        Some(EthernetPacket::Arp(ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Reply,
+            source_hardware_addr: local_hw_addr,
+            source_protocol_addr: addr,
+            target_hardware_addr: remote_hw_addr,
+            target_protocol_addr: Ipv4Address::UNSPECIFIED,
+        }))
+    );
+
+    // A conflicting announcement is answered with an announcement of our own.
+    let frame = arp_frame(&ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr: remote_hw_addr,
+        source_protocol_addr: addr,
+        target_hardware_addr: EthernetAddress([0; 6]),
+        target_protocol_addr: addr,
+    });
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            PacketMeta::default(),
+            &frame,
+            &mut iface.fragments
+        ),
+        Some(EthernetPacket::Arp(ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: local_hw_addr,
+            source_protocol_addr: addr,
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: addr,
+        }))
+    );
+    assert_eq!(iface.ipv4_link_local_addr(), Some(addr));
+
+    // A second conflict within the defend interval makes us give up the address.
+    iface.inner.now += Duration::from_secs(5);
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            PacketMeta::default(),
+            &frame,
+            &mut iface.fragments
+        ),
+        None
+    );
+    assert_eq!(iface.ipv4_link_local_addr(), None);
+    assert!(!iface.has_ip_addr(addr));
+}
+
+/// Parse the ARP frames among the sent frames, leaving out the IPv6 announcements.
+#[cfg(feature = "medium-ethernet")]
+fn parse_arp_frames(frames: &[std::vec::Vec<u8>]) -> std::vec::Vec<ArpRepr> {
+    frames
+        .iter()
+        .filter_map(|frame| {
+            let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+            if frame.ethertype() != EthernetProtocol::Arp {
+                return None;
+            }
+            assert_eq!(frame.dst_addr(), EthernetAddress::BROADCAST);
+            Some(ArpRepr::parse(&ArpPacket::new_checked(frame.payload()).unwrap()).unwrap())
+        })
+        .collect()
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_gratuitous_arp_after_update_ip(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    announce_egress(&mut iface, &mut device, Instant::from_secs(1));
+    iface.set_announce_count(1);
+
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let new_ip_addr = Ipv4Address([192, 168, 1, 5]);
+
+    // Only the added address is announced.
+    iface.update_ip_addrs(|addrs| {
+        addrs
+            .push(IpCidr::Ipv4(Ipv4Cidr::new(new_ip_addr, 24)))
+            .unwrap();
+    });
+    let frames = announce_egress(&mut iface, &mut device, Instant::from_secs(5));
+    assert_eq!(frames.len(), 1);
+    assert_eq!(
+        parse_arp_frames(&frames),
+        [ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: local_hw_addr,
+            source_protocol_addr: new_ip_addr,
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: new_ip_addr,
+        }]
+    );
+
+    // Removing addresses does not announce anything.
+    iface.update_ip_addrs(|addrs| addrs.truncate(1));
+    assert!(announce_egress(&mut iface, &mut device, Instant::from_secs(10)).is_empty());
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_gratuitous_arp_repeated(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    announce_egress(&mut iface, &mut device, Instant::from_secs(1));
+
+    let new_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x03]);
+    let local_ip_addr = Ipv4Address([192, 168, 1, 1]);
+
+    iface.set_announce_count(3);
+    iface.set_hardware_addr(new_hw_addr.into());
+
+    // The loopback address is never announced.
+    let frames = announce_egress(&mut iface, &mut device, Instant::from_secs(1));
+    assert_eq!(parse_arp_frames(&frames).len(), 1);
+    let frames = announce_egress(&mut iface, &mut device, Instant::from_secs(10));
+    let arp_reprs = parse_arp_frames(&frames);
+    assert_eq!(arp_reprs.len(), 2);
+    for arp_repr in arp_reprs {
+        assert_eq!(
+            arp_repr,
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Request,
+                source_hardware_addr: new_hw_addr,
+                source_protocol_addr: local_ip_addr,
+                target_hardware_addr: EthernetAddress([0; 6]),
+                target_protocol_addr: local_ip_addr,
+            }
+        );
+    }
+
+    // Announcements can be triggered explicitly, and disabled.
+    iface.announce_ip_addrs();
+    let frames = announce_egress(&mut iface, &mut device, Instant::from_secs(20));
+    assert_eq!(parse_arp_frames(&frames).len(), 3);
+    iface.set_announce_count(0);
+    iface.announce_ip_addrs();
+    assert!(announce_egress(&mut iface, &mut device, Instant::from_secs(30)).is_empty());
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_gratuitous_arp_updates_cache(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let local_ip_addr = Ipv4Address([192, 168, 1, 1]);
+    let remote_ip_addr = Ipv4Address([192, 168, 1, 2]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+    let new_remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x01]);
+
+    let gratuitous_arp = |hardware_addr| {
+        let repr = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: hardware_addr,
+            source_protocol_addr: remote_ip_addr,
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: remote_ip_addr,
+        };
+        let mut eth_bytes = vec![0u8; 42];
+        let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+        frame.set_dst_addr(EthernetAddress::BROADCAST);
+        frame.set_src_addr(hardware_addr);
+        frame.set_ethertype(EthernetProtocol::Arp);
+        repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+        eth_bytes
+    };
+
+    // An announcement for an unknown neighbor is ignored.
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            PacketMeta::default(),
+            &gratuitous_arp(remote_hw_addr),
+            &mut iface.fragments
+        ),
+        None
+    );
+    assert!(!iface.inner.has_neighbor(&remote_ip_addr.into()));
+
+    // An announcement for a known neighbor updates its hardware address.
+    iface
+        .inner
+        .neighbor_cache
+        .fill(remote_ip_addr.into(), remote_hw_addr.into(), Instant::ZERO);
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            PacketMeta::default(),
+            &gratuitous_arp(new_remote_hw_addr),
+            &mut iface.fragments
+        ),
+        None
+    );
+    assert_eq!(
+        iface.inner.lookup_hardware_addr(
+            MockTxToken,
+            &IpAddress::Ipv4(local_ip_addr),
+            &IpAddress::Ipv4(remote_ip_addr),
+            &mut iface.fragmenter,
+        ),
+        Ok((HardwareAddress::Ethernet(new_remote_hw_addr), MockTxToken))
+    );
+}
+
+/// Attach the interface from `setup` (on 192.168.1.0/24), and a second one on 10.0.0.0/8
+/// using `medium`, to a router.
+#[cfg(feature = "medium-ip")]
+fn setup_router<'a>(
+    medium: Medium,
+) -> (
+    Router<'static>,
+    (Interface, SocketSet<'a>, crate::tests::TestingDevice),
+    (Interface, SocketSet<'a>, crate::tests::TestingDevice),
+) {
+    use crate::iface::{RouterPacketBuffer, RouterPacketMetadata};
+
+    let queue = || RouterPacketBuffer::new(vec![RouterPacketMetadata::EMPTY; 4], vec![0; 4 * 1500]);
+
+    let (mut iface_a, sockets_a, device_a) = setup(Medium::Ip);
+    let (mut iface_b, sockets_b, device_b) = setup(medium);
+    iface_b.update_ip_addrs(|ip_addrs| {
+        ip_addrs.clear();
+        ip_addrs
+            .push(IpCidr::new(IpAddress::v4(10, 0, 0, 1), 8))
+            .unwrap();
+    });
+
+    let mut router = Router::new();
+    router.add_interface(&mut iface_a, queue()).unwrap();
+    router.add_interface(&mut iface_b, queue()).unwrap();
+
+    (
+        router,
+        (iface_a, sockets_a, device_a),
+        (iface_b, sockets_b, device_b),
+    )
+}
+
+/// Build a packet from 192.168.1.2, which is on the link of the first interface.
+#[cfg(feature = "medium-ip")]
+fn forwarded_packet(
+    dst_addr: Ipv4Address,
+    hop_limit: u8,
+    payload_len: usize,
+) -> (Ipv4Repr, std::vec::Vec<u8>) {
+    let repr = Ipv4Repr {
+        src_addr: Ipv4Address::new(192, 168, 1, 2),
+        dst_addr,
+        next_header: IpProtocol::Udp,
+        payload_len,
+        hop_limit,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
+    };
+    let mut bytes = vec![0xa5; repr.buffer_len() + payload_len];
+    repr.emit(
+        &mut Ipv4Packet::new_unchecked(&mut bytes[..]),
+        &ChecksumCapabilities::default(),
+    );
+    (repr, bytes)
+}
+
+/// Process a received packet, and hand it to the router if it is to be forwarded.
+#[cfg(feature = "medium-ip")]
+fn forward_ingress<'a>(
+    iface: &'a mut Interface,
+    sockets: &mut SocketSet<'_>,
+    router: &mut Router<'_>,
+    bytes: &'a [u8],
+) -> Option<Packet<'a>> {
+    let packet = iface.inner.process_ipv4(
+        sockets,
+        PacketMeta::default(),
+        &Ipv4Packet::new_checked(bytes).unwrap(),
+        &mut iface.fragments,
+        false,
+    )?;
+    iface.inner.forward(Some(router), packet)
+}
+
+/// Build the ICMP error the first interface sends back to 192.168.1.2.
+#[cfg(feature = "medium-ip")]
+fn forward_icmp_error(icmp_repr: Icmpv4Repr<'_>) -> Option<Packet<'_>> {
+    Some(Packet::new_ipv4(
+        Ipv4Repr {
+            src_addr: Ipv4Address::new(192, 168, 1, 1),
+            dst_addr: Ipv4Address::new(192, 168, 1, 2),
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
+        },
+        IpPayload::Icmpv4(icmp_repr),
+    ))
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_forward() {
+    let (mut router, (mut iface_a, mut sockets_a, _), (mut iface_b, _, mut device_b)) =
+        setup_router(Medium::Ip);
+
+    let (_, packet) = forwarded_packet(Ipv4Address::new(10, 0, 0, 2), 64, 8);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        None
+    );
+    assert_eq!(router.poll_at(), Some(Instant::ZERO));
+
+    assert!(iface_b.forward_egress(&mut device_b, &mut router));
+    assert!(!iface_b.forward_egress(&mut device_b, &mut router));
+    assert_eq!(router.poll_at(), None);
+
+    let bytes = device_b.queue.pop_front().unwrap();
+    let ipv4_packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    assert!(ipv4_packet.verify_checksum());
+    assert_eq!(ipv4_packet.hop_limit(), 63);
+    assert_eq!(ipv4_packet.dst_addr(), Ipv4Address::new(10, 0, 0, 2));
+    assert_eq!(ipv4_packet.payload(), &packet[20..]);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "medium-ethernet"))]
+fn test_forward_neighbor_pending() {
+    let (mut router, (mut iface_a, mut sockets_a, _), (mut iface_b, _, mut device_b)) =
+        setup_router(Medium::Ethernet);
+
+    // 10.0.0.3 is known, 10.0.0.2 is not.
+    let known_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x03]);
+    iface_b.inner.neighbor_cache.fill(
+        IpAddress::v4(10, 0, 0, 3),
+        known_hw_addr.into(),
+        Instant::ZERO,
+    );
+
+    let (_, pending) = forwarded_packet(Ipv4Address::new(10, 0, 0, 2), 64, 8);
+    let (_, known) = forwarded_packet(Ipv4Address::new(10, 0, 0, 3), 64, 8);
+    for packet in [&pending, &known] {
+        assert_eq!(
+            forward_ingress(&mut iface_a, &mut sockets_a, &mut router, packet),
+            None
+        );
+    }
+
+    // The packet waiting for its next hop does not hold up the other one.
+    while iface_b.forward_egress(&mut device_b, &mut router) {}
+    let frame = device_b.queue.pop_front().unwrap();
+    let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+    assert_eq!(frame.ethertype(), EthernetProtocol::Arp);
+    let frame = device_b.queue.pop_front().unwrap();
+    let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+    assert_eq!(frame.dst_addr(), known_hw_addr);
+    let ipv4_packet = Ipv4Packet::new_checked(frame.payload()).unwrap();
+    assert_eq!(ipv4_packet.dst_addr(), Ipv4Address::new(10, 0, 0, 3));
+    assert!(device_b.queue.is_empty());
+    assert_eq!(router.poll_at(), Some(Instant::from_secs(1)));
+
+    // The parked packet is sent once its next hop is discovered.
+    let pending_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x02]);
+    iface_b.inner.neighbor_cache.fill(
+        IpAddress::v4(10, 0, 0, 2),
+        pending_hw_addr.into(),
+        Instant::ZERO,
+    );
+    assert!(iface_b.forward_egress(&mut device_b, &mut router));
+    let frame = device_b.queue.pop_front().unwrap();
+    assert_eq!(
+        EthernetFrame::new_checked(&frame[..]).unwrap().dst_addr(),
+        pending_hw_addr
+    );
+    assert_eq!(router.poll_at(), None);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "medium-ethernet"))]
+fn test_forward_route_rule() {
+    use crate::iface::RouteRule;
+
+    let (mut router, (mut iface_a, mut sockets_a, _), (mut iface_b, _, mut device_b)) =
+        setup_router(Medium::Ethernet);
+
+    // Packets from 192.168.1.0/24 leave through 10.0.0.253, the others through 10.0.0.254.
+    let iface_id = router.lookup(&IpAddress::v4(10, 0, 0, 1)).unwrap();
+    let cidr = IpCidr::new(IpAddress::v4(172, 16, 0, 0), 16);
+    router.add_route(cidr, iface_id).unwrap();
+    iface_b
+        .routes_mut()
+        .add_default_ipv4_route(Ipv4Address::new(10, 0, 0, 254))
+        .unwrap();
+    iface_b
+        .routes_mut()
+        .add_route(Route {
+            table: 1,
+            ..Route::new_ipv4_gateway(Ipv4Address::new(10, 0, 0, 253))
+        })
+        .unwrap();
+    iface_b
+        .routes_mut()
+        .add_rule(RouteRule {
+            src: Some(IpCidr::new(IpAddress::v4(192, 168, 1, 0), 24)),
+            ..RouteRule::new(10, 1)
+        })
+        .unwrap();
+
+    let (_, packet) = forwarded_packet(Ipv4Address::new(172, 16, 0, 1), 64, 8);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        None
+    );
+
+    // The next hop is discovered from the address of the interface.
+    assert!(iface_b.forward_egress(&mut device_b, &mut router));
+    let frame = device_b.queue.pop_front().unwrap();
+    let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+    let arp_repr = ArpRepr::parse(&ArpPacket::new_checked(frame.payload()).unwrap()).unwrap();
+    assert!(matches!(
+        arp_repr,
+        ArpRepr::EthernetIpv4 {
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        } if source_protocol_addr == Ipv4Address::new(10, 0, 0, 1)
+            && target_protocol_addr == Ipv4Address::new(10, 0, 0, 253)
+    ));
+
+    let gateway_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0xfd]);
+    iface_b.inner.neighbor_cache.fill(
+        IpAddress::v4(10, 0, 0, 253),
+        gateway_hw_addr.into(),
+        Instant::ZERO,
+    );
+    assert!(iface_b.forward_egress(&mut device_b, &mut router));
+    let frame = device_b.queue.pop_front().unwrap();
+    assert_eq!(
+        EthernetFrame::new_checked(&frame[..]).unwrap().dst_addr(),
+        gateway_hw_addr
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "medium-ethernet"))]
+fn test_forward_neighbor_timeout() {
+    let (mut router, (mut iface_a, mut sockets_a, _), (mut iface_b, _, mut device_b)) =
+        setup_router(Medium::Ethernet);
+
+    let (_, packet) = forwarded_packet(Ipv4Address::new(10, 0, 0, 2), 64, 8);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        None
+    );
+
+    // Neighbor discovery is retried, until the packet is dropped.
+    let mut requests = 0;
+    while let Some(poll_at) = router.poll_at() {
+        iface_b.inner.now = poll_at;
+        while iface_b.forward_egress(&mut device_b, &mut router) {}
+        while device_b.queue.pop_front().is_some() {
+            requests += 1;
+        }
+    }
+    assert_eq!(requests, 3);
+    assert_eq!(iface_b.inner.now, Instant::from_secs(3));
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_forward_not_forwarded() {
+    let (mut router, (mut iface_a, mut sockets_a, mut device_a), _) = setup_router(Medium::Ip);
+
+    // Packets for other hosts are only forwarded by `poll_router`.
+    let (_, packet) = forwarded_packet(Ipv4Address::new(10, 0, 0, 2), 64, 8);
+    device_a
+        .queue
+        .push_back(packet[..].try_into().unwrap())
+        .unwrap();
+    iface_a.poll(Instant::ZERO, &mut device_a, &mut sockets_a);
+    assert!(device_a.queue.is_empty());
+    assert_eq!(router.poll_at(), None);
+
+    // Link-local destinations are never forwarded.
+    let (_, packet) = forwarded_packet(Ipv4Address::new(169, 254, 0, 2), 64, 8);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        None
+    );
+    assert_eq!(router.poll_at(), None);
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_forward_ttl_exceeded() {
+    let (mut router, (mut iface_a, mut sockets_a, _), _) = setup_router(Medium::Ip);
+
+    let (repr, packet) = forwarded_packet(Ipv4Address::new(10, 0, 0, 2), 1, 8);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        forward_icmp_error(Icmpv4Repr::TimeExceeded {
+            reason: Icmpv4TimeExceeded::TtlExpired,
+            header: repr,
+            data: &packet[20..],
+            extensions: IcmpExtensions::EMPTY,
+        })
+    );
+    assert_eq!(router.poll_at(), None);
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_forward_no_route() {
+    let (mut router, (mut iface_a, mut sockets_a, _), _) = setup_router(Medium::Ip);
+
+    let (repr, packet) = forwarded_packet(Ipv4Address::new(172, 16, 0, 1), 64, 8);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        forward_icmp_error(Icmpv4Repr::DstUnreachable {
+            reason: Icmpv4DstUnreachable::NetUnreachable,
+            header: repr,
+            data: &packet[20..],
+            extensions: IcmpExtensions::EMPTY,
+        })
+    );
+    assert_eq!(router.poll_at(), None);
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_forward_frag_required() {
+    let (mut router, (mut iface_a, mut sockets_a, _), _) = setup_router(Medium::Ip);
+
+    // The packet does not fit the 1500 octets MTU of the outgoing interface.
+    let (repr, mut packet) = forwarded_packet(Ipv4Address::new(10, 0, 0, 2), 64, 1490);
+    let mut ipv4_packet = Ipv4Packet::new_unchecked(&mut packet[..]);
+    ipv4_packet.set_dont_frag(true);
+    ipv4_packet.fill_checksum();
+
+    let data_len = icmp_reply_payload_len(1490, IPV4_MIN_MTU, 20);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        forward_icmp_error(Icmpv4Repr::FragRequired {
+            mtu: 1500,
+            header: repr,
+            data: &packet[20..][..data_len],
+            extensions: IcmpExtensions::EMPTY,
+        })
+    );
+    assert_eq!(router.poll_at(), None);
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_forward_redirect() {
+    let (mut router, (mut iface_a, mut sockets_a, mut device_a), _) = setup_router(Medium::Ip);
+
+    // 172.16.0.0/16 is reached through 192.168.1.254, on the link of the source.
+    let iface_id = router.lookup(&IpAddress::v4(192, 168, 1, 2)).unwrap();
+    let cidr = IpCidr::new(IpAddress::v4(172, 16, 0, 0), 16);
+    router.add_route(cidr, iface_id).unwrap();
+    iface_a
+        .routes_mut()
+        .add_default_ipv4_route(Ipv4Address::new(192, 168, 1, 254))
+        .unwrap();
+
+    let (repr, packet) = forwarded_packet(Ipv4Address::new(172, 16, 0, 1), 64, 8);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        forward_icmp_error(Icmpv4Repr::Redirect {
+            reason: Icmpv4Redirect::Host,
+            gateway: Ipv4Address::new(192, 168, 1, 254),
+            header: repr,
+            data: &packet[20..],
+        })
+    );
+
+    // The packet is forwarded anyway.
+    assert!(iface_a.forward_egress(&mut device_a, &mut router));
+    let bytes = device_a.queue.pop_front().unwrap();
+    assert_eq!(Ipv4Packet::new_checked(&bytes[..]).unwrap().hop_limit(), 63);
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_forward_dropping_route() {
+    let (mut router, (mut iface_a, mut sockets_a, _), (mut iface_b, _, _)) =
+        setup_router(Medium::Ip);
+
+    let cidr = IpCidr::new(IpAddress::v4(172, 16, 0, 0), 16);
+    let iface_id = router.lookup(&IpAddress::v4(10, 0, 0, 2)).unwrap();
+    router.add_route(cidr, iface_id).unwrap();
+
+    let (repr, packet) = forwarded_packet(Ipv4Address::new(172, 16, 0, 1), 64, 8);
+
+    iface_b
+        .routes_mut()
+        .add_route(Route::new_without_gateway(cidr, RouteKind::Unreachable))
+        .unwrap();
+    router.update_interface(iface_id, &iface_b);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        forward_icmp_error(Icmpv4Repr::DstUnreachable {
+            reason: Icmpv4DstUnreachable::HostUnreachable,
+            header: repr,
+            data: &packet[20..],
+            extensions: IcmpExtensions::EMPTY,
+        })
+    );
+
+    iface_b.routes_mut().update(|routes| routes.clear());
+    iface_b
+        .routes_mut()
+        .add_route(Route::new_without_gateway(cidr, RouteKind::Blackhole))
+        .unwrap();
+    router.update_interface(iface_id, &iface_b);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        None
+    );
+    assert_eq!(router.poll_at(), None);
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_lookup_route(#[case] medium: Medium) {
+    let (mut iface, _, _) = setup(medium);
+
+    let route = iface.lookup_route(&IpAddress::v4(192, 168, 1, 7)).unwrap();
+    assert_eq!(route.cidr, IpCidr::new(IpAddress::v4(192, 168, 1, 1), 24));
+    assert_eq!(route.kind, RouteKind::OnLink);
+    assert!(iface.lookup_route(&IpAddress::v4(8, 8, 8, 8)).is_none());
+
+    let gateway = Ipv4Address::new(192, 168, 1, 254);
+    iface.routes_mut().add_default_ipv4_route(gateway).unwrap();
+    let route = iface.lookup_route(&IpAddress::v4(8, 8, 8, 8)).unwrap();
+    assert_eq!(route.kind, RouteKind::Gateway);
+    assert_eq!(route.via_router, gateway.into());
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_redirect(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _) = setup(medium);
+
+    let router = Ipv4Address::new(192, 168, 1, 254);
+    let better_router = Ipv4Address::new(192, 168, 1, 253);
+    let dst_addr = Ipv4Address::new(8, 8, 8, 8);
+    iface.routes_mut().add_default_ipv4_route(router).unwrap();
+
+    let quoted = [0u8; 8];
+    let header = Ipv4Repr {
+        src_addr: Ipv4Address::new(192, 168, 1, 1),
+        dst_addr,
+        next_header: IpProtocol::Udp,
+        payload_len: quoted.len(),
+        hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
+    };
+    let redirect = |gateway| {
+        let icmp_repr = Icmpv4Repr::Redirect {
+            reason: Icmpv4Redirect::Net,
+            gateway,
+            header,
+            data: &quoted,
+        };
+        let mut bytes = vec![0u8; icmp_repr.buffer_len()];
+        icmp_repr.emit(
+            &mut Icmpv4Packet::new_unchecked(&mut bytes[..]),
+            &ChecksumCapabilities::default(),
+        );
+        bytes
+    };
+    let ip_repr = |src_addr, payload_len| Ipv4Repr {
+        src_addr,
+        dst_addr: Ipv4Address::new(192, 168, 1, 1),
+        next_header: IpProtocol::Icmp,
+        payload_len,
+        hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
+    };
+
+    // Redirects from other routers are ignored.
+    let bytes = redirect(better_router);
+    iface.inner.process_icmpv4(
+        &mut sockets,
+        ip_repr(Ipv4Address::new(192, 168, 1, 2), bytes.len()),
+        &bytes,
+    );
+    assert_eq!(
+        iface.inner.route(&dst_addr.into(), iface.inner.now),
+        Some(router.into())
+    );
+
+    // Gateways that are not on-link are ignored.
+    let bytes = redirect(Ipv4Address::new(10, 0, 0, 1));
+    iface
+        .inner
+        .process_icmpv4(&mut sockets, ip_repr(router, bytes.len()), &bytes);
+    assert_eq!(
+        iface.inner.route(&dst_addr.into(), iface.inner.now),
+        Some(router.into())
+    );
+
+    let bytes = redirect(better_router);
+    iface
+        .inner
+        .process_icmpv4(&mut sockets, ip_repr(router, bytes.len()), &bytes);
+    assert_eq!(
+        iface.inner.route(&dst_addr.into(), iface.inner.now),
+        Some(better_router.into())
+    );
+    let route = iface.lookup_route(&dst_addr.into()).unwrap();
+    assert_eq!(route.cidr, IpCidr::new(dst_addr.into(), 32));
+    assert_eq!(route.kind, RouteKind::Gateway);
+    assert_eq!(route.via_router, better_router.into());
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_source_routing(#[case] medium: Medium) {
+    use crate::iface::RouteRule;
+
+    let (mut iface, _, _) = setup(medium);
+
+    // A second uplink, whose replies must leave through its own gateway.
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs
+            .push(IpCidr::new(IpAddress::v4(10, 0, 0, 1), 24))
+            .unwrap();
+    });
+    let routes = iface.routes_mut();
+    routes
+        .add_default_ipv4_route(Ipv4Address::new(192, 168, 1, 254))
+        .unwrap();
+    routes
+        .add_route(Route {
+            table: 1,
+            ..Route::new_ipv4_gateway(Ipv4Address::new(10, 0, 0, 254))
+        })
+        .unwrap();
+    routes
+        .add_rule(RouteRule {
+            src: Some(IpCidr::new(IpAddress::v4(10, 0, 0, 0), 24)),
+            ..RouteRule::new(100, 1)
+        })
+        .unwrap();
+
+    let dst_addr = IpAddress::v4(8, 8, 8, 8);
+    let route_from = |iface: &Interface, src_addr| {
+        iface
+            .inner
+            .route_from(Some(&src_addr), &dst_addr, Instant::ZERO)
+    };
+    assert_eq!(
+        route_from(&iface, IpAddress::v4(192, 168, 1, 1)),
+        Some(IpAddress::v4(192, 168, 1, 254))
+    );
+    assert_eq!(
+        route_from(&iface, IpAddress::v4(10, 0, 0, 1)),
+        Some(IpAddress::v4(10, 0, 0, 254))
+    );
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "socket-udp", feature = "medium-ethernet"))]
+fn test_loopback_udp(#[case] medium: Medium) {
+    use crate::socket::udp;
+    use crate::wire::IpEndpoint;
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    // Keep the announcements of the setup addresses out of the device queue.
+    iface.set_announce_count(0);
+
+    let new_socket = || {
+        udp::Socket::new(
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 16]),
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 16]),
+        )
+    };
+    let client = sockets.add(new_socket());
+    let server = sockets.add(new_socket());
+    sockets.get_mut::<udp::Socket>(client).bind(1000).unwrap();
+    sockets.get_mut::<udp::Socket>(server).bind(2000).unwrap();
+
+    let own_addr = IpAddress::v4(192, 168, 1, 1);
+    sockets
+        .get_mut::<udp::Socket>(client)
+        .send_slice(b"hello", (own_addr, 2000))
+        .unwrap();
+
+    // The packet is queued on the interface instead of being transmitted.
+    assert!(iface.socket_egress(&mut device, &mut sockets));
+    assert!(device.queue.is_empty());
+    assert_eq!(iface.poll_at(Instant::ZERO, &sockets), Some(Instant::ZERO));
+
+    assert!(iface.poll(Instant::ZERO, &mut device, &mut sockets));
+    assert!(device.queue.is_empty());
+    assert_eq!(iface.poll_at(Instant::ZERO, &sockets), None);
+    assert_eq!(
+        sockets.get_mut::<udp::Socket>(server).recv(),
+        Ok((
+            &b"hello"[..],
+            udp::UdpMetadata {
+                local_address: Some(own_addr),
+                ..IpEndpoint::new(own_addr, 1000).into()
+            }
+        ))
+    );
+}
+
+#[test]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+fn test_loopback_from_device() {
+    use crate::socket::udp;
+
+    static UDP_PAYLOAD: [u8; 5] = [0x48, 0x65, 0x6c, 0x6c, 0x6f];
+
+    let (mut iface, mut sockets, _) = setup(Medium::Ip);
+    iface.update_ip_addrs(|ip_addrs| ip_addrs.retain(|cidr| !cidr.address().is_loopback()));
+
+    let socket = sockets.add(udp::Socket::new(
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 16]),
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 16]),
+    ));
+    sockets.get_mut::<udp::Socket>(socket).bind(5000).unwrap();
+
+    let src_addr = Ipv4Address::new(192, 168, 1, 2);
+    let dst_addr = Ipv4Address::new(127, 0, 0, 1);
+    let udp_repr = UdpRepr {
+        src_port: 5000,
+        dst_port: 5000,
+    };
+    let ipv4_repr = Ipv4Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Udp,
+        hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
+        options: Ipv4Options::EMPTY,
+    };
+    let mut bytes = vec![0u8; ipv4_repr.buffer_len() + ipv4_repr.payload_len];
+    ipv4_repr.emit(
+        &mut Ipv4Packet::new_unchecked(&mut bytes),
+        &ChecksumCapabilities::default(),
+    );
+    udp_repr.emit(
+        &mut UdpPacket::new_unchecked(&mut bytes[ipv4_repr.buffer_len()..]),
+        &src_addr.into(),
+        &dst_addr.into(),
+        UDP_PAYLOAD.len(),
+        |buf| buf.copy_from_slice(&UDP_PAYLOAD),
+        &ChecksumCapabilities::default(),
+    );
+
+    // Packets to a loopback address are dropped when the device receives them, and only
+    // delivered when they come from the loopback buffer.
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments,
+            false
+        ),
+        None
+    );
+    assert!(!sockets.get_mut::<udp::Socket>(socket).can_recv());
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments,
+            true
+        ),
+        None
+    );
+    assert!(sockets.get_mut::<udp::Socket>(socket).can_recv());
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "socket-tcp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "socket-tcp", feature = "medium-ethernet"))]
+fn test_loopback_tcp(#[case] medium: Medium) {
+    use crate::socket::tcp;
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    let new_socket = || {
+        tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; 64]),
+            tcp::SocketBuffer::new(vec![0; 64]),
+        )
+    };
+    let server = sockets.add(new_socket());
+    let client = sockets.add(new_socket());
+    sockets.get_mut::<tcp::Socket>(server).listen(80).unwrap();
+    sockets
+        .get_mut::<tcp::Socket>(client)
+        .connect(
+            iface.context(),
+            (IpAddress::v4(127, 0, 0, 1), 80),
+            (IpAddress::v4(127, 0, 0, 1), 49500),
+        )
+        .unwrap();
+
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    assert!(device.queue.is_empty());
+    assert_eq!(
+        sockets.get::<tcp::Socket>(server).state(),
+        tcp::State::Established
+    );
+    assert_eq!(
+        sockets.get::<tcp::Socket>(client).state(),
+        tcp::State::Established
+    );
+
+    sockets
+        .get_mut::<tcp::Socket>(client)
+        .send_slice(b"hello")
+        .unwrap();
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    let mut data = [0; 8];
+    assert_eq!(
+        sockets.get_mut::<tcp::Socket>(server).recv_slice(&mut data),
+        Ok(5)
+    );
+    assert_eq!(&data[..5], b"hello");
+}
+
+#[test]
+#[cfg(all(feature = "socket-tcp", feature = "medium-ip"))]
+fn test_loopback_tcp_mss() {
+    use crate::config::IFACE_LOOPBACK_BUFFER_SIZE;
+    use crate::socket::tcp;
+
+    // Segments sized by the MTU of the device would not fit the loopback buffer.
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+    iface.inner.caps.max_transmission_unit = 2 * IFACE_LOOPBACK_BUFFER_SIZE;
+
+    let new_socket = || {
+        tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; 4 * IFACE_LOOPBACK_BUFFER_SIZE]),
+            tcp::SocketBuffer::new(vec![0; 4 * IFACE_LOOPBACK_BUFFER_SIZE]),
+        )
+    };
+    let server = sockets.add(new_socket());
+    let client = sockets.add(new_socket());
+    sockets.get_mut::<tcp::Socket>(server).listen(80).unwrap();
+    sockets
+        .get_mut::<tcp::Socket>(client)
+        .connect(
+            iface.context(),
+            (IpAddress::v4(127, 0, 0, 1), 80),
+            (IpAddress::v4(127, 0, 0, 1), 49500),
+        )
+        .unwrap();
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+
+    let data = vec![0xa5; 3 * IFACE_LOOPBACK_BUFFER_SIZE];
+    sockets
+        .get_mut::<tcp::Socket>(client)
+        .set_nagle_enabled(false);
+    assert_eq!(
+        sockets.get_mut::<tcp::Socket>(client).send_slice(&data),
+        Ok(data.len())
+    );
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    assert!(device.queue.is_empty());
+    assert_eq!(sockets.get::<tcp::Socket>(server).recv_queue(), data.len());
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_dispatch_rule_key() {
+    let (mut iface, _, mut device) = setup(Medium::Ip);
+
+    let (repr, bytes) = forwarded_packet(Ipv4Address::new(192, 168, 1, 2), 64, 8);
+    let tx_token = device.transmit(Instant::ZERO).unwrap();
+    assert_eq!(
+        iface.inner.dispatch_ip(
+            tx_token,
+            PacketMeta::default(),
+            Packet::new_ipv4(repr, IpPayload::Raw(&bytes[repr.buffer_len()..])),
+            &mut iface.fragmenter,
+        ),
+        Ok(())
+    );
+
+    // The protocol and ports of the packet are not left for the next route lookups.
+    assert_eq!(iface.inner.rule_key.protocol, None);
+    assert_eq!(iface.inner.rule_key.ports, None);
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_source_route() {
+    let (mut router, (mut iface_a, mut sockets_a, _), _) = setup_router(Medium::Ip);
+
+    let source_routed = |dst_addr, pointer, next_header| {
+        let route = [10, 0, 0, 2];
+        let mut options = Ipv4Options::EMPTY;
+        options
+            .push(&Ipv4OptionRepr::LooseSourceRoute {
+                pointer,
+                route: &route,
+            })
+            .unwrap();
+        let (repr, _) = forwarded_packet(dst_addr, 64, 8);
+        let repr = Ipv4Repr {
+            next_header,
+            options,
+            ..repr
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len() + 8];
+        repr.emit(
+            &mut Ipv4Packet::new_unchecked(&mut bytes[..]),
+            &ChecksumCapabilities::default(),
+        );
+        (repr, bytes)
+    };
+
+    // We are asked to forward the packet along its route.
+    let (repr, packet) = source_routed(Ipv4Address::new(192, 168, 1, 1), 4, IpProtocol::Udp);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        forward_icmp_error(Icmpv4Repr::DstUnreachable {
+            reason: Icmpv4DstUnreachable::SrcRouteFailed,
+            header: repr,
+            data: &packet[repr.buffer_len()..],
+            extensions: IcmpExtensions::EMPTY,
+        })
+    );
+
+    // The packet would be forwarded to the next address of its route.
+    let (repr, packet) = source_routed(Ipv4Address::new(10, 0, 0, 3), 4, IpProtocol::Udp);
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        forward_icmp_error(Icmpv4Repr::DstUnreachable {
+            reason: Icmpv4DstUnreachable::SrcRouteFailed,
+            header: repr,
+            data: &packet[repr.buffer_len()..],
+            extensions: IcmpExtensions::EMPTY,
+        })
+    );
+    assert_eq!(router.poll_at(), None);
+
+    // Packets at the end of their route are processed as any other packet.
+    let (repr, packet) = source_routed(
+        Ipv4Address::new(192, 168, 1, 1),
+        8,
+        IpProtocol::Unknown(0xfd),
+    );
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        forward_icmp_error(Icmpv4Repr::DstUnreachable {
+            reason: Icmpv4DstUnreachable::ProtoUnreachable,
+            header: repr,
+            data: &packet[repr.buffer_len()..],
+            extensions: IcmpExtensions::EMPTY,
+        })
+    );
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4-fragmentation"))]
+fn test_forward_fragmentation_options() {
+    let (mut router, (mut iface_a, mut sockets_a, _), (mut iface_b, _, mut device_b)) =
+        setup_router(Medium::Ip);
+    iface_b.inner.caps.max_transmission_unit = 600;
+    let iface_id = router.lookup(&IpAddress::v4(10, 0, 0, 1)).unwrap();
+    router.update_interface(iface_id, &iface_b);
+
+    // The Security option is copied into all fragments, Record Route is not.
+    let (mut repr, _) = forwarded_packet(Ipv4Address::new(10, 0, 0, 2), 64, 1000);
+    repr.options
+        .push(&Ipv4OptionRepr::RecordRoute {
+            pointer: 4,
+            route: &[0; 8],
+        })
+        .unwrap();
+    repr.options
+        .push(&Ipv4OptionRepr::Security {
+            classification: 0xab,
+            protection_authority: &[],
+        })
+        .unwrap();
+    let mut packet = vec![0xa5; repr.buffer_len() + 1000];
+    let mut ipv4_packet = Ipv4Packet::new_unchecked(&mut packet[..]);
+    repr.emit(&mut ipv4_packet, &ChecksumCapabilities::default());
+    ipv4_packet.set_dont_frag(false);
+    ipv4_packet.fill_checksum();
+
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        None
+    );
+    assert!(iface_b.forward_egress(&mut device_b, &mut router));
+    while iface_b.ipv4_egress(&mut device_b) {}
+
+    let options: std::vec::Vec<_> = device_b
+        .queue
+        .iter()
+        .map(|fragment| {
+            let ipv4_packet = Ipv4Packet::new_checked(&fragment[..]).unwrap();
+            Ipv4Options::new(ipv4_packet.options()).unwrap()
+        })
+        .collect();
+    assert_eq!(options, [repr.options, repr.options.copied()]);
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4-fragmentation"))]
+fn test_forward_fragmentation_dscp_ecn() {
+    let (mut router, (mut iface_a, mut sockets_a, _), (mut iface_b, _, mut device_b)) =
+        setup_router(Medium::Ip);
+    iface_b.inner.caps.max_transmission_unit = 600;
+    let iface_id = router.lookup(&IpAddress::v4(10, 0, 0, 1)).unwrap();
+    router.update_interface(iface_id, &iface_b);
+
+    let (mut repr, _) = forwarded_packet(Ipv4Address::new(10, 0, 0, 2), 64, 1000);
+    repr.dscp = 46;
+    repr.ecn = 1;
+    let mut packet = vec![0xa5; repr.buffer_len() + 1000];
+    let mut ipv4_packet = Ipv4Packet::new_unchecked(&mut packet[..]);
+    repr.emit(&mut ipv4_packet, &ChecksumCapabilities::default());
+    ipv4_packet.set_dont_frag(false);
+    ipv4_packet.fill_checksum();
+
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        None
+    );
+    assert!(iface_b.forward_egress(&mut device_b, &mut router));
+    while iface_b.ipv4_egress(&mut device_b) {}
+
+    assert_eq!(device_b.queue.len(), 2);
+    for fragment in device_b.queue.iter() {
+        let ipv4_packet = Ipv4Packet::new_checked(&fragment[..]).unwrap();
+        assert_eq!((ipv4_packet.dscp(), ipv4_packet.ecn()), (46, 1));
+    }
+}
+
+#[test]
+#[cfg(all(feature = "medium-ip", feature = "socket-raw"))]
+fn test_forward_router_alert() {
+    let (mut router, (mut iface_a, mut sockets_a, _), (mut iface_b, _, mut device_b)) =
+        setup_router(Medium::Ip);
+
+    let raw_socket = raw::Socket::new(
+        Some(IpVersion::Ipv4),
+        Some(IpProtocol::Udp),
+        raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 2], vec![0; 128]),
+        raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 2], vec![0; 128]),
+    );
+    let raw_handle = sockets_a.add(raw_socket);
+
+    let (repr, _) = forwarded_packet(Ipv4Address::new(10, 0, 0, 2), 64, 8);
+    let mut repr = Ipv4Repr { ..repr };
+    repr.options.push(&Ipv4OptionRepr::RouterAlert(0)).unwrap();
+    let mut packet = vec![0xa5; repr.buffer_len() + 8];
+    repr.emit(
+        &mut Ipv4Packet::new_unchecked(&mut packet[..]),
+        &ChecksumCapabilities::default(),
+    );
+
+    // The packet is both forwarded and shown to the raw socket.
+    assert_eq!(
+        forward_ingress(&mut iface_a, &mut sockets_a, &mut router, &packet),
+        None
+    );
+    assert!(sockets_a.get_mut::<raw::Socket>(raw_handle).can_recv());
+
+    assert!(iface_b.forward_egress(&mut device_b, &mut router));
+    let bytes = device_b.queue.pop_front().unwrap();
+    let ipv4_packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(ipv4_packet.options(), &packet[20..24]);
+}
+
+#[test]
+#[cfg(all(
+    feature = "proto-ipv4-fragmentation",
+    feature = "medium-ip",
+    feature = "socket-udp"
+))]
+fn test_fragmentation_options() {
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+    iface.inner.caps.max_transmission_unit = 600;
+
+    let udp_rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 1000]);
+    let udp_tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 0]);
+    let udp_socket_handle = sockets.add(udp::Socket::new(udp_rx_buffer, udp_tx_buffer));
+    let socket = sockets.get_mut::<udp::Socket>(udp_socket_handle);
+    assert_eq!(socket.bind(4242), Ok(()));
+
+    // The Security option is copied into all fragments, Record Route is not.
+    let mut options = Ipv4Options::EMPTY;
+    options
+        .push(&Ipv4OptionRepr::RecordRoute {
+            pointer: 4,
+            route: &[0; 8],
+        })
+        .unwrap();
+    options
+        .push(&Ipv4OptionRepr::Security {
+            classification: 0xab,
+            protection_authority: &[],
+        })
+        .unwrap();
+
+    let addr = Ipv4Address::new(192, 168, 1, 1);
+    let payload = [0x55; 1000];
+    let udp_repr = UdpRepr {
+        src_port: 4242,
+        dst_port: 4242,
+    };
+    let ip_repr = Ipv4Repr {
+        src_addr: addr,
+        dst_addr: addr,
+        next_header: IpProtocol::Udp,
+        payload_len: udp_repr.header_len() + payload.len(),
+        hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options,
+    };
+
+    let tx_token = device.transmit(Instant::ZERO).unwrap();
+    assert_eq!(
+        iface.inner.dispatch_ip(
+            tx_token,
+            PacketMeta::default(),
+            Packet::new_ipv4(ip_repr, IpPayload::Udp(udp_repr, &payload)),
+            &mut iface.fragmenter,
+        ),
+        Ok(())
+    );
+    while iface.ipv4_egress(&mut device) {}
+
+    let fragments: std::vec::Vec<_> = device.queue.iter().map(|f| f.to_vec()).collect();
+    assert_eq!(fragments.len(), 2);
+
+    let mut headers = std::vec::Vec::new();
+    for fragment in &fragments {
+        assert!(fragment.len() <= 600);
+        let packet = Ipv4Packet::new_checked(&fragment[..]).unwrap();
+        assert!(packet.verify_checksum());
+        headers.push((
+            packet.header_len(),
+            packet.frag_offset(),
+            packet.more_frags(),
+        ));
+    }
+    // 560 octets is the largest multiple of 8 that fits in the first fragment.
+    assert_eq!(headers, [(36, 0, true), (24, 560, false)]);
+
+    for fragment in &fragments {
+        assert_eq!(
+            iface.inner.process_ipv4(
+                &mut sockets,
+                PacketMeta::default(),
+                &Ipv4Packet::new_checked(&fragment[..]).unwrap(),
+                &mut iface.fragments,
+                false,
+            ),
+            None
+        );
+    }
+
+    let socket = sockets.get_mut::<udp::Socket>(udp_socket_handle);
+    assert_eq!(socket.recv().unwrap().0, &payload[..]);
+}
+
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+fn sent_udp_src_ports(device: &mut crate::tests::TestingDevice) -> std::vec::Vec<u16> {
+    let mut ports = std::vec::Vec::new();
+    while let Some(frame) = device.queue.pop_front() {
+        let packet = Ipv4Packet::new_checked(&frame[..]).unwrap();
+        ports.push(UdpPacket::new_checked(packet.payload()).unwrap().src_port());
+    }
+    ports
+}
+
+#[test]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+fn test_egress_strict_priority() {
+    use crate::iface::SchedulingMode;
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+
+    let new_socket = |port| {
+        let mut socket = udp::Socket::new(
+            udp::PacketBuffer::new(vec![], vec![]),
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 32]),
+        );
+        socket.bind(port).unwrap();
+        socket
+            .send_slice(b"bulk", (IpAddress::v4(192, 168, 1, 2), 9))
+            .unwrap();
+        socket
+            .send_slice(b"bulk", (IpAddress::v4(192, 168, 1, 2), 9))
+            .unwrap();
+        socket
+    };
+    let bulk = sockets.add(new_socket(1000));
+    let control = sockets.add(new_socket(2000));
+    sockets.set_egress_class(bulk, 1);
+    assert_eq!(sockets.egress_class(control), 0);
+
+    // Without the scheduler, sockets are served in insertion order.
+    assert!(iface.socket_egress(&mut device, &mut sockets));
+    assert_eq!(sent_udp_src_ports(&mut device), [1000, 2000]);
+
+    // The bulk socket only sends once the control socket has nothing left to send.
+    iface
+        .egress_scheduler_mut()
+        .set_mode(Some(SchedulingMode::StrictPriority));
+    sockets
+        .get_mut::<udp::Socket>(bulk)
+        .send_slice(b"bulk", (IpAddress::v4(192, 168, 1, 2), 9))
+        .unwrap();
+    sockets
+        .get_mut::<udp::Socket>(control)
+        .send_slice(b"ctrl", (IpAddress::v4(192, 168, 1, 2), 9))
+        .unwrap();
+    while iface.socket_egress(&mut device, &mut sockets) {}
+    assert_eq!(sent_udp_src_ports(&mut device), [2000, 2000, 1000, 1000]);
+}
+
+#[test]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+fn test_egress_rate_limit() {
+    use crate::iface::{RateLimit, SchedulingMode};
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+
+    let mut socket = udp::Socket::new(
+        udp::PacketBuffer::new(vec![], vec![]),
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 160]),
+    );
+    socket.bind(1000).unwrap();
+    for _ in 0..2 {
+        socket
+            .send_slice(&[0; 80], (IpAddress::v4(192, 168, 1, 2), 9))
+            .unwrap();
+    }
+    sockets.add(socket);
+
+    // 1000 octets per second, and 108 octet packets.
+    let scheduler = iface.egress_scheduler_mut();
+    scheduler.set_mode(Some(SchedulingMode::StrictPriority));
+    scheduler.set_rate_limit(0, Some(RateLimit::new(1000, 100)));
+
+    assert!(iface.socket_egress(&mut device, &mut sockets));
+    assert!(!iface.socket_egress(&mut device, &mut sockets));
+    assert_eq!(sent_udp_src_ports(&mut device), [1000]);
+
+    let ready_at = Instant::ZERO + Duration::from_micros(8_001);
+    assert_eq!(iface.poll_at(Instant::ZERO, &sockets), Some(ready_at));
+
+    iface.inner.now = ready_at - Duration::from_micros(1);
+    assert!(!iface.socket_egress(&mut device, &mut sockets));
+    iface.inner.now = ready_at;
+    assert!(iface.socket_egress(&mut device, &mut sockets));
+    assert_eq!(sent_udp_src_ports(&mut device), [1000]);
+    assert_eq!(iface.poll_at(ready_at, &sockets), None);
+}
+
+/// Build a datagram from port 4000 of `src_addr` to port 5000 of `dst_addr`.
+#[cfg(feature = "socket-udp")]
+#[allow(unused)]
+fn udp_datagram(src_addr: Ipv4Address, dst_addr: Ipv4Address) -> (Ipv4Repr, std::vec::Vec<u8>) {
+    let udp_repr = UdpRepr {
+        src_port: 4000,
+        dst_port: 5000,
+    };
+    let ipv4_repr = Ipv4Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Udp,
+        payload_len: udp_repr.header_len() + 4,
+        hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
+    };
+    let mut bytes = vec![0u8; ipv4_repr.payload_len];
+    udp_repr.emit(
+        &mut UdpPacket::new_unchecked(&mut bytes[..]),
+        &src_addr.into(),
+        &dst_addr.into(),
+        4,
+        |buf| buf.copy_from_slice(b"ping"),
+        &ChecksumCapabilities::default(),
+    );
+    (ipv4_repr, bytes)
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "socket-udp", feature = "medium-ethernet"))]
+fn test_udp_connected_demux(#[case] medium: Medium) {
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let new_socket = || {
+        let mut socket = udp::Socket::new(
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 32]),
+            udp::PacketBuffer::new(vec![], vec![]),
+        );
+        socket.bind(5000).unwrap();
+        socket
+    };
+    let unconnected = sockets.add(new_socket());
+    let connected = sockets.add(new_socket());
+    sockets
+        .get_mut::<udp::Socket>(connected)
+        .connect((IpAddress::v4(192, 168, 1, 2), 4000))
+        .unwrap();
+
+    let mut deliver = |src_addr: Ipv4Address| {
+        let (ipv4_repr, bytes) = udp_datagram(src_addr, Ipv4Address::new(192, 168, 1, 1));
+        assert_eq!(
+            iface.inner.process_udp(
+                &mut sockets,
+                PacketMeta::default(),
+                false,
+                ipv4_repr.into(),
+                &bytes
+            ),
+            None
+        );
+    };
+
+    // The connected socket wins over the one bound to the same port, regardless of
+    // the order they were added in.
+    deliver(Ipv4Address::new(192, 168, 1, 2));
+    deliver(Ipv4Address::new(192, 168, 1, 3));
+
+    let recv_from = |sockets: &mut SocketSet, handle| {
+        sockets
+            .get_mut::<udp::Socket>(handle)
+            .recv()
+            .map(|(_, meta)| meta.endpoint.addr)
+    };
+    assert_eq!(
+        recv_from(&mut sockets, connected),
+        Ok(IpAddress::v4(192, 168, 1, 2))
+    );
+    assert_eq!(
+        recv_from(&mut sockets, unconnected),
+        Ok(IpAddress::v4(192, 168, 1, 3))
+    );
+    assert!(!sockets.get::<udp::Socket>(connected).can_recv());
+    assert!(!sockets.get::<udp::Socket>(unconnected).can_recv());
+}
+
+#[test]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+fn test_udp_reuse_port_mixed() {
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, _device) = setup(Medium::Ip);
+
+    let new_socket = |reuse_port| {
+        let mut socket = udp::Socket::new(
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 32]),
+            udp::PacketBuffer::new(vec![], vec![]),
+        );
+        socket.set_reuse_port(reuse_port);
+        socket.bind(5000).unwrap();
+        socket
+    };
+    let plain = sockets.add(new_socket(false));
+    let shared = sockets.add(new_socket(true));
+
+    // The socket without the flag is not left out by the one sharing the port.
+    let (ipv4_repr, bytes) = udp_datagram(Ipv4Address::new(192, 168, 1, 2), Ipv4Address::BROADCAST);
+    iface.inner.process_udp(
+        &mut sockets,
+        PacketMeta::default(),
+        false,
+        ipv4_repr.into(),
+        &bytes,
+    );
+    assert!(sockets.get_mut::<udp::Socket>(plain).recv().is_ok());
+    assert!(sockets.get_mut::<udp::Socket>(shared).recv().is_ok());
+    assert!(!sockets.get_mut::<udp::Socket>(plain).can_recv());
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "socket-udp", feature = "medium-ethernet"))]
+fn test_udp_reuse_port(#[case] medium: Medium) {
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let new_socket = |reuse_port| {
+        let mut socket = udp::Socket::new(
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 32]),
+            udp::PacketBuffer::new(vec![], vec![]),
+        );
+        socket.set_reuse_port(reuse_port);
+        socket.bind(5000).unwrap();
+        socket
+    };
+    let exclusive = sockets.add(new_socket(false));
+    let shared_a = sockets.add(new_socket(true));
+    let shared_b = sockets.add(new_socket(true));
+
+    let mut deliver = |dst_addr: Ipv4Address| {
+        let (ipv4_repr, bytes) = udp_datagram(Ipv4Address::new(192, 168, 1, 2), dst_addr);
+        iface.inner.process_udp(
+            &mut sockets,
+            PacketMeta::default(),
+            false,
+            ipv4_repr.into(),
+            &bytes,
+        );
+    };
+
+    // Broadcast datagrams are copied to every socket sharing the port, and to the first
+    // of the most specific sockets.
+    deliver(Ipv4Address::BROADCAST);
+    // Unicast datagrams still go to a single socket.
+    deliver(Ipv4Address::new(192, 168, 1, 1));
+
+    let count = |sockets: &mut SocketSet, handle| {
+        let socket = sockets.get_mut::<udp::Socket>(handle);
+        core::iter::from_fn(|| socket.recv().ok().map(|_| ())).count()
+    };
+    assert_eq!(count(&mut sockets, exclusive), 2);
+    assert_eq!(count(&mut sockets, shared_a), 1);
+    assert_eq!(count(&mut sockets, shared_b), 1);
+
+    // Without sockets sharing the port, the exclusive socket gets the datagram.
+    sockets
+        .get_mut::<udp::Socket>(shared_a)
+        .set_reuse_port(false);
+    sockets
+        .get_mut::<udp::Socket>(shared_b)
+        .set_reuse_port(false);
+    let (ipv4_repr, bytes) = udp_datagram(Ipv4Address::new(192, 168, 1, 2), Ipv4Address::BROADCAST);
+    iface.inner.process_udp(
+        &mut sockets,
+        PacketMeta::default(),
+        false,
+        ipv4_repr.into(),
+        &bytes,
+    );
+    assert_eq!(count(&mut sockets, exclusive), 1);
+    assert_eq!(count(&mut sockets, shared_a), 0);
+}
+
+#[test]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+fn test_udp_lite() {
+    use crate::phy::Checksum;
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+
+    let new_socket = |checksum_coverage| {
+        let mut socket = udp::Socket::new(
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 32]),
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 1], vec![0; 32]),
+        );
+        socket.set_checksum_coverage(checksum_coverage);
+        socket.bind(5000).unwrap();
+        socket
+    };
+    let plain = sockets.add(new_socket(None));
+    let lite = sockets.add(new_socket(Some(10)));
+    sockets
+        .get_mut::<udp::Socket>(lite)
+        .set_min_checksum_coverage(10);
+
+    let (src_addr, dst_addr) = (
+        Ipv4Address::new(192, 168, 1, 2),
+        Ipv4Address::new(192, 168, 1, 1),
+    );
+    let datagram = |checksum_coverage| {
+        let udp_repr = UdpLiteRepr {
+            src_port: 4000,
+            dst_port: 5000,
+            checksum_coverage,
+        };
+        let ipv4_repr = Ipv4Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::UdpLite,
+            payload_len: udp_repr.header_len() + 4,
+            hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
+        };
+        let mut bytes = vec![0u8; ipv4_repr.payload_len];
+        udp_repr.emit(
+            &mut UdpLitePacket::new_unchecked(&mut bytes[..]),
+            &src_addr.into(),
+            &dst_addr.into(),
+            |buf| buf.copy_from_slice(b"ping"),
+        );
+        (ipv4_repr, bytes)
+    };
+
+    // Errors in the octets not covered by the checksum are delivered to the UDP-Lite socket.
+    let (ipv4_repr, mut bytes) = datagram(10);
+    bytes[11] = b'!';
+    assert_eq!(
+        iface.inner.process_udp_lite(
+            &mut sockets,
+            PacketMeta::default(),
+            false,
+            ipv4_repr.into(),
+            &bytes,
+        ),
+        None
+    );
+    assert_eq!(
+        sockets.get_mut::<udp::Socket>(lite).recv().unwrap().0,
+        b"pin!"
+    );
+    assert!(!sockets.get_mut::<udp::Socket>(plain).can_recv());
+
+    // Datagrams covered less than the socket asks for are dropped, and the port is unreachable.
+    let (ipv4_repr, bytes) = datagram(8);
+    assert!(iface
+        .inner
+        .process_udp_lite(
+            &mut sockets,
+            PacketMeta::default(),
+            false,
+            ipv4_repr.into(),
+            &bytes,
+        )
+        .is_some());
+    assert!(!sockets.get_mut::<udp::Socket>(lite).can_recv());
+
+    // Sent datagrams carry the coverage of the socket.
+    sockets
+        .get_mut::<udp::Socket>(lite)
+        .send_slice(b"pong", (IpAddress::Ipv4(src_addr), 4000))
+        .unwrap();
+    assert!(iface.socket_egress(&mut device, &mut sockets));
+    let frame = device.queue.pop_front().unwrap();
+    let packet = Ipv4Packet::new_checked(&frame[..]).unwrap();
+    assert_eq!(packet.next_header(), IpProtocol::UdpLite);
+    let udp_packet = UdpLitePacket::new_checked(packet.payload()).unwrap();
+    assert_eq!(udp_packet.checksum_coverage(), 10);
+    assert_eq!(udp_packet.payload(), b"pong");
+    assert!(udp_packet.verify_checksum(&dst_addr.into(), &src_addr.into()));
+
+    // Devices offloading the UDP checksums do not compute the UDP-Lite ones.
+    iface.inner.caps.checksum.udp = Checksum::None;
+    let (ipv4_repr, mut bytes) = datagram(10);
+    bytes[8] = b'P';
+    assert!(iface
+        .inner
+        .process_udp_lite(
+            &mut sockets,
+            PacketMeta::default(),
+            false,
+            ipv4_repr.into(),
+            &bytes,
+        )
+        .is_none());
+    assert!(!sockets.get_mut::<udp::Socket>(lite).can_recv());
+
+    sockets
+        .get_mut::<udp::Socket>(lite)
+        .send_slice(b"pong", (IpAddress::Ipv4(src_addr), 4000))
+        .unwrap();
+    assert!(iface.socket_egress(&mut device, &mut sockets));
+    let frame = device.queue.pop_front().unwrap();
+    let packet = Ipv4Packet::new_checked(&frame[..]).unwrap();
+    let udp_packet = UdpLitePacket::new_checked(packet.payload()).unwrap();
+    assert!(udp_packet.verify_checksum(&dst_addr.into(), &src_addr.into()));
+}
+
+#[test]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+fn test_udp_segmentation() {
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+
+    let mut socket = udp::Socket::new(
+        udp::PacketBuffer::new(vec![], vec![]),
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 1], vec![0; 100]),
+    );
+    socket.bind(1000).unwrap();
+    socket.set_segment_size(Some(40));
+    let handle = sockets.add(socket);
+    let remote = (IpAddress::v4(192, 168, 1, 2), 9);
+
+    let sent_payload_lens = |device: &mut crate::tests::TestingDevice| {
+        let mut lens = std::vec::Vec::new();
+        while let Some(frame) = device.queue.pop_front() {
+            let packet = Ipv4Packet::new_checked(&frame[..]).unwrap();
+            lens.push(
+                UdpPacket::new_checked(packet.payload())
+                    .unwrap()
+                    .payload()
+                    .len(),
+            );
+        }
+        lens
+    };
+
+    // Without offload, the packet is split by the interface.
+    sockets
+        .get_mut::<udp::Socket>(handle)
+        .send_slice(&[0xaa; 100], remote)
+        .unwrap();
+    while iface.socket_egress(&mut device, &mut sockets) {}
+    assert_eq!(sent_payload_lens(&mut device), [40, 40, 20]);
+
+    // With offload, the device gets the whole packet.
+    iface.inner.caps.udp_segmentation_offload = true;
+    sockets
+        .get_mut::<udp::Socket>(handle)
+        .send_slice(&[0xaa; 100], remote)
+        .unwrap();
+    while iface.socket_egress(&mut device, &mut sockets) {}
+    assert_eq!(sent_payload_lens(&mut device), [100]);
+}
@@ -25,6 +25,7 @@ fn parse_ipv6(data: &[u8]) -> crate::wire::Result<Packet<'_>> {
         }
         IpProtocol::Ipv6NoNxt => todo!(),
         IpProtocol::Ipv6Opts => todo!(),
+        IpProtocol::UdpLite => todo!(),
         IpProtocol::Unknown(_) => todo!(),
     }
 }
@@ -51,7 +52,9 @@ fn multicast_source_address(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -83,6 +86,8 @@ fn hop_by_hop_skip_with_icmp(#[case] medium: Medium) {
             src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
             dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
             next_header: IpProtocol::Icmpv6,
             payload_len: 19,
         },
@@ -99,7 +104,9 @@ fn hop_by_hop_skip_with_icmp(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -134,7 +141,9 @@ fn hop_by_hop_discard_with_icmp(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -164,6 +173,8 @@ fn hop_by_hop_discard_param_problem(#[case] medium: Medium) {
             next_header: IpProtocol::Icmpv6,
             payload_len: 75,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
         },
         IpPayload::Icmpv6(Icmpv6Repr::ParamProblem {
             reason: Icmpv6ParamProblem::UnrecognizedOption,
@@ -174,6 +185,8 @@ fn hop_by_hop_discard_param_problem(#[case] medium: Medium) {
                 next_header: IpProtocol::HopByHop,
                 payload_len: 27,
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
             },
             data: &[
                 0x3a, 0x0, 0xC0, 0x0, 0x40, 0x0, 0x1, 0x0, 0x80, 0x0, 0x2c, 0x88, 0x0, 0x2a, 0x1,
@@ -188,7 +201,9 @@ fn hop_by_hop_discard_param_problem(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -221,6 +236,8 @@ fn hop_by_hop_discard_with_multicast(#[case] medium: Medium) {
             next_header: IpProtocol::Icmpv6,
             payload_len: 75,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
         },
         IpPayload::Icmpv6(Icmpv6Repr::ParamProblem {
             reason: Icmpv6ParamProblem::UnrecognizedOption,
@@ -231,6 +248,8 @@ fn hop_by_hop_discard_with_multicast(#[case] medium: Medium) {
                 next_header: IpProtocol::HopByHop,
                 payload_len: 27,
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
             },
             data: &[
                 0x3a, 0x0, 0x80, 0x0, 0x40, 0x0, 0x1, 0x0, 0x80, 0x0, 0x2c, 0x88, 0x0, 0x2a, 0x1,
@@ -245,7 +264,9 @@ fn hop_by_hop_discard_with_multicast(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -272,6 +293,8 @@ fn imcp_empty_echo_request(#[case] medium: Medium) {
                 src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
                 dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 8,
             },
@@ -288,6 +311,8 @@ fn imcp_empty_echo_request(#[case] medium: Medium) {
             src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
             dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
             next_header: IpProtocol::Icmpv6,
             payload_len: 8,
         },
@@ -304,7 +329,9 @@ fn imcp_empty_echo_request(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -332,6 +359,8 @@ fn icmp_echo_request(#[case] medium: Medium) {
                 src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
                 dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 19,
             },
@@ -348,6 +377,8 @@ fn icmp_echo_request(#[case] medium: Medium) {
             src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
             dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
             next_header: IpProtocol::Icmpv6,
             payload_len: 19,
         },
@@ -364,7 +395,9 @@ fn icmp_echo_request(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -392,6 +425,8 @@ fn icmp_echo_reply_as_input(#[case] medium: Medium) {
                 src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
                 dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 19,
             },
@@ -411,7 +446,9 @@ fn icmp_echo_reply_as_input(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -436,6 +473,8 @@ fn unknown_proto_with_multicast_dst_address(#[case] medium: Medium) {
             src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
             dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
             next_header: IpProtocol::Icmpv6,
             payload_len: 48,
         },
@@ -446,6 +485,8 @@ fn unknown_proto_with_multicast_dst_address(#[case] medium: Medium) {
                 src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
                 dst_addr: Ipv6Address::from_parts(&[0xff02, 0, 0, 0, 0, 0, 0, 0x0001]),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
                 next_header: IpProtocol::Unknown(0x0c),
                 payload_len: 0,
             },
@@ -459,7 +500,9 @@ fn unknown_proto_with_multicast_dst_address(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -485,6 +528,8 @@ fn unknown_proto(#[case] medium: Medium) {
             src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
             dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
             next_header: IpProtocol::Icmpv6,
             payload_len: 48,
         },
@@ -495,6 +540,8 @@ fn unknown_proto(#[case] medium: Medium) {
                 src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
                 dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
                 next_header: IpProtocol::Unknown(0x0c),
                 payload_len: 0,
             },
@@ -508,7 +555,9 @@ fn unknown_proto(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -533,6 +582,8 @@ fn ndsic_neighbor_advertisement_ethernet(#[case] medium: Medium) {
                 src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
                 dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
                 hop_limit: 255,
+                dscp: 0,
+                ecn: 0,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 32,
             },
@@ -552,7 +603,9 @@ fn ndsic_neighbor_advertisement_ethernet(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -587,6 +640,8 @@ fn ndsic_neighbor_advertisement_ethernet_multicast_addr(#[case] medium: Medium)
                 src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
                 dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
                 hop_limit: 255,
+                dscp: 0,
+                ecn: 0,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 32,
             },
@@ -608,7 +663,9 @@ fn ndsic_neighbor_advertisement_ethernet_multicast_addr(#[case] medium: Medium)
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -641,6 +698,8 @@ fn ndsic_neighbor_advertisement_ieee802154(#[case] medium: Medium) {
                 src_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0002]),
                 dst_addr: Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 0x0001]),
                 hop_limit: 255,
+                dscp: 0,
+                ecn: 0,
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 40,
             },
@@ -660,7 +719,9 @@ fn ndsic_neighbor_advertisement_ieee802154(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            None,
+            false,
         ),
         response
     );
@@ -698,6 +759,8 @@ fn test_handle_valid_ndisc_request(#[case] medium: Medium) {
         dst_addr: local_ip_addr.solicited_node(),
         next_header: IpProtocol::Icmpv6,
         hop_limit: 0xff,
+        dscp: 0,
+        ecn: 0,
         payload_len: solicit.buffer_len(),
     });
 
@@ -724,6 +787,8 @@ fn test_handle_valid_ndisc_request(#[case] medium: Medium) {
         dst_addr: remote_ip_addr,
         next_header: IpProtocol::Icmpv6,
         hop_limit: 0xff,
+        dscp: 0,
+        ecn: 0,
         payload_len: icmpv6_expected.buffer_len(),
     };
 
@@ -753,6 +818,134 @@ fn test_handle_valid_ndisc_request(#[case] medium: Medium) {
     );
 }
 
+#[cfg(feature = "medium-ethernet")]
+fn redirect(router: Ipv6Address, target_addr: Ipv6Address, dest_addr: Ipv6Address) -> Ipv6Repr {
+    Ipv6Repr {
+        src_addr: router,
+        dst_addr: Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1),
+        next_header: IpProtocol::Icmpv6,
+        hop_limit: 0xff,
+        dscp: 0,
+        ecn: 0,
+        payload_len: NdiscRepr::Redirect {
+            target_addr,
+            dest_addr,
+            lladdr: None,
+            redirected_hdr: None,
+        }
+        .buffer_len(),
+    }
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_redirect(#[case] medium: Medium) {
+    let (mut iface, _sockets, _device) = setup(medium);
+
+    let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x100);
+    let better_router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x200);
+    let dest_addr = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let better_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x02]);
+    iface.routes_mut().add_default_ipv6_route(router).unwrap();
+
+    let repr = NdiscRepr::Redirect {
+        target_addr: better_router,
+        dest_addr,
+        lladdr: Some(better_hw_addr.into()),
+        redirected_hdr: None,
+    };
+    assert_eq!(
+        iface
+            .inner
+            .process_ndisc(redirect(router, better_router, dest_addr), repr),
+        None
+    );
+
+    // Packets to the destination go to the advertised router from now on.
+    assert_eq!(
+        iface.inner.route(&dest_addr.into(), iface.inner.now),
+        Some(better_router.into())
+    );
+    let route = iface.lookup_route(&dest_addr.into()).unwrap();
+    assert_eq!(route.cidr, IpCidr::new(dest_addr.into(), 128));
+    assert_eq!(route.via_router, better_router.into());
+    assert!(iface
+        .inner
+        .neighbor_cache
+        .lookup(&better_router.into(), iface.inner.now)
+        .found());
+
+    // The redirect expires.
+    let expires_at = route.expires_at.unwrap();
+    assert_eq!(
+        iface.inner.route(&dest_addr.into(), expires_at),
+        Some(router.into())
+    );
+
+    // Changing the route table forgets the redirects.
+    iface.routes_mut();
+    assert_eq!(
+        iface.inner.route(&dest_addr.into(), iface.inner.now),
+        Some(router.into())
+    );
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_redirect_ignored(#[case] medium: Medium) {
+    let (mut iface, _sockets, _device) = setup(medium);
+
+    let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x100);
+    let other_router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x300);
+    let better_router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x200);
+    let dest_addr = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    iface.routes_mut().add_default_ipv6_route(router).unwrap();
+
+    let repr = NdiscRepr::Redirect {
+        target_addr: better_router,
+        dest_addr,
+        lladdr: None,
+        redirected_hdr: None,
+    };
+
+    // Only the current next hop may redirect.
+    iface
+        .inner
+        .process_ndisc(redirect(other_router, better_router, dest_addr), repr);
+    assert_eq!(
+        iface.inner.route(&dest_addr.into(), iface.inner.now),
+        Some(router.into())
+    );
+
+    // The target must be link-local, unless it is the destination.
+    let global_target = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0x200);
+    let bad_repr = NdiscRepr::Redirect {
+        target_addr: global_target,
+        dest_addr,
+        lladdr: None,
+        redirected_hdr: None,
+    };
+    iface
+        .inner
+        .process_ndisc(redirect(router, global_target, dest_addr), bad_repr);
+    assert_eq!(
+        iface.inner.route(&dest_addr.into(), iface.inner.now),
+        Some(router.into())
+    );
+
+    // Nothing is accepted when redirects are disabled.
+    iface.set_accept_redirects(false);
+    iface
+        .inner
+        .process_ndisc(redirect(router, better_router, dest_addr), repr);
+    assert_eq!(
+        iface.inner.route(&dest_addr.into(), iface.inner.now),
+        Some(router.into())
+    );
+}
+
 #[rstest]
 #[case(Medium::Ip)]
 #[cfg(feature = "medium-ip")]
@@ -826,6 +1019,8 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         dst_addr,
         next_header: IpProtocol::Udp,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
         payload_len: udp_repr.header_len() + MAX_PAYLOAD_LEN,
     };
     let payload = packet.into_inner();
@@ -834,6 +1029,7 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         reason: Icmpv6DstUnreachable::PortUnreachable,
         header: ip_repr,
         data: &payload[..MAX_PAYLOAD_LEN],
+        extensions: IcmpExtensions::EMPTY,
     };
 
     let expected_ip_repr = Ipv6Repr {
@@ -841,6 +1037,8 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         dst_addr: src_addr,
         next_header: IpProtocol::Icmpv6,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
         payload_len: expected_icmp_repr.buffer_len(),
     };
 
@@ -1169,3 +1367,794 @@ fn get_source_address_empty_interface() {
         Ipv6Address::LOOPBACK
     );
 }
+
+#[cfg(feature = "medium-ip")]
+#[test]
+fn get_source_address_policy_table() {
+    use crate::iface::AddressPolicy;
+
+    let (mut iface, _, _) = setup(Medium::Ip);
+
+    const OWN_UNIQUE_LOCAL_ADDR: Ipv6Address = Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+    const OWN_GLOBAL_UNICAST_ADDR: Ipv6Address = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    const DST_ADDR: Ipv6Address = Ipv6Address::new(0x2001, 0xdb9, 0, 0, 0, 0, 0, 1);
+
+    iface.update_ip_addrs(|addrs| {
+        addrs.clear();
+        addrs
+            .push(IpCidr::Ipv6(Ipv6Cidr::new(OWN_UNIQUE_LOCAL_ADDR, 64)))
+            .unwrap();
+        addrs
+            .push(IpCidr::Ipv6(Ipv6Cidr::new(OWN_GLOBAL_UNICAST_ADDR, 64)))
+            .unwrap();
+    });
+
+    // The global address has the same label as the destination.
+    assert_eq!(
+        iface.get_source_address_ipv6(&DST_ADDR),
+        OWN_GLOBAL_UNICAST_ADDR
+    );
+
+    // Give the destination the label of unique local addresses.
+    let prefix = Ipv6Cidr::new(Ipv6Address::new(0x2001, 0xdb9, 0, 0, 0, 0, 0, 0), 32);
+    iface
+        .policy_table_mut()
+        .add_policy(AddressPolicy::new(prefix, 40, 13))
+        .unwrap();
+    assert_eq!(
+        iface.get_source_address_ipv6(&DST_ADDR),
+        OWN_UNIQUE_LOCAL_ADDR
+    );
+}
+
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv4"))]
+#[test]
+fn sort_destination_addrs() {
+    let (mut iface, _, _) = setup(Medium::Ip);
+
+    let global_v6 = IpAddress::v6(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let global_v4 = IpAddress::v4(8, 8, 8, 8);
+    let local_v6 = IpAddress::v6(0xfdbe, 0, 0, 0, 0, 0, 0, 2);
+    let local_v4 = IpAddress::v4(192, 168, 1, 2);
+    let mut addrs = [global_v6, global_v4, local_v6, local_v4];
+
+    // Destinations without a route can not be used.
+    iface.sort_destination_addrs(&mut addrs);
+    assert_eq!(addrs, [local_v4, local_v6, global_v6, global_v4]);
+
+    // IPv4 is preferred over IPv6 from a unique local source to a global destination,
+    // and the destinations with the longest matching prefix come first.
+    let routes = iface.routes_mut();
+    routes
+        .add_default_ipv4_route(Ipv4Address::new(192, 168, 1, 254))
+        .unwrap();
+    routes
+        .add_default_ipv6_route(Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0x100))
+        .unwrap();
+    let mut addrs = [global_v6, global_v4, local_v6, local_v4];
+    iface.sort_destination_addrs(&mut addrs);
+    assert_eq!(addrs, [local_v4, global_v4, local_v6, global_v6]);
+}
+
+#[rstest]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn unsolicited_neighbor_advert_after_update_ip(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    announce_egress(&mut iface, &mut device, Instant::from_secs(1));
+
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let new_ip_addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 5);
+
+    iface.set_announce_count(2);
+    iface.update_ip_addrs(|addrs| {
+        addrs
+            .push(IpCidr::Ipv6(Ipv6Cidr::new(new_ip_addr, 64)))
+            .unwrap();
+    });
+
+    let frames = announce_egress(&mut iface, &mut device, Instant::from_secs(5));
+    assert_eq!(frames.len(), 2);
+
+    let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+        flags: NdiscNeighborFlags::OVERRIDE,
+        target_addr: new_ip_addr,
+        lladdr: Some(HardwareAddress::Ethernet(local_hw_addr).into()),
+    });
+    for frame in frames {
+        let frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+        assert_eq!(
+            frame.dst_addr(),
+            EthernetAddress([0x33, 0x33, 0x00, 0x00, 0x00, 0x01])
+        );
+        assert_eq!(
+            parse_ipv6(frame.payload()),
+            Ok(Packet::new_ipv6(
+                Ipv6Repr {
+                    src_addr: new_ip_addr,
+                    dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+                    next_header: IpProtocol::Icmpv6,
+                    payload_len: advert.buffer_len(),
+                    hop_limit: 0xff,
+                    dscp: 0,
+                    ecn: 0,
+                },
+                IpPayload::Icmpv6(advert),
+            ))
+        );
+    }
+}
+
+/// Attach the interface from `setup` (on fdbe::/64), and a second one on fdbf::/64,
+/// to a router.
+#[cfg(feature = "medium-ip")]
+fn setup_router<'a>() -> (
+    Router<'static>,
+    (Interface, SocketSet<'a>, crate::tests::TestingDevice),
+    (Interface, SocketSet<'a>, crate::tests::TestingDevice),
+) {
+    use crate::iface::{RouterPacketBuffer, RouterPacketMetadata};
+
+    let queue = || RouterPacketBuffer::new(vec![RouterPacketMetadata::EMPTY; 4], vec![0; 4 * 1500]);
+
+    let (mut iface_a, sockets_a, device_a) = setup(Medium::Ip);
+    let (mut iface_b, sockets_b, device_b) = setup(Medium::Ip);
+    iface_b.update_ip_addrs(|ip_addrs| {
+        ip_addrs.clear();
+        ip_addrs
+            .push(IpCidr::new(IpAddress::v6(0xfdbf, 0, 0, 0, 0, 0, 0, 1), 64))
+            .unwrap();
+    });
+
+    let mut router = Router::new();
+    router.add_interface(&mut iface_a, queue()).unwrap();
+    router.add_interface(&mut iface_b, queue()).unwrap();
+
+    (
+        router,
+        (iface_a, sockets_a, device_a),
+        (iface_b, sockets_b, device_b),
+    )
+}
+
+/// Build a packet from fdbe::2 to fdbf::2.
+#[cfg(feature = "medium-ip")]
+fn forwarded_packet(payload_len: usize) -> (Ipv6Repr, std::vec::Vec<u8>) {
+    let repr = Ipv6Repr {
+        src_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2),
+        dst_addr: Ipv6Address::new(0xfdbf, 0, 0, 0, 0, 0, 0, 2),
+        next_header: IpProtocol::Udp,
+        payload_len,
+        hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+    };
+    let mut bytes = vec![0xa5; repr.buffer_len() + payload_len];
+    repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes[..]));
+    (repr, bytes)
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_forward() {
+    let (mut router, (mut iface_a, mut sockets_a, _), (mut iface_b, _, mut device_b)) =
+        setup_router();
+
+    let (repr, packet) = forwarded_packet(8);
+    let response = iface_a.inner.process_ipv6(
+        &mut sockets_a,
+        PacketMeta::default(),
+        &Ipv6Packet::new_checked(&packet[..]).unwrap(),
+        None,
+        false,
+    );
+    assert_eq!(
+        response,
+        Some(Packet::new_ipv6(repr, IpPayload::Forward(&packet)))
+    );
+    assert_eq!(
+        iface_a.inner.forward(Some(&mut router), response.unwrap()),
+        None
+    );
+
+    assert!(iface_b.forward_egress(&mut device_b, &mut router));
+    let bytes = device_b.queue.pop_front().unwrap();
+    let ipv6_packet = Ipv6Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(ipv6_packet.hop_limit(), 63);
+    assert_eq!(ipv6_packet.payload(), &packet[40..]);
+}
+
+#[test]
+#[cfg(feature = "medium-ip")]
+fn test_forward_packet_too_big() {
+    let (mut router, (mut iface_a, mut sockets_a, _), _) = setup_router();
+
+    // The packet does not fit the 1500 octets MTU of the outgoing interface.
+    let (repr, packet) = forwarded_packet(1470);
+    let response = iface_a
+        .inner
+        .process_ipv6(
+            &mut sockets_a,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&packet[..]).unwrap(),
+            None,
+            false,
+        )
+        .unwrap();
+
+    let data_len = icmp_reply_payload_len(1470, IPV6_MIN_MTU, 40);
+    let icmp_repr = Icmpv6Repr::PktTooBig {
+        mtu: 1500,
+        header: repr,
+        data: &packet[40..][..data_len],
+    };
+    assert_eq!(
+        iface_a.inner.forward(Some(&mut router), response),
+        Some(Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+                dst_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2),
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_repr.buffer_len(),
+                hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
+            },
+            IpPayload::Icmpv6(icmp_repr),
+        ))
+    );
+    assert_eq!(router.poll_at(), None);
+}
+
+/// Build an IPv6 fragment from `fdbe::2` to `fdbe::1`, carrying part of an ICMPv6 packet.
+#[cfg(all(
+    feature = "proto-ipv6-fragmentation",
+    feature = "medium-ip",
+    feature = "medium-ethernet"
+))]
+fn ipv6_fragment(frag_offset: u16, more_frags: bool, data: &[u8]) -> std::vec::Vec<u8> {
+    let repr = Ipv6Repr {
+        src_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2),
+        dst_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+        next_header: IpProtocol::Ipv6Frag,
+        payload_len: 8 + data.len(),
+        hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+    };
+    let mut bytes = vec![0; repr.buffer_len() + 8 + data.len()];
+    repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes[..]));
+
+    let mut ext_hdr = Ipv6ExtHeader::new_unchecked(&mut bytes[40..]);
+    ext_hdr.set_next_header(IpProtocol::Icmpv6);
+    ext_hdr.set_header_len(0);
+    Ipv6FragmentRepr {
+        frag_offset,
+        more_frags,
+        ident: 0x12345678,
+    }
+    .emit(&mut Ipv6FragmentHeader::new_unchecked(&mut bytes[42..48]));
+    bytes[48..].copy_from_slice(data);
+    bytes
+}
+
+/// Return an ICMPv6 echo request from `fdbe::2` to `fdbe::1` carrying `data`.
+#[cfg(all(
+    feature = "proto-ipv6-fragmentation",
+    feature = "medium-ip",
+    feature = "medium-ethernet"
+))]
+fn echo_request(data: &[u8]) -> std::vec::Vec<u8> {
+    let repr = Icmpv6Repr::EchoRequest {
+        ident: 42,
+        seq_no: 420,
+        data,
+    };
+    let mut bytes = vec![0; repr.buffer_len()];
+    repr.emit(
+        &Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2),
+        &Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+        &mut Icmpv6Packet::new_unchecked(&mut bytes[..]),
+        &ChecksumCapabilities::default(),
+    );
+    bytes
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv6-fragmentation", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv6-fragmentation", feature = "medium-ethernet"))]
+fn test_fragment_reassembly(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let data = [0xaa; 64];
+    let icmp = echo_request(&data);
+
+    // The last fragment arrives first.
+    let last = ipv6_fragment(4, false, &icmp[32..]);
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&last[..]).unwrap(),
+            Some(&mut iface.fragments),
+            false,
+        ),
+        None
+    );
+
+    let first = ipv6_fragment(0, true, &icmp[..32]);
+    let icmp_repr = Icmpv6Repr::EchoReply {
+        ident: 42,
+        seq_no: 420,
+        data: &data,
+    };
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&first[..]).unwrap(),
+            Some(&mut iface.fragments),
+            false,
+        ),
+        Some(Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+                dst_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2),
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_repr.buffer_len(),
+                hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
+            },
+            IpPayload::Icmpv6(icmp_repr),
+        ))
+    );
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv6-fragmentation", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv6-fragmentation", feature = "medium-ethernet"))]
+fn test_fragment_overlap(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let icmp = echo_request(&[0xaa; 64]);
+
+    // The second fragment overlaps the first one, which discards the whole packet.
+    for fragment in [
+        ipv6_fragment(0, true, &icmp[..32]),
+        ipv6_fragment(2, false, &icmp[16..]),
+        ipv6_fragment(4, false, &icmp[32..]),
+    ] {
+        assert_eq!(
+            iface.inner.process_ipv6(
+                &mut sockets,
+                PacketMeta::default(),
+                &Ipv6Packet::new_checked(&fragment[..]).unwrap(),
+                Some(&mut iface.fragments),
+                false,
+            ),
+            None
+        );
+    }
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv6-fragmentation", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv6-fragmentation", feature = "medium-ethernet"))]
+fn test_fragment_bad_length(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let icmp = echo_request(&[0xaa; 64]);
+    let fragment = ipv6_fragment(0, true, &icmp[..30]);
+    let repr = Ipv6Repr::parse(&Ipv6Packet::new_checked(&fragment[..]).unwrap()).unwrap();
+
+    let icmp_repr = Icmpv6Repr::ParamProblem {
+        reason: Icmpv6ParamProblem::ErroneousHdrField,
+        pointer: 4,
+        header: repr,
+        data: &fragment[40..],
+    };
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&fragment[..]).unwrap(),
+            Some(&mut iface.fragments),
+            false,
+        ),
+        Some(Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+                dst_addr: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2),
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_repr.buffer_len(),
+                hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
+            },
+            IpPayload::Icmpv6(icmp_repr),
+        ))
+    );
+}
+
+#[test]
+#[cfg(all(
+    feature = "proto-ipv6-fragmentation",
+    feature = "medium-ip",
+    feature = "socket-udp"
+))]
+fn test_fragmentation() {
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+    iface.inner.caps.max_transmission_unit = 600;
+
+    let udp_rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 1000]);
+    let udp_tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 0]);
+    let udp_socket_handle = sockets.add(udp::Socket::new(udp_rx_buffer, udp_tx_buffer));
+    let socket = sockets.get_mut::<udp::Socket>(udp_socket_handle);
+    assert_eq!(socket.bind(4242), Ok(()));
+
+    let addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let payload = [0x55; 1000];
+    let udp_repr = UdpRepr {
+        src_port: 4242,
+        dst_port: 4242,
+    };
+    let ip_repr = Ipv6Repr {
+        src_addr: addr,
+        dst_addr: addr,
+        next_header: IpProtocol::Udp,
+        payload_len: udp_repr.header_len() + payload.len(),
+        hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+    };
+
+    let tx_token = device.transmit(Instant::ZERO).unwrap();
+    assert_eq!(
+        iface.inner.dispatch_ip(
+            tx_token,
+            PacketMeta::default(),
+            Packet::new_ipv6(ip_repr, IpPayload::Udp(udp_repr, &payload)),
+            &mut iface.fragmenter,
+        ),
+        Ok(())
+    );
+    assert!(iface.ipv6_egress(&mut device));
+    assert!(!iface.ipv6_egress(&mut device));
+
+    let fragments: std::vec::Vec<_> = device.queue.iter().map(|f| f.to_vec()).collect();
+    assert_eq!(fragments.len(), 2);
+
+    let mut offsets = std::vec::Vec::new();
+    for fragment in &fragments {
+        assert!(fragment.len() <= 600);
+        let packet = Ipv6Packet::new_checked(&fragment[..]).unwrap();
+        assert_eq!(packet.next_header(), IpProtocol::Ipv6Frag);
+        let ext_hdr = Ipv6ExtHeader::new_checked(packet.payload()).unwrap();
+        assert_eq!(ext_hdr.next_header(), IpProtocol::Udp);
+        let frag_hdr = Ipv6FragmentHeader::new_checked(ext_hdr.payload()).unwrap();
+        offsets.push((frag_hdr.frag_offset(), frag_hdr.more_frags()));
+    }
+    // 552 octets is the largest multiple of 8 that fits in the first fragment.
+    assert_eq!(offsets, [(0, true), (552 / 8, false)]);
+
+    for fragment in &fragments {
+        assert_eq!(
+            iface.inner.process_ipv6(
+                &mut sockets,
+                PacketMeta::default(),
+                &Ipv6Packet::new_checked(&fragment[..]).unwrap(),
+                Some(&mut iface.fragments),
+                false,
+            ),
+            None
+        );
+    }
+
+    let socket = sockets.get_mut::<udp::Socket>(udp_socket_handle);
+    assert_eq!(socket.recv().unwrap().0, &payload[..]);
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "medium-ip", feature = "proto-ipv6-temporary-addresses"))]
+fn test_temporary_addresses(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let public_addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let dst_addr = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+
+    iface.set_ipv6_temporary_lifetimes(Duration::from_secs(100), Duration::from_secs(50));
+    iface.enable_ipv6_temporary_addresses();
+    let temp_addrs: std::vec::Vec<_> = iface.ipv6_temporary_addrs().collect();
+    assert_eq!(temp_addrs.len(), 1);
+    let old_addr = temp_addrs[0];
+    assert_ne!(old_addr, public_addr);
+    assert!(Ipv6Cidr::new(public_addr, 64).contains_addr(&old_addr));
+    assert!(iface.has_ip_addr(old_addr));
+    assert_eq!(iface.get_source_address_ipv6(&dst_addr), old_addr);
+
+    // A new address is generated before the old one is deprecated, and used once it is.
+    let regen_at = iface.inner.poll_at().unwrap();
+    assert!(regen_at >= Instant::from_secs(28) && regen_at <= Instant::from_secs(48));
+    iface.poll(regen_at, &mut device, &mut sockets);
+    let temp_addrs: std::vec::Vec<_> = iface.ipv6_temporary_addrs().collect();
+    assert_eq!(temp_addrs.len(), 2);
+    let new_addr = temp_addrs[1];
+    assert_eq!(iface.get_source_address_ipv6(&dst_addr), old_addr);
+
+    iface.poll(regen_at + Duration::from_secs(3), &mut device, &mut sockets);
+    assert!(iface.has_ip_addr(old_addr));
+    assert_eq!(iface.get_source_address_ipv6(&dst_addr), new_addr);
+
+    // The old address is removed at the end of its valid lifetime.
+    iface.poll(Instant::from_secs(100), &mut device, &mut sockets);
+    assert!(!iface.has_ip_addr(old_addr));
+    assert!(iface.has_ip_addr(new_addr));
+
+    iface.disable_ipv6_temporary_addresses();
+    assert_eq!(iface.ipv6_temporary_addrs().count(), 0);
+    assert!(!iface.has_ip_addr(new_addr));
+    assert_eq!(iface.get_source_address_ipv6(&dst_addr), public_addr);
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "socket-udp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "socket-udp", feature = "medium-ethernet"))]
+#[case(Medium::Ieee802154)]
+#[cfg(all(feature = "socket-udp", feature = "medium-ieee802154"))]
+fn test_loopback_udp(#[case] medium: Medium) {
+    use crate::socket::udp;
+    use crate::wire::IpEndpoint;
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    let new_socket = || {
+        udp::Socket::new(
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 32]),
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 32]),
+        )
+    };
+    let client = sockets.add(new_socket());
+    let server = sockets.add(new_socket());
+    sockets.get_mut::<udp::Socket>(client).bind(1000).unwrap();
+    sockets.get_mut::<udp::Socket>(server).bind(2000).unwrap();
+
+    let own_addr = IpAddress::v6(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let socket = sockets.get_mut::<udp::Socket>(client);
+    socket.send_slice(b"hello", (own_addr, 2000)).unwrap();
+    socket
+        .send_slice(b"world", (IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1), 2000))
+        .unwrap();
+
+    assert!(iface.socket_egress(&mut device, &mut sockets));
+    assert!(device.queue.is_empty());
+    assert_eq!(iface.poll_at(Instant::ZERO, &sockets), Some(Instant::ZERO));
+
+    assert!(iface.poll(Instant::ZERO, &mut device, &mut sockets));
+    assert!(device.queue.is_empty());
+    let socket = sockets.get_mut::<udp::Socket>(server);
+    assert_eq!(
+        socket.recv(),
+        Ok((
+            &b"hello"[..],
+            udp::UdpMetadata {
+                local_address: Some(own_addr),
+                ..IpEndpoint::new(own_addr, 1000).into()
+            }
+        ))
+    );
+    let loopback_addr = IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1);
+    assert_eq!(
+        socket.recv(),
+        Ok((
+            &b"world"[..],
+            udp::UdpMetadata {
+                local_address: Some(loopback_addr),
+                ..IpEndpoint::new(loopback_addr, 1000).into()
+            }
+        ))
+    );
+}
+
+/// Build an IPv6 packet carrying an MLD message with a Router Alert option, as sent by
+/// another host of the link.
+#[cfg(feature = "proto-multicast-querier")]
+#[allow(unused)]
+fn mld_packet(src_addr: Ipv6Address, dst_addr: Ipv6Address, mld_repr: MldRepr) -> Vec<u8> {
+    let icmp_repr = Icmpv6Repr::Mld(mld_repr);
+    let ipv6_repr = Ipv6Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::HopByHop,
+        payload_len: 8 + icmp_repr.buffer_len(),
+        hop_limit: 1,
+        dscp: 0,
+        ecn: 0,
+    };
+    let mut bytes = vec![0; ipv6_repr.buffer_len() + ipv6_repr.payload_len];
+    ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes[..]));
+    let hbh = &mut bytes[ipv6_repr.buffer_len()..];
+    hbh[..8].copy_from_slice(&[0x3a, 0x00, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00]);
+    icmp_repr.emit(
+        &src_addr,
+        &dst_addr,
+        &mut Icmpv6Packet::new_unchecked(&mut hbh[8..]),
+        &ChecksumCapabilities::default(),
+    );
+    bytes
+}
+
+/// Receive the MLD queries sent by the interface, as their destination, multicast address
+/// and maximum response code.
+#[cfg(feature = "proto-multicast-querier")]
+#[allow(unused)]
+fn recv_mld_queries(
+    device: &mut crate::tests::TestingDevice,
+    timestamp: Instant,
+) -> Vec<(Ipv6Address, Ipv6Address, u16)> {
+    let medium = device.capabilities().medium;
+    recv_all(device, timestamp)
+        .iter()
+        .filter_map(|frame| {
+            let ipv6_packet = match medium {
+                #[cfg(feature = "medium-ethernet")]
+                Medium::Ethernet => {
+                    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+                    Ipv6Packet::new_checked(eth_frame.payload()).ok()?
+                }
+                #[cfg(feature = "medium-ip")]
+                Medium::Ip => Ipv6Packet::new_checked(&frame[..]).ok()?,
+                #[cfg(feature = "medium-ieee802154")]
+                Medium::Ieee802154 => todo!(),
+            };
+            let ipv6_repr = Ipv6Repr::parse(&ipv6_packet).ok()?;
+            if ipv6_repr.next_header != IpProtocol::HopByHop {
+                return None;
+            }
+            assert_eq!(
+                ipv6_repr.src_addr,
+                Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)
+            );
+            assert_eq!(ipv6_repr.hop_limit, 1);
+
+            let ext_header = Ipv6ExtHeader::new_checked(ipv6_packet.payload()).unwrap();
+            let ext_repr = Ipv6ExtHeaderRepr::parse(&ext_header).unwrap();
+            assert_eq!(ext_repr.next_header, IpProtocol::Icmpv6);
+            let hbh_header = Ipv6HopByHopHeader::new_checked(ext_repr.data).unwrap();
+            let hbh_repr = Ipv6HopByHopRepr::parse(&hbh_header).unwrap();
+            assert_eq!(hbh_repr.options[0], Ipv6OptionRepr::RouterAlert(0));
+
+            let icmp_packet = Icmpv6Packet::new_checked(&ipv6_packet.payload()[8..]).unwrap();
+            let icmp_repr = Icmpv6Repr::parse(
+                &ipv6_repr.src_addr,
+                &ipv6_repr.dst_addr,
+                &icmp_packet,
+                &ChecksumCapabilities::default(),
+            )
+            .unwrap();
+            match icmp_repr {
+                Icmpv6Repr::Mld(MldRepr::Query {
+                    max_resp_code,
+                    mcast_addr,
+                    qrv,
+                    qqic,
+                    ..
+                }) => {
+                    assert_eq!((qrv, qqic), (2, 125));
+                    Some((ipv6_repr.dst_addr, mcast_addr, max_resp_code))
+                }
+                repr => panic!("unexpected ICMPv6 message {repr:?}"),
+            }
+        })
+        .collect()
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "proto-multicast-querier", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "proto-multicast-querier", feature = "medium-ethernet"))]
+fn test_mld_querier(#[case] medium: Medium) {
+    use crate::wire::{MldAddressRecord, MldRecordType};
+
+    let group = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 0x1234);
+    let host = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.enable_multicast_querier();
+
+    let mut recv_queries = |iface: &mut Interface, timestamp: Instant| {
+        iface.inner.now = timestamp;
+        while iface.multicast_querier_egress(&mut device) {}
+        recv_mld_queries(&mut device, timestamp)
+    };
+
+    // General query
+    assert_eq!(
+        recv_queries(&mut iface, Instant::ZERO),
+        vec![(
+            Ipv6Address::LINK_LOCAL_ALL_NODES,
+            Ipv6Address::UNSPECIFIED,
+            10_000
+        )]
+    );
+    assert!(iface.is_mld_querier());
+
+    let mut process_report = |iface: &mut Interface, record_type: MldRecordType| {
+        let mut record = [0; 20];
+        let mut record_buf = MldAddressRecord::new_unchecked(&mut record[..]);
+        record_buf.set_record_type(record_type);
+        record_buf.set_aux_data_len(0);
+        record_buf.set_num_srcs(0);
+        record_buf.set_mcast_addr(group);
+        let report = mld_packet(
+            host,
+            Ipv6Address::LINK_LOCAL_ALL_MLDV2_ROUTERS,
+            MldRepr::Report {
+                nr_mcast_addr_rcrds: 1,
+                data: &record,
+            },
+        );
+        let response = iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&report[..]).unwrap(),
+            None,
+            false,
+        );
+        assert_eq!(response, None);
+    };
+
+    // A host reports a group
+    let timestamp = Instant::from_secs(5);
+    iface.inner.now = timestamp;
+    process_report(&mut iface, MldRecordType::ModeIsExclude);
+    assert!(iface.has_multicast_listeners(group));
+
+    // The host leaves the group, which is queried before being forgotten
+    process_report(&mut iface, MldRecordType::ChangeToInclude);
+    assert_eq!(
+        recv_queries(&mut iface, timestamp),
+        vec![(group, group, 1000)]
+    );
+    assert_eq!(
+        recv_queries(&mut iface, Instant::from_secs(6)),
+        vec![(group, group, 1000)]
+    );
+    assert_eq!(recv_queries(&mut iface, Instant::from_secs(7)), vec![]);
+    assert!(!iface.has_multicast_listeners(group));
+
+    // A querier with a lower address wins the election
+    let query = mld_packet(
+        Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0),
+        Ipv6Address::LINK_LOCAL_ALL_NODES,
+        MldRepr::Query {
+            max_resp_code: 10_000,
+            mcast_addr: Ipv6Address::UNSPECIFIED,
+            s_flag: false,
+            qrv: 2,
+            qqic: 125,
+            num_srcs: 0,
+            data: &[],
+        },
+    );
+    iface.inner.process_ipv6(
+        &mut sockets,
+        PacketMeta::default(),
+        &Ipv6Packet::new_checked(&query[..]).unwrap(),
+        None,
+        false,
+    );
+    assert!(!iface.is_mld_querier());
+    assert_eq!(recv_queries(&mut iface, Instant::from_secs(100)), vec![]);
+}
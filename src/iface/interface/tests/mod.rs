@@ -38,6 +38,27 @@ fn recv_all(device: &mut crate::tests::TestingDevice, timestamp: Instant) -> Vec
     pkts
 }
 
+/// Send the address announcements that are due until `until`, returning the sent frames.
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+#[allow(unused)]
+fn announce_egress(
+    iface: &mut Interface,
+    device: &mut crate::tests::TestingDevice,
+    until: Instant,
+) -> std::vec::Vec<std::vec::Vec<u8>> {
+    let mut frames = std::vec::Vec::new();
+    loop {
+        while iface.announce_egress(device) {}
+        while let Some(frame) = device.queue.pop_front() {
+            frames.push(frame.to_vec());
+        }
+        if iface.inner.now >= until {
+            break frames;
+        }
+        iface.inner.now += Duration::from_millis(100);
+    }
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 struct MockTxToken;
@@ -100,6 +121,8 @@ fn test_handle_udp_broadcast(
         next_header: IpProtocol::Udp,
         payload_len: udp_repr.header_len() + UDP_PAYLOAD.len(),
         hop_limit: 0x40,
+        dscp: 0,
+        ecn: 0,
     });
     #[cfg(all(not(feature = "proto-ipv6"), feature = "proto-ipv4"))]
     let ip_repr = IpRepr::Ipv4(Ipv4Repr {
@@ -125,6 +148,8 @@ fn test_handle_udp_broadcast(
         &ChecksumCapabilities::default(),
     );
 
+    let dst_addr = ip_repr.dst_addr();
+
     // Packet should be handled by bound UDP socket
     assert_eq!(
         iface.inner.process_udp(
@@ -143,7 +168,13 @@ fn test_handle_udp_broadcast(
     assert!(socket.can_recv());
     assert_eq!(
         socket.recv(),
-        Ok((&UDP_PAYLOAD[..], IpEndpoint::new(src_ip.into(), 67).into()))
+        Ok((
+            &UDP_PAYLOAD[..],
+            udp::UdpMetadata {
+                local_address: Some(dst_addr),
+                ..IpEndpoint::new(src_ip.into(), 67).into()
+            }
+        ))
     );
 }
 
@@ -183,6 +214,8 @@ pub fn tcp_not_accepted() {
                 next_header: IpProtocol::Tcp,
                 payload_len: tcp.buffer_len(),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
             }),
             &tcp_bytes,
         ),
@@ -193,6 +226,8 @@ pub fn tcp_not_accepted() {
                 next_header: IpProtocol::Tcp,
                 payload_len: tcp.buffer_len(),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
             },
             IpPayload::Tcp(TcpRepr {
                 src_port: 4243,
@@ -226,9 +261,64 @@ pub fn tcp_not_accepted() {
                 next_header: IpProtocol::Tcp,
                 payload_len: tcp.buffer_len(),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
             }),
             &tcp_bytes,
         ),
         None,
     );
 }
+
+#[test]
+#[cfg(feature = "socket-ethernet")]
+fn test_ethernet_socket() {
+    use crate::socket::ethernet;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ethernet);
+
+    let lldp = EthernetProtocol::Unknown(0x88cc);
+    let socket = ethernet::Socket::new(
+        Some(lldp),
+        ethernet::PacketBuffer::new(vec![ethernet::PacketMetadata::EMPTY; 2], vec![0; 128]),
+        ethernet::PacketBuffer::new(vec![ethernet::PacketMetadata::EMPTY; 2], vec![0; 128]),
+    );
+    let handle = sockets.add(socket);
+
+    let frame = |dst_addr, ethertype| {
+        let repr = EthernetRepr {
+            src_addr: EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]),
+            dst_addr,
+            ethertype,
+        };
+        let mut bytes = vec![0x2a; repr.buffer_len() + 8];
+        repr.emit(&mut EthernetFrame::new_unchecked(&mut bytes[..]));
+        bytes
+    };
+    let nearest_bridge = EthernetAddress([0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e]);
+    let other_host = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x01]);
+
+    // Only frames with the socket's EtherType addressed to the interface are received.
+    let received = frame(nearest_bridge, lldp);
+    for bytes in [
+        &received,
+        &frame(other_host, lldp),
+        &frame(nearest_bridge, EthernetProtocol::Unknown(0x888e)),
+    ] {
+        device
+            .queue
+            .push_back(bytes[..].try_into().unwrap())
+            .unwrap();
+        iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    }
+    let socket = sockets.get_mut::<ethernet::Socket>(handle);
+    assert_eq!(socket.recv(), Ok(&received[..]));
+    assert!(!socket.can_recv());
+
+    // Frames are sent as they are.
+    let sent = frame(nearest_bridge, lldp);
+    socket.send_slice(&sent).unwrap();
+    assert!(iface.socket_egress(&mut device, &mut sockets));
+    assert_eq!(device.queue.pop_front().as_deref(), Some(&sent[..]));
+    assert!(device.queue.is_empty());
+}
@@ -43,6 +43,8 @@ fn icmp_echo_request(#[case] medium: Medium) {
             src_addr: Ipv6Address::from_parts(&[0xfe80, 0, 0, 0, 0x180b, 0x4242, 0x4242, 0x4242]),
             dst_addr: Ipv6Address::from_parts(&[0xfe80, 0, 0, 0, 0x241c, 0x2957, 0x34a6, 0x3a62]),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
             next_header: IpProtocol::Icmpv6,
             payload_len: 64,
         },
@@ -217,6 +219,8 @@ fn test_echo_request_sixlowpan_128_bytes() {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 136,
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
             },
             IpPayload::Icmpv6(Icmpv6Repr::EchoReply {
                 ident: 39,
@@ -368,14 +372,19 @@ In at rhoncus tortor. Cras blandit tellus diam, varius vestibulum nibh commodo n
         socket.recv(),
         Ok((
             &udp_data[..],
-            IpEndpoint {
-                addr: IpAddress::Ipv6(Ipv6Address([
-                    0xfe, 0x80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x40, 0x42, 0x42, 0x42, 0x42, 0x42,
-                    0xb, 0x1a,
-                ])),
-                port: 54217,
+            udp::UdpMetadata {
+                local_address: Some(
+                    Ipv6Address::new(0xfe80, 0x0, 0x0, 0x0, 0x92fc, 0x48c2, 0xa441, 0xfc76).into()
+                ),
+                ..IpEndpoint {
+                    addr: IpAddress::Ipv6(Ipv6Address([
+                        0xfe, 0x80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x40, 0x42, 0x42, 0x42, 0x42,
+                        0x42, 0xb, 0x1a,
+                    ])),
+                    port: 54217,
+                }
+                .into()
             }
-            .into()
         ))
     );
 
@@ -391,6 +400,8 @@ In at rhoncus tortor. Cras blandit tellus diam, varius vestibulum nibh commodo n
                 next_header: IpProtocol::Udp,
                 payload_len: udp_data.len(),
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
             },
             IpPayload::Udp(
                 UdpRepr {
@@ -433,3 +444,59 @@ In at rhoncus tortor. Cras blandit tellus diam, varius vestibulum nibh commodo n
         ]
     );
 }
+
+#[test]
+#[cfg(all(feature = "socket-udp", feature = "medium-ieee802154"))]
+fn test_sixlowpan_udp_lite() {
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ieee802154);
+
+    let mut socket = udp::Socket::new(
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 1], vec![0; 32]),
+        udp::PacketBuffer::new(vec![], vec![]),
+    );
+    socket.set_checksum_coverage(Some(8));
+    socket.bind(1234).unwrap();
+    let handle = sockets.add(socket);
+
+    let src_addr = Ipv6Address::from_parts(&[0xfe80, 0, 0, 0, 0, 0, 0, 2]);
+    let dst_addr = Ipv6Address::from_parts(&[0xfe80, 0, 0, 0, 0, 0, 0, 1]);
+    let udp_repr = UdpLiteRepr {
+        src_port: 4321,
+        dst_port: 1234,
+        checksum_coverage: 8,
+    };
+
+    // There is no NHC for UDP-Lite, the header is sent inline.
+    let tx_token = device.transmit(Instant::now()).unwrap();
+    iface.inner.dispatch_ieee802154(
+        iface.inner.hardware_addr.ieee802154_or_panic(),
+        tx_token,
+        PacketMeta::default(),
+        Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr,
+                dst_addr,
+                next_header: IpProtocol::UdpLite,
+                payload_len: udp_repr.header_len() + 4,
+                hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
+            },
+            IpPayload::UdpLite(udp_repr, b"ping"),
+        ),
+        &mut iface.fragmenter,
+    );
+    let frame = device.queue.pop_front().unwrap();
+
+    iface.inner.process_ieee802154(
+        &mut sockets,
+        PacketMeta::default(),
+        &frame[..],
+        &mut iface.fragments,
+    );
+    let (payload, meta) = sockets.get_mut::<udp::Socket>(handle).recv().unwrap();
+    assert_eq!(payload, b"ping");
+    assert_eq!(meta.endpoint, (IpAddress::Ipv6(src_addr), 4321).into());
+}
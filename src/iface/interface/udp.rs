@@ -3,6 +3,8 @@ use super::*;
 #[cfg(feature = "socket-dns")]
 use crate::socket::dns::Socket as DnsSocket;
 
+#[cfg(feature = "socket-udp")]
+use crate::iface::SocketHandle;
 #[cfg(feature = "socket-udp")]
 use crate::socket::udp::Socket as UdpSocket;
 
@@ -25,14 +27,15 @@ impl InterfaceInner {
         ));
 
         #[cfg(feature = "socket-udp")]
-        for udp_socket in sockets
-            .items_mut()
-            .filter_map(|i| UdpSocket::downcast_mut(&mut i.socket))
-        {
-            if udp_socket.accepts(self, &ip_repr, &udp_repr) {
-                udp_socket.process(self, meta, &ip_repr, &udp_repr, udp_packet.payload());
-                return None;
-            }
+        if self.udp_socket_ingress(
+            sockets,
+            meta,
+            &ip_repr,
+            &udp_repr,
+            None,
+            udp_packet.payload(),
+        ) {
+            return None;
         }
 
         #[cfg(feature = "socket-dns")]
@@ -46,6 +49,107 @@ impl InterfaceInner {
             }
         }
 
+        self.udp_port_unreachable(handled_by_raw_socket, ip_repr, ip_payload)
+    }
+
+    #[cfg(feature = "socket-udp")]
+    pub(super) fn process_udp_lite<'frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        meta: PacketMeta,
+        handled_by_raw_socket: bool,
+        ip_repr: IpRepr,
+        ip_payload: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
+        let (src_addr, dst_addr) = (ip_repr.src_addr(), ip_repr.dst_addr());
+        let udp_packet = check!(UdpLitePacket::new_checked(ip_payload));
+        let udp_lite_repr = check!(UdpLiteRepr::parse(&udp_packet, &src_addr, &dst_addr));
+        let udp_repr = UdpRepr {
+            src_port: udp_lite_repr.src_port,
+            dst_port: udp_lite_repr.dst_port,
+        };
+
+        let covered_len = Some(udp_packet.covered_len());
+        if self.udp_socket_ingress(
+            sockets,
+            meta,
+            &ip_repr,
+            &udp_repr,
+            covered_len,
+            udp_packet.payload(),
+        ) {
+            return None;
+        }
+
+        self.udp_port_unreachable(handled_by_raw_socket, ip_repr, ip_payload)
+    }
+
+    /// Deliver a UDP or UDP-Lite datagram to the sockets, and return whether any socket
+    /// accepted it. `covered_len` is the checksum coverage of UDP-Lite datagrams.
+    #[cfg(feature = "socket-udp")]
+    fn udp_socket_ingress(
+        &mut self,
+        sockets: &mut SocketSet,
+        meta: PacketMeta,
+        ip_repr: &IpRepr,
+        udp_repr: &UdpRepr,
+        covered_len: Option<usize>,
+        payload: &[u8],
+    ) -> bool {
+        let dst_addr = ip_repr.dst_addr();
+        let fan_out = dst_addr.is_multicast() || self.is_broadcast(&dst_addr);
+
+        // Several sockets may share a port, the most specific one gets the datagram.
+        let mut best: Option<(SocketHandle, u8)> = None;
+        let mut shared = false;
+        for item in sockets.items() {
+            if let Some(udp_socket) = UdpSocket::downcast(&item.socket) {
+                if !udp_socket.accepts_coverage(covered_len)
+                    || !udp_socket.accepts(self, ip_repr, udp_repr)
+                {
+                    continue;
+                }
+                shared |= fan_out && udp_socket.reuse_port();
+                if best.map_or(true, |(_, best)| udp_socket.specificity() > best) {
+                    best = Some((item.meta.handle, udp_socket.specificity()));
+                }
+            }
+        }
+
+        // Multicast and broadcast datagrams are copied to all the sockets sharing them, and
+        // to the most specific socket, which gets them whether it shares them or not.
+        if shared {
+            let best = best.map(|(handle, _)| handle);
+            for item in sockets.items_mut() {
+                let handle = item.meta.handle;
+                if let Some(udp_socket) = UdpSocket::downcast_mut(&mut item.socket) {
+                    if (udp_socket.reuse_port() || best == Some(handle))
+                        && udp_socket.accepts_coverage(covered_len)
+                        && udp_socket.accepts(self, ip_repr, udp_repr)
+                    {
+                        udp_socket.process(self, meta, ip_repr, udp_repr, payload);
+                    }
+                }
+            }
+            return true;
+        }
+
+        if let Some((handle, _)) = best {
+            let udp_socket = sockets.get_mut::<UdpSocket>(handle);
+            udp_socket.process(self, meta, ip_repr, udp_repr, payload);
+            return true;
+        }
+
+        false
+    }
+
+    /// Reply to a UDP or UDP-Lite datagram that no socket handled.
+    fn udp_port_unreachable<'frame>(
+        &mut self,
+        handled_by_raw_socket: bool,
+        ip_repr: IpRepr,
+        ip_payload: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
         // The packet wasn't handled by a socket, send an ICMP port unreachable packet.
         match ip_repr {
             #[cfg(feature = "proto-ipv4")]
@@ -60,6 +164,7 @@ impl InterfaceInner {
                     reason: Icmpv4DstUnreachable::PortUnreachable,
                     header: ipv4_repr,
                     data: &ip_payload[0..payload_len],
+                    extensions: IcmpExtensions::EMPTY,
                 };
                 self.icmpv4_reply(ipv4_repr, icmpv4_reply_repr)
             }
@@ -71,6 +176,7 @@ impl InterfaceInner {
                     reason: Icmpv6DstUnreachable::PortUnreachable,
                     header: ipv6_repr,
                     data: &ip_payload[0..payload_len],
+                    extensions: IcmpExtensions::EMPTY,
                 };
                 self.icmpv6_reply(ipv6_repr, icmpv6_reply_repr)
             }
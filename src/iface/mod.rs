@@ -4,21 +4,34 @@ The `iface` module deals with the *network interfaces*. It filters incoming fram
 provides lookup and caching of hardware addresses, and handles management packets.
 */
 
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+mod destination;
 mod fragmentation;
 mod interface;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
 mod neighbor;
+#[cfg(feature = "proto-ipv6")]
+mod policy;
 mod route;
+mod router;
 #[cfg(feature = "proto-rpl")]
 mod rpl;
+mod scheduler;
 mod socket_meta;
 mod socket_set;
 
 mod packet;
 
-#[cfg(feature = "proto-igmp")]
-pub use self::interface::MulticastError;
 pub use self::interface::{Config, Interface, InterfaceInner as Context};
+#[cfg(feature = "proto-igmp")]
+pub use self::interface::{MulticastError, MulticastFilterMode};
 
-pub use self::route::{Route, RouteTableFull, Routes};
+#[cfg(feature = "proto-ipv6")]
+pub use self::policy::{AddressPolicy, PolicyTable, PolicyTableFull};
+pub use self::route::{Route, RouteKind, RouteRule, RouteTableFull, Routes};
+pub use self::router::{
+    InterfaceId, PacketBuffer as RouterPacketBuffer, PacketMetadata as RouterPacketMetadata,
+    Router, RouterFull,
+};
+pub use self::scheduler::{EgressScheduler, RateLimit, SchedulingMode};
 pub use self::socket_set::{SocketHandle, SocketSet, SocketStorage};
@@ -98,8 +98,8 @@ impl<'p> Packet<'p> {
                     &caps.checksum,
                 )
             }
-            #[cfg(feature = "socket-raw")]
             IpPayload::Raw(raw_packet) => payload.copy_from_slice(raw_packet),
+            IpPayload::Forward(_) => unreachable!(),
             #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
             IpPayload::Udp(udp_repr, inner_payload) => udp_repr.emit(
                 &mut UdpPacket::new_unchecked(payload),
@@ -109,6 +109,15 @@ impl<'p> Packet<'p> {
                 |buf| buf.copy_from_slice(inner_payload),
                 &caps.checksum,
             ),
+            #[cfg(feature = "socket-udp")]
+            IpPayload::UdpLite(udp_repr, inner_payload) => udp_repr.emit(
+                &mut UdpLitePacket::new_unchecked(
+                    &mut payload[..udp_repr.header_len() + inner_payload.len()],
+                ),
+                &_ip_repr.src_addr(),
+                &_ip_repr.dst_addr(),
+                |buf| buf.copy_from_slice(inner_payload),
+            ),
             #[cfg(feature = "socket-tcp")]
             IpPayload::Tcp(mut tcp_repr) => {
                 // This is a terrible hack to make TCP performance more acceptable on systems
@@ -171,19 +180,36 @@ pub(crate) struct PacketV6<'p> {
     pub(crate) payload: IpPayload<'p>,
 }
 
+#[cfg(feature = "proto-ipv6")]
+impl<'p> PacketV6<'p> {
+    /// Return the 6LoWPAN next header for the payload of the packet.
+    #[cfg(feature = "proto-sixlowpan")]
+    pub(crate) fn payload_sixlowpan_next_header(&self) -> SixlowpanNextHeader {
+        match &self.payload {
+            // Raw payloads are not compressed, the protocol is in the IPv6 header.
+            IpPayload::Raw(_) => SixlowpanNextHeader::Uncompressed(self.header.next_header),
+            payload => payload.as_sixlowpan_next_header(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub(crate) enum IpPayload<'p> {
     #[cfg(feature = "proto-ipv4")]
     Icmpv4(Icmpv4Repr<'p>),
     #[cfg(feature = "proto-igmp")]
-    Igmp(IgmpRepr),
+    Igmp(IgmpRepr<'p>),
     #[cfg(feature = "proto-ipv6")]
     Icmpv6(Icmpv6Repr<'p>),
-    #[cfg(feature = "socket-raw")]
     Raw(&'p [u8]),
+    /// A whole IP packet, header included, that was received and must be
+    /// forwarded to another host. It is never emitted as is.
+    Forward(&'p [u8]),
     #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
     Udp(UdpRepr, &'p [u8]),
+    #[cfg(feature = "socket-udp")]
+    UdpLite(UdpLiteRepr, &'p [u8]),
     #[cfg(feature = "socket-tcp")]
     Tcp(TcpRepr<'p>),
     #[cfg(feature = "socket-dhcpv4")]
@@ -206,8 +232,29 @@ impl<'p> IpPayload<'p> {
             Self::Tcp(_) => SixlowpanNextHeader::Uncompressed(IpProtocol::Tcp),
             #[cfg(feature = "socket-udp")]
             Self::Udp(..) => SixlowpanNextHeader::Compressed,
-            #[cfg(feature = "socket-raw")]
-            Self::Raw(_) => todo!(),
+            // There is no compression of UDP-Lite headers, they are sent inline.
+            #[cfg(feature = "socket-udp")]
+            Self::UdpLite(..) => SixlowpanNextHeader::Uncompressed(IpProtocol::UdpLite),
+            Self::Raw(_) => unreachable!(),
+            Self::Forward(_) => unreachable!(),
+        }
+    }
+
+    /// Return the source and destination ports of the payload, if it is a TCP, UDP or
+    /// UDP-Lite packet with the given `protocol`.
+    pub(crate) fn ports(&self, protocol: IpProtocol) -> Option<(u16, u16)> {
+        match self {
+            #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
+            Self::Udp(udp_repr, _) => Some((udp_repr.src_port, udp_repr.dst_port)),
+            #[cfg(feature = "socket-udp")]
+            Self::UdpLite(udplite_repr, _) => Some((udplite_repr.src_port, udplite_repr.dst_port)),
+            #[cfg(feature = "socket-tcp")]
+            Self::Tcp(tcp_repr) => Some((tcp_repr.src_port, tcp_repr.dst_port)),
+            #[cfg(feature = "socket-dhcpv4")]
+            Self::Dhcpv4(udp_repr, _) => Some((udp_repr.src_port, udp_repr.dst_port)),
+            Self::Raw(payload) => super::route::transport_ports(protocol, payload),
+            #[allow(unreachable_patterns)]
+            _ => None,
         }
     }
 }
@@ -229,13 +276,11 @@ pub(crate) fn icmp_reply_payload_len(len: usize, mtu: usize, header_len: usize)
 pub(crate) enum IgmpReportState {
     Inactive,
     ToGeneralQuery {
-        version: IgmpVersion,
         timeout: crate::time::Instant,
         interval: crate::time::Duration,
         next_index: usize,
     },
     ToSpecificQuery {
-        version: IgmpVersion,
         timeout: crate::time::Instant,
         group: Ipv4Address,
     },
@@ -0,0 +1,271 @@
+use heapless::Vec;
+
+use crate::config::IFACE_MAX_ADDRESS_POLICY_COUNT;
+use crate::wire::{IpAddress, Ipv6Address, Ipv6Cidr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PolicyTableFull;
+
+impl core::fmt::Display for PolicyTableFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Policy table full")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PolicyTableFull {}
+
+/// An entry of the policy table, see [RFC 6724 § 2.1].
+///
+/// [RFC 6724 § 2.1]: https://www.rfc-editor.org/rfc/rfc6724#section-2.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AddressPolicy {
+    pub prefix: Ipv6Cidr,
+    /// Destinations with a higher precedence are sorted first.
+    pub precedence: u8,
+    /// Source addresses with the same label as the destination are preferred.
+    pub label: u8,
+}
+
+impl AddressPolicy {
+    pub const fn new(prefix: Ipv6Cidr, precedence: u8, label: u8) -> AddressPolicy {
+        AddressPolicy {
+            prefix,
+            precedence,
+            label,
+        }
+    }
+}
+
+/// The policy table used for source and destination address selection, see [RFC 6724].
+///
+/// IPv4 addresses are looked up as IPv4-mapped IPv6 addresses. Addresses that do not
+/// match any entry have precedence 0 and label 0.
+///
+/// [RFC 6724]: https://www.rfc-editor.org/rfc/rfc6724
+#[derive(Debug, Clone)]
+pub struct PolicyTable {
+    storage: Vec<AddressPolicy, IFACE_MAX_ADDRESS_POLICY_COUNT>,
+}
+
+impl PolicyTable {
+    /// Creates a new empty policy table.
+    pub fn new() -> Self {
+        Self {
+            storage: Vec::new(),
+        }
+    }
+
+    /// Update the entries of the policy table.
+    pub fn update<F: FnOnce(&mut Vec<AddressPolicy, IFACE_MAX_ADDRESS_POLICY_COUNT>)>(
+        &mut self,
+        f: F,
+    ) {
+        f(&mut self.storage);
+    }
+
+    /// Add an entry to the policy table.
+    ///
+    /// On success, returns the previous entry for the same prefix, if any.
+    pub fn add_policy(
+        &mut self,
+        policy: AddressPolicy,
+    ) -> Result<Option<AddressPolicy>, PolicyTableFull> {
+        let old = self.remove_policy(&policy.prefix);
+        self.storage.push(policy).map_err(|_| PolicyTableFull)?;
+        Ok(old)
+    }
+
+    /// Remove the entry for `prefix` from the policy table.
+    ///
+    /// Returns the removed entry, if any.
+    pub fn remove_policy(&mut self, prefix: &Ipv6Cidr) -> Option<AddressPolicy> {
+        let index = self
+            .storage
+            .iter()
+            .position(|policy| policy.prefix == *prefix)?;
+        Some(self.storage.remove(index))
+    }
+
+    /// Return the entry with the longest prefix matching `addr`, if any.
+    pub fn lookup(&self, addr: &IpAddress) -> Option<&AddressPolicy> {
+        let addr = match addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(addr) => Ipv6Address::from(*addr),
+            IpAddress::Ipv6(addr) => *addr,
+        };
+        self.storage
+            .iter()
+            .filter(|policy| policy.prefix.contains_addr(&addr))
+            .max_by_key(|policy| policy.prefix.prefix_len())
+    }
+
+    pub(crate) fn precedence(&self, addr: &IpAddress) -> u8 {
+        self.lookup(addr).map_or(0, |policy| policy.precedence)
+    }
+
+    pub(crate) fn label(&self, addr: &IpAddress) -> u8 {
+        self.lookup(addr).map_or(0, |policy| policy.label)
+    }
+}
+
+impl Default for PolicyTable {
+    /// The default policy table, see [RFC 6724 § 2.1].
+    ///
+    /// Entries are dropped when `IFACE_MAX_ADDRESS_POLICY_COUNT` is too small to hold them.
+    ///
+    /// [RFC 6724 § 2.1]: https://www.rfc-editor.org/rfc/rfc6724#section-2.1
+    fn default() -> Self {
+        const fn policy(
+            a0: u16,
+            a1: u16,
+            a5: u16,
+            a7: u16,
+            prefix_len: u8,
+            precedence: u8,
+            label: u8,
+        ) -> AddressPolicy {
+            AddressPolicy::new(
+                Ipv6Cidr::new(Ipv6Address::new(a0, a1, 0, 0, 0, a5, 0, a7), prefix_len),
+                precedence,
+                label,
+            )
+        }
+
+        let mut table = Self::new();
+        for policy in [
+            policy(0, 0, 0, 1, 128, 50, 0),
+            policy(0, 0, 0, 0, 0, 40, 1),
+            policy(0, 0, 0xffff, 0, 96, 35, 4),
+            policy(0x2002, 0, 0, 0, 16, 30, 2),
+            policy(0x2001, 0, 0, 0, 32, 5, 5),
+            policy(0xfc00, 0, 0, 0, 7, 3, 13),
+            policy(0, 0, 0, 0, 96, 1, 3),
+            policy(0xfec0, 0, 0, 0, 10, 1, 11),
+            policy(0x3ffe, 0, 0, 0, 16, 1, 12),
+        ] {
+            if table.storage.push(policy).is_err() {
+                break;
+            }
+        }
+        table
+    }
+}
+
+/// Return the scope of an address, see [RFC 6724 § 3.1] and [RFC 6724 § 3.2].
+///
+/// [RFC 6724 § 3.1]: https://www.rfc-editor.org/rfc/rfc6724#section-3.1
+/// [RFC 6724 § 3.2]: https://www.rfc-editor.org/rfc/rfc6724#section-3.2
+pub(crate) fn scope(addr: &IpAddress) -> u8 {
+    const LINK_LOCAL: u8 = 0x2;
+    const SITE_LOCAL: u8 = 0x5;
+    const GLOBAL: u8 = 0xe;
+
+    match addr {
+        #[cfg(feature = "proto-ipv4")]
+        IpAddress::Ipv4(addr) => {
+            if addr.is_loopback() || addr.is_link_local() {
+                LINK_LOCAL
+            } else {
+                GLOBAL
+            }
+        }
+        IpAddress::Ipv6(addr) => {
+            if addr.is_multicast() {
+                addr.as_bytes()[1] & 0x0f
+            } else if addr.is_loopback() || addr.is_link_local() {
+                LINK_LOCAL
+            } else if addr.as_bytes()[0] == 0xfe && addr.as_bytes()[1] & 0xc0 == 0xc0 {
+                // Deprecated site-local addresses, fec0::/10.
+                SITE_LOCAL
+            } else {
+                GLOBAL
+            }
+        }
+    }
+}
+
+/// Return the length of the longest prefix that `a` and `b` have in common, capped at
+/// `max_len`, see [RFC 6724 § 2.2].
+///
+/// [RFC 6724 § 2.2]: https://www.rfc-editor.org/rfc/rfc6724#section-2.2
+pub(crate) fn common_prefix_len(a: &IpAddress, b: &IpAddress, max_len: u8) -> u8 {
+    let mut bits = 0;
+    for (l, r) in a.as_bytes().iter().zip(b.as_bytes().iter()) {
+        if l == r {
+            bits += 8;
+        } else {
+            bits += (l ^ r).leading_zeros() as u8;
+            break;
+        }
+    }
+    bits.min(max_len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_lookup() {
+        let table = PolicyTable::default();
+
+        let check = |addr: Ipv6Address, precedence, label| {
+            let addr = IpAddress::Ipv6(addr);
+            assert_eq!(table.precedence(&addr), precedence);
+            assert_eq!(table.label(&addr), label);
+        };
+        check(Ipv6Address::LOOPBACK, 50, 0);
+        check(Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 40, 1);
+        check(
+            Ipv6Address::new(0x2002, 0xc000, 0x0201, 0, 0, 0, 0, 1),
+            30,
+            2,
+        );
+        check(Ipv6Address::new(0x2001, 0, 0, 0, 0, 0, 0, 1), 5, 5);
+        check(Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1), 3, 13);
+        check(Ipv6Address::new(0xfec0, 0, 0, 0, 0, 0, 0, 1), 1, 11);
+
+        #[cfg(feature = "proto-ipv4")]
+        {
+            let addr = IpAddress::v4(192, 0, 2, 1);
+            assert_eq!(table.precedence(&addr), 35);
+            assert_eq!(table.label(&addr), 4);
+        }
+    }
+
+    #[test]
+    fn test_add_remove() {
+        let mut table = PolicyTable::default();
+        let prefix = Ipv6Cidr::new(Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 0), 8);
+        let addr = IpAddress::v6(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+
+        assert_eq!(
+            table.add_policy(AddressPolicy::new(prefix, 45, 14)),
+            Ok(None)
+        );
+        assert_eq!(table.precedence(&addr), 45);
+        assert_eq!(table.label(&addr), 14);
+
+        assert_eq!(
+            table.remove_policy(&prefix),
+            Some(AddressPolicy::new(prefix, 45, 14))
+        );
+        assert_eq!(table.precedence(&addr), 3);
+    }
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(scope(&IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 1)), 0x2);
+        assert_eq!(scope(&IpAddress::v6(0xfec0, 0, 0, 0, 0, 0, 0, 1)), 0x5);
+        assert_eq!(scope(&IpAddress::v6(0xfd00, 0, 0, 0, 0, 0, 0, 1)), 0xe);
+        assert_eq!(scope(&IpAddress::v6(0xff05, 0, 0, 0, 0, 0, 0, 1)), 0x5);
+        #[cfg(feature = "proto-ipv4")]
+        {
+            assert_eq!(scope(&IpAddress::v4(169, 254, 1, 1)), 0x2);
+            assert_eq!(scope(&IpAddress::v4(192, 0, 2, 1)), 0xe);
+        }
+    }
+}
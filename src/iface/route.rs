@@ -1,8 +1,9 @@
 use heapless::Vec;
 
-use crate::config::IFACE_MAX_ROUTE_COUNT;
+use crate::config::{IFACE_MAX_ROUTE_COUNT, IFACE_MAX_ROUTE_RULE_COUNT};
+use crate::iface::SocketHandle;
 use crate::time::Instant;
-use crate::wire::{IpAddress, IpCidr};
+use crate::wire::{IpAddress, IpCidr, IpProtocol};
 #[cfg(feature = "proto-ipv4")]
 use crate::wire::{Ipv4Address, Ipv4Cidr};
 #[cfg(feature = "proto-ipv6")]
@@ -21,6 +22,22 @@ impl core::fmt::Display for RouteTableFull {
 #[cfg(feature = "std")]
 impl std::error::Error for RouteTableFull {}
 
+/// What to do with packets that match a route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RouteKind {
+    /// Send packets to the `via_router` of the route.
+    Gateway,
+    /// Send packets directly to their destination, which is on the link
+    /// (ie. "ip route add `cidr` dev `iface`"). `via_router` is ignored.
+    OnLink,
+    /// Silently drop packets. `via_router` is ignored.
+    Blackhole,
+    /// Drop packets, and report the destination as unreachable with an ICMP error
+    /// when forwarding. `via_router` is ignored.
+    Unreachable,
+}
+
 /// A prefix of addresses that should be routed via a router
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -31,6 +48,95 @@ pub struct Route {
     pub preferred_until: Option<Instant>,
     /// `None` means "forever".
     pub expires_at: Option<Instant>,
+    /// Among the routes for the same prefix, the ones with the lowest metric are used.
+    /// When several routes share the lowest metric, packets are spread over them.
+    pub metric: u32,
+    pub kind: RouteKind,
+    /// The route table the route belongs to. Routes outside of the
+    /// [`MAIN_TABLE`](Routes::MAIN_TABLE) are only used for packets selected
+    /// by a [`RouteRule`].
+    pub table: u8,
+}
+
+/// A routing rule, selecting the route table used for some outgoing packets
+/// (ie. "ip rule add from `src` lookup `table`").
+///
+/// A rule matches a packet when all of its selectors match. Rules are tried in order
+/// of priority, and the route is looked up in the table of each matching rule until a
+/// route is found. The [`MAIN_TABLE`](Routes::MAIN_TABLE) is used when no rule yields
+/// a route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RouteRule {
+    /// Rules with a lower priority are tried first.
+    pub priority: u32,
+    /// Match packets with a source address in this prefix.
+    pub src: Option<IpCidr>,
+    /// Match packets with this mark in their [`PacketMeta`](crate::phy::PacketMeta).
+    #[cfg(feature = "packetmeta-mark")]
+    pub mark: Option<u32>,
+    /// Match packets sent by this socket.
+    pub socket: Option<SocketHandle>,
+    /// The route table to look the route up in.
+    pub table: u8,
+}
+
+impl RouteRule {
+    /// Returns a rule using `table` for all packets.
+    pub fn new(priority: u32, table: u8) -> RouteRule {
+        RouteRule {
+            priority,
+            src: None,
+            #[cfg(feature = "packetmeta-mark")]
+            mark: None,
+            socket: None,
+            table,
+        }
+    }
+
+    fn matches(&self, src_addr: Option<&IpAddress>, key: &RuleKey) -> bool {
+        if let Some(src) = self.src {
+            if !src_addr.map_or(false, |addr| src.contains_addr(addr)) {
+                return false;
+            }
+        }
+        #[cfg(feature = "packetmeta-mark")]
+        if let Some(mark) = self.mark {
+            if key.mark != mark {
+                return false;
+            }
+        }
+        if let Some(socket) = self.socket {
+            if key.socket != Some(socket) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The properties of an outgoing packet, other than its addresses, that routing
+/// rules match, or that tell its flow apart from the others.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RuleKey {
+    #[cfg(feature = "packetmeta-mark")]
+    pub(crate) mark: u32,
+    pub(crate) socket: Option<SocketHandle>,
+    pub(crate) protocol: Option<IpProtocol>,
+    /// The source and destination ports, for TCP, UDP and UDP-Lite packets.
+    pub(crate) ports: Option<(u16, u16)>,
+}
+
+/// Return the source and destination ports at the start of the `payload` of a TCP, UDP
+/// or UDP-Lite packet.
+pub(crate) fn transport_ports(protocol: IpProtocol, payload: &[u8]) -> Option<(u16, u16)> {
+    match protocol {
+        IpProtocol::Tcp | IpProtocol::Udp | IpProtocol::UdpLite if payload.len() >= 4 => Some((
+            u16::from_be_bytes([payload[0], payload[1]]),
+            u16::from_be_bytes([payload[2], payload[3]]),
+        )),
+        _ => None,
+    }
 }
 
 #[cfg(feature = "proto-ipv4")]
@@ -40,40 +146,67 @@ const IPV6_DEFAULT: IpCidr =
     IpCidr::Ipv6(Ipv6Cidr::new(Ipv6Address::new(0, 0, 0, 0, 0, 0, 0, 0), 0));
 
 impl Route {
-    /// Returns a route to 0.0.0.0/0 via the `gateway`, with no expiry.
-    #[cfg(feature = "proto-ipv4")]
-    pub fn new_ipv4_gateway(gateway: Ipv4Address) -> Route {
+    /// Returns a route to `cidr` via the `gateway`, with no expiry.
+    pub fn new_gateway(cidr: IpCidr, gateway: IpAddress) -> Route {
         Route {
-            cidr: IPV4_DEFAULT,
-            via_router: gateway.into(),
+            cidr,
+            via_router: gateway,
             preferred_until: None,
             expires_at: None,
+            metric: 0,
+            kind: RouteKind::Gateway,
+            table: Routes::MAIN_TABLE,
         }
     }
 
+    /// Returns a route to 0.0.0.0/0 via the `gateway`, with no expiry.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn new_ipv4_gateway(gateway: Ipv4Address) -> Route {
+        Route::new_gateway(IPV4_DEFAULT, gateway.into())
+    }
+
     /// Returns a route to ::/0 via the `gateway`, with no expiry.
     #[cfg(feature = "proto-ipv6")]
     pub fn new_ipv6_gateway(gateway: Ipv6Address) -> Route {
+        Route::new_gateway(IPV6_DEFAULT, gateway.into())
+    }
+
+    /// Returns a route of the given kind to `cidr`, without a gateway and with no expiry.
+    pub fn new_without_gateway(cidr: IpCidr, kind: RouteKind) -> Route {
         Route {
-            cidr: IPV6_DEFAULT,
-            via_router: gateway.into(),
-            preferred_until: None,
-            expires_at: None,
+            via_router: cidr.address(),
+            kind,
+            ..Route::new_gateway(cidr, cidr.address())
+        }
+    }
+
+    /// Returns the address packets for `dst_addr` are sent to when using this route,
+    /// or `None` if they are dropped.
+    pub fn next_hop(&self, dst_addr: &IpAddress) -> Option<IpAddress> {
+        match self.kind {
+            RouteKind::Gateway => Some(self.via_router),
+            RouteKind::OnLink => Some(*dst_addr),
+            RouteKind::Blackhole | RouteKind::Unreachable => None,
         }
     }
 }
 
 /// A routing table.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Routes {
     storage: Vec<Route, IFACE_MAX_ROUTE_COUNT>,
+    rules: Vec<RouteRule, IFACE_MAX_ROUTE_RULE_COUNT>,
 }
 
 impl Routes {
+    /// The route table used for all packets, after the tables selected by routing rules.
+    pub const MAIN_TABLE: u8 = 254;
+
     /// Creates a new empty routing table.
     pub fn new() -> Self {
         Self {
             storage: Vec::new(),
+            rules: Vec::new(),
         }
     }
 
@@ -82,7 +215,21 @@ impl Routes {
         f(&mut self.storage);
     }
 
-    /// Add a default ipv4 gateway (ie. "ip route add 0.0.0.0/0 via `gateway`").
+    /// Update the routing rules of this node.
+    pub fn update_rules<F: FnOnce(&mut Vec<RouteRule, IFACE_MAX_ROUTE_RULE_COUNT>)>(
+        &mut self,
+        f: F,
+    ) {
+        f(&mut self.rules);
+    }
+
+    /// Add a routing rule.
+    pub fn add_rule(&mut self, rule: RouteRule) -> Result<(), RouteTableFull> {
+        self.rules.push(rule).map_err(|_| RouteTableFull)
+    }
+
+    /// Add a default ipv4 gateway (ie. "ip route add 0.0.0.0/0 via `gateway`") to the
+    /// main table.
     ///
     /// On success, returns the previous default route, if any.
     #[cfg(feature = "proto-ipv4")]
@@ -97,7 +244,8 @@ impl Routes {
         Ok(old)
     }
 
-    /// Add a default ipv6 gateway (ie. "ip -6 route add ::/0 via `gateway`").
+    /// Add a default ipv6 gateway (ie. "ip -6 route add ::/0 via `gateway`") to the
+    /// main table.
     ///
     /// On success, returns the previous default route, if any.
     #[cfg(feature = "proto-ipv6")]
@@ -121,7 +269,7 @@ impl Routes {
             .storage
             .iter()
             .enumerate()
-            .find(|(_, r)| r.cidr == IPV4_DEFAULT)
+            .find(|(_, r)| r.cidr == IPV4_DEFAULT && r.table == Self::MAIN_TABLE)
         {
             Some(self.storage.remove(i))
         } else {
@@ -138,7 +286,7 @@ impl Routes {
             .storage
             .iter()
             .enumerate()
-            .find(|(_, r)| r.cidr == IPV6_DEFAULT)
+            .find(|(_, r)| r.cidr == IPV6_DEFAULT && r.table == Self::MAIN_TABLE)
         {
             Some(self.storage.remove(i))
         } else {
@@ -146,26 +294,135 @@ impl Routes {
         }
     }
 
-    pub(crate) fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
-        assert!(addr.is_unicast());
+    /// Add a route.
+    ///
+    /// Several routes may be added for the same prefix, to spread packets over them
+    /// (equal-cost multipath), or to keep backup routes with a higher metric.
+    pub fn add_route(&mut self, route: Route) -> Result<(), RouteTableFull> {
+        self.storage.push(route).map_err(|_| RouteTableFull)
+    }
 
-        self.storage
-            .iter()
-            // Keep only matching routes
-            .filter(|route| {
+    /// Return the route used for packets from `src_addr` to `dst_addr`, if any.
+    ///
+    /// The tables selected by the routing rules matching the source address are tried
+    /// first, then the main table. In a table, the most specific route with the lowest
+    /// metric is used. When several routes are equally good, one of them is picked by
+    /// hashing the source and destination addresses, so that the packets between two
+    /// hosts all take the same path. The interface also hashes the protocol and the
+    /// ports of the packets it sends, spreading the flows between two hosts.
+    ///
+    /// # Panics
+    /// This function panics if `dst_addr` is not unicast.
+    pub fn lookup_route(
+        &self,
+        dst_addr: &IpAddress,
+        src_addr: Option<&IpAddress>,
+        timestamp: Instant,
+    ) -> Option<&Route> {
+        self.lookup_route_with(dst_addr, src_addr, &RuleKey::default(), timestamp)
+    }
+
+    /// Return the route used for packets from `src_addr` to `dst_addr`, with the
+    /// properties in `key`, if any.
+    pub(crate) fn lookup_route_with(
+        &self,
+        dst_addr: &IpAddress,
+        src_addr: Option<&IpAddress>,
+        key: &RuleKey,
+        timestamp: Instant,
+    ) -> Option<&Route> {
+        assert!(dst_addr.is_unicast());
+
+        let mut last_priority = None;
+        loop {
+            // Try the rules in order of priority, without sorting them in place so that
+            // their order stays the one set by the user.
+            let rule = self
+                .rules
+                .iter()
+                .enumerate()
+                .filter(|(i, rule)| {
+                    last_priority.map_or(true, |last| (rule.priority, *i) > last)
+                        && rule.matches(src_addr, key)
+                })
+                .min_by_key(|(i, rule)| (rule.priority, *i));
+            let (i, rule) = match rule {
+                Some(rule) => rule,
+                None => break,
+            };
+            last_priority = Some((rule.priority, i));
+
+            if let Some(route) = self.lookup_table(rule.table, dst_addr, src_addr, key, timestamp) {
+                return Some(route);
+            }
+        }
+
+        self.lookup_table(Self::MAIN_TABLE, dst_addr, src_addr, key, timestamp)
+    }
+
+    fn lookup_table(
+        &self,
+        table: u8,
+        dst_addr: &IpAddress,
+        src_addr: Option<&IpAddress>,
+        key: &RuleKey,
+        timestamp: Instant,
+    ) -> Option<&Route> {
+        let matching = || {
+            self.storage.iter().filter(move |route| {
                 if let Some(expires_at) = route.expires_at {
                     if timestamp > expires_at {
                         return false;
                     }
                 }
-                route.cidr.contains_addr(addr)
+                route.table == table && route.cidr.contains_addr(dst_addr)
             })
-            // pick the most specific one (highest prefix_len)
-            .max_by_key(|route| route.cidr.prefix_len())
-            .map(|route| route.via_router)
+        };
+
+        // Pick the most specific routes (highest prefix_len), then the preferred ones
+        // among them (lowest metric).
+        let best = matching()
+            .map(|route| (route.cidr.prefix_len(), core::cmp::Reverse(route.metric)))
+            .max()?;
+        let equal_cost = || {
+            matching().filter(move |route| {
+                (route.cidr.prefix_len(), core::cmp::Reverse(route.metric)) == best
+            })
+        };
+
+        let count = equal_cost().count();
+        let index = if count > 1 {
+            flow_hash(src_addr, dst_addr, key) as usize % count
+        } else {
+            0
+        };
+        equal_cost().nth(index)
+    }
+
+    #[cfg(any(feature = "proto-ipv4", test))]
+    pub(crate) fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
+        self.lookup_route(addr, None, timestamp)?.next_hop(addr)
     }
 }
 
+/// Hash the addresses, protocol and ports of a flow to pick one of several equal-cost
+/// routes, like the layer 4 multipath hash policy of Linux.
+fn flow_hash(src_addr: Option<&IpAddress>, dst_addr: &IpAddress, key: &RuleKey) -> u32 {
+    let src_bytes = src_addr.map(|addr| addr.as_bytes()).unwrap_or(&[]);
+    let protocol = key.protocol.map(u8::from);
+    let (src_port, dst_port) = key.ports.unwrap_or((0, 0));
+    // 32-bit FNV-1a.
+    src_bytes
+        .iter()
+        .chain(dst_addr.as_bytes())
+        .chain(protocol.as_ref())
+        .chain(&src_port.to_be_bytes())
+        .chain(&dst_port.to_be_bytes())
+        .fold(0x811c9dc5, |hash, byte| {
+            (hash ^ *byte as u32).wrapping_mul(0x01000193)
+        })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -246,6 +503,9 @@ mod test {
             via_router: ADDR_1A.into(),
             preferred_until: None,
             expires_at: None,
+            metric: 0,
+            kind: RouteKind::Gateway,
+            table: Routes::MAIN_TABLE,
         };
         routes.update(|storage| {
             storage.push(route).unwrap();
@@ -277,6 +537,9 @@ mod test {
             via_router: ADDR_2A.into(),
             preferred_until: Some(Instant::from_millis(10)),
             expires_at: Some(Instant::from_millis(10)),
+            metric: 0,
+            kind: RouteKind::Gateway,
+            table: Routes::MAIN_TABLE,
         };
         routes.update(|storage| {
             storage.push(route2).unwrap();
@@ -324,4 +587,207 @@ mod test {
             Some(ADDR_2A.into())
         );
     }
+
+    #[test]
+    fn test_metric() {
+        let mut routes = Routes::new();
+        let now = Instant::from_millis(0);
+
+        routes
+            .add_route(Route {
+                metric: 10,
+                ..Route::new_gateway(cidr_1().into(), ADDR_1A.into())
+            })
+            .unwrap();
+        routes
+            .add_route(Route {
+                metric: 5,
+                ..Route::new_gateway(cidr_1().into(), ADDR_1B.into())
+            })
+            .unwrap();
+        assert_eq!(routes.lookup(&ADDR_1C.into(), now), Some(ADDR_1B.into()));
+
+        // A more specific route wins, whatever its metric.
+        let host_len = ADDR_1C.as_bytes().len() as u8 * 8;
+        let host = Route::new_gateway(IpCidr::new(ADDR_1C.into(), host_len), ADDR_1A.into());
+        routes
+            .add_route(Route {
+                metric: 100,
+                ..host
+            })
+            .unwrap();
+        assert_eq!(routes.lookup(&ADDR_1C.into(), now), Some(ADDR_1A.into()));
+        assert_eq!(routes.lookup(&ADDR_1B.into(), now), Some(ADDR_1B.into()));
+    }
+
+    #[test]
+    fn test_ecmp() {
+        let mut routes = Routes::new();
+        let now = Instant::from_millis(0);
+
+        routes
+            .add_route(Route::new_gateway(cidr_2().into(), ADDR_1A.into()))
+            .unwrap();
+        routes
+            .add_route(Route::new_gateway(cidr_2().into(), ADDR_1B.into()))
+            .unwrap();
+
+        let next_hop = |src_addr: IpAddress| {
+            routes
+                .lookup_route(&ADDR_2A.into(), Some(&src_addr), now)
+                .unwrap()
+                .via_router
+        };
+
+        // Packets of a flow always take the same path, and flows are spread over both.
+        let srcs = [ADDR_1A, ADDR_1B, ADDR_1C, ADDR_2B];
+        for src in srcs {
+            assert_eq!(next_hop(src.into()), next_hop(src.into()));
+        }
+        for via in [ADDR_1A, ADDR_1B] {
+            assert!(srcs.iter().any(|src| next_hop((*src).into()) == via.into()));
+        }
+
+        // The flows between two hosts are spread by their protocol and ports.
+        let next_hop = |protocol, ports| {
+            let key = RuleKey {
+                protocol: Some(protocol),
+                ports,
+                ..RuleKey::default()
+            };
+            routes
+                .lookup_route_with(&ADDR_2A.into(), Some(&ADDR_1C.into()), &key, now)
+                .unwrap()
+                .via_router
+        };
+        for via in [ADDR_1A, ADDR_1B] {
+            assert!((49152..49160)
+                .any(|port| next_hop(IpProtocol::Tcp, Some((port, 80))) == via.into()));
+        }
+        let payload = [0xc0, 0x00, 0x00, 0x50, 0x00, 0x00];
+        assert_eq!(
+            transport_ports(IpProtocol::Udp, &payload),
+            Some((49152, 80))
+        );
+        assert_eq!(transport_ports(IpProtocol::Icmpv6, &payload), None);
+        assert_eq!(transport_ports(IpProtocol::Tcp, &payload[..3]), None);
+    }
+
+    #[test]
+    fn test_kind() {
+        let mut routes = Routes::new();
+        let now = Instant::from_millis(0);
+
+        routes
+            .add_route(Route::new_without_gateway(
+                cidr_1().into(),
+                RouteKind::OnLink,
+            ))
+            .unwrap();
+        assert_eq!(routes.lookup(&ADDR_1C.into(), now), Some(ADDR_1C.into()));
+
+        for kind in [RouteKind::Blackhole, RouteKind::Unreachable] {
+            routes.update(|storage| storage.clear());
+            routes
+                .add_route(Route::new_without_gateway(cidr_2().into(), kind))
+                .unwrap();
+            assert_eq!(routes.lookup(&ADDR_2A.into(), now), None);
+            assert_eq!(
+                routes
+                    .lookup_route(&ADDR_2A.into(), None, now)
+                    .map(|route| route.kind),
+                Some(kind)
+            );
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut routes = Routes::new();
+        let now = Instant::from_millis(0);
+
+        routes
+            .add_route(Route::new_gateway(cidr_2().into(), ADDR_1A.into()))
+            .unwrap();
+        routes
+            .add_route(Route {
+                table: 1,
+                ..Route::new_gateway(cidr_2().into(), ADDR_1B.into())
+            })
+            .unwrap();
+        routes
+            .add_route(Route {
+                table: 2,
+                ..Route::new_gateway(cidr_2().into(), ADDR_1C.into())
+            })
+            .unwrap();
+
+        let next_hop = |routes: &Routes, src_addr: IpAddress, key: &RuleKey| {
+            routes
+                .lookup_route_with(&ADDR_2A.into(), Some(&src_addr), key, now)
+                .unwrap()
+                .via_router
+        };
+        let key = RuleKey::default();
+
+        // Without rules, only the main table is used.
+        assert_eq!(next_hop(&routes, ADDR_1B.into(), &key), ADDR_1A.into());
+
+        // Packets from ADDR_2B use table 1.
+        let host_len = ADDR_2B.as_bytes().len() as u8 * 8;
+        routes
+            .add_rule(RouteRule {
+                src: Some(IpCidr::new(ADDR_2B.into(), host_len)),
+                ..RouteRule::new(10, 1)
+            })
+            .unwrap();
+        assert_eq!(next_hop(&routes, ADDR_2B.into(), &key), ADDR_1B.into());
+        assert_eq!(next_hop(&routes, ADDR_1B.into(), &key), ADDR_1A.into());
+
+        // Rules with a lower priority win.
+        routes.add_rule(RouteRule::new(5, 2)).unwrap();
+        assert_eq!(next_hop(&routes, ADDR_2B.into(), &key), ADDR_1C.into());
+        routes.update_rules(|rules| {
+            rules.pop();
+        });
+
+        // Tables without a route for the destination are skipped.
+        routes.add_rule(RouteRule::new(1, 3)).unwrap();
+        assert_eq!(next_hop(&routes, ADDR_2B.into(), &key), ADDR_1B.into());
+        routes.update_rules(|rules| rules.clear());
+
+        // Packets sent by a socket.
+        let socket = SocketHandle::default();
+        routes
+            .add_rule(RouteRule {
+                socket: Some(socket),
+                ..RouteRule::new(10, 1)
+            })
+            .unwrap();
+        assert_eq!(next_hop(&routes, ADDR_1B.into(), &key), ADDR_1A.into());
+        let socket_key = RuleKey {
+            socket: Some(socket),
+            ..RuleKey::default()
+        };
+        assert_eq!(
+            next_hop(&routes, ADDR_1B.into(), &socket_key),
+            ADDR_1B.into()
+        );
+
+        // Packets with a mark.
+        #[cfg(feature = "packetmeta-mark")]
+        {
+            routes
+                .add_rule(RouteRule {
+                    mark: Some(7),
+                    ..RouteRule::new(10, 2)
+                })
+                .unwrap();
+            let mark_key = RuleKey {
+                mark: 7,
+                ..RuleKey::default()
+            };
+            assert_eq!(next_hop(&routes, ADDR_1B.into(), &mark_key), ADDR_1C.into());
+        }
+    }
 }
@@ -0,0 +1,268 @@
+use heapless::Vec;
+
+use super::route::{RouteKind, RouteTableFull, Routes};
+use super::Interface;
+use crate::config::{IFACE_MAX_ADDR_COUNT, ROUTER_MAX_IFACE_COUNT, ROUTER_MAX_ROUTE_COUNT};
+use crate::storage;
+use crate::time::Instant;
+use crate::wire::{IpAddress, IpCidr};
+
+/// Metadata of a packet waiting to be forwarded: the time it was queued.
+pub type PacketMetadata = storage::PacketMetadata<Instant>;
+
+/// Queue of packets waiting to be forwarded out of an interface.
+pub type PacketBuffer<'a> = storage::PacketBuffer<'a, Instant>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RouterFull;
+
+impl core::fmt::Display for RouterFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Router interface table full")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RouterFull {}
+
+/// Identifier of an interface attached to a [`Router`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterfaceId(usize);
+
+/// The state of an interface attached to a router.
+#[derive(Debug)]
+pub(crate) struct Port<'a> {
+    /// The addresses of the interface, which make up its connected routes.
+    ip_addrs: Vec<IpCidr, IFACE_MAX_ADDR_COUNT>,
+    /// The largest IP packet that can be sent out of the interface.
+    ip_mtu: usize,
+    /// The routes of the interface, which may drop forwarded packets.
+    routes: Routes,
+    /// Packets waiting to be sent out of the interface.
+    pub(crate) queue: PacketBuffer<'a>,
+    /// When to retry sending the packets of the queue whose next hop was not
+    /// yet discovered.
+    pub(crate) retry_at: Instant,
+    /// How many packets were moved to the tail of the queue to wait for their next
+    /// hop since a packet was last sent, dropped or queued.
+    pub(crate) parked: usize,
+}
+
+impl<'a> Port<'a> {
+    /// Move the packet at the head of the queue to its tail, so that it waits for its
+    /// next hop without holding up the packets behind it. Returns `false` if every
+    /// packet of the queue is waiting, or if there is no room to move the packet.
+    pub(crate) fn park(&mut self) -> bool {
+        if self.parked >= self.queue.packet_count() || self.queue.requeue().is_err() {
+            return false;
+        }
+        self.parked += 1;
+        true
+    }
+}
+
+/// A router, forwarding IP packets between several interfaces.
+///
+/// Interfaces are attached with [`add_interface`](Self::add_interface), and must then be
+/// polled with [`Interface::poll_router`] instead of [`Interface::poll`]. Packets that are
+/// received on an attached interface but are not addressed to it are forwarded out of the
+/// interface selected by the routing table of the router.
+///
+/// The routing table of the router picks the outgoing interface. The addresses of each
+/// interface are connected routes, and other prefixes are routed with
+/// [`add_route`](Self::add_route). The next hop on the outgoing interface is then picked
+/// as usual, from the routes of that interface (see [`Interface::routes_mut`]).
+#[derive(Debug)]
+pub struct Router<'a> {
+    ports: Vec<Port<'a>, ROUTER_MAX_IFACE_COUNT>,
+    routes: Vec<(IpCidr, InterfaceId), ROUTER_MAX_ROUTE_COUNT>,
+}
+
+impl<'a> Router<'a> {
+    /// Create a router without interfaces.
+    pub fn new() -> Self {
+        Self {
+            ports: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+
+    /// Attach an interface to the router, enabling forwarding on it.
+    ///
+    /// Packets forwarded out of the interface wait in `queue` until they are sent by
+    /// [`Interface::poll_router`].
+    pub fn add_interface(
+        &mut self,
+        iface: &mut Interface,
+        queue: PacketBuffer<'a>,
+    ) -> Result<InterfaceId, RouterFull> {
+        let id = InterfaceId(self.ports.len());
+        self.ports
+            .push(Port {
+                ip_addrs: Vec::new(),
+                ip_mtu: 0,
+                routes: Routes::new(),
+                queue,
+                retry_at: Instant::ZERO,
+                parked: 0,
+            })
+            .map_err(|_| RouterFull)?;
+
+        iface.inner.set_iface_id(Some(id));
+        self.update_interface(id, iface);
+        Ok(id)
+    }
+
+    /// Route packets for the `cidr` prefix out of the `iface` interface.
+    ///
+    /// On success, returns the interface that was previously used for the prefix, if any.
+    pub fn add_route(
+        &mut self,
+        cidr: IpCidr,
+        iface: InterfaceId,
+    ) -> Result<Option<InterfaceId>, RouteTableFull> {
+        let old = self.remove_route(cidr);
+        self.routes
+            .push((cidr, iface))
+            .map_err(|_| RouteTableFull)?;
+        Ok(old)
+    }
+
+    /// Remove the route for the `cidr` prefix.
+    ///
+    /// Returns the interface that was used for the prefix, if any.
+    pub fn remove_route(&mut self, cidr: IpCidr) -> Option<InterfaceId> {
+        let index = self.routes.iter().position(|(c, _)| *c == cidr)?;
+        Some(self.routes.remove(index).1)
+    }
+
+    /// Return the interface packets for `addr` are forwarded out of, if any.
+    ///
+    /// The most specific route is used. Connected routes are preferred over other
+    /// routes of the same length.
+    pub fn lookup(&self, addr: &IpAddress) -> Option<InterfaceId> {
+        let connected = self.ports.iter().enumerate().flat_map(|(i, port)| {
+            port.ip_addrs
+                .iter()
+                .map(move |cidr| (*cidr, InterfaceId(i)))
+        });
+
+        self.routes
+            .iter()
+            .copied()
+            .chain(connected)
+            .filter(|(cidr, _)| cidr.contains_addr(addr))
+            .max_by_key(|(cidr, _)| cidr.prefix_len())
+            .map(|(_, iface)| iface)
+    }
+
+    /// Return a _soft deadline_ for polling the attached interfaces the next time,
+    /// because packets are waiting to be forwarded.
+    pub fn poll_at(&self) -> Option<Instant> {
+        self.ports
+            .iter()
+            .filter(|port| !port.queue.is_empty())
+            .map(|port| port.retry_at)
+            .min()
+    }
+
+    /// Update the connected routes and the MTU of an attached interface.
+    pub(crate) fn update_interface(&mut self, id: InterfaceId, iface: &Interface) {
+        let port = &mut self.ports[id.0];
+        port.ip_addrs.clear();
+        port.ip_addrs.extend(iface.ip_addrs().iter().copied());
+        port.ip_mtu = iface.inner.forwarding_mtu();
+        port.routes = iface.routes().clone();
+    }
+
+    /// Return the kind of the route of an attached interface that drops packets from
+    /// `src_addr` to `dst_addr`, if any.
+    pub(crate) fn dropping_route(
+        &self,
+        id: InterfaceId,
+        src_addr: &IpAddress,
+        dst_addr: &IpAddress,
+        timestamp: Instant,
+    ) -> Option<RouteKind> {
+        let port = &self.ports[id.0];
+        if port
+            .ip_addrs
+            .iter()
+            .any(|cidr| cidr.contains_addr(dst_addr))
+        {
+            return None;
+        }
+
+        let route = port
+            .routes
+            .lookup_route(dst_addr, Some(src_addr), timestamp)?;
+        route.next_hop(dst_addr).is_none().then_some(route.kind)
+    }
+
+    /// Return the largest packet that can be forwarded out of the interface.
+    pub(crate) fn ip_mtu(&self, id: InterfaceId) -> usize {
+        self.ports[id.0].ip_mtu
+    }
+
+    pub(crate) fn port_mut(&mut self, id: InterfaceId) -> &mut Port<'a> {
+        &mut self.ports[id.0]
+    }
+}
+
+impl<'a> Default for Router<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "proto-ipv4", feature = "medium-ip", feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::iface::Config;
+    use crate::phy::{Loopback, Medium};
+    use crate::wire::{HardwareAddress, Ipv4Address, Ipv4Cidr};
+    use std::vec;
+
+    fn iface(cidr: IpCidr) -> Interface {
+        let mut device = Loopback::new(Medium::Ip);
+        let mut iface =
+            Interface::new(Config::new(HardwareAddress::Ip), &mut device, Instant::ZERO);
+        iface.update_ip_addrs(|addrs| addrs.push(cidr).unwrap());
+        iface
+    }
+
+    fn queue() -> PacketBuffer<'static> {
+        PacketBuffer::new(vec![PacketMetadata::EMPTY; 1], vec![0; 64])
+    }
+
+    #[test]
+    fn test_lookup() {
+        let cidr_a = IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::new(192, 168, 1, 1), 24));
+        let cidr_b = IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 1), 8));
+
+        let mut router = Router::new();
+        let a = router.add_interface(&mut iface(cidr_a), queue()).unwrap();
+        let b = router.add_interface(&mut iface(cidr_b), queue()).unwrap();
+
+        let addr = |a, b, c, d| IpAddress::Ipv4(Ipv4Address::new(a, b, c, d));
+        assert_eq!(router.lookup(&addr(192, 168, 1, 20)), Some(a));
+        assert_eq!(router.lookup(&addr(10, 1, 2, 3)), Some(b));
+        assert_eq!(router.lookup(&addr(172, 16, 0, 1)), None);
+
+        // The default route.
+        let default = IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0));
+        assert_eq!(router.add_route(default, b), Ok(None));
+        assert_eq!(router.lookup(&addr(172, 16, 0, 1)), Some(b));
+
+        // A more specific route wins over a connected route.
+        let specific = IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::new(10, 1, 0, 0), 16));
+        assert_eq!(router.add_route(specific, a), Ok(None));
+        assert_eq!(router.lookup(&addr(10, 1, 2, 3)), Some(a));
+        assert_eq!(router.lookup(&addr(10, 2, 2, 3)), Some(b));
+
+        assert_eq!(router.remove_route(specific), Some(a));
+        assert_eq!(router.lookup(&addr(10, 1, 2, 3)), Some(b));
+    }
+}
@@ -0,0 +1,373 @@
+use crate::config::IFACE_EGRESS_CLASS_COUNT;
+use crate::time::{Duration, Instant};
+
+/// Token buckets count octets in millionths, so that refilling them for any amount of
+/// microseconds is exact.
+const TOKEN_SCALE: i64 = 1_000_000;
+
+/// Scale of the virtual time of the weighted fair queuing, so that small weights still
+/// make a difference for small packets.
+const VIRTUAL_TIME_SCALE: u64 = 1 << 16;
+
+/// How the egress scheduler picks the traffic class to serve next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SchedulingMode {
+    /// Sockets of a class only send when every class with a lower number has nothing
+    /// to send.
+    StrictPriority,
+    /// Classes share the link in proportion to their weight, counted in octets.
+    WeightedFair,
+}
+
+/// A token bucket rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RateLimit {
+    /// Sustained rate, in octets per second. A rate of 0 never refills the bucket: the
+    /// class is disabled once its burst is spent, until its rate limit is set again.
+    pub rate: u32,
+    /// Amount of octets that can be sent in a burst after the class was idle. A burst
+    /// smaller than a packet, including zero, lets one packet through at a time.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Create a rate limit of `rate` octets per second, with bursts of `burst` octets.
+    pub const fn new(rate: u32, burst: u32) -> RateLimit {
+        RateLimit { rate, burst }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct TokenBucket {
+    limit: RateLimit,
+    /// Available octets, scaled by `TOKEN_SCALE`. A class may send while this is
+    /// positive, and its last packet can take it below zero.
+    tokens: i64,
+    refilled_at: Option<Instant>,
+}
+
+impl TokenBucket {
+    const fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: Self::capacity(limit),
+            refilled_at: None,
+        }
+    }
+
+    /// Return the most tokens the bucket holds. It is never empty when full, or a class
+    /// with no burst could not send at all.
+    const fn capacity(limit: RateLimit) -> i64 {
+        let burst = if limit.burst == 0 { 1 } else { limit.burst };
+        burst as i64 * TOKEN_SCALE
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(refilled_at) = self.refilled_at {
+            if now > refilled_at {
+                let elapsed = (now - refilled_at).total_micros() as i64;
+                self.tokens = self
+                    .tokens
+                    .saturating_add(elapsed.saturating_mul(self.limit.rate as i64))
+                    .min(Self::capacity(self.limit));
+            }
+        }
+        self.refilled_at = Some(now);
+    }
+
+    fn has_tokens(&self) -> bool {
+        self.tokens > 0
+    }
+
+    /// Return when the bucket will have tokens again, if it has none now.
+    fn ready_at(&self) -> Option<Instant> {
+        if self.has_tokens() {
+            return None;
+        }
+        let refilled_at = self.refilled_at?;
+        if self.limit.rate == 0 {
+            return None;
+        }
+        let micros = -self.tokens / self.limit.rate as i64 + 1;
+        Some(refilled_at + Duration::from_micros(micros as u64))
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.tokens = self
+            .tokens
+            .saturating_sub((len as i64).saturating_mul(TOKEN_SCALE));
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct TrafficClass {
+    weight: u16,
+    bucket: Option<TokenBucket>,
+    /// Octets sent by this class divided by its weight, for the weighted fair queuing.
+    virtual_time: u64,
+}
+
+impl TrafficClass {
+    const fn new() -> Self {
+        Self {
+            weight: 1,
+            bucket: None,
+            virtual_time: 0,
+        }
+    }
+}
+
+/// The egress scheduler of an interface.
+///
+/// By default, the scheduler is disabled and [`Interface::poll`] serves the sockets in the
+/// order they were added to the [`SocketSet`]. Once a [`SchedulingMode`] is set, every
+/// socket belongs to one of `IFACE_EGRESS_CLASS_COUNT` traffic classes, class 0 unless
+/// changed with [`SocketSet::set_egress_class`], and the sockets are served class by class.
+/// Each class can also be rate limited with a token bucket; the sockets of a class that
+/// exceeded its rate wait, and [`Interface::poll_at`] returns when they may send again.
+///
+/// [`Interface::poll`]: crate::iface::Interface::poll
+/// [`Interface::poll_at`]: crate::iface::Interface::poll_at
+/// [`SocketSet`]: crate::iface::SocketSet
+/// [`SocketSet::set_egress_class`]: crate::iface::SocketSet::set_egress_class
+#[derive(Debug, Clone)]
+pub struct EgressScheduler {
+    mode: Option<SchedulingMode>,
+    classes: [TrafficClass; IFACE_EGRESS_CLASS_COUNT],
+}
+
+impl EgressScheduler {
+    /// The amount of traffic classes.
+    pub const CLASS_COUNT: usize = IFACE_EGRESS_CLASS_COUNT;
+
+    /// Create a disabled egress scheduler.
+    pub const fn new() -> Self {
+        Self {
+            mode: None,
+            classes: [TrafficClass::new(); IFACE_EGRESS_CLASS_COUNT],
+        }
+    }
+
+    /// Return the scheduling mode, or `None` if the scheduler is disabled.
+    pub fn mode(&self) -> Option<SchedulingMode> {
+        self.mode
+    }
+
+    /// Set the scheduling mode, or disable the scheduler with `None`.
+    pub fn set_mode(&mut self, mode: Option<SchedulingMode>) {
+        self.mode = mode;
+        for class in self.classes.iter_mut() {
+            class.virtual_time = 0;
+        }
+    }
+
+    /// Return the weight of a class.
+    ///
+    /// # Panics
+    /// This function panics if the class does not exist.
+    pub fn weight(&self, class: usize) -> u16 {
+        self.classes[class].weight
+    }
+
+    /// Set the weight of a class, used by [`SchedulingMode::WeightedFair`]. Classes have a
+    /// weight of 1 by default.
+    ///
+    /// # Panics
+    /// This function panics if the class does not exist, or if the weight is zero.
+    pub fn set_weight(&mut self, class: usize, weight: u16) {
+        if weight == 0 {
+            panic!("the weight of a traffic class must not be zero")
+        }
+        self.classes[class].weight = weight;
+    }
+
+    /// Return the rate limit of a class.
+    ///
+    /// # Panics
+    /// This function panics if the class does not exist.
+    pub fn rate_limit(&self, class: usize) -> Option<RateLimit> {
+        self.classes[class].bucket.map(|bucket| bucket.limit)
+    }
+
+    /// Set or remove the rate limit of a class. The token bucket of the class starts full.
+    ///
+    /// # Panics
+    /// This function panics if the class does not exist.
+    pub fn set_rate_limit(&mut self, class: usize, limit: Option<RateLimit>) {
+        self.classes[class].bucket = limit.map(TokenBucket::new);
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.mode.is_some()
+    }
+
+    pub(crate) fn refill(&mut self, now: Instant) {
+        for class in self.classes.iter_mut() {
+            if let Some(bucket) = class.bucket.as_mut() {
+                bucket.refill(now);
+            }
+        }
+    }
+
+    /// Return the classes in the order they should be offered to send.
+    pub(crate) fn class_order(&self) -> [usize; IFACE_EGRESS_CLASS_COUNT] {
+        let mut order = [0; IFACE_EGRESS_CLASS_COUNT];
+        for (i, class) in order.iter_mut().enumerate() {
+            *class = i;
+        }
+        if self.mode == Some(SchedulingMode::WeightedFair) {
+            // Lower classes win ties.
+            order.sort_unstable_by_key(|&class| (self.classes[class].virtual_time, class));
+        }
+        order
+    }
+
+    /// Check whether the sockets of a class may send.
+    pub(crate) fn may_send(&self, class: usize) -> bool {
+        self.classes[class]
+            .bucket
+            .map_or(true, |bucket| bucket.has_tokens())
+    }
+
+    /// Return when the sockets of a class may send again, if they may not send now.
+    pub(crate) fn ready_at(&self, class: usize) -> Option<Instant> {
+        if !self.is_enabled() {
+            return None;
+        }
+        self.classes[class]
+            .bucket
+            .and_then(|bucket| bucket.ready_at())
+    }
+
+    /// Account for a packet of `len` octets sent by a class.
+    pub(crate) fn sent(&mut self, class: usize, len: usize) {
+        let class = &mut self.classes[class];
+        if let Some(bucket) = class.bucket.as_mut() {
+            bucket.consume(len);
+        }
+        class.virtual_time = class
+            .virtual_time
+            .saturating_add(len as u64 * VIRTUAL_TIME_SCALE / class.weight as u64);
+    }
+
+    /// Account for a class having nothing to send while `served` was served.
+    ///
+    /// An idle class must not save up a share of the link to use later, so its
+    /// virtual time catches up with the one of the served class.
+    pub(crate) fn idle(&mut self, class: usize, served: usize) {
+        let virtual_time = self.classes[served].virtual_time;
+        let class = &mut self.classes[class];
+        class.virtual_time = class.virtual_time.max(virtual_time);
+    }
+
+    /// Map the class of a socket to an existing class.
+    pub(crate) fn clamp_class(class: usize) -> usize {
+        class.min(IFACE_EGRESS_CLASS_COUNT - 1)
+    }
+}
+
+impl Default for EgressScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(RateLimit::new(1000, 1500));
+        let t0 = Instant::from_millis(0);
+        bucket.refill(t0);
+        assert!(bucket.has_tokens());
+        assert_eq!(bucket.ready_at(), None);
+
+        // The burst may be exceeded by the last packet.
+        bucket.consume(1000);
+        assert!(bucket.has_tokens());
+        bucket.consume(1000);
+        assert!(!bucket.has_tokens());
+        assert_eq!(bucket.ready_at(), Some(t0 + Duration::from_micros(500_001)));
+
+        bucket.refill(t0 + Duration::from_millis(500));
+        assert!(!bucket.has_tokens());
+        bucket.refill(t0 + Duration::from_micros(500_001));
+        assert!(bucket.has_tokens());
+
+        // The bucket never holds more than the burst.
+        bucket.refill(t0 + Duration::from_secs(10));
+        bucket.consume(1500);
+        assert!(!bucket.has_tokens());
+    }
+
+    #[test]
+    fn test_token_bucket_no_burst() {
+        let mut bucket = TokenBucket::new(RateLimit::new(1000, 0));
+        let t0 = Instant::from_millis(0);
+        bucket.refill(t0);
+        assert!(bucket.has_tokens());
+
+        // Each packet waits until the previous one was paid for.
+        bucket.consume(100);
+        assert!(!bucket.has_tokens());
+        assert_eq!(bucket.ready_at(), Some(t0 + Duration::from_micros(99_001)));
+        bucket.refill(t0 + Duration::from_secs(10));
+        assert!(bucket.has_tokens());
+    }
+
+    #[test]
+    fn test_strict_priority_order() {
+        let mut scheduler = EgressScheduler::new();
+        scheduler.set_mode(Some(SchedulingMode::StrictPriority));
+        scheduler.sent(0, 1000);
+        let mut expected = [0; EgressScheduler::CLASS_COUNT];
+        for (i, class) in expected.iter_mut().enumerate() {
+            *class = i;
+        }
+        assert_eq!(scheduler.class_order(), expected);
+    }
+
+    #[test]
+    fn test_weighted_fair_order() {
+        let mut scheduler = EgressScheduler::new();
+        scheduler.set_mode(Some(SchedulingMode::WeightedFair));
+        scheduler.set_weight(1, 3);
+
+        // Class 1 sends three times as many octets as class 0.
+        let mut sent = [0; 2];
+        for _ in 0..400 {
+            let order = scheduler.class_order();
+            let class = order.iter().copied().find(|&class| class < 2).unwrap();
+            scheduler.sent(class, 100);
+            sent[class] += 100;
+        }
+        assert_eq!(sent, [10_000, 30_000]);
+    }
+
+    #[test]
+    fn test_weighted_fair_idle() {
+        let mut scheduler = EgressScheduler::new();
+        scheduler.set_mode(Some(SchedulingMode::WeightedFair));
+
+        // Class 1 does not bank its share while it has nothing to send.
+        for _ in 0..10 {
+            scheduler.sent(0, 100);
+            scheduler.idle(1, 0);
+        }
+        scheduler.sent(1, 100);
+        let order = scheduler.class_order();
+        assert_eq!(order.iter().find(|&&class| class < 2), Some(&0));
+    }
+
+    #[test]
+    #[should_panic(expected = "the weight of a traffic class must not be zero")]
+    fn test_zero_weight() {
+        EgressScheduler::new().set_weight(0, 0);
+    }
+}
@@ -36,6 +36,8 @@ pub(crate) struct Meta {
     pub(crate) handle: SocketHandle,
     /// See [NeighborState](struct.NeighborState.html).
     neighbor_state: NeighborState,
+    /// Traffic class of the egress scheduler this socket belongs to.
+    pub(crate) egress_class: usize,
 }
 
 impl Meta {
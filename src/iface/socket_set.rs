@@ -117,6 +117,33 @@ impl<'a> SocketSet<'a> {
         }
     }
 
+    /// Return the traffic class of a socket, see [EgressScheduler].
+    ///
+    /// # Panics
+    /// This function may panic if the handle does not belong to this socket set.
+    ///
+    /// [EgressScheduler]: crate::iface::EgressScheduler
+    pub fn egress_class(&self, handle: SocketHandle) -> usize {
+        match self.sockets[handle.0].inner.as_ref() {
+            Some(item) => item.meta.egress_class,
+            None => panic!("handle does not refer to a valid socket"),
+        }
+    }
+
+    /// Set the traffic class of a socket, see [EgressScheduler]. Sockets belong to class 0
+    /// when added; classes past the last one of the scheduler are treated as the last one.
+    ///
+    /// # Panics
+    /// This function may panic if the handle does not belong to this socket set.
+    ///
+    /// [EgressScheduler]: crate::iface::EgressScheduler
+    pub fn set_egress_class(&mut self, handle: SocketHandle, class: usize) {
+        match self.sockets[handle.0].inner.as_mut() {
+            Some(item) => item.meta.egress_class = class,
+            None => panic!("handle does not refer to a valid socket"),
+        }
+    }
+
     /// Remove a socket from the set, without changing its state.
     ///
     /// # Panics
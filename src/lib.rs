@@ -107,9 +107,10 @@ compile_error!("You must enable at least one of the following features: proto-ip
         feature = "socket-icmp",
         feature = "socket-dhcpv4",
         feature = "socket-dns",
+        feature = "socket-ethernet",
     ))
 ))]
-compile_error!("If you enable the socket feature, you must enable at least one of the following features: socket-raw, socket-udp, socket-tcp, socket-icmp, socket-dhcpv4, socket-dns");
+compile_error!("If you enable the socket feature, you must enable at least one of the following features: socket-raw, socket-udp, socket-tcp, socket-icmp, socket-dhcpv4, socket-dns, socket-ethernet");
 
 #[cfg(all(
     feature = "socket",
@@ -139,11 +140,20 @@ pub mod config {
     pub const FRAGMENTATION_BUFFER_SIZE: usize = 1500;
     pub const IFACE_MAX_ADDR_COUNT: usize = 8;
     pub const IFACE_MAX_MULTICAST_GROUP_COUNT: usize = 4;
+    pub const IFACE_MAX_MULTICAST_SOURCE_COUNT: usize = 4;
+    pub const IFACE_MAX_MULTICAST_LISTENER_COUNT: usize = 4;
     pub const IFACE_MAX_ROUTE_COUNT: usize = 4;
+    pub const IFACE_MAX_ROUTE_RULE_COUNT: usize = 4;
+    pub const IFACE_MAX_ADDRESS_POLICY_COUNT: usize = 10;
+    pub const IFACE_LOOPBACK_BUFFER_SIZE: usize = 1536;
+    pub const IFACE_EGRESS_CLASS_COUNT: usize = 4;
     pub const IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT: usize = 4;
     pub const IFACE_NEIGHBOR_CACHE_COUNT: usize = 3;
+    pub const IFACE_DESTINATION_CACHE_COUNT: usize = 3;
     pub const REASSEMBLY_BUFFER_COUNT: usize = 4;
     pub const REASSEMBLY_BUFFER_SIZE: usize = 1500;
+    pub const ROUTER_MAX_IFACE_COUNT: usize = 3;
+    pub const ROUTER_MAX_ROUTE_COUNT: usize = 4;
     pub const RPL_RELATIONS_BUFFER_COUNT: usize = 16;
     pub const RPL_PARENTS_BUFFER_COUNT: usize = 8;
     pub const IPV6_HBH_MAX_OPTIONS: usize = 2;
@@ -155,6 +155,10 @@ pub use self::tuntap_interface::TunTapInterface;
 /// {
 ///     meta.id = 15;
 /// }
+/// #[cfg(feature = "packetmeta-mark")]
+/// {
+///     meta.mark = 2;
+/// }
 /// ```
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
@@ -162,6 +166,17 @@ pub use self::tuntap_interface::TunTapInterface;
 pub struct PacketMeta {
     #[cfg(feature = "packetmeta-id")]
     pub id: u32,
+    /// A mark for outgoing packets, which routing rules can match (like `SO_MARK` and
+    /// `fwmark` on Linux).
+    #[cfg(feature = "packetmeta-mark")]
+    pub mark: u32,
+    /// For a UDP packet given to a device with [`DeviceCapabilities::udp_segmentation_offload`],
+    /// the size of the datagrams the device must split its payload into. The IP and UDP
+    /// headers of the packet hold the length and checksums of the whole payload; the device
+    /// must fix them up for each datagram.
+    ///
+    /// The interface sets this field, it is always `None` for received packets.
+    pub segment_size: Option<u16>,
 }
 
 /// A description of checksum behavior for a particular protocol.
@@ -270,6 +285,14 @@ pub struct DeviceCapabilities {
     /// If the network device is capable of verifying or computing checksums for some protocols,
     /// it can request that the stack not do so in software to improve performance.
     pub checksum: ChecksumCapabilities,
+
+    /// UDP segmentation offload.
+    ///
+    /// If `true`, the device can split a UDP packet into several datagrams of the size
+    /// given by [`PacketMeta::segment_size`], so UDP sockets with a segment size give it
+    /// their large packets in one piece, instead of splitting them in software. Such a
+    /// packet may be larger than the MTU.
+    pub udp_segmentation_offload: bool,
 }
 
 impl DeviceCapabilities {
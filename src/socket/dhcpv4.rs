@@ -5,8 +5,9 @@ use crate::iface::Context;
 use crate::time::{Duration, Instant};
 use crate::wire::dhcpv4::field as dhcpv4_field;
 use crate::wire::{
-    DhcpMessageType, DhcpPacket, DhcpRepr, IpAddress, IpProtocol, Ipv4Address, Ipv4Cidr, Ipv4Repr,
-    UdpRepr, DHCP_CLIENT_PORT, DHCP_MAX_DNS_SERVER_COUNT, DHCP_SERVER_PORT, UDP_HEADER_LEN,
+    DhcpMessageType, DhcpPacket, DhcpRepr, IpAddress, IpProtocol, Ipv4Address, Ipv4Cidr,
+    Ipv4Options, Ipv4Repr, UdpRepr, DHCP_CLIENT_PORT, DHCP_MAX_DNS_SERVER_COUNT, DHCP_SERVER_PORT,
+    UDP_HEADER_LEN,
 };
 use crate::wire::{DhcpOption, HardwareAddress};
 use heapless::Vec;
@@ -603,6 +604,9 @@ impl<'a> Socket<'a> {
             next_header: IpProtocol::Udp,
             payload_len: 0, // filled right before emit
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
         };
 
         match &mut self.state {
@@ -897,6 +901,9 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     };
 
     const IP_BROADCAST_ADDRESSED: Ipv4Repr = Ipv4Repr {
@@ -905,6 +912,9 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     };
 
     const IP_SERVER_BROADCAST: Ipv4Repr = Ipv4Repr {
@@ -913,6 +923,9 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     };
 
     const IP_RECV: Ipv4Repr = Ipv4Repr {
@@ -921,6 +934,9 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     };
 
     const IP_SEND: Ipv4Repr = Ipv4Repr {
@@ -929,6 +945,9 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 0,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     };
 
     const UDP_SEND: UdpRepr = UdpRepr {
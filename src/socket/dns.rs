@@ -307,7 +307,9 @@ impl<'a> Socket<'a> {
 
     /// Get the result of a query.
     ///
-    /// If the query is completed, the query slot is automatically freed.
+    /// If the query is completed, the query slot is automatically freed. When IPv6 is
+    /// enabled, the addresses are sorted in the order they should be tried in, as
+    /// described in RFC 6724 § 6.
     ///
     /// # Panics
     /// Panics if the QueryHandle corresponds to a free slot.
@@ -371,9 +373,10 @@ impl<'a> Socket<'a> {
             || (udp_repr.src_port == MDNS_DNS_PORT)
     }
 
+    #[cfg_attr(not(feature = "proto-ipv6"), allow(unused_variables))]
     pub(crate) fn process(
         &mut self,
-        _cx: &mut Context,
+        cx: &mut Context,
         ip_repr: &IpRepr,
         udp_repr: &UdpRepr,
         payload: &[u8],
@@ -510,6 +513,10 @@ impl<'a> Socket<'a> {
                     }
                 }
 
+                // Sort the addresses in the order they should be connected to.
+                #[cfg(feature = "proto-ipv6")]
+                cx.sort_destination_addrs(&mut addresses);
+
                 q.set_state(if addresses.is_empty() {
                     State::Failure
                 } else {
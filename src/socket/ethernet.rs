@@ -0,0 +1,401 @@
+use core::cmp::min;
+#[cfg(feature = "async")]
+use core::task::Waker;
+
+use crate::iface::Context;
+use crate::socket::PollAt;
+#[cfg(feature = "async")]
+use crate::socket::WakerRegistration;
+
+use crate::storage::Empty;
+use crate::wire::{EthernetFrame, EthernetProtocol};
+
+/// Error returned by [`Socket::send`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SendError {
+    BufferFull,
+}
+
+impl core::fmt::Display for SendError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            SendError::BufferFull => write!(f, "buffer full"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SendError {}
+
+/// Error returned by [`Socket::recv`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RecvError {
+    Exhausted,
+    Truncated,
+}
+
+impl core::fmt::Display for RecvError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            RecvError::Exhausted => write!(f, "exhausted"),
+            RecvError::Truncated => write!(f, "truncated"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RecvError {}
+
+/// An Ethernet frame metadata.
+pub type PacketMetadata = crate::storage::PacketMetadata<()>;
+
+/// An Ethernet frame ring buffer.
+pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, ()>;
+
+/// A raw Ethernet socket.
+///
+/// An Ethernet socket receives copies of the frames with a given EtherType, or of all
+/// frames, addressed to the interface, including those the interface handles itself.
+/// It sends complete Ethernet frames, header included, as they are. It owns transmit
+/// and receive frame buffers, and is only useful on interfaces with [`Medium::Ethernet`].
+///
+/// [`Medium::Ethernet`]: crate::phy::Medium::Ethernet
+#[derive(Debug)]
+pub struct Socket<'a> {
+    ethertype: Option<EthernetProtocol>,
+    rx_buffer: PacketBuffer<'a>,
+    tx_buffer: PacketBuffer<'a>,
+    #[cfg(feature = "async")]
+    rx_waker: WakerRegistration,
+    #[cfg(feature = "async")]
+    tx_waker: WakerRegistration,
+}
+
+impl<'a> Socket<'a> {
+    /// Create an Ethernet socket receiving the frames with the given EtherType, or all
+    /// frames with `None`, with the given buffers.
+    pub fn new(
+        ethertype: Option<EthernetProtocol>,
+        rx_buffer: PacketBuffer<'a>,
+        tx_buffer: PacketBuffer<'a>,
+    ) -> Socket<'a> {
+        Socket {
+            ethertype,
+            rx_buffer,
+            tx_buffer,
+            #[cfg(feature = "async")]
+            rx_waker: WakerRegistration::new(),
+            #[cfg(feature = "async")]
+            tx_waker: WakerRegistration::new(),
+        }
+    }
+
+    /// Register a waker for receive operations.
+    ///
+    /// The waker is woken on state changes that might affect the return value
+    /// of `recv` method calls, such as receiving data, or the socket closing.
+    ///
+    /// Notes:
+    ///
+    /// - Only one waker can be registered at a time. If another waker was previously registered,
+    ///   it is overwritten and will no longer be woken.
+    /// - The Waker is woken only once. Once woken, you must register it again to receive more wakes.
+    /// - "Spurious wakes" are allowed: a wake doesn't guarantee the result of `recv` has
+    ///   necessarily changed.
+    #[cfg(feature = "async")]
+    pub fn register_recv_waker(&mut self, waker: &Waker) {
+        self.rx_waker.register(waker)
+    }
+
+    /// Register a waker for send operations.
+    ///
+    /// The waker is woken on state changes that might affect the return value
+    /// of `send` method calls, such as space becoming available in the transmit
+    /// buffer, or the socket closing.
+    ///
+    /// Notes:
+    ///
+    /// - Only one waker can be registered at a time. If another waker was previously registered,
+    ///   it is overwritten and will no longer be woken.
+    /// - The Waker is woken only once. Once woken, you must register it again to receive more wakes.
+    /// - "Spurious wakes" are allowed: a wake doesn't guarantee the result of `send` has
+    ///   necessarily changed.
+    #[cfg(feature = "async")]
+    pub fn register_send_waker(&mut self, waker: &Waker) {
+        self.tx_waker.register(waker)
+    }
+
+    /// Return the EtherType the socket receives, or `None` if it receives all frames.
+    #[inline]
+    pub fn ethertype(&self) -> Option<EthernetProtocol> {
+        self.ethertype
+    }
+
+    /// Check whether the transmit buffer is full.
+    #[inline]
+    pub fn can_send(&self) -> bool {
+        !self.tx_buffer.is_full()
+    }
+
+    /// Check whether the receive buffer is not empty.
+    #[inline]
+    pub fn can_recv(&self) -> bool {
+        !self.rx_buffer.is_empty()
+    }
+
+    /// Return the maximum number frames the socket can receive.
+    #[inline]
+    pub fn packet_recv_capacity(&self) -> usize {
+        self.rx_buffer.packet_capacity()
+    }
+
+    /// Return the maximum number frames the socket can transmit.
+    #[inline]
+    pub fn packet_send_capacity(&self) -> usize {
+        self.tx_buffer.packet_capacity()
+    }
+
+    /// Return the maximum number of bytes inside the recv buffer.
+    #[inline]
+    pub fn payload_recv_capacity(&self) -> usize {
+        self.rx_buffer.payload_capacity()
+    }
+
+    /// Return the maximum number of bytes inside the transmit buffer.
+    #[inline]
+    pub fn payload_send_capacity(&self) -> usize {
+        self.tx_buffer.payload_capacity()
+    }
+
+    /// Enqueue a frame to send, and return a pointer to its contents.
+    ///
+    /// This function returns `Err(Error::Exhausted)` if the transmit buffer is full,
+    /// and `Err(Error::Truncated)` if there is not enough transmit buffer capacity
+    /// to ever send this frame.
+    ///
+    /// The buffer must be filled with a complete Ethernet frame, header included but
+    /// without the frame check sequence. Frames shorter than an Ethernet header are
+    /// silently dropped.
+    pub fn send(&mut self, size: usize) -> Result<&mut [u8], SendError> {
+        let frame_buf = self
+            .tx_buffer
+            .enqueue(size, ())
+            .map_err(|_| SendError::BufferFull)?;
+
+        net_trace!("ethernet: buffer to send {} octets", frame_buf.len());
+        Ok(frame_buf)
+    }
+
+    /// Enqueue a frame to be send and pass the buffer to the provided closure.
+    /// The closure then returns the size of the data written into the buffer.
+    ///
+    /// Also see [send](#method.send).
+    pub fn send_with<F>(&mut self, max_size: usize, f: F) -> Result<usize, SendError>
+    where
+        F: FnOnce(&mut [u8]) -> usize,
+    {
+        let size = self
+            .tx_buffer
+            .enqueue_with_infallible(max_size, (), f)
+            .map_err(|_| SendError::BufferFull)?;
+
+        net_trace!("ethernet: buffer to send {} octets", size);
+
+        Ok(size)
+    }
+
+    /// Enqueue a frame to send, and fill it from a slice.
+    ///
+    /// See also [send](#method.send).
+    pub fn send_slice(&mut self, data: &[u8]) -> Result<(), SendError> {
+        self.send(data.len())?.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Dequeue a frame, and return a pointer to its contents, header included.
+    ///
+    /// This function returns `Err(Error::Exhausted)` if the receive buffer is empty.
+    pub fn recv(&mut self) -> Result<&[u8], RecvError> {
+        let ((), frame_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
+
+        net_trace!("ethernet: receive {} buffered octets", frame_buf.len());
+        Ok(frame_buf)
+    }
+
+    /// Dequeue a frame, and copy it into the given slice.
+    ///
+    /// **Note**: when the size of the provided buffer is smaller than the size of the frame,
+    /// the frame is dropped and a `RecvError::Truncated` error is returned.
+    ///
+    /// See also [recv](#method.recv).
+    pub fn recv_slice(&mut self, data: &mut [u8]) -> Result<usize, RecvError> {
+        let buffer = self.recv()?;
+        if data.len() < buffer.len() {
+            return Err(RecvError::Truncated);
+        }
+
+        let length = min(data.len(), buffer.len());
+        data[..length].copy_from_slice(&buffer[..length]);
+        Ok(length)
+    }
+
+    /// Peek at a frame in the receive buffer and return a pointer to its contents
+    /// without removing it from the receive buffer.
+    /// This function otherwise behaves identically to [recv](#method.recv).
+    ///
+    /// It returns `Err(Error::Exhausted)` if the receive buffer is empty.
+    pub fn peek(&mut self) -> Result<&[u8], RecvError> {
+        let ((), frame_buf) = self.rx_buffer.peek().map_err(|_| RecvError::Exhausted)?;
+
+        net_trace!("ethernet: receive {} buffered octets", frame_buf.len());
+
+        Ok(frame_buf)
+    }
+
+    /// Peek at a frame in the receive buffer, copy it into the given slice,
+    /// and return the amount of octets copied without removing it from the receive buffer.
+    /// This function otherwise behaves identically to [recv_slice](#method.recv_slice).
+    ///
+    /// **Note**: when the size of the provided buffer is smaller than the size of the frame,
+    /// no data is copied into the provided buffer and a `RecvError::Truncated` error is returned.
+    ///
+    /// See also [peek](#method.peek).
+    pub fn peek_slice(&mut self, data: &mut [u8]) -> Result<usize, RecvError> {
+        let buffer = self.peek()?;
+        if data.len() < buffer.len() {
+            return Err(RecvError::Truncated);
+        }
+
+        let length = min(data.len(), buffer.len());
+        data[..length].copy_from_slice(&buffer[..length]);
+        Ok(length)
+    }
+
+    pub(crate) fn accepts(&self, frame: &EthernetFrame<&[u8]>) -> bool {
+        self.ethertype
+            .map_or(true, |ethertype| frame.ethertype() == ethertype)
+    }
+
+    pub(crate) fn process(&mut self, _cx: &mut Context, frame: &EthernetFrame<&[u8]>) {
+        debug_assert!(self.accepts(frame));
+
+        let frame = frame.as_ref();
+        net_trace!("ethernet: receiving {} octets", frame.len());
+
+        match self.rx_buffer.enqueue(frame.len(), ()) {
+            Ok(buf) => buf.copy_from_slice(frame),
+            Err(_) => net_trace!("ethernet: buffer full, dropped incoming frame"),
+        }
+
+        #[cfg(feature = "async")]
+        self.rx_waker.wake();
+    }
+
+    pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut Context, &[u8]) -> Result<(), E>,
+    {
+        let res = self.tx_buffer.dequeue_with(|&mut (), buffer| {
+            if EthernetFrame::new_checked(&*buffer).is_err() {
+                net_trace!("ethernet: malformed frame in queue, dropping.");
+                return Ok(());
+            }
+
+            net_trace!("ethernet: sending {} octets", buffer.len());
+            emit(cx, buffer)
+        });
+        match res {
+            Err(Empty) => Ok(()),
+            Ok(Err(e)) => Err(e),
+            Ok(Ok(())) => {
+                #[cfg(feature = "async")]
+                self.tx_waker.wake();
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn poll_at(&self, _cx: &mut Context) -> PollAt {
+        if self.tx_buffer.is_empty() {
+            PollAt::Ingress
+        } else {
+            PollAt::Now
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::phy::Medium;
+    use crate::tests::setup;
+
+    use super::*;
+    use crate::wire::{EthernetAddress, EthernetRepr};
+
+    fn buffer(packets: usize) -> PacketBuffer<'static> {
+        PacketBuffer::new(vec![PacketMetadata::EMPTY; packets], vec![0; 32 * packets])
+    }
+
+    const LLDP: EthernetProtocol = EthernetProtocol::Unknown(0x88cc);
+
+    fn frame(ethertype: EthernetProtocol) -> std::vec::Vec<u8> {
+        let repr = EthernetRepr {
+            src_addr: EthernetAddress([0x02, 0, 0, 0, 0, 2]),
+            dst_addr: EthernetAddress([0x01, 0x80, 0xc2, 0, 0, 0x0e]),
+            ethertype,
+        };
+        let mut bytes = vec![0xaa; repr.buffer_len() + 4];
+        repr.emit(&mut EthernetFrame::new_unchecked(&mut bytes[..]));
+        bytes
+    }
+
+    #[test]
+    fn test_send_truncated() {
+        let mut socket = Socket::new(Some(LLDP), buffer(0), buffer(1));
+        assert_eq!(socket.send(48), Err(SendError::BufferFull));
+    }
+
+    #[test]
+    fn test_send_dispatch() {
+        let (mut iface, _, _) = setup(Medium::Ethernet);
+        let cx = iface.context();
+        let mut socket = Socket::new(Some(LLDP), buffer(0), buffer(2));
+
+        // Frames shorter than a header are dropped.
+        assert_eq!(socket.send_slice(&[0; 4]), Ok(()));
+        assert_eq!(socket.send_slice(&frame(LLDP)), Ok(()));
+        assert_eq!(socket.dispatch(cx, |_, _| Err(())), Ok(()));
+        assert_eq!(socket.dispatch(cx, |_, _| Err(())), Err(()));
+        assert_eq!(
+            socket.dispatch(cx, |_, bytes| {
+                assert_eq!(bytes, &frame(LLDP)[..]);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+        assert!(socket.can_send());
+    }
+
+    #[test]
+    fn test_recv_process() {
+        let (mut iface, _, _) = setup(Medium::Ethernet);
+        let cx = iface.context();
+
+        let bytes = frame(LLDP);
+        let lldp = EthernetFrame::new_checked(&bytes[..]).unwrap();
+        let other_bytes = frame(EthernetProtocol::Ipv4);
+        let other = EthernetFrame::new_checked(&other_bytes[..]).unwrap();
+
+        let mut socket = Socket::new(Some(LLDP), buffer(1), buffer(0));
+        assert_eq!(socket.recv(), Err(RecvError::Exhausted));
+        assert!(!socket.accepts(&other));
+        assert!(socket.accepts(&lldp));
+        socket.process(cx, &lldp);
+        assert_eq!(socket.recv(), Ok(&bytes[..]));
+
+        let socket = Socket::new(None, buffer(1), buffer(0));
+        assert!(socket.accepts(&other));
+    }
+}
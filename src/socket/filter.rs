@@ -0,0 +1,430 @@
+/// Error returned by [`Program::new`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProgramError {
+    Empty,
+    TooLong,
+    InvalidInstruction,
+    NoReturn,
+}
+
+impl core::fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            ProgramError::Empty => write!(f, "empty"),
+            ProgramError::TooLong => write!(f, "too long"),
+            ProgramError::InvalidInstruction => write!(f, "invalid instruction"),
+            ProgramError::NoReturn => write!(f, "no return"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProgramError {}
+
+/// The maximum number of instructions in a program.
+pub const MAX_INSTRUCTIONS: usize = 4096;
+
+/// The number of words of scratch memory available to a program.
+const MEMORY_WORDS: usize = 16;
+
+// Instruction classes.
+const LD: u16 = 0x00;
+const LDX: u16 = 0x01;
+const ST: u16 = 0x02;
+const STX: u16 = 0x03;
+const ALU: u16 = 0x04;
+const JMP: u16 = 0x05;
+const RET: u16 = 0x06;
+const MISC: u16 = 0x07;
+
+// Load sizes.
+const W: u16 = 0x00;
+const H: u16 = 0x08;
+const B: u16 = 0x10;
+
+// Load modes.
+const IMM: u16 = 0x00;
+const ABS: u16 = 0x20;
+const IND: u16 = 0x40;
+const MEM: u16 = 0x60;
+const LEN: u16 = 0x80;
+const MSH: u16 = 0xa0;
+
+// ALU operations.
+const ADD: u16 = 0x00;
+const SUB: u16 = 0x10;
+const MUL: u16 = 0x20;
+const DIV: u16 = 0x30;
+const OR: u16 = 0x40;
+const AND: u16 = 0x50;
+const LSH: u16 = 0x60;
+const RSH: u16 = 0x70;
+const NEG: u16 = 0x80;
+const MOD: u16 = 0x90;
+const XOR: u16 = 0xa0;
+
+// Jump operations.
+const JA: u16 = 0x00;
+const JEQ: u16 = 0x10;
+const JGT: u16 = 0x20;
+const JGE: u16 = 0x30;
+const JSET: u16 = 0x40;
+
+// Operand sources.
+const K: u16 = 0x00;
+const X: u16 = 0x08;
+const A: u16 = 0x10;
+
+// Register transfers.
+const TAX: u16 = 0x00;
+const TXA: u16 = 0x80;
+
+/// A classic BPF instruction.
+///
+/// The layout is the one of the Linux `sock_filter` structure, so programs can be
+/// written by hand or generated with e.g. `tcpdump -dd`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Instruction {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+impl Instruction {
+    /// Create an instruction from its opcode, jump offsets and constant.
+    pub const fn new(code: u16, jt: u8, jf: u8, k: u32) -> Instruction {
+        Instruction { code, jt, jf, k }
+    }
+
+    /// Check that the instruction is known and stays within a program of `len`
+    /// instructions when found at `pc`.
+    fn is_valid(&self, pc: usize, len: usize) -> bool {
+        // The sum is checked, a jump wrapping around on 32-bit targets would never end.
+        let in_program = |offset: u32| {
+            (offset as usize)
+                .checked_add(pc + 1)
+                .map_or(false, |target| target < len)
+        };
+
+        match self.code & 0x07 {
+            LD => match self.code & !0x07 {
+                c if c == W | ABS || c == H | ABS || c == B | ABS => true,
+                c if c == W | IND || c == H | IND || c == B | IND => true,
+                c if c == W | LEN || c == W | IMM => true,
+                c if c == W | MEM => (self.k as usize) < MEMORY_WORDS,
+                _ => false,
+            },
+            LDX => match self.code & !0x07 {
+                c if c == W | IMM || c == W | LEN || c == B | MSH => true,
+                c if c == W | MEM => (self.k as usize) < MEMORY_WORDS,
+                _ => false,
+            },
+            ST | STX => self.code & !0x07 == 0 && (self.k as usize) < MEMORY_WORDS,
+            ALU => match (self.code & 0xf0, self.code & 0x08) {
+                (DIV | MOD, K) => self.code & !0xf8 == ALU && self.k != 0,
+                (ADD | SUB | MUL | DIV | OR | AND | LSH | RSH | MOD | XOR, _) => {
+                    self.code & !0xf8 == ALU
+                }
+                (NEG, K) => self.code == ALU | NEG,
+                _ => false,
+            },
+            JMP => match self.code & 0xf0 {
+                JA => self.code == JMP | JA && in_program(self.k),
+                JEQ | JGT | JGE | JSET => {
+                    self.code & !0xf8 == JMP
+                        && in_program(self.jt as u32)
+                        && in_program(self.jf as u32)
+                }
+                _ => false,
+            },
+            RET => matches!(self.code & !0x07, K | A),
+            MISC => matches!(self.code & !0x07, TAX | TXA),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// A classic BPF packet filter program.
+///
+/// A program attached to a raw or ICMP socket runs on every packet the socket would
+/// receive, before the packet is copied into the socket's receive buffer. The packet
+/// is only received if the program returns a non-zero value; it is always received
+/// whole.
+///
+/// Programs are checked when created: all jumps go forward and stay within the
+/// program, which ends with a return instruction, so running them always terminates.
+/// Loads outside of the packet, and divisions by zero, end the program with a zero
+/// return value.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Program<'a> {
+    instructions: &'a [Instruction],
+}
+
+impl<'a> Program<'a> {
+    /// Check and create a program from its instructions.
+    pub fn new(instructions: &'a [Instruction]) -> Result<Program<'a>, ProgramError> {
+        if instructions.is_empty() {
+            return Err(ProgramError::Empty);
+        }
+        if instructions.len() > MAX_INSTRUCTIONS {
+            return Err(ProgramError::TooLong);
+        }
+        for (pc, instruction) in instructions.iter().enumerate() {
+            if !instruction.is_valid(pc, instructions.len()) {
+                return Err(ProgramError::InvalidInstruction);
+            }
+        }
+        if instructions[instructions.len() - 1].code & 0x07 != RET {
+            return Err(ProgramError::NoReturn);
+        }
+
+        Ok(Program { instructions })
+    }
+
+    /// Return the instructions of the program.
+    pub fn instructions(&self) -> &'a [Instruction] {
+        self.instructions
+    }
+
+    /// Run the program on a packet, and return its result.
+    pub fn run(&self, packet: &[u8]) -> u32 {
+        self.run_split(packet, &[])
+    }
+
+    /// Check whether the program accepts a packet.
+    pub fn accepts(&self, packet: &[u8]) -> bool {
+        self.run(packet) != 0
+    }
+
+    /// Run the program on a packet made of a header and a payload held apart.
+    pub(crate) fn run_split(&self, header: &[u8], payload: &[u8]) -> u32 {
+        let len = header.len() + payload.len();
+        let load = |offset: u32, size: usize| -> Option<u32> {
+            let offset = offset as usize;
+            if offset.checked_add(size)? > len {
+                return None;
+            }
+            Some((offset..offset + size).fold(0, |value, i| {
+                let byte = if i < header.len() {
+                    header[i]
+                } else {
+                    payload[i - header.len()]
+                };
+                (value << 8) | byte as u32
+            }))
+        };
+        let size = |code: u16| match code & 0x18 {
+            W => 4,
+            H => 2,
+            _ => 1,
+        };
+
+        let mut a: u32 = 0;
+        let mut x: u32 = 0;
+        let mut mem = [0u32; MEMORY_WORDS];
+        let mut pc = 0;
+        loop {
+            let ins = self.instructions[pc];
+            pc += 1;
+            match ins.code & 0x07 {
+                LD => {
+                    a = match ins.code & 0xe0 {
+                        ABS => match load(ins.k, size(ins.code)) {
+                            Some(value) => value,
+                            None => return 0,
+                        },
+                        IND => match load(x.wrapping_add(ins.k), size(ins.code)) {
+                            Some(value) => value,
+                            None => return 0,
+                        },
+                        LEN => len as u32,
+                        MEM => mem[ins.k as usize],
+                        _ => ins.k,
+                    }
+                }
+                LDX => {
+                    x = match ins.code & 0xe0 {
+                        MSH => match load(ins.k, 1) {
+                            Some(value) => (value & 0x0f) << 2,
+                            None => return 0,
+                        },
+                        LEN => len as u32,
+                        MEM => mem[ins.k as usize],
+                        _ => ins.k,
+                    }
+                }
+                ST => mem[ins.k as usize] = a,
+                STX => mem[ins.k as usize] = x,
+                ALU => {
+                    let operand = if ins.code & 0x08 == X { x } else { ins.k };
+                    a = match ins.code & 0xf0 {
+                        ADD => a.wrapping_add(operand),
+                        SUB => a.wrapping_sub(operand),
+                        MUL => a.wrapping_mul(operand),
+                        DIV | MOD if operand == 0 => return 0,
+                        DIV => a / operand,
+                        MOD => a % operand,
+                        OR => a | operand,
+                        AND => a & operand,
+                        LSH => a.checked_shl(operand).unwrap_or(0),
+                        RSH => a.checked_shr(operand).unwrap_or(0),
+                        NEG => a.wrapping_neg(),
+                        _ => a ^ operand,
+                    }
+                }
+                JMP => {
+                    let operand = if ins.code & 0x08 == X { x } else { ins.k };
+                    let taken = match ins.code & 0xf0 {
+                        JA => {
+                            pc += ins.k as usize;
+                            continue;
+                        }
+                        JEQ => a == operand,
+                        JGT => a > operand,
+                        JGE => a >= operand,
+                        _ => a & operand != 0,
+                    };
+                    pc += if taken { ins.jt } else { ins.jf } as usize;
+                }
+                RET => return if ins.code & 0x18 == A { a } else { ins.k },
+                _ => {
+                    if ins.code & 0xf8 == TXA {
+                        a = x
+                    } else {
+                        x = a
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // IPv4 packets with a destination port of 7 in their UDP header, from `tcpdump -dd`.
+    const UDP_ECHO: [Instruction; 10] = [
+        Instruction::new(0x30, 0, 0, 0x00000009), // ldb [9]
+        Instruction::new(0x15, 0, 7, 0x00000011), // jeq #17, l2, drop
+        Instruction::new(0x28, 0, 0, 0x00000006), // l2: ldh [6]
+        Instruction::new(0x45, 5, 0, 0x00001fff), // jset #0x1fff, drop, l4
+        Instruction::new(0xb1, 0, 0, 0x00000000), // l4: ldxb 4*([0]&0xf)
+        Instruction::new(0x48, 0, 0, 0x00000002), // ldh [x + 2]
+        Instruction::new(0x15, 0, 2, 0x00000007), // jeq #7, accept, drop
+        Instruction::new(0x80, 0, 0, 0x00000000), // accept: ld #len
+        Instruction::new(0x16, 0, 0, 0x00000000), // ret a
+        Instruction::new(0x06, 0, 0, 0x00000000), // drop: ret #0
+    ];
+
+    fn udp_packet(dst_port: u16) -> [u8; 28] {
+        let mut packet = [0u8; 28];
+        packet[0] = 0x45;
+        packet[9] = 17;
+        packet[22..24].copy_from_slice(&dst_port.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_run() {
+        let program = Program::new(&UDP_ECHO).unwrap();
+        assert_eq!(program.run(&udp_packet(7)), 28);
+        assert!(!program.accepts(&udp_packet(8)));
+
+        let mut fragment = udp_packet(7);
+        fragment[7] = 1;
+        assert!(!program.accepts(&fragment));
+    }
+
+    #[test]
+    fn test_run_split() {
+        let program = Program::new(&UDP_ECHO).unwrap();
+        let packet = udp_packet(7);
+        assert_eq!(program.run_split(&packet[..21], &packet[21..]), 28);
+        assert_eq!(program.run_split(&packet[..20], &packet[20..23]), 0);
+    }
+
+    #[test]
+    fn test_alu_and_memory() {
+        let program = [
+            Instruction::new(0x00, 0, 0, 6), // ld #6
+            Instruction::new(0x02, 0, 0, 3), // st M[3]
+            Instruction::new(0x01, 0, 0, 4), // ldx #4
+            Instruction::new(0x2c, 0, 0, 0), // mul x
+            Instruction::new(0x94, 0, 0, 5), // mod #5
+            Instruction::new(0x61, 0, 0, 3), // ldx M[3]
+            Instruction::new(0x0c, 0, 0, 0), // add x
+            Instruction::new(0x16, 0, 0, 0), // ret a
+        ];
+        assert_eq!(Program::new(&program).unwrap().run(&[]), 10);
+
+        let division_by_zero = [
+            Instruction::new(0x00, 0, 0, 6), // ld #6
+            Instruction::new(0x3c, 0, 0, 0), // div x
+            Instruction::new(0x06, 0, 0, 1), // ret #1
+        ];
+        assert_eq!(Program::new(&division_by_zero).unwrap().run(&[]), 0);
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let program = [
+            Instruction::new(0x20, 0, 0, 0xffff_fffe), // ld [0xfffffffe]
+            Instruction::new(0x06, 0, 0, 1),           // ret #1
+        ];
+        assert_eq!(Program::new(&program).unwrap().run(&[0; 8]), 0);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Program::new(&[]), Err(ProgramError::Empty));
+        assert_eq!(
+            Program::new(&[Instruction::new(0x00, 0, 0, 0)]),
+            Err(ProgramError::NoReturn)
+        );
+        // Jump past the end.
+        assert_eq!(
+            Program::new(&[
+                Instruction::new(0x15, 1, 0, 0),
+                Instruction::new(0x06, 0, 0, 0)
+            ]),
+            Err(ProgramError::InvalidInstruction)
+        );
+        // A jump wrapping around the address space.
+        assert_eq!(
+            Program::new(&[
+                Instruction::new(0x05, 0, 0, u32::MAX),
+                Instruction::new(0x06, 0, 0, 0)
+            ]),
+            Err(ProgramError::InvalidInstruction)
+        );
+        // Division by a zero constant.
+        assert_eq!(
+            Program::new(&[
+                Instruction::new(0x34, 0, 0, 0),
+                Instruction::new(0x06, 0, 0, 0)
+            ]),
+            Err(ProgramError::InvalidInstruction)
+        );
+        // Scratch memory out of bounds.
+        assert_eq!(
+            Program::new(&[
+                Instruction::new(0x02, 0, 0, 16),
+                Instruction::new(0x06, 0, 0, 0)
+            ]),
+            Err(ProgramError::InvalidInstruction)
+        );
+        // Unknown opcode.
+        assert_eq!(
+            Program::new(&[
+                Instruction::new(0xff, 0, 0, 0),
+                Instruction::new(0x06, 0, 0, 0)
+            ]),
+            Err(ProgramError::InvalidInstruction)
+        );
+    }
+}
@@ -3,16 +3,17 @@ use core::cmp;
 use core::task::Waker;
 
 use crate::phy::ChecksumCapabilities;
+use crate::socket::filter::Program;
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
 use crate::socket::{Context, PollAt};
 
 use crate::storage::Empty;
-use crate::wire::IcmpRepr;
+use crate::wire::{IcmpExtensions, IcmpRepr};
 #[cfg(feature = "proto-ipv4")]
-use crate::wire::{Icmpv4Packet, Icmpv4Repr, Ipv4Repr};
+use crate::wire::{Icmpv4Message, Icmpv4Packet, Icmpv4Repr, Ipv4Options, Ipv4Repr};
 #[cfg(feature = "proto-ipv6")]
-use crate::wire::{Icmpv6Packet, Icmpv6Repr, Ipv6Repr};
+use crate::wire::{Icmpv6Message, Icmpv6Packet, Icmpv6Repr, Ipv6Repr};
 use crate::wire::{IpAddress, IpListenEndpoint, IpProtocol, IpRepr};
 use crate::wire::{UdpPacket, UdpRepr};
 
@@ -99,11 +100,35 @@ impl Endpoint {
     }
 }
 
-/// An ICMP packet metadata.
-pub type PacketMetadata = crate::storage::PacketMetadata<IpAddress>;
+/// An ICMP message received by a socket, see [Socket::recv_message].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Message<'a> {
+    /// The address of the node that sent the message, e.g. the router where a probe expired.
+    pub src_addr: IpAddress,
+    /// The hop limit of the IP packet holding the message.
+    pub hop_limit: u8,
+    /// The ICMP type of the message.
+    pub msg_type: u8,
+    /// The ICMP code of the message.
+    pub msg_code: u8,
+    /// The start of the datagram an error message was sent in response to, IP header
+    /// included, e.g. the probe that expired. It is empty for other messages.
+    pub quoted: &'a [u8],
+    /// The extensions of an error message, e.g. the MPLS labels of the probe.
+    pub extensions: IcmpExtensions<'a>,
+    /// The whole message, ICMP header included.
+    pub payload: &'a [u8],
+}
+
+/// An ICMP packet metadata, holding the remote address and the hop limit of a packet.
+///
+/// The hop limit of received packets is the one they arrived with. Packets to send
+/// with a hop limit of 0 use the hop limit of the socket.
+pub type PacketMetadata = crate::storage::PacketMetadata<(IpAddress, u8)>;
 
 /// An ICMP packet ring buffer.
-pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, IpAddress>;
+pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, (IpAddress, u8)>;
 
 /// A ICMP socket
 ///
@@ -122,6 +147,12 @@ pub struct Socket<'a> {
     endpoint: Endpoint,
     /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     hop_limit: Option<u8>,
+    /// The Differentiated Services Code Point used in outgoing packets.
+    dscp: u8,
+    /// The Explicit Congestion Notification bits used in outgoing packets.
+    ecn: u8,
+    /// The packet filter program attached to the socket.
+    filter: Option<Program<'a>>,
     #[cfg(feature = "async")]
     rx_waker: WakerRegistration,
     #[cfg(feature = "async")]
@@ -136,6 +167,9 @@ impl<'a> Socket<'a> {
             tx_buffer,
             endpoint: Default::default(),
             hop_limit: None,
+            dscp: 0,
+            ecn: 0,
+            filter: None,
             #[cfg(feature = "async")]
             rx_waker: WakerRegistration::new(),
             #[cfg(feature = "async")]
@@ -205,6 +239,64 @@ impl<'a> Socket<'a> {
         self.hop_limit = hop_limit
     }
 
+    /// Return the Differentiated Services Code Point used in outgoing packets.
+    ///
+    /// See also the [set_dscp](#method.set_dscp) method
+    pub fn dscp(&self) -> u8 {
+        self.dscp
+    }
+
+    /// Set the Differentiated Services Code Point used in outgoing packets, see the
+    /// [traffic class](crate::socket#traffic-class) of the sockets.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the value does not fit in six bits.
+    pub fn set_dscp(&mut self, dscp: u8) {
+        if dscp > 0x3f {
+            panic!("the DSCP value of a packet must fit in six bits")
+        }
+
+        self.dscp = dscp
+    }
+
+    /// Return the Explicit Congestion Notification bits used in outgoing packets.
+    ///
+    /// See also the [set_ecn](#method.set_ecn) method
+    pub fn ecn(&self) -> u8 {
+        self.ecn
+    }
+
+    /// Set the Explicit Congestion Notification bits used in outgoing packets, see the
+    /// [traffic class](crate::socket#traffic-class) of the sockets.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the value does not fit in two bits.
+    pub fn set_ecn(&mut self, ecn: u8) {
+        if ecn > 0x03 {
+            panic!("the ECN value of a packet must fit in two bits")
+        }
+
+        self.ecn = ecn
+    }
+
+    /// Return the packet filter program attached to the socket.
+    ///
+    /// See also the [set_filter](#method.set_filter) method.
+    #[inline]
+    pub fn filter(&self) -> Option<Program<'a>> {
+        self.filter
+    }
+
+    /// Attach a packet filter program to the socket, or detach it with `None`.
+    ///
+    /// The program runs on every ICMP message, without its IP header, the socket would receive, and only the
+    /// ones it accepts are copied into the receive buffer.
+    pub fn set_filter(&mut self, filter: Option<Program<'a>>) {
+        self.filter = filter
+    }
+
     /// Bind the socket to the given endpoint.
     ///
     /// This function returns `Err(Error::Illegal)` if the socket was open
@@ -329,13 +421,47 @@ impl<'a> Socket<'a> {
     /// `Err(Error::Truncated)` if the requested size is larger than the packet buffer
     /// size, and `Err(Error::Unaddressable)` if the remote address is unspecified.
     pub fn send(&mut self, size: usize, endpoint: IpAddress) -> Result<&mut [u8], SendError> {
+        self.send_impl(size, endpoint, 0)
+    }
+
+    /// Enqueue a packet to be sent to a given remote address with the given time-to-live
+    /// (IPv4) or hop limit (IPv6) value, and return a pointer to its payload.
+    ///
+    /// The hop limit overrides the one of the socket for this packet only, e.g. for the
+    /// probes of a traceroute, see also [recv_message](#method.recv_message).
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a hop limit value of 0 is given. See [RFC 1122 § 3.2.1.7].
+    ///
+    /// [RFC 1122 § 3.2.1.7]: https://tools.ietf.org/html/rfc1122#section-3.2.1.7
+    pub fn send_with_hop_limit(
+        &mut self,
+        size: usize,
+        endpoint: IpAddress,
+        hop_limit: u8,
+    ) -> Result<&mut [u8], SendError> {
+        // A host MUST NOT send a datagram with a hop limit value of 0
+        if hop_limit == 0 {
+            panic!("the time-to-live value of a packet must not be zero")
+        }
+
+        self.send_impl(size, endpoint, hop_limit)
+    }
+
+    fn send_impl(
+        &mut self,
+        size: usize,
+        endpoint: IpAddress,
+        hop_limit: u8,
+    ) -> Result<&mut [u8], SendError> {
         if endpoint.is_unspecified() {
             return Err(SendError::Unaddressable);
         }
 
         let packet_buf = self
             .tx_buffer
-            .enqueue(size, endpoint)
+            .enqueue(size, (endpoint, hop_limit))
             .map_err(|_| SendError::BufferFull)?;
 
         net_trace!("icmp:{}: buffer to send {} octets", endpoint, size);
@@ -362,7 +488,7 @@ impl<'a> Socket<'a> {
 
         let size = self
             .tx_buffer
-            .enqueue_with_infallible(max_size, endpoint, f)
+            .enqueue_with_infallible(max_size, (endpoint, 0), f)
             .map_err(|_| SendError::BufferFull)?;
 
         net_trace!("icmp:{}: buffer to send {} octets", endpoint, size);
@@ -378,12 +504,28 @@ impl<'a> Socket<'a> {
         Ok(())
     }
 
+    /// Enqueue a packet to be sent to a given remote address with the given hop limit,
+    /// and fill it from a slice.
+    ///
+    /// See also [send_with_hop_limit](#method.send_with_hop_limit).
+    pub fn send_slice_with_hop_limit(
+        &mut self,
+        data: &[u8],
+        endpoint: IpAddress,
+        hop_limit: u8,
+    ) -> Result<(), SendError> {
+        let packet_buf = self.send_with_hop_limit(data.len(), endpoint, hop_limit)?;
+        packet_buf.copy_from_slice(data);
+        Ok(())
+    }
+
     /// Dequeue a packet received from a remote endpoint, and return the `IpAddress` as well
     /// as a pointer to the payload.
     ///
     /// This function returns `Err(Error::Exhausted)` if the receive buffer is empty.
     pub fn recv(&mut self) -> Result<(&[u8], IpAddress), RecvError> {
-        let (endpoint, packet_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
+        let ((endpoint, _), packet_buf) =
+            self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
 
         net_trace!(
             "icmp:{}: receive {} buffered octets",
@@ -412,6 +554,64 @@ impl<'a> Socket<'a> {
         Ok((length, endpoint))
     }
 
+    /// Dequeue a message, and return it along with the address of its sender, its type
+    /// and code, and the datagram quoted and the extensions carried by error messages.
+    ///
+    /// This is useful to match the errors with the probes that caused them, e.g. the
+    /// Time Exceeded messages sent by the routers along the path of a traceroute.
+    ///
+    /// This function returns `Err(Error::Exhausted)` if the receive buffer is empty.
+    ///
+    /// See also [recv](#method.recv).
+    pub fn recv_message(&mut self) -> Result<Message<'_>, RecvError> {
+        let ((src_addr, hop_limit), payload) =
+            self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
+
+        net_trace!(
+            "icmp:{}: receive {} buffered octets",
+            src_addr,
+            payload.len()
+        );
+
+        // The length field of the messages supporting extensions counts 32-bit words for
+        // ICMPv4 and 64-bit words for ICMPv6.
+        let (quoted, extensions) = match src_addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(_) => match Icmpv4Message::from(payload[0]) {
+                Icmpv4Message::DstUnreachable
+                | Icmpv4Message::TimeExceeded
+                | Icmpv4Message::ParamProblem => {
+                    IcmpExtensions::split(&payload[8..], payload[5] as usize * 4)
+                }
+                message if message.is_error() => (&payload[8..], IcmpExtensions::EMPTY),
+                _ => (&[][..], IcmpExtensions::EMPTY),
+            },
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(_) => match Icmpv6Message::from(payload[0]) {
+                Icmpv6Message::DstUnreachable | Icmpv6Message::TimeExceeded => {
+                    IcmpExtensions::split(&payload[8..], payload[4] as usize * 8)
+                }
+                message if message.is_error() => (&payload[8..], IcmpExtensions::EMPTY),
+                _ => (&[][..], IcmpExtensions::EMPTY),
+            },
+        };
+        Ok(Message {
+            src_addr,
+            hop_limit,
+            msg_type: payload[0],
+            msg_code: payload[1],
+            quoted,
+            extensions,
+            payload,
+        })
+    }
+
+    /// Check whether the packet filter program of the socket accepts an ICMP message.
+    pub(crate) fn passes_filter(&self, icmp_payload: &[u8]) -> bool {
+        self.filter
+            .map_or(true, |filter| filter.accepts(icmp_payload))
+    }
+
     /// Fitler determining whether the socket accepts a given ICMPv4 packet.
     /// Accepted packets are enqueued into the socket's receive buffer.
     #[cfg(feature = "proto-ipv4")]
@@ -443,6 +643,20 @@ impl<'a> Socket<'a> {
                     Err(_) => false,
                 }
             }
+            // If we are bound to a specific ICMP identifier value, also accept the
+            // errors caused by our Echo Requests, e.g. the Time Exceeded messages
+            // sent by the routers along the path of a traceroute.
+            (
+                &Endpoint::Ident(bound_ident),
+                &Icmpv4Repr::DstUnreachable { data, header, .. }
+                | &Icmpv4Repr::TimeExceeded { data, header, .. }
+                | &Icmpv4Repr::FragRequired { data, header, .. },
+            ) if header.next_header == IpProtocol::Icmp => {
+                let packet = Icmpv4Packet::new_unchecked(data);
+                data.len() >= 8
+                    && packet.msg_type() == Icmpv4Message::EchoRequest
+                    && packet.echo_ident() == bound_ident
+            }
             // If we are bound to a specific ICMP identifier value, only accept an
             // Echo Request/Reply with the identifier field matching the endpoint
             // port.
@@ -485,6 +699,20 @@ impl<'a> Socket<'a> {
                     Err(_) => false,
                 }
             }
+            // If we are bound to a specific ICMP identifier value, also accept the
+            // errors caused by our Echo Requests, e.g. the Time Exceeded messages
+            // sent by the routers along the path of a traceroute.
+            (
+                &Endpoint::Ident(bound_ident),
+                &Icmpv6Repr::DstUnreachable { data, header, .. }
+                | &Icmpv6Repr::PktTooBig { data, header, .. }
+                | &Icmpv6Repr::TimeExceeded { data, header, .. },
+            ) if header.next_header == IpProtocol::Icmpv6 => {
+                let packet = Icmpv6Packet::new_unchecked(data);
+                data.len() >= 8
+                    && packet.msg_type() == Icmpv6Message::EchoRequest
+                    && packet.echo_ident() == bound_ident
+            }
             // If we are bound to a specific ICMP identifier value, only accept an
             // Echo Request/Reply with the identifier field matching the endpoint
             // port.
@@ -505,10 +733,10 @@ impl<'a> Socket<'a> {
     ) {
         net_trace!("icmp: receiving {} octets", icmp_repr.buffer_len());
 
-        match self
-            .rx_buffer
-            .enqueue(icmp_repr.buffer_len(), ip_repr.src_addr.into())
-        {
+        match self.rx_buffer.enqueue(
+            icmp_repr.buffer_len(),
+            (ip_repr.src_addr.into(), ip_repr.hop_limit),
+        ) {
             Ok(packet_buf) => {
                 icmp_repr.emit(
                     &mut Icmpv4Packet::new_unchecked(packet_buf),
@@ -531,10 +759,10 @@ impl<'a> Socket<'a> {
     ) {
         net_trace!("icmp: receiving {} octets", icmp_repr.buffer_len());
 
-        match self
-            .rx_buffer
-            .enqueue(icmp_repr.buffer_len(), ip_repr.src_addr.into())
-        {
+        match self.rx_buffer.enqueue(
+            icmp_repr.buffer_len(),
+            (ip_repr.src_addr.into(), ip_repr.hop_limit),
+        ) {
             Ok(packet_buf) => icmp_repr.emit(
                 &ip_repr.src_addr,
                 &ip_repr.dst_addr,
@@ -552,77 +780,90 @@ impl<'a> Socket<'a> {
     where
         F: FnOnce(&mut Context, (IpRepr, IcmpRepr)) -> Result<(), E>,
     {
-        let hop_limit = self.hop_limit.unwrap_or(64);
-        let res = self.tx_buffer.dequeue_with(|remote_endpoint, packet_buf| {
-            net_trace!(
-                "icmp:{}: sending {} octets",
-                remote_endpoint,
-                packet_buf.len()
-            );
-            match *remote_endpoint {
-                #[cfg(feature = "proto-ipv4")]
-                IpAddress::Ipv4(dst_addr) => {
-                    let src_addr = match cx.get_source_address_ipv4(&dst_addr) {
-                        Some(addr) => addr,
-                        None => {
-                            net_trace!(
-                                "icmp:{}: not find suitable source address, dropping",
-                                remote_endpoint
-                            );
-                            return Ok(());
-                        }
-                    };
-                    let packet = Icmpv4Packet::new_unchecked(&*packet_buf);
-                    let repr = match Icmpv4Repr::parse(&packet, &ChecksumCapabilities::ignored()) {
-                        Ok(x) => x,
-                        Err(_) => {
-                            net_trace!(
-                                "icmp:{}: malformed packet in queue, dropping",
-                                remote_endpoint
-                            );
-                            return Ok(());
-                        }
-                    };
-                    let ip_repr = IpRepr::Ipv4(Ipv4Repr {
-                        src_addr,
-                        dst_addr,
-                        next_header: IpProtocol::Icmp,
-                        payload_len: repr.buffer_len(),
-                        hop_limit,
-                    });
-                    emit(cx, (ip_repr, IcmpRepr::Ipv4(repr)))
-                }
-                #[cfg(feature = "proto-ipv6")]
-                IpAddress::Ipv6(dst_addr) => {
-                    let src_addr = cx.get_source_address_ipv6(&dst_addr);
-
-                    let packet = Icmpv6Packet::new_unchecked(&*packet_buf);
-                    let repr = match Icmpv6Repr::parse(
-                        &src_addr,
-                        &dst_addr,
-                        &packet,
-                        &ChecksumCapabilities::ignored(),
-                    ) {
-                        Ok(x) => x,
-                        Err(_) => {
-                            net_trace!(
-                                "icmp:{}: malformed packet in queue, dropping",
-                                remote_endpoint
-                            );
-                            return Ok(());
-                        }
-                    };
-                    let ip_repr = IpRepr::Ipv6(Ipv6Repr {
-                        src_addr,
-                        dst_addr,
-                        next_header: IpProtocol::Icmpv6,
-                        payload_len: repr.buffer_len(),
-                        hop_limit,
-                    });
-                    emit(cx, (ip_repr, IcmpRepr::Ipv6(repr)))
+        let default_hop_limit = self.hop_limit.unwrap_or(64);
+        let (dscp, ecn) = (self.dscp, self.ecn);
+        let res = self
+            .tx_buffer
+            .dequeue_with(|(remote_endpoint, hop_limit), packet_buf| {
+                let hop_limit = match *hop_limit {
+                    0 => default_hop_limit,
+                    hop_limit => hop_limit,
+                };
+                net_trace!(
+                    "icmp:{}: sending {} octets",
+                    remote_endpoint,
+                    packet_buf.len()
+                );
+                match *remote_endpoint {
+                    #[cfg(feature = "proto-ipv4")]
+                    IpAddress::Ipv4(dst_addr) => {
+                        let src_addr = match cx.get_source_address_ipv4(&dst_addr) {
+                            Some(addr) => addr,
+                            None => {
+                                net_trace!(
+                                    "icmp:{}: not find suitable source address, dropping",
+                                    remote_endpoint
+                                );
+                                return Ok(());
+                            }
+                        };
+                        let packet = Icmpv4Packet::new_unchecked(&*packet_buf);
+                        let repr =
+                            match Icmpv4Repr::parse(&packet, &ChecksumCapabilities::ignored()) {
+                                Ok(x) => x,
+                                Err(_) => {
+                                    net_trace!(
+                                        "icmp:{}: malformed packet in queue, dropping",
+                                        remote_endpoint
+                                    );
+                                    return Ok(());
+                                }
+                            };
+                        let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+                            src_addr,
+                            dst_addr,
+                            next_header: IpProtocol::Icmp,
+                            payload_len: repr.buffer_len(),
+                            hop_limit,
+                            dscp,
+                            ecn,
+                            options: Ipv4Options::EMPTY,
+                        });
+                        emit(cx, (ip_repr, IcmpRepr::Ipv4(repr)))
+                    }
+                    #[cfg(feature = "proto-ipv6")]
+                    IpAddress::Ipv6(dst_addr) => {
+                        let src_addr = cx.get_source_address_ipv6(&dst_addr);
+
+                        let packet = Icmpv6Packet::new_unchecked(&*packet_buf);
+                        let repr = match Icmpv6Repr::parse(
+                            &src_addr,
+                            &dst_addr,
+                            &packet,
+                            &ChecksumCapabilities::ignored(),
+                        ) {
+                            Ok(x) => x,
+                            Err(_) => {
+                                net_trace!(
+                                    "icmp:{}: malformed packet in queue, dropping",
+                                    remote_endpoint
+                                );
+                                return Ok(());
+                            }
+                        };
+                        let ip_repr = IpRepr::Ipv6(Ipv6Repr {
+                            src_addr,
+                            dst_addr,
+                            next_header: IpProtocol::Icmpv6,
+                            payload_len: repr.buffer_len(),
+                            hop_limit,
+                            dscp,
+                            ecn,
+                        });
+                        emit(cx, (ip_repr, IcmpRepr::Ipv6(repr)))
+                    }
                 }
-            }
-        });
+            });
         match res {
             Err(Empty) => Ok(()),
             Ok(Err(e)) => Err(e),
@@ -676,7 +917,10 @@ mod test_ipv4 {
     use rstest::*;
 
     use super::tests_common::*;
-    use crate::wire::{Icmpv4DstUnreachable, IpEndpoint, Ipv4Address};
+    use crate::wire::{
+        IcmpExtensionObjectRepr, Icmpv4DstUnreachable, Icmpv4TimeExceeded, IpEndpoint, Ipv4Address,
+        MplsLabelStack,
+    };
 
     const REMOTE_IPV4: Ipv4Address = Ipv4Address([192, 168, 1, 2]);
     const LOCAL_IPV4: Ipv4Address = Ipv4Address([192, 168, 1, 1]);
@@ -697,6 +941,9 @@ mod test_ipv4 {
         next_header: IpProtocol::Icmp,
         payload_len: 24,
         hop_limit: 0x40,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     });
 
     static REMOTE_IPV4_REPR: Ipv4Repr = Ipv4Repr {
@@ -705,6 +952,9 @@ mod test_ipv4 {
         next_header: IpProtocol::Icmp,
         payload_len: 24,
         hop_limit: 0x40,
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::EMPTY,
     };
 
     #[test]
@@ -803,6 +1053,9 @@ mod test_ipv4 {
                         next_header: IpProtocol::Icmp,
                         payload_len: ECHOV4_REPR.buffer_len(),
                         hop_limit: 0x2a,
+                        dscp: 0,
+                        ecn: 0,
+                        options: Ipv4Options::EMPTY,
                     })
                 );
                 Ok::<_, ()>(())
@@ -811,6 +1064,37 @@ mod test_ipv4 {
         );
     }
 
+    #[rstest]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    fn test_set_dscp_ecn_v4(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut s = socket(buffer(0), buffer(1));
+        let checksum = ChecksumCapabilities::default();
+
+        let mut bytes = [0xff; 24];
+        let mut packet = Icmpv4Packet::new_unchecked(&mut bytes);
+        ECHOV4_REPR.emit(&mut packet, &checksum);
+
+        s.set_dscp(46);
+        s.set_ecn(0b01);
+
+        assert_eq!(
+            s.send_slice(&*packet.into_inner(), REMOTE_IPV4.into()),
+            Ok(())
+        );
+        assert_eq!(
+            s.dispatch(cx, |_, (ip_repr, _)| {
+                assert_eq!(ip_repr.dscp(), 46);
+                assert_eq!(ip_repr.ecn(), 0b01);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+    }
+
     #[rstest]
     #[case::ethernet(Medium::Ethernet)]
     #[cfg(feature = "medium-ethernet")]
@@ -900,8 +1184,12 @@ mod test_ipv4 {
                 next_header: IpProtocol::Icmp,
                 payload_len: 12,
                 hop_limit: 0x40,
+                dscp: 0,
+                ecn: 0,
+                options: Ipv4Options::EMPTY,
             },
             data,
+            extensions: IcmpExtensions::EMPTY,
         };
         let ip_repr = Ipv4Repr {
             src_addr: REMOTE_IPV4,
@@ -909,6 +1197,9 @@ mod test_ipv4 {
             next_header: IpProtocol::Icmp,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 0x40,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
         };
 
         assert!(!socket.can_recv());
@@ -928,6 +1219,189 @@ mod test_ipv4 {
         );
         assert!(!socket.can_recv());
     }
+
+    #[rstest]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    fn test_send_with_hop_limit_v4(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut s = socket(buffer(0), buffer(2));
+        let checksum = ChecksumCapabilities::default();
+
+        let mut bytes = [0xff; 24];
+        let mut packet = Icmpv4Packet::new_unchecked(&mut bytes);
+        ECHOV4_REPR.emit(&mut packet, &checksum);
+        let data = &*packet.into_inner();
+
+        assert_eq!(
+            s.send_slice_with_hop_limit(data, REMOTE_IPV4.into(), 3),
+            Ok(())
+        );
+        assert_eq!(s.send_slice(data, REMOTE_IPV4.into()), Ok(()));
+
+        // The first probe uses its own hop limit, the second the default one.
+        for hop_limit in [3, 0x40] {
+            assert_eq!(
+                s.dispatch(cx, |_, (ip_repr, _)| {
+                    assert_eq!(ip_repr.hop_limit(), hop_limit);
+                    Ok::<_, ()>(())
+                }),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "the time-to-live value of a packet must not be zero")]
+    fn test_send_with_zero_hop_limit() {
+        let mut s = socket(buffer(0), buffer(1));
+        let _ = s.send_with_hop_limit(24, REMOTE_IPV4.into(), 0);
+    }
+
+    #[rstest]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    fn test_recv_time_exceeded(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut socket = socket(buffer(1), buffer(1));
+        assert_eq!(socket.bind(Endpoint::Ident(0x1234)), Ok(()));
+
+        let checksum = ChecksumCapabilities::default();
+
+        // The probe as it was sent, quoted by the router where it expired.
+        let mut bytes = [0xff; 24];
+        let mut packet = Icmpv4Packet::new_unchecked(&mut bytes);
+        ECHOV4_REPR.emit(&mut packet, &checksum);
+        let probe = &*packet.into_inner();
+
+        let router = Ipv4Address([10, 0, 0, 1]);
+        let icmp_repr = Icmpv4Repr::TimeExceeded {
+            reason: Icmpv4TimeExceeded::TtlExpired,
+            header: Ipv4Repr {
+                src_addr: LOCAL_IPV4,
+                dst_addr: REMOTE_IPV4,
+                next_header: IpProtocol::Icmp,
+                payload_len: 24,
+                hop_limit: 1,
+                dscp: 0,
+                ecn: 0,
+                options: Ipv4Options::EMPTY,
+            },
+            data: &probe[..8],
+            extensions: IcmpExtensions::EMPTY,
+        };
+        let ip_repr = Ipv4Repr {
+            src_addr: router,
+            dst_addr: LOCAL_IPV4,
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 0x3f,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
+        };
+
+        assert!(socket.accepts_v4(cx, &ip_repr, &icmp_repr));
+        socket.process_v4(cx, &ip_repr, &icmp_repr);
+
+        let message = socket.recv_message().unwrap();
+        assert_eq!(message.src_addr, router.into());
+        assert_eq!(message.hop_limit, 0x3f);
+        assert_eq!(message.msg_type, u8::from(Icmpv4Message::TimeExceeded));
+        assert_eq!(message.msg_code, u8::from(Icmpv4TimeExceeded::TtlExpired));
+        assert_eq!(message.quoted.len(), 28);
+        assert_eq!(&message.quoted[20..], &probe[..8]);
+        assert_eq!(message.payload.len(), 36);
+        assert!(message.extensions.is_empty());
+
+        // A probe of another socket is not accepted.
+        let mut bytes = [0xff; 24];
+        let mut packet = Icmpv4Packet::new_unchecked(&mut bytes);
+        Icmpv4Repr::EchoRequest {
+            ident: 0x4321,
+            seq_no: 0x5678,
+            data: &[0xff; 16],
+        }
+        .emit(&mut packet, &checksum);
+        let probe = &*packet.into_inner();
+        let icmp_repr = match icmp_repr {
+            Icmpv4Repr::TimeExceeded { reason, header, .. } => Icmpv4Repr::TimeExceeded {
+                reason,
+                header,
+                data: &probe[..8],
+                extensions: IcmpExtensions::EMPTY,
+            },
+            _ => unreachable!(),
+        };
+        assert!(!socket.accepts_v4(cx, &ip_repr, &icmp_repr));
+    }
+
+    #[rstest]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    fn test_recv_time_exceeded_extensions(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY], vec![0; 256]);
+        let mut socket = socket(rx_buffer, buffer(0));
+        assert_eq!(socket.bind(Endpoint::Ident(0x1234)), Ok(()));
+
+        let checksum = ChecksumCapabilities::default();
+
+        let mut bytes = [0xff; 24];
+        let mut packet = Icmpv4Packet::new_unchecked(&mut bytes);
+        ECHOV4_REPR.emit(&mut packet, &checksum);
+        let probe = &*packet.into_inner();
+
+        let labels = [0x00, 0x01, 0x21, 0x01];
+        let mut objects = [0u8; 8];
+        let extensions = IcmpExtensions::from_objects(
+            &[IcmpExtensionObjectRepr::MplsLabelStack(
+                MplsLabelStack::new(&labels).unwrap(),
+            )],
+            &mut objects,
+        )
+        .unwrap();
+        let icmp_repr = Icmpv4Repr::TimeExceeded {
+            reason: Icmpv4TimeExceeded::TtlExpired,
+            header: Ipv4Repr {
+                src_addr: LOCAL_IPV4,
+                dst_addr: REMOTE_IPV4,
+                next_header: IpProtocol::Icmp,
+                payload_len: 24,
+                hop_limit: 1,
+                dscp: 0,
+                ecn: 0,
+                options: Ipv4Options::EMPTY,
+            },
+            data: probe,
+            extensions,
+        };
+        let ip_repr = Ipv4Repr {
+            src_addr: Ipv4Address([10, 0, 0, 1]),
+            dst_addr: LOCAL_IPV4,
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 0x3f,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
+        };
+
+        assert!(socket.accepts_v4(cx, &ip_repr, &icmp_repr));
+        socket.process_v4(cx, &ip_repr, &icmp_repr);
+
+        // The quoted datagram is padded, and the extensions follow it.
+        let message = socket.recv_message().unwrap();
+        assert_eq!(message.quoted.len(), 128);
+        assert_eq!(&message.quoted[20..44], probe);
+        assert_eq!(message.extensions, extensions);
+    }
 }
 
 #[cfg(all(test, feature = "proto-ipv6"))]
@@ -960,6 +1434,8 @@ mod test_ipv6 {
         next_header: IpProtocol::Icmpv6,
         payload_len: 24,
         hop_limit: 0x40,
+        dscp: 0,
+        ecn: 0,
     };
 
     static REMOTE_IPV6_REPR: Ipv6Repr = Ipv6Repr {
@@ -968,6 +1444,8 @@ mod test_ipv6 {
         next_header: IpProtocol::Icmpv6,
         payload_len: 24,
         hop_limit: 0x40,
+        dscp: 0,
+        ecn: 0,
     };
 
     #[test]
@@ -1066,6 +1544,8 @@ mod test_ipv6 {
                         next_header: IpProtocol::Icmpv6,
                         payload_len: ECHOV6_REPR.buffer_len(),
                         hop_limit: 0x2a,
+                        dscp: 0,
+                        ecn: 0,
                     })
                 );
                 Ok::<_, ()>(())
@@ -1194,8 +1674,11 @@ mod test_ipv6 {
                 next_header: IpProtocol::Icmpv6,
                 payload_len: 12,
                 hop_limit: 0x40,
+                dscp: 0,
+                ecn: 0,
             },
             data,
+            extensions: IcmpExtensions::EMPTY,
         };
         let ip_repr = Ipv6Repr {
             src_addr: REMOTE_IPV6,
@@ -1203,6 +1686,8 @@ mod test_ipv6 {
             next_header: IpProtocol::Icmpv6,
             payload_len: icmp_repr.buffer_len(),
             hop_limit: 0x40,
+            dscp: 0,
+            ecn: 0,
         };
 
         assert!(!socket.can_recv());
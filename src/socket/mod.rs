@@ -9,6 +9,22 @@ interface. Specifically, in the Berkeley interface the buffering is implicit:
 the operating system decides on the good size for a buffer and manages it.
 The interface implemented by this module uses explicit buffering: you decide on the good
 size for a buffer, allocate it, and let the networking stack use it.
+
+# Traffic class
+
+The TCP, UDP and ICMP sockets mark their outgoing packets with a Differentiated Services
+Code Point, set with their `set_dscp` method, and the UDP and ICMP sockets with
+Explicit Congestion Notification bits, set with `set_ecn`. TCP packets are never marked
+as ECN-capable.
+
+The DSCP is placed in the upper six bits of the IPv4 Type of Service or the IPv6
+Traffic Class field, see [RFC 2474]. Sockets use the default forwarding class (0)
+unless told otherwise; e.g. voice traffic is commonly marked Expedited Forwarding (46).
+The ECN bits are the lower two bits of the field, see [RFC 3168]. Sockets mark their
+packets as not ECN-capable (0) unless told otherwise.
+
+[RFC 2474]: https://tools.ietf.org/html/rfc2474
+[RFC 3168]: https://tools.ietf.org/html/rfc3168
 */
 
 use crate::iface::Context;
@@ -18,6 +34,10 @@ use crate::time::Instant;
 pub mod dhcpv4;
 #[cfg(feature = "socket-dns")]
 pub mod dns;
+#[cfg(feature = "socket-ethernet")]
+pub mod ethernet;
+#[cfg(any(feature = "socket-raw", feature = "socket-icmp"))]
+pub mod filter;
 #[cfg(feature = "socket-icmp")]
 pub mod icmp;
 #[cfg(feature = "socket-raw")]
@@ -69,6 +89,8 @@ pub enum Socket<'a> {
     Dhcpv4(dhcpv4::Socket<'a>),
     #[cfg(feature = "socket-dns")]
     Dns(dns::Socket<'a>),
+    #[cfg(feature = "socket-ethernet")]
+    Ethernet(ethernet::Socket<'a>),
 }
 
 impl<'a> Socket<'a> {
@@ -86,6 +108,8 @@ impl<'a> Socket<'a> {
             Socket::Dhcpv4(s) => s.poll_at(cx),
             #[cfg(feature = "socket-dns")]
             Socket::Dns(s) => s.poll_at(cx),
+            #[cfg(feature = "socket-ethernet")]
+            Socket::Ethernet(s) => s.poll_at(cx),
         }
     }
 }
@@ -139,3 +163,5 @@ from_socket!(tcp::Socket<'a>, Tcp);
 from_socket!(dhcpv4::Socket<'a>, Dhcpv4);
 #[cfg(feature = "socket-dns")]
 from_socket!(dns::Socket<'a>, Dns);
+#[cfg(feature = "socket-ethernet")]
+from_socket!(ethernet::Socket<'a>, Ethernet);
@@ -3,12 +3,13 @@ use core::cmp::min;
 use core::task::Waker;
 
 use crate::iface::Context;
+use crate::socket::filter::Program;
 use crate::socket::PollAt;
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
 
 use crate::storage::Empty;
-use crate::wire::{IpProtocol, IpRepr, IpVersion};
+use crate::wire::{IpAddress, IpProtocol, IpRepr, IpVersion};
 #[cfg(feature = "proto-ipv4")]
 use crate::wire::{Ipv4Packet, Ipv4Repr};
 #[cfg(feature = "proto-ipv6")]
@@ -80,12 +81,17 @@ pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, ()>;
 
 /// A raw IP socket.
 ///
-/// A raw socket is bound to a specific IP protocol, and owns
-/// transmit and receive packet buffers.
+/// A raw socket is bound to a specific IP version and protocol, or to any of them,
+/// and owns transmit and receive packet buffers.
 #[derive(Debug)]
 pub struct Socket<'a> {
-    ip_version: IpVersion,
-    ip_protocol: IpProtocol,
+    ip_version: Option<IpVersion>,
+    ip_protocol: Option<IpProtocol>,
+    unclaimed_only: bool,
+    header_included: bool,
+    remote_address: Option<IpAddress>,
+    hop_limit: Option<u8>,
+    filter: Option<Program<'a>>,
     rx_buffer: PacketBuffer<'a>,
     tx_buffer: PacketBuffer<'a>,
     #[cfg(feature = "async")]
@@ -97,15 +103,23 @@ pub struct Socket<'a> {
 impl<'a> Socket<'a> {
     /// Create a raw IP socket bound to the given IP version and datagram protocol,
     /// with the given buffers.
+    ///
+    /// A socket created with `None` as IP version or protocol receives the packets
+    /// of any IP version or protocol.
     pub fn new(
-        ip_version: IpVersion,
-        ip_protocol: IpProtocol,
+        ip_version: Option<IpVersion>,
+        ip_protocol: Option<IpProtocol>,
         rx_buffer: PacketBuffer<'a>,
         tx_buffer: PacketBuffer<'a>,
     ) -> Socket<'a> {
         Socket {
             ip_version,
             ip_protocol,
+            unclaimed_only: false,
+            header_included: true,
+            remote_address: None,
+            hop_limit: None,
+            filter: None,
             rx_buffer,
             tx_buffer,
             #[cfg(feature = "async")]
@@ -152,16 +166,135 @@ impl<'a> Socket<'a> {
 
     /// Return the IP version the socket is bound to.
     #[inline]
-    pub fn ip_version(&self) -> IpVersion {
+    pub fn ip_version(&self) -> Option<IpVersion> {
         self.ip_version
     }
 
     /// Return the IP protocol the socket is bound to.
     #[inline]
-    pub fn ip_protocol(&self) -> IpProtocol {
+    pub fn ip_protocol(&self) -> Option<IpProtocol> {
         self.ip_protocol
     }
 
+    /// Return whether the socket only receives the packets no other socket claimed.
+    ///
+    /// See also the [set_unclaimed_only](#method.set_unclaimed_only) method.
+    #[inline]
+    pub fn unclaimed_only(&self) -> bool {
+        self.unclaimed_only
+    }
+
+    /// Set whether the socket only receives the packets no other socket claimed.
+    ///
+    /// Such a socket only receives the packets that were not accepted by any other raw
+    /// socket, and whose protocol is not handled by the interface itself; these packets
+    /// would otherwise be answered with an ICMP Protocol Unreachable or Parameter Problem
+    /// message. This is mostly useful with a wildcard protocol.
+    pub fn set_unclaimed_only(&mut self, unclaimed_only: bool) {
+        self.unclaimed_only = unclaimed_only
+    }
+
+    /// Return whether the packets sent through the socket include their IP header.
+    ///
+    /// See also the [set_header_included](#method.set_header_included) method.
+    #[inline]
+    pub fn header_included(&self) -> bool {
+        self.header_included
+    }
+
+    /// Set whether the packets sent through the socket include their IP header,
+    /// like the `IP_HDRINCL` socket option.
+    ///
+    /// By default, the packets sent include their IP header. Otherwise, they only
+    /// contain the IP payload, and the interface builds the header from the socket
+    /// parameters: the IP protocol, the [remote address], and the [hop limit]. The
+    /// source address is chosen by the interface. Packets are dropped if the socket
+    /// has no protocol or no remote address when they are sent.
+    ///
+    /// [remote address]: #method.set_remote_address
+    /// [hop limit]: #method.set_hop_limit
+    pub fn set_header_included(&mut self, header_included: bool) {
+        self.header_included = header_included
+    }
+
+    /// Return the destination address of the packets sent without their IP header.
+    ///
+    /// See also the [set_remote_address](#method.set_remote_address) method.
+    #[inline]
+    pub fn remote_address(&self) -> Option<IpAddress> {
+        self.remote_address
+    }
+
+    /// Set the destination address of the packets sent without their IP header.
+    ///
+    /// The address is read when the packets are sent, not when they are enqueued.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the address is unspecified, or does not match the IP
+    /// version of the socket.
+    pub fn set_remote_address(&mut self, remote_address: Option<IpAddress>) {
+        if let Some(addr) = remote_address {
+            if addr.is_unspecified() {
+                panic!("the remote address of a raw socket must be specified")
+            }
+            if self
+                .ip_version
+                .map_or(false, |version| version != addr.version())
+            {
+                panic!("the remote address of a raw socket must match its IP version")
+            }
+        }
+
+        self.remote_address = remote_address
+    }
+
+    /// Return the time-to-live (IPv4) or hop limit (IPv6) value used in the packets
+    /// sent without their IP header.
+    ///
+    /// See also the [set_hop_limit](#method.set_hop_limit) method.
+    #[inline]
+    pub fn hop_limit(&self) -> Option<u8> {
+        self.hop_limit
+    }
+
+    /// Set the time-to-live (IPv4) or hop limit (IPv6) value used in the packets
+    /// sent without their IP header.
+    ///
+    /// A socket without an explicitly set hop limit value uses the default [IANA recommended]
+    /// value (64). The value is read when the packets are sent, not when they are enqueued.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a hop limit value of 0 is given. See [RFC 1122 § 3.2.1.7].
+    ///
+    /// [IANA recommended]: https://www.iana.org/assignments/ip-parameters/ip-parameters.xhtml
+    /// [RFC 1122 § 3.2.1.7]: https://tools.ietf.org/html/rfc1122#section-3.2.1.7
+    pub fn set_hop_limit(&mut self, hop_limit: Option<u8>) {
+        // A host MUST NOT send a datagram with a hop limit value of 0
+        if let Some(0) = hop_limit {
+            panic!("the time-to-live value of a packet must not be zero")
+        }
+
+        self.hop_limit = hop_limit
+    }
+
+    /// Return the packet filter program attached to the socket.
+    ///
+    /// See also the [set_filter](#method.set_filter) method.
+    #[inline]
+    pub fn filter(&self) -> Option<Program<'a>> {
+        self.filter
+    }
+
+    /// Attach a packet filter program to the socket, or detach it with `None`.
+    ///
+    /// The program runs on every packet, IP header included, the socket would receive, and only the
+    /// ones it accepts are copied into the receive buffer.
+    pub fn set_filter(&mut self, filter: Option<Program<'a>>) {
+        self.filter = filter
+    }
+
     /// Check whether the transmit buffer is full.
     #[inline]
     pub fn can_send(&self) -> bool {
@@ -208,7 +341,9 @@ impl<'a> Socket<'a> {
     /// IP version or protocol, the packet will be silently dropped.
     ///
     /// **Note:** The IP header is parsed and re-serialized, and may not match
-    /// the header actually transmitted bit for bit.
+    /// the header actually transmitted bit for bit. When the socket does not
+    /// [include the header](#method.set_header_included), the buffer only holds the
+    /// IP payload.
     pub fn send(&mut self, size: usize) -> Result<&mut [u8], SendError> {
         let packet_buf = self
             .tx_buffer
@@ -216,7 +351,7 @@ impl<'a> Socket<'a> {
             .map_err(|_| SendError::BufferFull)?;
 
         net_trace!(
-            "raw:{}:{}: buffer to send {} octets",
+            "raw:{:?}:{:?}: buffer to send {} octets",
             self.ip_version,
             self.ip_protocol,
             packet_buf.len()
@@ -238,7 +373,7 @@ impl<'a> Socket<'a> {
             .map_err(|_| SendError::BufferFull)?;
 
         net_trace!(
-            "raw:{}:{}: buffer to send {} octets",
+            "raw:{:?}:{:?}: buffer to send {} octets",
             self.ip_version,
             self.ip_protocol,
             size
@@ -265,7 +400,7 @@ impl<'a> Socket<'a> {
         let ((), packet_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
 
         net_trace!(
-            "raw:{}:{}: receive {} buffered octets",
+            "raw:{:?}:{:?}: receive {} buffered octets",
             self.ip_version,
             self.ip_protocol,
             packet_buf.len()
@@ -299,7 +434,7 @@ impl<'a> Socket<'a> {
         let ((), packet_buf) = self.rx_buffer.peek().map_err(|_| RecvError::Exhausted)?;
 
         net_trace!(
-            "raw:{}:{}: receive {} buffered octets",
+            "raw:{:?}:{:?}: receive {} buffered octets",
             self.ip_version,
             self.ip_protocol,
             packet_buf.len()
@@ -328,16 +463,34 @@ impl<'a> Socket<'a> {
     }
 
     pub(crate) fn accepts(&self, ip_repr: &IpRepr) -> bool {
-        if ip_repr.version() != self.ip_version {
+        if self
+            .ip_version
+            .map_or(false, |version| ip_repr.version() != version)
+        {
             return false;
         }
-        if ip_repr.next_header() != self.ip_protocol {
+        if self
+            .ip_protocol
+            .map_or(false, |protocol| ip_repr.next_header() != protocol)
+        {
             return false;
         }
 
         true
     }
 
+    pub(crate) fn passes_filter(&self, cx: &mut Context, ip_repr: &IpRepr, payload: &[u8]) -> bool {
+        let filter = match self.filter {
+            Some(filter) => filter,
+            None => return true,
+        };
+
+        let mut header = [0u8; 60];
+        let header = &mut header[..ip_repr.header_len()];
+        ip_repr.emit(&mut *header, &cx.checksum_caps());
+        filter.run_split(header, payload) != 0
+    }
+
     pub(crate) fn process(&mut self, cx: &mut Context, ip_repr: &IpRepr, payload: &[u8]) {
         debug_assert!(self.accepts(ip_repr));
 
@@ -345,7 +498,7 @@ impl<'a> Socket<'a> {
         let total_len = header_len + payload.len();
 
         net_trace!(
-            "raw:{}:{}: receiving {} octets",
+            "raw:{:?}:{:?}: receiving {} octets",
             self.ip_version,
             self.ip_protocol,
             total_len
@@ -357,7 +510,7 @@ impl<'a> Socket<'a> {
                 buf[header_len..].copy_from_slice(payload);
             }
             Err(_) => net_trace!(
-                "raw:{}:{}: buffer full, dropped incoming packet",
+                "raw:{:?}:{:?}: buffer full, dropped incoming packet",
                 self.ip_version,
                 self.ip_protocol
             ),
@@ -373,8 +526,32 @@ impl<'a> Socket<'a> {
     {
         let ip_protocol = self.ip_protocol;
         let ip_version = self.ip_version;
+        let header_included = self.header_included;
+        let remote_address = self.remote_address;
+        let hop_limit = self.hop_limit.unwrap_or(64);
         let _checksum_caps = &cx.checksum_caps();
         let res = self.tx_buffer.dequeue_with(|&mut (), buffer| {
+            if !header_included {
+                let (next_header, dst_addr) = match (ip_protocol, remote_address) {
+                    (Some(next_header), Some(dst_addr)) => (next_header, dst_addr),
+                    _ => {
+                        net_trace!("raw: no protocol or remote address to send to, dropping.");
+                        return Ok(());
+                    }
+                };
+                let src_addr = match cx.get_source_address(&dst_addr) {
+                    Some(addr) => addr,
+                    None => {
+                        net_trace!("raw: no suitable source address, dropping.");
+                        return Ok(());
+                    }
+                };
+                let ip_repr = IpRepr::new(src_addr, dst_addr, next_header, buffer.len(), hop_limit);
+
+                net_trace!("raw:{:?}:{:?}: sending", ip_version, ip_protocol);
+                return emit(cx, (ip_repr, &*buffer));
+            }
+
             match IpVersion::of_packet(buffer) {
                 #[cfg(feature = "proto-ipv4")]
                 Ok(IpVersion::Ipv4) => {
@@ -385,7 +562,7 @@ impl<'a> Socket<'a> {
                             return Ok(());
                         }
                     };
-                    if packet.next_header() != ip_protocol {
+                    if ip_protocol.map_or(false, |protocol| packet.next_header() != protocol) {
                         net_trace!("raw: sent packet with wrong ip protocol, dropping.");
                         return Ok(());
                     }
@@ -405,7 +582,7 @@ impl<'a> Socket<'a> {
                             return Ok(());
                         }
                     };
-                    net_trace!("raw:{}:{}: sending", ip_version, ip_protocol);
+                    net_trace!("raw:{:?}:{:?}: sending", ip_version, ip_protocol);
                     emit(cx, (IpRepr::Ipv4(ipv4_repr), packet.payload()))
                 }
                 #[cfg(feature = "proto-ipv6")]
@@ -417,7 +594,7 @@ impl<'a> Socket<'a> {
                             return Ok(());
                         }
                     };
-                    if packet.next_header() != ip_protocol {
+                    if ip_protocol.map_or(false, |protocol| packet.next_header() != protocol) {
                         net_trace!("raw: sent ipv6 packet with wrong ip protocol, dropping.");
                         return Ok(());
                     }
@@ -430,7 +607,7 @@ impl<'a> Socket<'a> {
                         }
                     };
 
-                    net_trace!("raw:{}:{}: sending", ip_version, ip_protocol);
+                    net_trace!("raw:{:?}:{:?}: sending", ip_version, ip_protocol);
                     emit(cx, (IpRepr::Ipv6(ipv6_repr), packet.payload()))
                 }
                 Err(_) => {
@@ -468,7 +645,7 @@ mod test {
     use super::*;
     use crate::wire::IpRepr;
     #[cfg(feature = "proto-ipv4")]
-    use crate::wire::{Ipv4Address, Ipv4Repr};
+    use crate::wire::{Ipv4Address, Ipv4Options, Ipv4Repr};
     #[cfg(feature = "proto-ipv6")]
     use crate::wire::{Ipv6Address, Ipv6Repr};
 
@@ -485,8 +662,8 @@ mod test {
             tx_buffer: PacketBuffer<'static>,
         ) -> Socket<'static> {
             Socket::new(
-                IpVersion::Ipv4,
-                IpProtocol::Unknown(IP_PROTO),
+                Some(IpVersion::Ipv4),
+                Some(IpProtocol::Unknown(IP_PROTO)),
                 rx_buffer,
                 tx_buffer,
             )
@@ -499,6 +676,9 @@ mod test {
             next_header: IpProtocol::Unknown(IP_PROTO),
             payload_len: 4,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
         });
         pub const PACKET_BYTES: [u8; 24] = [
             0x45, 0x00, 0x00, 0x18, 0x00, 0x00, 0x40, 0x00, 0x40, 0x3f, 0x00, 0x00, 0x0a, 0x00,
@@ -516,8 +696,8 @@ mod test {
             tx_buffer: PacketBuffer<'static>,
         ) -> Socket<'static> {
             Socket::new(
-                IpVersion::Ipv6,
-                IpProtocol::Unknown(IP_PROTO),
+                Some(IpVersion::Ipv6),
+                Some(IpProtocol::Unknown(IP_PROTO)),
                 rx_buffer,
                 tx_buffer,
             )
@@ -536,6 +716,8 @@ mod test {
             next_header: IpProtocol::Unknown(IP_PROTO),
             payload_len: 4,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
         });
 
         pub const PACKET_BYTES: [u8; 44] = [
@@ -823,8 +1005,8 @@ mod test {
         #[cfg(feature = "proto-ipv4")]
         {
             let socket = Socket::new(
-                IpVersion::Ipv4,
-                IpProtocol::Unknown(ipv4_locals::IP_PROTO + 1),
+                Some(IpVersion::Ipv4),
+                Some(IpProtocol::Unknown(ipv4_locals::IP_PROTO + 1)),
                 buffer(1),
                 buffer(1),
             );
@@ -835,8 +1017,8 @@ mod test {
         #[cfg(feature = "proto-ipv6")]
         {
             let socket = Socket::new(
-                IpVersion::Ipv6,
-                IpProtocol::Unknown(ipv6_locals::IP_PROTO + 1),
+                Some(IpVersion::Ipv6),
+                Some(IpProtocol::Unknown(ipv6_locals::IP_PROTO + 1)),
                 buffer(1),
                 buffer(1),
             );
@@ -845,4 +1027,116 @@ mod test {
             assert!(!socket.accepts(&ipv4_locals::HEADER_REPR));
         }
     }
+
+    #[test]
+    fn test_accepts_wildcard() {
+        let socket = Socket::new(None, None, buffer(1), buffer(1));
+        #[cfg(feature = "proto-ipv4")]
+        assert!(socket.accepts(&ipv4_locals::HEADER_REPR));
+        #[cfg(feature = "proto-ipv6")]
+        assert!(socket.accepts(&ipv6_locals::HEADER_REPR));
+
+        #[cfg(feature = "proto-ipv4")]
+        {
+            let socket = Socket::new(Some(IpVersion::Ipv4), None, buffer(1), buffer(1));
+            assert!(socket.accepts(&ipv4_locals::HEADER_REPR));
+            #[cfg(feature = "proto-ipv6")]
+            assert!(!socket.accepts(&ipv6_locals::HEADER_REPR));
+        }
+    }
+
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    fn test_send_header_not_included(#[case] medium: Medium) {
+        #[cfg(feature = "proto-ipv4")]
+        {
+            let (mut iface, _, _) = setup(medium);
+            let cx = iface.context();
+            let mut socket = ipv4_locals::socket(buffer(0), buffer(2));
+            socket.set_header_included(false);
+            socket.set_hop_limit(Some(1));
+
+            // Dropped without a remote address.
+            assert_eq!(socket.send_slice(&ipv4_locals::PACKET_PAYLOAD), Ok(()));
+            assert_eq!(socket.dispatch(cx, |_, _| unreachable!()), Ok::<_, ()>(()));
+
+            socket.set_remote_address(Some(Ipv4Address([192, 168, 1, 2]).into()));
+            assert_eq!(socket.send_slice(&ipv4_locals::PACKET_PAYLOAD), Ok(()));
+            assert_eq!(
+                socket.dispatch(cx, |_, (ip_repr, ip_payload)| {
+                    assert_eq!(
+                        ip_repr,
+                        IpRepr::Ipv4(Ipv4Repr {
+                            src_addr: Ipv4Address([192, 168, 1, 1]),
+                            dst_addr: Ipv4Address([192, 168, 1, 2]),
+                            next_header: IpProtocol::Unknown(ipv4_locals::IP_PROTO),
+                            payload_len: 4,
+                            hop_limit: 1,
+                            dscp: 0,
+                            ecn: 0,
+                            options: Ipv4Options::EMPTY,
+                        })
+                    );
+                    assert_eq!(ip_payload, &ipv4_locals::PACKET_PAYLOAD);
+                    Ok::<_, ()>(())
+                }),
+                Ok(())
+            );
+        }
+        #[cfg(feature = "proto-ipv6")]
+        {
+            let (mut iface, _, _) = setup(medium);
+            let cx = iface.context();
+            let mut socket = ipv6_locals::socket(buffer(0), buffer(1));
+            socket.set_header_included(false);
+            socket.set_remote_address(Some(Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2).into()));
+
+            assert_eq!(socket.send_slice(&ipv6_locals::PACKET_PAYLOAD), Ok(()));
+            assert_eq!(
+                socket.dispatch(cx, |_, (ip_repr, ip_payload)| {
+                    assert_eq!(ip_repr, ipv6_locals::HEADER_REPR);
+                    assert_eq!(ip_payload, &ipv6_locals::PACKET_PAYLOAD);
+                    Ok::<_, ()>(())
+                }),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "proto-ipv4", feature = "proto-ipv6"))]
+    #[should_panic(expected = "the remote address of a raw socket must match its IP version")]
+    fn test_set_remote_address_wrong_version() {
+        let mut socket = ipv4_locals::socket(buffer(0), buffer(1));
+        socket.set_remote_address(Some(Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2).into()));
+    }
+
+    #[cfg(feature = "proto-ipv4")]
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    fn test_passes_filter(#[case] medium: Medium) {
+        use crate::socket::filter::{Instruction, Program};
+
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+        let mut socket = ipv4_locals::socket(buffer(1), buffer(0));
+        assert!(socket.passes_filter(cx, &ipv4_locals::HEADER_REPR, &[0xbb; 4]));
+
+        // Only receive the packets whose payload starts with 0xaa.
+        const FIRST_BYTE: [Instruction; 4] = [
+            Instruction::new(0x30, 0, 0, 20),   // ldb [20]
+            Instruction::new(0x15, 0, 1, 0xaa), // jeq #0xaa, accept, drop
+            Instruction::new(0x06, 0, 0, 1),    // accept: ret #1
+            Instruction::new(0x06, 0, 0, 0),    // drop: ret #0
+        ];
+        socket.set_filter(Some(Program::new(&FIRST_BYTE).unwrap()));
+        assert!(socket.passes_filter(cx, &ipv4_locals::HEADER_REPR, &ipv4_locals::PACKET_PAYLOAD));
+        assert!(!socket.passes_filter(cx, &ipv4_locals::HEADER_REPR, &[0xbb; 4]));
+    }
 }
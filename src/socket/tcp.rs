@@ -426,6 +426,8 @@ pub struct Socket<'a> {
     keep_alive: Option<Duration>,
     /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     hop_limit: Option<u8>,
+    /// The Differentiated Services Code Point used in outgoing packets.
+    dscp: u8,
     /// Address passed to listen(). Listen address is set when listen() is called and
     /// used every time the socket is reset back to the LISTEN state.
     listen_endpoint: IpListenEndpoint,
@@ -520,6 +522,7 @@ impl<'a> Socket<'a> {
             timeout: None,
             keep_alive: None,
             hop_limit: None,
+            dscp: 0,
             listen_endpoint: IpListenEndpoint::default(),
             tuple: None,
             local_seq_no: TcpSeqNumber::default(),
@@ -758,6 +761,27 @@ impl<'a> Socket<'a> {
         self.hop_limit = hop_limit
     }
 
+    /// Return the Differentiated Services Code Point used in outgoing packets.
+    ///
+    /// See also the [set_dscp](#method.set_dscp) method
+    pub fn dscp(&self) -> u8 {
+        self.dscp
+    }
+
+    /// Set the Differentiated Services Code Point used in outgoing packets, see the
+    /// [traffic class](crate::socket#traffic-class) of the sockets.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the value does not fit in six bits.
+    pub fn set_dscp(&mut self, dscp: u8) {
+        if dscp > 0x3f {
+            panic!("the DSCP value of a packet must fit in six bits")
+        }
+
+        self.dscp = dscp
+    }
+
     /// Return the local endpoint, or None if not connected.
     #[inline]
     pub fn local_endpoint(&self) -> Option<IpEndpoint> {
@@ -2056,7 +2080,8 @@ impl<'a> Socket<'a> {
         };
 
         // Max segment size we're able to send due to MTU limitations.
-        let local_mss = cx.ip_mtu() - ip_header_len - TCP_HEADER_LEN;
+        let local_mss =
+            cx.ip_mtu_to(&self.tuple.unwrap().remote.addr) - ip_header_len - TCP_HEADER_LEN;
 
         // The effective max segment size, taking into account our and remote's limits.
         let effective_mss = local_mss.min(self.remote_mss);
@@ -2232,6 +2257,7 @@ impl<'a> Socket<'a> {
             0,
             self.hop_limit.unwrap_or(64),
         );
+        ip_repr.set_dscp(self.dscp);
 
         // Construct the basic TCP representation, an empty ACK packet.
         // We'll adjust this to be more specific as needed.
@@ -2306,7 +2332,7 @@ impl<'a> Socket<'a> {
                 // 3. MSS we can send, determined by our MTU.
                 let size = win_limit
                     .min(self.remote_mss)
-                    .min(cx.ip_mtu() - ip_repr.header_len() - TCP_HEADER_LEN);
+                    .min(cx.ip_mtu_to(&ip_repr.dst_addr()) - ip_repr.header_len() - TCP_HEADER_LEN);
 
                 let offset = self.remote_last_seq - self.local_seq_no;
                 repr.payload = self.tx_buffer.get_allocated(offset, size);
@@ -2368,7 +2394,8 @@ impl<'a> Socket<'a> {
 
         if repr.control == TcpControl::Syn {
             // Fill the MSS option. See RFC 6691 for an explanation of this calculation.
-            let max_segment_size = cx.ip_mtu() - ip_repr.header_len() - TCP_HEADER_LEN;
+            let max_segment_size =
+                cx.ip_mtu_to(&ip_repr.dst_addr()) - ip_repr.header_len() - TCP_HEADER_LEN;
             repr.max_seg_size = Some(max_segment_size as u16);
         }
 
@@ -2530,6 +2557,7 @@ mod test {
         if #[cfg(feature = "proto-ipv4")] {
             use crate::wire::Ipv4Address as IpvXAddress;
             use crate::wire::Ipv4Repr as IpvXRepr;
+            use crate::wire::Ipv4Options;
             use IpRepr::Ipv4 as IpReprIpvX;
 
             const LOCAL_ADDR: IpvXAddress = IpvXAddress([192, 168, 1, 1]);
@@ -2562,6 +2590,10 @@ mod test {
         next_header: IpProtocol::Tcp,
         payload_len: 20,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        #[cfg(feature = "proto-ipv4")]
+        options: Ipv4Options::EMPTY,
     });
     const SEND_TEMPL: TcpRepr<'static> = TcpRepr {
         src_port: REMOTE_PORT,
@@ -2582,6 +2614,10 @@ mod test {
         next_header: IpProtocol::Tcp,
         payload_len: 20,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        #[cfg(feature = "proto-ipv4")]
+        options: Ipv4Options::EMPTY,
     });
     const RECV_TEMPL: TcpRepr<'static> = TcpRepr {
         src_port: LOCAL_PORT,
@@ -2632,6 +2668,10 @@ mod test {
             next_header: IpProtocol::Tcp,
             payload_len: repr.buffer_len(),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            #[cfg(feature = "proto-ipv4")]
+            options: Ipv4Options::EMPTY,
         });
         net_trace!("send: {}", repr);
 
@@ -6713,6 +6753,24 @@ mod test {
         assert_eq!(s.hop_limit(), Some(0x2a));
     }
 
+    #[test]
+    fn test_set_dscp() {
+        let mut s = socket_syn_received();
+
+        s.set_dscp(46);
+        assert_eq!(
+            s.socket.dispatch(&mut s.cx, |_, (ip_repr, _)| {
+                assert_eq!(ip_repr.dscp(), 46);
+                assert_eq!(ip_repr.ecn(), 0);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+
+        s.reset();
+        assert_eq!(s.dscp(), 46);
+    }
+
     #[test]
     #[should_panic(expected = "the time-to-live value of a packet must not be zero")]
     fn test_set_hop_limit_zero() {
@@ -7345,6 +7403,10 @@ mod test {
             next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            #[cfg(feature = "proto-ipv4")]
+            options: Ipv4Options::EMPTY,
         });
         assert!(s.socket.accepts(&mut s.cx, &ip_repr, &tcp_repr));
 
@@ -7354,6 +7416,10 @@ mod test {
             next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            #[cfg(feature = "proto-ipv4")]
+            options: Ipv4Options::EMPTY,
         });
         assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_src, &tcp_repr));
 
@@ -7363,6 +7429,10 @@ mod test {
             next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            #[cfg(feature = "proto-ipv4")]
+            options: Ipv4Options::EMPTY,
         });
         assert!(!s.socket.accepts(&mut s.cx, &ip_repr_wrong_dst, &tcp_repr));
     }
@@ -8,21 +8,44 @@ use crate::socket::PollAt;
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
 use crate::storage::Empty;
-use crate::wire::{IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, UdpRepr};
+use crate::wire::{IpAddress, IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, UdpRepr};
 
 /// Metadata for a sent or received UDP packet.
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct UdpMetadata {
     pub endpoint: IpEndpoint,
+    /// The local address of the datagram.
+    ///
+    /// For received datagrams, this is their destination address, which tells unicast,
+    /// broadcast and multicast datagrams apart on a socket bound to any address. When
+    /// sending, this is the source address to use instead of the one the socket is bound
+    /// to or the one selected by the interface. An address that is not a unicast address
+    /// of the interface, such as the destination of a received broadcast datagram, is
+    /// ignored, as is a loopback address or a link-local address when the destination is
+    /// further away.
+    pub local_address: Option<IpAddress>,
     pub meta: PacketMeta,
+    /// Differentiated Services Code Point for this datagram, overriding the socket setting.
+    ///
+    /// Only used when sending, where it must fit in six bits; always `None` for received
+    /// datagrams.
+    pub dscp: Option<u8>,
+    /// Explicit Congestion Notification bits for this datagram, overriding the socket setting.
+    ///
+    /// Only used when sending, where they must fit in two bits; always `None` for received
+    /// datagrams.
+    pub ecn: Option<u8>,
 }
 
 impl<T: Into<IpEndpoint>> From<T> for UdpMetadata {
     fn from(value: T) -> Self {
         Self {
             endpoint: value.into(),
+            local_address: None,
             meta: PacketMeta::default(),
+            dscp: None,
+            ecn: None,
         }
     }
 }
@@ -37,6 +60,16 @@ impl core::fmt::Display for UdpMetadata {
     }
 }
 
+/// Panic if the DSCP or ECN value of a datagram to send does not fit its field.
+fn check_traffic_class(meta: &UdpMetadata) {
+    if meta.dscp.map_or(false, |dscp| dscp > 0x3f) {
+        panic!("the DSCP value of a packet must fit in six bits")
+    }
+    if meta.ecn.map_or(false, |ecn| ecn > 0x03) {
+        panic!("the ECN value of a packet must fit in two bits")
+    }
+}
+
 /// A UDP packet metadata.
 pub type PacketMetadata = crate::storage::PacketMetadata<UdpMetadata>;
 
@@ -63,6 +96,26 @@ impl core::fmt::Display for BindError {
 #[cfg(feature = "std")]
 impl std::error::Error for BindError {}
 
+/// Error returned by [`Socket::connect`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConnectError {
+    InvalidState,
+    Unaddressable,
+}
+
+impl core::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ConnectError::InvalidState => write!(f, "invalid state"),
+            ConnectError::Unaddressable => write!(f, "unaddressable"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConnectError {}
+
 /// Error returned by [`Socket::send`]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -110,10 +163,27 @@ impl std::error::Error for RecvError {}
 #[derive(Debug)]
 pub struct Socket<'a> {
     endpoint: IpListenEndpoint,
+    /// The only remote endpoint datagrams are accepted from, once connected.
+    remote_endpoint: Option<IpEndpoint>,
+    /// Whether multicast and broadcast datagrams are shared with the other sockets on the port.
+    reuse_port: bool,
+    /// The checksum coverage of outgoing UDP-Lite datagrams, if the socket uses UDP-Lite.
+    checksum_coverage: Option<u16>,
+    /// The minimum checksum coverage of incoming UDP-Lite datagrams.
+    min_checksum_coverage: u16,
+    /// The size of the datagrams the packets in the transmit buffer are split into.
+    segment_size: Option<u16>,
+    /// The segment size of the first packet in the transmit buffer and the amount of its
+    /// octets already sent, once it started being split into several datagrams.
+    segment_progress: Option<(usize, usize)>,
     rx_buffer: PacketBuffer<'a>,
     tx_buffer: PacketBuffer<'a>,
     /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
     hop_limit: Option<u8>,
+    /// The Differentiated Services Code Point used in outgoing packets.
+    dscp: u8,
+    /// The Explicit Congestion Notification bits used in outgoing packets.
+    ecn: u8,
     #[cfg(feature = "async")]
     rx_waker: WakerRegistration,
     #[cfg(feature = "async")]
@@ -125,9 +195,17 @@ impl<'a> Socket<'a> {
     pub fn new(rx_buffer: PacketBuffer<'a>, tx_buffer: PacketBuffer<'a>) -> Socket<'a> {
         Socket {
             endpoint: IpListenEndpoint::default(),
+            remote_endpoint: None,
+            reuse_port: false,
+            checksum_coverage: None,
+            min_checksum_coverage: 0,
+            segment_size: None,
+            segment_progress: None,
             rx_buffer,
             tx_buffer,
             hop_limit: None,
+            dscp: 0,
+            ecn: 0,
             #[cfg(feature = "async")]
             rx_waker: WakerRegistration::new(),
             #[cfg(feature = "async")]
@@ -203,6 +281,142 @@ impl<'a> Socket<'a> {
         self.hop_limit = hop_limit
     }
 
+    /// Return the Differentiated Services Code Point used in outgoing packets.
+    ///
+    /// See also the [set_dscp](#method.set_dscp) method
+    pub fn dscp(&self) -> u8 {
+        self.dscp
+    }
+
+    /// Set the Differentiated Services Code Point used in outgoing packets, see the
+    /// [traffic class](crate::socket#traffic-class) of the sockets.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the value does not fit in six bits.
+    pub fn set_dscp(&mut self, dscp: u8) {
+        if dscp > 0x3f {
+            panic!("the DSCP value of a packet must fit in six bits")
+        }
+
+        self.dscp = dscp
+    }
+
+    /// Return the Explicit Congestion Notification bits used in outgoing packets.
+    ///
+    /// See also the [set_ecn](#method.set_ecn) method
+    pub fn ecn(&self) -> u8 {
+        self.ecn
+    }
+
+    /// Set the Explicit Congestion Notification bits used in outgoing packets, see the
+    /// [traffic class](crate::socket#traffic-class) of the sockets.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the value does not fit in two bits.
+    pub fn set_ecn(&mut self, ecn: u8) {
+        if ecn > 0x03 {
+            panic!("the ECN value of a packet must fit in two bits")
+        }
+
+        self.ecn = ecn
+    }
+
+    /// Return whether the socket shares multicast and broadcast datagrams with the other
+    /// sockets bound to its port.
+    ///
+    /// See also the [set_reuse_port](#method.set_reuse_port) method
+    pub fn reuse_port(&self) -> bool {
+        self.reuse_port
+    }
+
+    /// Set whether the socket shares multicast and broadcast datagrams with the other
+    /// sockets bound to its port, like `SO_REUSEPORT` on BSD.
+    ///
+    /// A multicast or broadcast datagram is copied to every socket accepting it that has
+    /// this flag set, and to the most specific socket accepting it, with or without the
+    /// flag. Other sockets without the flag do not receive it. Unicast datagrams always go
+    /// to a single socket.
+    pub fn set_reuse_port(&mut self, reuse_port: bool) {
+        self.reuse_port = reuse_port
+    }
+
+    /// Return the checksum coverage of the UDP-Lite datagrams sent by the socket, or `None`
+    /// if the socket uses plain UDP.
+    ///
+    /// See also the [set_checksum_coverage](#method.set_checksum_coverage) method
+    pub fn checksum_coverage(&self) -> Option<u16> {
+        self.checksum_coverage
+    }
+
+    /// Make the socket send and receive UDP-Lite datagrams, or plain UDP datagrams with
+    /// `None`. See [RFC 3828].
+    ///
+    /// The checksum of the datagrams sent covers their first `coverage` octets, header
+    /// included, or the whole datagram if `coverage` is zero or larger than the datagram.
+    /// Errors in the rest of the payload are not detected, and such datagrams are delivered
+    /// to the application instead of being dropped. A UDP-Lite socket only receives
+    /// UDP-Lite datagrams, and a UDP socket only receives UDP datagrams.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the coverage is not zero and does not cover the header.
+    ///
+    /// [RFC 3828]: https://tools.ietf.org/html/rfc3828
+    pub fn set_checksum_coverage(&mut self, coverage: Option<u16>) {
+        if let Some(1..=7) = coverage {
+            panic!("the checksum coverage of a UDP-Lite datagram must cover its header")
+        }
+
+        self.checksum_coverage = coverage
+    }
+
+    /// Return the minimum checksum coverage of the UDP-Lite datagrams received by the socket.
+    ///
+    /// See also the [set_min_checksum_coverage](#method.set_min_checksum_coverage) method
+    pub fn min_checksum_coverage(&self) -> u16 {
+        self.min_checksum_coverage
+    }
+
+    /// Set the minimum checksum coverage of the UDP-Lite datagrams received by the socket.
+    ///
+    /// Datagrams whose checksum covers fewer octets, header included, are dropped. A socket
+    /// without an explicitly set minimum accepts any coverage (0).
+    pub fn set_min_checksum_coverage(&mut self, coverage: u16) {
+        self.min_checksum_coverage = coverage
+    }
+
+    /// Return the size of the datagrams the sent packets are split into.
+    ///
+    /// See also the [set_segment_size](#method.set_segment_size) method
+    pub fn segment_size(&self) -> Option<u16> {
+        self.segment_size
+    }
+
+    /// Set the size of the datagrams the sent packets are split into, like `UDP_SEGMENT`
+    /// on Linux.
+    ///
+    /// With a segment size, a packet larger than it is sent as several datagrams of that
+    /// size, the last one holding the remainder, so that a single large buffer can carry
+    /// many datagrams to the same endpoint. If the device supports UDP segmentation
+    /// offload (see [`DeviceCapabilities::udp_segmentation_offload`]), the packet is given
+    /// to the device in one piece instead, and the device splits it. A packet already
+    /// partly sent keeps being split with the segment size it started with.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a segment size of 0 is given.
+    ///
+    /// [`DeviceCapabilities::udp_segmentation_offload`]: crate::phy::DeviceCapabilities::udp_segmentation_offload
+    pub fn set_segment_size(&mut self, segment_size: Option<u16>) {
+        if let Some(0) = segment_size {
+            panic!("the segment size of a packet must not be zero")
+        }
+
+        self.segment_size = segment_size
+    }
+
     /// Bind the socket to the given endpoint.
     ///
     /// This function returns `Err(Error::Illegal)` if the socket was open
@@ -229,13 +443,57 @@ impl<'a> Socket<'a> {
         Ok(())
     }
 
+    /// Connect the socket to the given remote endpoint.
+    ///
+    /// A connected socket only accepts datagrams sent from the remote endpoint, and
+    /// takes precedence over the sockets bound to the same port that are not connected.
+    /// Datagrams sent with [send_connected](#method.send_connected) go to the remote
+    /// endpoint.
+    ///
+    /// This function returns `Err(ConnectError::InvalidState)` if the socket is not bound
+    /// (see [is_open](#method.is_open)), and `Err(ConnectError::Unaddressable)` if the
+    /// remote address or port is unspecified, or if the address is not of the same IP
+    /// version as the bound address.
+    pub fn connect<T: Into<IpEndpoint>>(&mut self, remote_endpoint: T) -> Result<(), ConnectError> {
+        let remote_endpoint = remote_endpoint.into();
+        if remote_endpoint.addr.is_unspecified() || remote_endpoint.port == 0 {
+            return Err(ConnectError::Unaddressable);
+        }
+        if let Some(addr) = self.endpoint.addr {
+            if addr.version() != remote_endpoint.addr.version() {
+                return Err(ConnectError::Unaddressable);
+            }
+        }
+
+        if !self.is_open() {
+            return Err(ConnectError::InvalidState);
+        }
+
+        self.remote_endpoint = Some(remote_endpoint);
+        Ok(())
+    }
+
+    /// Disconnect the socket from its remote endpoint, accepting datagrams from any
+    /// remote endpoint again.
+    pub fn disconnect(&mut self) {
+        self.remote_endpoint = None;
+    }
+
+    /// Return the remote endpoint the socket is connected to, if any.
+    #[inline]
+    pub fn remote_endpoint(&self) -> Option<IpEndpoint> {
+        self.remote_endpoint
+    }
+
     /// Close the socket.
     pub fn close(&mut self) {
         // Clear the bound endpoint of the socket.
         self.endpoint = IpListenEndpoint::default();
+        self.remote_endpoint = None;
 
         // Reset the RX and TX buffers of the socket.
         self.tx_buffer.reset();
+        self.segment_progress = None;
         self.rx_buffer.reset();
 
         #[cfg(feature = "async")]
@@ -294,12 +552,18 @@ impl<'a> Socket<'a> {
     /// `Err(Error::Unaddressable)` if local or remote port, or remote address are unspecified,
     /// and `Err(Error::Truncated)` if there is not enough transmit buffer capacity
     /// to ever send this packet.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the DSCP or ECN value of the metadata does not fit in
+    /// six or two bits, like [set_dscp](#method.set_dscp) and [set_ecn](#method.set_ecn).
     pub fn send(
         &mut self,
         size: usize,
         meta: impl Into<UdpMetadata>,
     ) -> Result<&mut [u8], SendError> {
         let meta = meta.into();
+        check_traffic_class(&meta);
         if self.endpoint.port == 0 {
             return Err(SendError::Unaddressable);
         }
@@ -339,6 +603,7 @@ impl<'a> Socket<'a> {
         F: FnOnce(&mut [u8]) -> usize,
     {
         let meta = meta.into();
+        check_traffic_class(&meta);
         if self.endpoint.port == 0 {
             return Err(SendError::Unaddressable);
         }
@@ -375,6 +640,46 @@ impl<'a> Socket<'a> {
         Ok(())
     }
 
+    /// Enqueue several packets to be sent, each with its own remote endpoint, and return
+    /// how many were enqueued.
+    ///
+    /// The packets are enqueued in order until one of them cannot be; if it is the first
+    /// one, its error is returned. See also [send_slice](#method.send_slice).
+    pub fn send_many<'d, I, M>(&mut self, packets: I) -> Result<usize, SendError>
+    where
+        I: IntoIterator<Item = (&'d [u8], M)>,
+        M: Into<UdpMetadata>,
+    {
+        let mut count = 0;
+        for (data, meta) in packets {
+            match self.send_slice(data, meta) {
+                Ok(()) => count += 1,
+                Err(err) if count == 0 => return Err(err),
+                Err(_) => break,
+            }
+        }
+        Ok(count)
+    }
+
+    /// Enqueue a packet to be sent to the connected remote endpoint, and return a pointer
+    /// to its payload.
+    ///
+    /// This function returns `Err(SendError::Unaddressable)` if the socket is not
+    /// connected. See also [send](#method.send).
+    pub fn send_connected(&mut self, size: usize) -> Result<&mut [u8], SendError> {
+        let remote_endpoint = self.remote_endpoint.ok_or(SendError::Unaddressable)?;
+        self.send(size, remote_endpoint)
+    }
+
+    /// Enqueue a packet to be sent to the connected remote endpoint, and fill it from a
+    /// slice.
+    ///
+    /// See also [send_connected](#method.send_connected).
+    pub fn send_slice_connected(&mut self, data: &[u8]) -> Result<(), SendError> {
+        self.send_connected(data.len())?.copy_from_slice(data);
+        Ok(())
+    }
+
     /// Dequeue a packet received from a remote endpoint, and return the endpoint as well
     /// as a pointer to the payload.
     ///
@@ -392,6 +697,30 @@ impl<'a> Socket<'a> {
         Ok((payload_buf, remote_endpoint))
     }
 
+    /// Dequeue up to `max_count` packets received from remote endpoints, pass each payload
+    /// and its metadata to the provided closure, and return how many were dequeued.
+    ///
+    /// This function returns `Err(Error::Exhausted)` if the receive buffer is empty.
+    /// See also [recv](#method.recv).
+    pub fn recv_many<F>(&mut self, max_count: usize, mut f: F) -> Result<usize, RecvError>
+    where
+        F: FnMut(&[u8], UdpMetadata),
+    {
+        if self.rx_buffer.is_empty() {
+            return Err(RecvError::Exhausted);
+        }
+
+        let mut count = 0;
+        while count < max_count {
+            match self.recv() {
+                Ok((payload, meta)) => f(payload, meta),
+                Err(_) => break,
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
     /// Dequeue a packet received from a remote endpoint, copy the payload into the given slice,
     /// and return the amount of octets copied as well as the endpoint.
     ///
@@ -463,10 +792,31 @@ impl<'a> Socket<'a> {
         {
             return false;
         }
+        if let Some(remote_endpoint) = self.remote_endpoint {
+            if remote_endpoint.addr != ip_repr.src_addr() || remote_endpoint.port != repr.src_port {
+                return false;
+            }
+        }
 
         true
     }
 
+    /// Check whether the socket accepts a datagram of its protocol, given the amount of
+    /// octets covered by its checksum for UDP-Lite, or `None` for UDP.
+    pub(crate) fn accepts_coverage(&self, covered_len: Option<usize>) -> bool {
+        match (self.checksum_coverage, covered_len) {
+            (None, None) => true,
+            (Some(_), Some(covered_len)) => covered_len >= self.min_checksum_coverage as usize,
+            _ => false,
+        }
+    }
+
+    /// Return how specific the endpoints of the socket are, for the sockets sharing a
+    /// port. Connected sockets are the most specific, then sockets bound to an address.
+    pub(crate) fn specificity(&self) -> u8 {
+        (self.remote_endpoint.is_some() as u8) << 1 | self.endpoint.addr.is_some() as u8
+    }
+
     pub(crate) fn process(
         &mut self,
         cx: &mut Context,
@@ -493,7 +843,10 @@ impl<'a> Socket<'a> {
 
         let metadata = UdpMetadata {
             endpoint: remote_endpoint,
+            local_address: Some(ip_repr.dst_addr()),
             meta,
+            dscp: None,
+            ecn: None,
         };
 
         match self.rx_buffer.enqueue(size, metadata) {
@@ -515,9 +868,24 @@ impl<'a> Socket<'a> {
     {
         let endpoint = self.endpoint;
         let hop_limit = self.hop_limit.unwrap_or(64);
+        let (dscp, ecn) = (self.dscp, self.ecn);
+        let protocol = match self.checksum_coverage {
+            Some(_) => IpProtocol::UdpLite,
+            None => IpProtocol::Udp,
+        };
+
+        // A packet already partly sent keeps the segment size it started with.
+        let (segment_size, segment_offset) = match self.segment_progress {
+            Some((size, offset)) => (Some(size), offset),
+            None => (self.segment_size.map(usize::from), 0),
+        };
+        let mut next_segment_progress = None;
 
         let res = self.tx_buffer.dequeue_with(|packet_meta, payload_buf| {
-            let src_addr = match endpoint.addr {
+            let local_address = packet_meta
+                .local_address
+                .filter(|addr| cx.is_source_address_for(addr, &packet_meta.endpoint.addr));
+            let src_addr = match local_address.or(endpoint.addr) {
                 Some(addr) => addr,
                 None => match cx.get_source_address(&packet_meta.endpoint.addr) {
                     Some(addr) => addr,
@@ -532,31 +900,73 @@ impl<'a> Socket<'a> {
                 },
             };
 
+            let repr = UdpRepr {
+                src_port: endpoint.port,
+                dst_port: packet_meta.endpoint.port,
+            };
+            let new_ip_repr = |payload_len| {
+                let mut ip_repr = IpRepr::new(
+                    src_addr,
+                    packet_meta.endpoint.addr,
+                    protocol,
+                    repr.header_len() + payload_len,
+                    hop_limit,
+                );
+                ip_repr.set_dscp(packet_meta.dscp.unwrap_or(dscp));
+                ip_repr.set_ecn(packet_meta.ecn.unwrap_or(ecn));
+                ip_repr
+            };
+
+            // Packets larger than the segment size are split into several datagrams, one
+            // per call, unless the device splits them itself.
+            let mut meta = packet_meta.meta;
+            meta.segment_size = None;
+            let payload = match segment_size {
+                Some(size) if payload_buf.len() > size => {
+                    let offload = protocol == IpProtocol::Udp
+                        && segment_offset == 0
+                        && cx.udp_segmentation_offload()
+                        && !cx.is_loopback_destination(&packet_meta.endpoint.addr)
+                        && new_ip_repr(payload_buf.len()).buffer_len() <= u16::MAX as usize;
+                    if offload {
+                        meta.segment_size = Some(size as u16);
+                        &payload_buf[..]
+                    } else {
+                        let end = min(segment_offset + size, payload_buf.len());
+                        if end < payload_buf.len() {
+                            next_segment_progress = Some((size, end));
+                        }
+                        &payload_buf[segment_offset..end]
+                    }
+                }
+                _ => &payload_buf[..],
+            };
+
             net_trace!(
                 "udp:{}:{}: sending {} octets",
                 endpoint,
                 packet_meta.endpoint,
-                payload_buf.len()
+                payload.len()
             );
 
-            let repr = UdpRepr {
-                src_port: endpoint.port,
-                dst_port: packet_meta.endpoint.port,
-            };
-            let ip_repr = IpRepr::new(
-                src_addr,
-                packet_meta.endpoint.addr,
-                IpProtocol::Udp,
-                repr.header_len() + payload_buf.len(),
-                hop_limit,
-            );
+            let ip_repr = new_ip_repr(payload.len());
+            emit(cx, meta, (ip_repr, repr, payload)).map_err(Some)?;
 
-            emit(cx, packet_meta.meta, (ip_repr, repr, payload_buf))
+            // Keep the packet until its last datagram is sent.
+            match next_segment_progress {
+                Some(_) => Err(None),
+                None => Ok(()),
+            }
         });
         match res {
             Err(Empty) => Ok(()),
-            Ok(Err(e)) => Err(e),
+            Ok(Err(Some(e))) => Err(e),
+            Ok(Err(None)) => {
+                self.segment_progress = next_segment_progress;
+                Ok(())
+            }
             Ok(Ok(())) => {
+                self.segment_progress = None;
                 #[cfg(feature = "async")]
                 self.tx_waker.wake();
                 Ok(())
@@ -605,11 +1015,14 @@ mod test {
         if #[cfg(feature = "proto-ipv4")] {
             use crate::wire::Ipv4Address as IpvXAddress;
             use crate::wire::Ipv4Repr as IpvXRepr;
+            use crate::wire::Ipv4Options;
             use IpRepr::Ipv4 as IpReprIpvX;
 
             const LOCAL_ADDR: IpvXAddress = IpvXAddress([192, 168, 1, 1]);
             const REMOTE_ADDR: IpvXAddress = IpvXAddress([192, 168, 1, 2]);
             const OTHER_ADDR: IpvXAddress = IpvXAddress([192, 168, 1, 3]);
+            #[allow(unused)]
+            const LOOPBACK_ADDR: IpvXAddress = IpvXAddress([127, 0, 0, 1]);
         } else {
             use crate::wire::Ipv6Address as IpvXAddress;
             use crate::wire::Ipv6Repr as IpvXRepr;
@@ -624,6 +1037,8 @@ mod test {
             const OTHER_ADDR: IpvXAddress = IpvXAddress([
                 0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3,
             ]);
+            #[allow(unused)]
+            const LOOPBACK_ADDR: IpvXAddress = IpvXAddress::LOOPBACK;
         }
     }
 
@@ -636,12 +1051,25 @@ mod test {
         port: REMOTE_PORT,
     };
 
+    /// Metadata of the datagrams received from `REMOTE_END`.
+    #[allow(unused)]
+    pub fn remote_meta() -> UdpMetadata {
+        UdpMetadata {
+            local_address: Some(LOCAL_ADDR.into()),
+            ..REMOTE_END.into()
+        }
+    }
+
     pub const LOCAL_IP_REPR: IpRepr = IpReprIpvX(IpvXRepr {
         src_addr: LOCAL_ADDR,
         dst_addr: REMOTE_ADDR,
         next_header: IpProtocol::Udp,
         payload_len: 8 + 6,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        #[cfg(feature = "proto-ipv4")]
+        options: Ipv4Options::EMPTY,
     });
 
     pub const REMOTE_IP_REPR: IpRepr = IpReprIpvX(IpvXRepr {
@@ -650,6 +1078,10 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 8 + 6,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        #[cfg(feature = "proto-ipv4")]
+        options: Ipv4Options::EMPTY,
     });
 
     pub const BAD_IP_REPR: IpRepr = IpReprIpvX(IpvXRepr {
@@ -658,6 +1090,10 @@ mod test {
         next_header: IpProtocol::Udp,
         payload_len: 8 + 6,
         hop_limit: 64,
+        dscp: 0,
+        ecn: 0,
+        #[cfg(feature = "proto-ipv4")]
+        options: Ipv4Options::EMPTY,
     });
 
     const LOCAL_UDP_REPR: UdpRepr = UdpRepr {
@@ -685,6 +1121,33 @@ mod test {
         assert_eq!(socket.bind(2), Err(BindError::InvalidState));
     }
 
+    #[test]
+    fn test_connect() {
+        let mut socket = socket(buffer(0), buffer(1));
+        assert_eq!(socket.connect(REMOTE_END), Err(ConnectError::InvalidState));
+        assert_eq!(
+            socket.send_slice_connected(b"abcdef"),
+            Err(SendError::Unaddressable)
+        );
+
+        assert_eq!(socket.bind(LOCAL_END), Ok(()));
+        assert_eq!(
+            socket.connect(IpEndpoint {
+                port: 0,
+                ..REMOTE_END
+            }),
+            Err(ConnectError::Unaddressable)
+        );
+        assert_eq!(socket.connect(REMOTE_END), Ok(()));
+        assert_eq!(socket.remote_endpoint(), Some(REMOTE_END));
+
+        socket.disconnect();
+        assert_eq!(socket.remote_endpoint(), None);
+        assert_eq!(socket.connect(REMOTE_END), Ok(()));
+        socket.close();
+        assert_eq!(socket.remote_endpoint(), None);
+    }
+
     #[test]
     #[should_panic(expected = "the time-to-live value of a packet must not be zero")]
     fn test_set_hop_limit_zero() {
@@ -774,6 +1237,57 @@ mod test {
         assert!(socket.can_send());
     }
 
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    #[case::ieee802154(Medium::Ieee802154)]
+    #[cfg(feature = "medium-ieee802154")]
+    fn test_send_local_address(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+        let mut socket = socket(buffer(0), buffer(3));
+
+        assert_eq!(socket.bind(LOCAL_PORT), Ok(()));
+
+        // The local address replaces the one selected by the interface.
+        let meta = UdpMetadata {
+            local_address: Some(LOOPBACK_ADDR.into()),
+            ..IpEndpoint::new(LOOPBACK_ADDR.into(), REMOTE_PORT).into()
+        };
+        assert_eq!(socket.send_slice(b"abcdef", meta), Ok(()));
+        // Loopback addresses are not used for other destinations.
+        let meta = UdpMetadata {
+            local_address: Some(LOOPBACK_ADDR.into()),
+            ..REMOTE_END.into()
+        };
+        assert_eq!(socket.send_slice(b"abcdef", meta), Ok(()));
+        // Addresses that are not assigned to the interface are ignored.
+        let meta = UdpMetadata {
+            local_address: Some(OTHER_ADDR.into()),
+            ..REMOTE_END.into()
+        };
+        assert_eq!(socket.send_slice(b"abcdef", meta), Ok(()));
+
+        assert_eq!(
+            socket.dispatch(cx, |_, _, (ip_repr, _, _)| {
+                assert_eq!(ip_repr.src_addr(), LOOPBACK_ADDR.into());
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+        for _ in 0..2 {
+            assert_eq!(
+                socket.dispatch(cx, |_, _, (ip_repr, _, _)| {
+                    assert_eq!(ip_repr, LOCAL_IP_REPR);
+                    Ok::<_, ()>(())
+                }),
+                Ok(())
+            );
+        }
+    }
+
     #[rstest]
     #[case::ip(Medium::Ip)]
     #[cfg(feature = "medium-ip")]
@@ -811,10 +1325,136 @@ mod test {
             PAYLOAD,
         );
 
-        assert_eq!(socket.recv(), Ok((&b"abcdef"[..], REMOTE_END.into())));
+        assert_eq!(socket.recv(), Ok((&b"abcdef"[..], remote_meta())));
         assert!(!socket.can_recv());
     }
 
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    #[case::ieee802154(Medium::Ieee802154)]
+    #[cfg(feature = "medium-ieee802154")]
+    fn test_send_recv_many(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut socket = socket(buffer(3), buffer(2));
+        assert_eq!(socket.bind(LOCAL_END), Ok(()));
+
+        let packets = [
+            (&b"abc"[..], REMOTE_END),
+            (&b"def"[..], REMOTE_END),
+            (&b"ghi"[..], REMOTE_END),
+        ];
+        assert_eq!(socket.send_many(packets), Ok(2));
+        assert_eq!(socket.send_many(packets), Err(SendError::BufferFull));
+
+        for _ in 0..3 {
+            socket.process(
+                cx,
+                PacketMeta::default(),
+                &REMOTE_IP_REPR,
+                &REMOTE_UDP_REPR,
+                PAYLOAD,
+            );
+        }
+        let mut received = 0;
+        assert_eq!(
+            socket.recv_many(2, |payload, meta| {
+                assert_eq!(payload, PAYLOAD);
+                assert_eq!(meta, remote_meta());
+                received += 1;
+            }),
+            Ok(2)
+        );
+        assert_eq!(received, 2);
+        assert_eq!(socket.recv_many(2, |_, _| ()), Ok(1));
+        assert_eq!(socket.recv_many(2, |_, _| ()), Err(RecvError::Exhausted));
+    }
+
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    #[case::ieee802154(Medium::Ieee802154)]
+    #[cfg(feature = "medium-ieee802154")]
+    fn test_segment_size(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut socket = socket(buffer(0), buffer(1));
+        assert_eq!(socket.bind(LOCAL_END), Ok(()));
+        socket.set_segment_size(Some(4));
+        assert_eq!(socket.send_slice(b"abcdefghij", REMOTE_END), Ok(()));
+
+        // A failed datagram is sent again.
+        assert_eq!(
+            socket.dispatch(cx, |_, _, (_, _, payload)| {
+                assert_eq!(payload, b"abcd");
+                Err(())
+            }),
+            Err(())
+        );
+        for segment in [&b"abcd"[..], b"efgh", b"ij"] {
+            assert!(!socket.can_send());
+            assert_eq!(
+                socket.dispatch(cx, |_, meta, (ip_repr, _, payload)| {
+                    assert_eq!(payload, segment);
+                    assert_eq!(ip_repr.payload_len(), 8 + segment.len());
+                    assert_eq!(meta.segment_size, None);
+                    Ok::<_, ()>(())
+                }),
+                Ok(())
+            );
+        }
+        assert!(socket.can_send());
+    }
+
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    #[case::ieee802154(Medium::Ieee802154)]
+    #[cfg(feature = "medium-ieee802154")]
+    fn test_segment_size_changed(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut socket = socket(buffer(0), buffer(2));
+        assert_eq!(socket.bind(LOCAL_END), Ok(()));
+        socket.set_segment_size(Some(4));
+        assert_eq!(socket.send_slice(b"abcdefghij", REMOTE_END), Ok(()));
+        assert_eq!(socket.send_slice(b"abcdefghij", REMOTE_END), Ok(()));
+
+        // The packet being sent keeps its segment size, the next one uses the new size.
+        let mut sent = std::vec::Vec::new();
+        for i in 0..4 {
+            if i == 1 {
+                socket.set_segment_size(None);
+            }
+            assert_eq!(
+                socket.dispatch(cx, |_, _, (_, _, payload)| {
+                    sent.push(payload.to_vec());
+                    Ok::<_, ()>(())
+                }),
+                Ok(())
+            );
+        }
+        assert_eq!(sent, [&b"abcd"[..], b"efgh", b"ij", b"abcdefghij"]);
+        assert!(socket.can_send());
+    }
+
+    #[test]
+    #[should_panic(expected = "the segment size of a packet must not be zero")]
+    fn test_set_segment_size_zero() {
+        let mut s = socket(buffer(0), buffer(1));
+        s.set_segment_size(Some(0));
+    }
+
     #[rstest]
     #[case::ip(Medium::Ip)]
     #[cfg(feature = "medium-ip")]
@@ -839,8 +1479,8 @@ mod test {
             &REMOTE_UDP_REPR,
             PAYLOAD,
         );
-        assert_eq!(socket.peek(), Ok((&b"abcdef"[..], &REMOTE_END.into(),)));
-        assert_eq!(socket.recv(), Ok((&b"abcdef"[..], REMOTE_END.into(),)));
+        assert_eq!(socket.peek(), Ok((&b"abcdef"[..], &remote_meta(),)));
+        assert_eq!(socket.recv(), Ok((&b"abcdef"[..], remote_meta(),)));
         assert_eq!(socket.peek(), Err(RecvError::Exhausted));
     }
 
@@ -928,6 +1568,10 @@ mod test {
                         next_header: IpProtocol::Udp,
                         payload_len: 8 + 6,
                         hop_limit: 0x2a,
+                        dscp: 0,
+                        ecn: 0,
+                        #[cfg(feature = "proto-ipv4")]
+                        options: Ipv4Options::EMPTY,
                     })
                 );
                 Ok::<_, ()>(())
@@ -936,6 +1580,105 @@ mod test {
         );
     }
 
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    #[case::ieee802154(Medium::Ieee802154)]
+    #[cfg(feature = "medium-ieee802154")]
+    fn test_set_dscp_ecn(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut s = socket(buffer(0), buffer(2));
+
+        assert_eq!(s.bind(LOCAL_END), Ok(()));
+
+        s.set_dscp(46);
+        s.set_ecn(0b01);
+        assert_eq!(s.send_slice(b"abcdef", REMOTE_END), Ok(()));
+
+        let mut meta = UdpMetadata::from(REMOTE_END);
+        meta.dscp = Some(10);
+        meta.ecn = Some(0b10);
+        assert_eq!(s.send_slice(b"abcdef", meta), Ok(()));
+
+        assert_eq!(
+            s.dispatch(cx, |_, _, (ip_repr, _, _)| {
+                assert_eq!(ip_repr.dscp(), 46);
+                assert_eq!(ip_repr.ecn(), 0b01);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            s.dispatch(cx, |_, _, (ip_repr, _, _)| {
+                assert_eq!(ip_repr.dscp(), 10);
+                assert_eq!(ip_repr.ecn(), 0b10);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the DSCP value of a packet must fit in six bits")]
+    fn test_set_dscp_too_large() {
+        let mut s = socket(buffer(0), buffer(1));
+        s.set_dscp(64);
+    }
+
+    #[test]
+    #[should_panic(expected = "the ECN value of a packet must fit in two bits")]
+    fn test_send_ecn_too_large() {
+        let mut s = socket(buffer(0), buffer(1));
+        assert_eq!(s.bind(LOCAL_END), Ok(()));
+
+        let mut meta = UdpMetadata::from(REMOTE_END);
+        meta.ecn = Some(4);
+        let _ = s.send_slice(b"abcdef", meta);
+    }
+
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    #[case::ieee802154(Medium::Ieee802154)]
+    #[cfg(feature = "medium-ieee802154")]
+    fn test_udp_lite(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut s = socket(buffer(0), buffer(1));
+        assert_eq!(s.bind(LOCAL_END), Ok(()));
+        assert!(s.accepts_coverage(None));
+        assert!(!s.accepts_coverage(Some(8)));
+
+        s.set_checksum_coverage(Some(8));
+        s.set_min_checksum_coverage(10);
+        assert!(!s.accepts_coverage(None));
+        assert!(!s.accepts_coverage(Some(8)));
+        assert!(s.accepts_coverage(Some(10)));
+
+        assert_eq!(s.send_slice(b"abcdef", REMOTE_END), Ok(()));
+        assert_eq!(
+            s.dispatch(cx, |_, _, (ip_repr, _, _)| {
+                assert_eq!(ip_repr.next_header(), IpProtocol::UdpLite);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the checksum coverage of a UDP-Lite datagram must cover its header")]
+    fn test_set_checksum_coverage_too_small() {
+        let mut s = socket(buffer(0), buffer(1));
+        s.set_checksum_coverage(Some(4));
+    }
+
     #[rstest]
     #[case::ip(Medium::Ip)]
     #[cfg(feature = "medium-ip")]
@@ -957,6 +1700,54 @@ mod test {
         assert!(!socket.accepts(cx, &REMOTE_IP_REPR, &udp_repr));
     }
 
+    #[rstest]
+    #[case::ip(Medium::Ip)]
+    #[cfg(feature = "medium-ip")]
+    #[case::ethernet(Medium::Ethernet)]
+    #[cfg(feature = "medium-ethernet")]
+    #[case::ieee802154(Medium::Ieee802154)]
+    #[cfg(feature = "medium-ieee802154")]
+    fn test_connected(#[case] medium: Medium) {
+        let (mut iface, _, _) = setup(medium);
+        let cx = iface.context();
+
+        let mut socket = socket(buffer(1), buffer(1));
+        assert_eq!(socket.bind(LOCAL_PORT), Ok(()));
+        assert_eq!(socket.specificity(), 0);
+        assert_eq!(socket.connect(REMOTE_END), Ok(()));
+        assert_eq!(socket.specificity(), 2);
+
+        // Only datagrams from the remote endpoint are accepted.
+        let mut udp_repr = REMOTE_UDP_REPR;
+        assert!(socket.accepts(cx, &REMOTE_IP_REPR, &udp_repr));
+        udp_repr.src_port += 1;
+        assert!(!socket.accepts(cx, &REMOTE_IP_REPR, &udp_repr));
+        let other_ip_repr = IpReprIpvX(IpvXRepr {
+            src_addr: OTHER_ADDR,
+            dst_addr: LOCAL_ADDR,
+            next_header: IpProtocol::Udp,
+            payload_len: 8 + 6,
+            hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            #[cfg(feature = "proto-ipv4")]
+            options: Ipv4Options::EMPTY,
+        });
+        assert!(!socket.accepts(cx, &other_ip_repr, &REMOTE_UDP_REPR));
+
+        // Datagrams are sent to the remote endpoint by default.
+        assert_eq!(socket.send_slice_connected(b"abcdef"), Ok(()));
+        assert_eq!(
+            socket.dispatch(cx, |_, _, (ip_repr, udp_repr, payload)| {
+                assert_eq!(ip_repr, LOCAL_IP_REPR);
+                assert_eq!(udp_repr, LOCAL_UDP_REPR);
+                assert_eq!(payload, PAYLOAD);
+                Ok::<_, ()>(())
+            }),
+            Ok(())
+        );
+    }
+
     #[rstest]
     #[case::ip(Medium::Ip)]
     #[cfg(feature = "medium-ip")]
@@ -1013,7 +1804,7 @@ mod test {
             dst_port: LOCAL_PORT,
         };
         socket.process(cx, PacketMeta::default(), &REMOTE_IP_REPR, &repr, &[]);
-        assert_eq!(socket.recv(), Ok((&[][..], REMOTE_END.into())));
+        assert_eq!(socket.recv(), Ok((&[][..], remote_meta())));
     }
 
     #[test]
@@ -318,6 +318,12 @@ impl Assembler {
         Ok(self.remove_front())
     }
 
+    /// Return whether any of the octets in the given range have already been added.
+    pub fn overlaps(&self, offset: usize, size: usize) -> bool {
+        self.iter_data(0)
+            .any(|(start, end)| offset < end && start < offset + size)
+    }
+
     /// Iterate over all of the contiguous data ranges.
     ///
     /// This is used in calculating what data ranges have been received. The offset indicates the
@@ -691,6 +697,19 @@ mod test {
         assert_eq!(assr.add_then_remove_front(0, 3), Ok(3));
     }
 
+    #[test]
+    fn test_overlaps() {
+        let mut assr = Assembler::new();
+        assert!(!assr.overlaps(0, 10));
+        assert_eq!(assr.add(0, 10), Ok(()));
+        assert_eq!(assr.add(20, 10), Ok(()));
+        assert!(assr.overlaps(5, 10));
+        assert!(assr.overlaps(25, 1));
+        assert!(assr.overlaps(0, 40));
+        assert!(!assr.overlaps(10, 10));
+        assert!(!assr.overlaps(30, 10));
+    }
+
     // Test against an obviously-correct but inefficient bitmap impl.
     #[test]
     fn test_random() {
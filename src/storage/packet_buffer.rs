@@ -229,6 +229,84 @@ impl<'a, H> PacketBuffer<'a, H> {
         }
     }
 
+    /// Move the packet at the head of the buffer to its tail, or return `Err(Full)`
+    /// if the buffer has no room for a copy of the packet.
+    ///
+    /// This function does nothing if the buffer is empty.
+    #[cfg(any(
+        feature = "medium-ethernet",
+        feature = "medium-ip",
+        feature = "medium-ieee802154"
+    ))]
+    pub(crate) fn requeue(&mut self) -> Result<(), Full>
+    where
+        H: Clone,
+    {
+        self.dequeue_padding();
+
+        let (size, header) = match self.metadata_ring.get_allocated(0, 1).first() {
+            Some(metadata) => (metadata.size, metadata.header.clone().unwrap()),
+            None => return Ok(()),
+        };
+
+        // The copy must be contiguous, like the packets enqueued by `enqueue`.
+        let window = self.payload_ring.window();
+        let contig_window = self.payload_ring.contiguous_window();
+        let padding = if contig_window < size {
+            Some(contig_window)
+        } else {
+            None
+        };
+        if window < size + padding.unwrap_or(0)
+            || self.metadata_ring.window() < 1 + padding.iter().count()
+        {
+            return Err(Full);
+        }
+
+        if let Some(padding) = padding {
+            *self.metadata_ring.enqueue_one()? = PacketMetadata::padding(padding);
+            // note(discard): function does not write to the result
+            // enqueued padding buffer location
+            let _buf_enqueued = self.payload_ring.enqueue_many(padding);
+        }
+
+        // The packet and its copy are in the same ring, so copy through a small buffer.
+        let mut offset = 0;
+        while offset < size {
+            let mut chunk = [0; 64];
+            let chunk_len = chunk.len().min(size - offset);
+            let read = self
+                .payload_ring
+                .read_allocated(offset, &mut chunk[..chunk_len]);
+            let written = self.payload_ring.write_unallocated(offset, &chunk[..read]);
+            debug_assert!(written == read);
+            offset += read;
+        }
+        self.payload_ring.enqueue_unallocated(size);
+        *self.metadata_ring.enqueue_one()? = PacketMetadata::packet(size, header);
+
+        self.metadata_ring.dequeue_allocated(1);
+        self.payload_ring.dequeue_allocated(size);
+        Ok(())
+    }
+
+    /// Return the number of packets in the buffer.
+    #[cfg(any(
+        feature = "medium-ethernet",
+        feature = "medium-ip",
+        feature = "medium-ieee802154"
+    ))]
+    pub(crate) fn packet_count(&self) -> usize {
+        (0..self.metadata_ring.len())
+            .filter(|&index| {
+                self.metadata_ring
+                    .get_allocated(index, 1)
+                    .first()
+                    .map_or(false, |metadata| !metadata.is_padding())
+            })
+            .count()
+    }
+
     /// Return the maximum number packets that can be stored.
     pub fn packet_capacity(&self) -> usize {
         self.metadata_ring.capacity()
@@ -386,6 +464,33 @@ mod test {
         assert!(buffer.enqueue(5, ()).is_ok());
     }
 
+    #[cfg(any(
+        feature = "medium-ethernet",
+        feature = "medium-ip",
+        feature = "medium-ieee802154"
+    ))]
+    #[test]
+    fn test_requeue() {
+        let mut buffer = buffer();
+        buffer.enqueue(4, ()).unwrap().copy_from_slice(b"abcd");
+        buffer.enqueue(6, ()).unwrap().copy_from_slice(b"efghij");
+        assert_eq!(buffer.requeue(), Ok(()));
+        assert_eq!(buffer.packet_count(), 2);
+        assert_eq!(buffer.peek().unwrap().1, &b"efghij"[..]);
+
+        // There is no room for a contiguous copy.
+        assert_eq!(buffer.requeue(), Err(Full));
+        assert_eq!(buffer.dequeue().unwrap().1, &b"efghij"[..]);
+
+        // The copy wraps around to the beginning of the ring buffer.
+        assert_eq!(buffer.requeue(), Ok(()));
+        assert_eq!(buffer.metadata_ring.len(), 2);
+        assert_eq!(buffer.packet_count(), 1);
+        assert_eq!(buffer.dequeue().unwrap().1, &b"abcd"[..]);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.requeue(), Ok(()));
+    }
+
     #[test]
     fn clear() {
         let mut buffer = buffer();
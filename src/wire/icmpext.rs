@@ -0,0 +1,894 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, Result};
+use crate::wire::ip::checksum;
+use crate::wire::IpAddress;
+#[cfg(feature = "proto-ipv4")]
+use crate::wire::Ipv4Address;
+#[cfg(feature = "proto-ipv6")]
+use crate::wire::Ipv6Address;
+
+/// Version of the ICMP extension structure, see [RFC 4884 § 7].
+///
+/// [RFC 4884 § 7]: https://www.rfc-editor.org/rfc/rfc4884#section-7
+pub const VERSION: u8 = 2;
+
+/// Minimum length of the original datagram field of an ICMP message carrying extensions.
+pub const MIN_ORIGINAL_DATAGRAM_LEN: usize = 128;
+
+/// Length of the extension header, holding the version and the checksum.
+pub const HEADER_LEN: usize = 4;
+
+enum_with_unknown! {
+    /// ICMP extension object class.
+    pub enum ClassNum(u8) {
+        /// MPLS Label Stack, see [RFC 4950](https://www.rfc-editor.org/rfc/rfc4950)
+        MplsLabelStack       = 1,
+        /// Interface Information, see [RFC 5837](https://www.rfc-editor.org/rfc/rfc5837)
+        InterfaceInformation = 2,
+    }
+}
+
+impl fmt::Display for ClassNum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClassNum::MplsLabelStack => write!(f, "MPLS Label Stack"),
+            ClassNum::InterfaceInformation => write!(f, "Interface Information"),
+            ClassNum::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+enum_with_unknown! {
+    /// Role of the interface described by an Interface Information object.
+    pub enum InterfaceRole(u8) {
+        /// The interface the datagram was received on
+        IncomingInterface = 0,
+        /// A sub-IP component of the incoming interface
+        SubIpComponent    = 1,
+        /// The interface the datagram would have been forwarded on
+        OutgoingInterface = 2,
+        /// The next hop the datagram would have been forwarded to
+        NextHop           = 3,
+    }
+}
+
+/// A read/write wrapper around an ICMP extension object.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExtensionObject<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of an extension object
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |             Length            |   Class-Num   |   C-Type      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// |   // (Object payload) //                                      |
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The Length counts all the octets of the object.
+//
+// See https://www.rfc-editor.org/rfc/rfc4884#section-7 for details.
+mod field {
+    #![allow(non_snake_case)]
+
+    use crate::wire::field::*;
+
+    // 4-bit version of the extension structure, followed by 12 reserved bits.
+    pub const VERSION: usize = 0;
+    // 16-bit one's complement checksum of the extension structure.
+    pub const CHECKSUM: Field = 2..4;
+
+    // 16-bit unsigned integer. Length of the object, in octets.
+    pub const LENGTH: Field = 0..2;
+    // 8-bit identifier of the class of the object.
+    pub const CLASS_NUM: usize = 2;
+    // 8-bit identifier of the type of the object within its class.
+    pub const C_TYPE: usize = 3;
+    // Variable-length field. Object payload.
+    pub const fn PAYLOAD(length: u16) -> Field {
+        4..length as usize
+    }
+}
+
+impl<T: AsRef<[u8]>> ExtensionObject<T> {
+    /// Create a raw octet buffer with an ICMP extension object structure.
+    pub const fn new_unchecked(buffer: T) -> ExtensionObject<T> {
+        ExtensionObject { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<ExtensionObject<T>> {
+        let obj = Self::new_unchecked(buffer);
+        obj.check_len()?;
+        Ok(obj)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short, or if the object length is
+    /// shorter than the object header.
+    ///
+    /// The result of this check is invalidated by calling [set_length].
+    ///
+    /// [set_length]: #method.set_length
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < field::C_TYPE + 1 {
+            return Err(Error);
+        }
+
+        let length = self.length() as usize;
+        if length <= field::C_TYPE || len < length {
+            return Err(Error);
+        }
+
+        Ok(())
+    }
+
+    /// Consume the extension object, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the length of the object, including the object header.
+    #[inline]
+    pub fn length(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::LENGTH])
+    }
+
+    /// Return the class of the object.
+    #[inline]
+    pub fn class_num(&self) -> ClassNum {
+        let data = self.buffer.as_ref();
+        ClassNum::from(data[field::CLASS_NUM])
+    }
+
+    /// Return the type of the object within its class.
+    #[inline]
+    pub fn c_type(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::C_TYPE]
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> ExtensionObject<&'a T> {
+    /// Return the object payload.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let len = self.length();
+        let data = self.buffer.as_ref();
+        &data[field::PAYLOAD(len)]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> ExtensionObject<T> {
+    /// Set the length of the object, including the object header.
+    #[inline]
+    pub fn set_length(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::LENGTH], value)
+    }
+
+    /// Set the class of the object.
+    #[inline]
+    pub fn set_class_num(&mut self, value: ClassNum) {
+        let data = self.buffer.as_mut();
+        data[field::CLASS_NUM] = value.into()
+    }
+
+    /// Set the type of the object within its class.
+    #[inline]
+    pub fn set_c_type(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::C_TYPE] = value
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]> + ?Sized> ExtensionObject<&mut T> {
+    /// Return a mutable pointer to the object payload.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let len = self.length();
+        let data = self.buffer.as_mut();
+        &mut data[field::PAYLOAD(len)]
+    }
+}
+
+/// An entry of an MPLS label stack, see [RFC 3032 § 2.1].
+///
+/// [RFC 3032 § 2.1]: https://www.rfc-editor.org/rfc/rfc3032#section-2.1
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MplsLabel {
+    /// The 20-bit label value.
+    pub label: u32,
+    /// The 3-bit traffic class.
+    pub traffic_class: u8,
+    /// Whether this is the last entry of the stack.
+    pub bottom_of_stack: bool,
+    pub ttl: u8,
+}
+
+impl MplsLabel {
+    /// The length of a label stack entry.
+    pub const LEN: usize = 4;
+
+    /// Parse a label stack entry.
+    pub fn parse(data: &[u8]) -> Result<MplsLabel> {
+        if data.len() < Self::LEN {
+            return Err(Error);
+        }
+        let entry = NetworkEndian::read_u32(data);
+        Ok(MplsLabel {
+            label: entry >> 12,
+            traffic_class: ((entry >> 9) & 0x7) as u8,
+            bottom_of_stack: entry & 0x100 != 0,
+            ttl: entry as u8,
+        })
+    }
+
+    /// Emit a label stack entry into the first octets of `data`.
+    pub fn emit(&self, data: &mut [u8]) {
+        let entry = ((self.label & 0xfffff) << 12)
+            | ((self.traffic_class as u32 & 0x7) << 9)
+            | ((self.bottom_of_stack as u32) << 8)
+            | self.ttl as u32;
+        NetworkEndian::write_u32(&mut data[..Self::LEN], entry)
+    }
+}
+
+/// The label stack of an MPLS Label Stack object, in its wire format.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MplsLabelStack<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> MplsLabelStack<'a> {
+    /// Create a label stack from its wire format.
+    ///
+    /// Returns `Err(Error)` if the stack is empty or not a list of entries.
+    pub fn new(data: &'a [u8]) -> Result<MplsLabelStack<'a>> {
+        if data.is_empty() || data.len() % MplsLabel::LEN != 0 {
+            return Err(Error);
+        }
+        Ok(MplsLabelStack { data })
+    }
+
+    /// Return the label stack in its wire format.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Iterate over the entries of the stack, from the top one.
+    pub fn iter(&self) -> impl Iterator<Item = MplsLabel> + 'a {
+        self.data
+            .chunks_exact(MplsLabel::LEN)
+            // NOTE(unwrap): the chunks are complete entries.
+            .map(|entry| MplsLabel::parse(entry).unwrap())
+    }
+}
+
+/// The contents of an Interface Information object, see [RFC 5837 § 4].
+///
+/// [RFC 5837 § 4]: https://www.rfc-editor.org/rfc/rfc5837#section-4
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterfaceInformation<'a> {
+    pub role: InterfaceRole,
+    pub if_index: Option<u32>,
+    pub address: Option<IpAddress>,
+    /// The UTF-8 name of the interface, at most 63 octets long.
+    pub name: Option<&'a [u8]>,
+    pub mtu: Option<u32>,
+}
+
+impl<'a> InterfaceInformation<'a> {
+    // Flags of the C-Type of an Interface Information object.
+    const IF_INDEX: u8 = 0x08;
+    const ADDRESS: u8 = 0x04;
+    const NAME: u8 = 0x02;
+    const MTU: u8 = 0x01;
+
+    /// The maximum length of an interface name.
+    pub const MAX_NAME_LEN: usize = 63;
+
+    fn parse(c_type: u8, mut data: &'a [u8]) -> Result<InterfaceInformation<'a>> {
+        let mut info = InterfaceInformation {
+            role: InterfaceRole::from(c_type >> 6),
+            if_index: None,
+            address: None,
+            name: None,
+            mtu: None,
+        };
+
+        if c_type & Self::IF_INDEX != 0 {
+            if data.len() < 4 {
+                return Err(Error);
+            }
+            info.if_index = Some(NetworkEndian::read_u32(data));
+            data = &data[4..];
+        }
+        if c_type & Self::ADDRESS != 0 {
+            if data.len() < 4 {
+                return Err(Error);
+            }
+            let (address, len) = match NetworkEndian::read_u16(data) {
+                #[cfg(feature = "proto-ipv4")]
+                1 if data.len() >= 8 => (Ipv4Address::from_bytes(&data[4..8]).into(), 8),
+                #[cfg(feature = "proto-ipv6")]
+                2 if data.len() >= 20 => (Ipv6Address::from_bytes(&data[4..20]).into(), 20),
+                _ => return Err(Error),
+            };
+            info.address = Some(address);
+            data = &data[len..];
+        }
+        if c_type & Self::NAME != 0 {
+            let len = *data.first().ok_or(Error)? as usize;
+            if len == 0 || len % 4 != 0 || data.len() < len {
+                return Err(Error);
+            }
+            let name = &data[1..len];
+            let name_len = name.iter().rposition(|&c| c != 0).map_or(0, |pos| pos + 1);
+            info.name = Some(&name[..name_len]);
+            data = &data[len..];
+        }
+        if c_type & Self::MTU != 0 {
+            if data.len() < 4 {
+                return Err(Error);
+            }
+            info.mtu = Some(NetworkEndian::read_u32(data));
+        }
+
+        Ok(info)
+    }
+
+    /// Return the type of the object within the Interface Information class.
+    pub fn c_type(&self) -> u8 {
+        let mut c_type = u8::from(self.role) << 6;
+        if self.if_index.is_some() {
+            c_type |= Self::IF_INDEX;
+        }
+        if self.address.is_some() {
+            c_type |= Self::ADDRESS;
+        }
+        if self.name.is_some() {
+            c_type |= Self::NAME;
+        }
+        if self.mtu.is_some() {
+            c_type |= Self::MTU;
+        }
+        c_type
+    }
+
+    /// Return the length of the name sub-object, padded to a multiple of 4 octets.
+    const fn name_len(name: &[u8]) -> usize {
+        (name.len() + 1 + 3) & !3
+    }
+
+    /// Return the length of the object payload.
+    pub const fn buffer_len(&self) -> usize {
+        let mut len = 0;
+        if self.if_index.is_some() {
+            len += 4;
+        }
+        match self.address {
+            #[cfg(feature = "proto-ipv4")]
+            Some(IpAddress::Ipv4(_)) => len += 8,
+            #[cfg(feature = "proto-ipv6")]
+            Some(IpAddress::Ipv6(_)) => len += 20,
+            None => (),
+        }
+        if let Some(name) = self.name {
+            len += Self::name_len(name);
+        }
+        if self.mtu.is_some() {
+            len += 4;
+        }
+        len
+    }
+
+    fn emit(&self, mut data: &mut [u8]) {
+        if let Some(if_index) = self.if_index {
+            NetworkEndian::write_u32(data, if_index);
+            data = &mut data[4..];
+        }
+        if let Some(address) = self.address {
+            let (afi, bytes) = match address {
+                #[cfg(feature = "proto-ipv4")]
+                IpAddress::Ipv4(ref address) => (1, address.as_bytes()),
+                #[cfg(feature = "proto-ipv6")]
+                IpAddress::Ipv6(ref address) => (2, address.as_bytes()),
+            };
+            NetworkEndian::write_u16(&mut data[0..2], afi);
+            data[2..4].fill(0);
+            data[4..4 + bytes.len()].copy_from_slice(bytes);
+            data = &mut data[4 + bytes.len()..];
+        }
+        if let Some(name) = self.name {
+            let len = Self::name_len(name);
+            data[0] = len as u8;
+            data[1..1 + name.len()].copy_from_slice(name);
+            data[1 + name.len()..len].fill(0);
+            data = &mut data[len..];
+        }
+        if let Some(mtu) = self.mtu {
+            NetworkEndian::write_u32(data, mtu);
+        }
+    }
+}
+
+/// A high-level representation of an ICMP extension object.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ObjectRepr<'a> {
+    /// MPLS Label Stack, see [RFC 4950 § 7](https://www.rfc-editor.org/rfc/rfc4950#section-7).
+    MplsLabelStack(MplsLabelStack<'a>),
+    /// Interface Information, see
+    /// [RFC 5837 § 4](https://www.rfc-editor.org/rfc/rfc5837#section-4).
+    InterfaceInformation(InterfaceInformation<'a>),
+    Unknown {
+        class_num: ClassNum,
+        c_type: u8,
+        payload: &'a [u8],
+    },
+}
+
+impl<'a> ObjectRepr<'a> {
+    /// Parse an ICMP extension object and return a high-level representation.
+    pub fn parse<T>(obj: &ExtensionObject<&'a T>) -> Result<ObjectRepr<'a>>
+    where
+        T: AsRef<[u8]> + ?Sized,
+    {
+        obj.check_len()?;
+        match (obj.class_num(), obj.c_type()) {
+            (ClassNum::MplsLabelStack, 1) => {
+                MplsLabelStack::new(obj.payload()).map(ObjectRepr::MplsLabelStack)
+            }
+            (ClassNum::InterfaceInformation, c_type) => {
+                InterfaceInformation::parse(c_type, obj.payload())
+                    .map(ObjectRepr::InterfaceInformation)
+            }
+            (class_num, c_type) => Ok(ObjectRepr::Unknown {
+                class_num,
+                c_type,
+                payload: obj.payload(),
+            }),
+        }
+    }
+
+    /// Return the length of an object that will be emitted from this high-level representation.
+    pub const fn buffer_len(&self) -> usize {
+        field::C_TYPE
+            + 1
+            + match *self {
+                ObjectRepr::MplsLabelStack(stack) => stack.data.len(),
+                ObjectRepr::InterfaceInformation(ref info) => info.buffer_len(),
+                ObjectRepr::Unknown { payload, .. } => payload.len(),
+            }
+    }
+
+    /// Emit a high-level representation into an ICMP extension object.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]> + ?Sized>(
+        &self,
+        obj: &mut ExtensionObject<&'a mut T>,
+    ) {
+        obj.set_length(self.buffer_len() as u16);
+        match *self {
+            ObjectRepr::MplsLabelStack(stack) => {
+                obj.set_class_num(ClassNum::MplsLabelStack);
+                obj.set_c_type(1);
+                obj.payload_mut().copy_from_slice(stack.data);
+            }
+            ObjectRepr::InterfaceInformation(ref info) => {
+                obj.set_class_num(ClassNum::InterfaceInformation);
+                obj.set_c_type(info.c_type());
+                info.emit(obj.payload_mut());
+            }
+            ObjectRepr::Unknown {
+                class_num,
+                c_type,
+                payload,
+            } => {
+                obj.set_class_num(class_num);
+                obj.set_c_type(c_type);
+                obj.payload_mut().copy_from_slice(payload);
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Display for ObjectRepr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ObjectRepr::MplsLabelStack(stack) => {
+                write!(f, "ICMP extension {}", ClassNum::MplsLabelStack)?;
+                for entry in stack.iter() {
+                    write!(f, " label={} ttl={}", entry.label, entry.ttl)?;
+                }
+                Ok(())
+            }
+            ObjectRepr::InterfaceInformation(ref info) => {
+                write!(f, "ICMP extension {}", ClassNum::InterfaceInformation)?;
+                write!(f, " role={:?}", info.role)?;
+                if let Some(if_index) = info.if_index {
+                    write!(f, " ifindex={if_index}")?;
+                }
+                if let Some(address) = info.address {
+                    write!(f, " addr={address}")?;
+                }
+                if let Some(mtu) = info.mtu {
+                    write!(f, " mtu={mtu}")?;
+                }
+                Ok(())
+            }
+            ObjectRepr::Unknown {
+                class_num, c_type, ..
+            } => write!(f, "ICMP extension {class_num} c_type={c_type}"),
+        }
+    }
+}
+
+/// An iterator for ICMP extension objects.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExtensionObjectsIterator<'a> {
+    pos: usize,
+    data: &'a [u8],
+    done: bool,
+}
+
+impl<'a> ExtensionObjectsIterator<'a> {
+    /// Create a new `ExtensionObjectsIterator`, used to iterate over the objects following
+    /// an extension header.
+    pub fn new(data: &'a [u8]) -> ExtensionObjectsIterator<'a> {
+        ExtensionObjectsIterator {
+            pos: 0,
+            data,
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for ExtensionObjectsIterator<'a> {
+    type Item = Result<ObjectRepr<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() || self.done {
+            return None;
+        }
+
+        match ExtensionObject::new_checked(&self.data[self.pos..]) {
+            Ok(obj) => {
+                self.pos += obj.length() as usize;
+                Some(ObjectRepr::parse(&obj))
+            }
+            // Do not continue after an object with a bad length.
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// The extension objects of an ICMP error message, see [RFC 4884].
+///
+/// The objects are stored in their wire format, without the extension header.
+///
+/// [RFC 4884]: https://www.rfc-editor.org/rfc/rfc4884
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Extensions<'a> {
+    objects: &'a [u8],
+}
+
+impl<'a> Extensions<'a> {
+    /// No extensions.
+    pub const EMPTY: Extensions<'a> = Extensions { objects: &[] };
+
+    /// Create the extensions from the wire format of their objects.
+    ///
+    /// Returns `Err(Error)` if the length of an object is malformed.
+    pub fn new(objects: &'a [u8]) -> Result<Extensions<'a>> {
+        let mut pos = 0;
+        while pos < objects.len() {
+            pos += ExtensionObject::new_checked(&objects[pos..])?.length() as usize;
+        }
+        Ok(Extensions { objects })
+    }
+
+    /// Emit the objects into `buffer`, and return the extensions holding them.
+    ///
+    /// Returns `Err(Error)` if the objects do not fit in the buffer.
+    pub fn from_objects(objects: &[ObjectRepr], buffer: &'a mut [u8]) -> Result<Extensions<'a>> {
+        let len = objects.iter().map(|obj| obj.buffer_len()).sum();
+        if buffer.len() < len {
+            return Err(Error);
+        }
+
+        let mut pos = 0;
+        for obj in objects {
+            let end = pos + obj.buffer_len();
+            obj.emit(&mut ExtensionObject::new_unchecked(&mut buffer[pos..end]));
+            pos = end;
+        }
+        Ok(Extensions {
+            objects: &buffer[..len],
+        })
+    }
+
+    /// Parse the extension structure following the original datagram field of an ICMP
+    /// message, with its extension header.
+    ///
+    /// Returns `Err(Error)` if the version, the checksum or the objects are malformed.
+    pub fn parse(data: &'a [u8]) -> Result<Extensions<'a>> {
+        if data.len() < HEADER_LEN || data[field::VERSION] >> 4 != VERSION {
+            return Err(Error);
+        }
+        // A zero checksum is not verified.
+        if NetworkEndian::read_u16(&data[field::CHECKSUM]) != 0 && checksum::data(data) != !0 {
+            return Err(Error);
+        }
+        Extensions::new(&data[HEADER_LEN..])
+    }
+
+    /// Emit the extension structure, with its extension header, into `data`.
+    /// Nothing is emitted when there are no extensions.
+    ///
+    /// # Panics
+    /// This function panics if `data` is shorter than [buffer_len].
+    ///
+    /// [buffer_len]: #method.buffer_len
+    pub fn emit(&self, data: &mut [u8]) {
+        if self.is_empty() {
+            return;
+        }
+        let data = &mut data[..self.buffer_len()];
+        data[field::VERSION] = VERSION << 4;
+        data[field::VERSION + 1] = 0;
+        NetworkEndian::write_u16(&mut data[field::CHECKSUM], 0);
+        data[HEADER_LEN..].copy_from_slice(self.objects);
+        let checksum = !checksum::data(data);
+        NetworkEndian::write_u16(&mut data[field::CHECKSUM], checksum)
+    }
+
+    /// Return the objects in their wire format.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.objects
+    }
+
+    /// Return whether there are no extensions.
+    pub const fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Iterate over the objects.
+    pub fn iter(&self) -> ExtensionObjectsIterator<'a> {
+        ExtensionObjectsIterator::new(self.objects)
+    }
+
+    /// Return the length of the extension structure, with its extension header.
+    /// It is zero when there are no extensions.
+    pub const fn buffer_len(&self) -> usize {
+        if self.is_empty() {
+            0
+        } else {
+            HEADER_LEN + self.objects.len()
+        }
+    }
+
+    /// Split the data following the header of an ICMP error message into the original
+    /// datagram and the extensions, from the length of the original datagram in octets.
+    ///
+    /// Messages without a length, and messages with a length exceeding their data or
+    /// malformed extensions, have no extensions.
+    pub(crate) fn split(data: &'a [u8], original_len: usize) -> (&'a [u8], Extensions<'a>) {
+        if original_len == 0 || original_len > data.len() {
+            return (data, Extensions::EMPTY);
+        }
+        let (original, extensions) = data.split_at(original_len);
+        (
+            original,
+            Extensions::parse(extensions).unwrap_or(Extensions::EMPTY),
+        )
+    }
+
+    /// Return the length of the original datagram field holding `len` octets, padded to
+    /// `align` octets and to [MIN_ORIGINAL_DATAGRAM_LEN] when there are extensions.
+    pub(crate) const fn original_datagram_len(&self, len: usize, align: usize) -> usize {
+        if self.is_empty() {
+            len
+        } else if len < MIN_ORIGINAL_DATAGRAM_LEN {
+            MIN_ORIGINAL_DATAGRAM_LEN
+        } else {
+            (len + align - 1) & !(align - 1)
+        }
+    }
+}
+
+impl Default for Extensions<'_> {
+    fn default() -> Self {
+        Extensions::EMPTY
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static MPLS_OBJECT_BYTES: [u8; 12] = [
+        0x00, 0x0c, 0x01, 0x01, 0x00, 0x01, 0x20, 0xfe, 0x00, 0x02, 0x01, 0x01,
+    ];
+
+    #[test]
+    fn test_check_len() {
+        assert_eq!(
+            Err(Error),
+            ExtensionObject::new_unchecked(&MPLS_OBJECT_BYTES[..3]).check_len()
+        );
+        // length shorter than the object header
+        assert_eq!(
+            Err(Error),
+            ExtensionObject::new_unchecked(&[0x00, 0x03, 0x01, 0x01]).check_len()
+        );
+        // truncated payload
+        assert_eq!(
+            Err(Error),
+            ExtensionObject::new_unchecked(&MPLS_OBJECT_BYTES[..11]).check_len()
+        );
+        assert_eq!(
+            Ok(()),
+            ExtensionObject::new_unchecked(&MPLS_OBJECT_BYTES).check_len()
+        );
+    }
+
+    #[test]
+    fn test_mpls_label_stack() {
+        let obj = ExtensionObject::new_checked(&MPLS_OBJECT_BYTES[..]).unwrap();
+        let stack = match ObjectRepr::parse(&obj) {
+            Ok(ObjectRepr::MplsLabelStack(stack)) => stack,
+            other => panic!("unexpected {other:?}"),
+        };
+        let mut labels = stack.iter();
+        assert_eq!(
+            labels.next(),
+            Some(MplsLabel {
+                label: 18,
+                traffic_class: 0,
+                bottom_of_stack: false,
+                ttl: 0xfe,
+            })
+        );
+        assert_eq!(
+            labels.next(),
+            Some(MplsLabel {
+                label: 32,
+                traffic_class: 0,
+                bottom_of_stack: true,
+                ttl: 1,
+            })
+        );
+        assert_eq!(labels.next(), None);
+
+        let mut bytes = [0u8; 12];
+        ObjectRepr::MplsLabelStack(stack).emit(&mut ExtensionObject::new_unchecked(&mut bytes));
+        assert_eq!(bytes, MPLS_OBJECT_BYTES);
+
+        let mut entry = [0u8; 4];
+        MplsLabel {
+            label: 32,
+            traffic_class: 5,
+            bottom_of_stack: true,
+            ttl: 1,
+        }
+        .emit(&mut entry);
+        assert_eq!(entry, [0x00, 0x02, 0x0b, 0x01]);
+
+        assert_eq!(MplsLabelStack::new(&[]), Err(Error));
+        assert_eq!(MplsLabelStack::new(&MPLS_OBJECT_BYTES[4..10]), Err(Error));
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_interface_information() {
+        let repr = ObjectRepr::InterfaceInformation(InterfaceInformation {
+            role: InterfaceRole::IncomingInterface,
+            if_index: Some(3),
+            address: Some(Ipv4Address::new(192, 168, 1, 1).into()),
+            name: Some(b"eth0"),
+            mtu: Some(1500),
+        });
+        let bytes = [
+            0x00, 0x1c, 0x02, 0x0f, 0x00, 0x00, 0x00, 0x03, 0x00, 0x01, 0x00, 0x00, 0xc0, 0xa8,
+            0x01, 0x01, 0x08, 0x65, 0x74, 0x68, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0xdc,
+        ];
+        assert_eq!(repr.buffer_len(), bytes.len());
+
+        let mut buffer = [0xffu8; 28];
+        repr.emit(&mut ExtensionObject::new_unchecked(&mut buffer));
+        assert_eq!(buffer, bytes);
+
+        let obj = ExtensionObject::new_checked(&bytes[..]).unwrap();
+        assert_eq!(ObjectRepr::parse(&obj), Ok(repr));
+
+        // Only the MTU of the outgoing interface.
+        let bytes = [0x00, 0x08, 0x02, 0x81, 0x00, 0x00, 0x05, 0x00];
+        let obj = ExtensionObject::new_checked(&bytes[..]).unwrap();
+        assert_eq!(
+            ObjectRepr::parse(&obj),
+            Ok(ObjectRepr::InterfaceInformation(InterfaceInformation {
+                role: InterfaceRole::OutgoingInterface,
+                if_index: None,
+                address: None,
+                name: None,
+                mtu: Some(1280),
+            }))
+        );
+
+        // A truncated name sub-object.
+        let bytes = [0x00, 0x08, 0x02, 0x02, 0x08, 0x65, 0x74, 0x68];
+        let obj = ExtensionObject::new_checked(&bytes[..]).unwrap();
+        assert_eq!(ObjectRepr::parse(&obj), Err(Error));
+    }
+
+    #[test]
+    fn test_extensions() {
+        let mut objects = [0u8; 16];
+        let unknown = ObjectRepr::Unknown {
+            class_num: ClassNum::Unknown(3),
+            c_type: 1,
+            payload: &[0xaa; 4],
+        };
+        let stack = MplsLabelStack::new(&MPLS_OBJECT_BYTES[4..8]).unwrap();
+        let extensions =
+            Extensions::from_objects(&[ObjectRepr::MplsLabelStack(stack), unknown], &mut objects)
+                .unwrap();
+        assert_eq!(extensions.buffer_len(), 20);
+
+        let mut bytes = [0u8; 20];
+        extensions.emit(&mut bytes);
+        assert_eq!(&bytes[..4], &[0x20, 0x00, 0x65, 0x99]);
+
+        let parsed = Extensions::parse(&bytes).unwrap();
+        assert_eq!(parsed, extensions);
+        let mut iter = parsed.iter();
+        assert_eq!(iter.next(), Some(Ok(ObjectRepr::MplsLabelStack(stack))));
+        assert_eq!(iter.next(), Some(Ok(unknown)));
+        assert_eq!(iter.next(), None);
+
+        // Bad checksum.
+        bytes[19] ^= 0xff;
+        assert_eq!(Extensions::parse(&bytes), Err(Error));
+        // Bad version.
+        bytes[19] ^= 0xff;
+        bytes[0] = 0x10;
+        assert_eq!(Extensions::parse(&bytes), Err(Error));
+
+        assert_eq!(
+            Extensions::from_objects(&[unknown], &mut [0u8; 4]),
+            Err(Error)
+        );
+        assert_eq!(Extensions::new(&MPLS_OBJECT_BYTES[..8]), Err(Error));
+        assert_eq!(Extensions::EMPTY.buffer_len(), 0);
+    }
+
+    #[test]
+    fn test_original_datagram_len() {
+        let extensions = Extensions::new(&MPLS_OBJECT_BYTES).unwrap();
+        assert_eq!(Extensions::EMPTY.original_datagram_len(28, 4), 28);
+        assert_eq!(extensions.original_datagram_len(28, 4), 128);
+        assert_eq!(extensions.original_datagram_len(130, 4), 132);
+        assert_eq!(extensions.original_datagram_len(130, 8), 136);
+    }
+}
@@ -4,7 +4,7 @@ use core::{cmp, fmt};
 use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::ip::checksum;
-use crate::wire::{Ipv4Packet, Ipv4Repr};
+use crate::wire::{IcmpExtensions, Ipv4Address, Ipv4Options, Ipv4Packet, Ipv4Repr};
 
 enum_with_unknown! {
     /// Internet protocol control message type.
@@ -32,6 +32,20 @@ enum_with_unknown! {
     }
 }
 
+impl Message {
+    /// Return a boolean value indicating if the message type is an error message,
+    /// quoting the start of the datagram it was sent in response to.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            *self,
+            Message::DstUnreachable
+                | Message::Redirect
+                | Message::TimeExceeded
+                | Message::ParamProblem
+        )
+    }
+}
+
 impl fmt::Display for Message {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -128,6 +142,18 @@ enum_with_unknown! {
     }
 }
 
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Redirect::Net => write!(f, "redirect for network"),
+            Redirect::Host => write!(f, "redirect for host"),
+            Redirect::NetToS => write!(f, "redirect for ToS and network"),
+            Redirect::HostToS => write!(f, "redirect for ToS and host"),
+            Redirect::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
 enum_with_unknown! {
     /// Internet protocol control message subtype for type "Time Exceeded".
     pub enum TimeExceeded(u8) {
@@ -175,6 +201,9 @@ mod field {
     pub const CHECKSUM: Field = 2..4;
 
     pub const UNUSED: Field = 4..8;
+    pub const LENGTH: usize = 5;
+    pub const GATEWAY: Field = 4..8;
+    pub const NEXT_HOP_MTU: Field = 6..8;
 
     pub const ECHO_IDENT: Field = 4..6;
     pub const ECHO_SEQNO: Field = 6..8;
@@ -259,6 +288,43 @@ impl<T: AsRef<[u8]>> Packet<T> {
         NetworkEndian::read_u16(&data[field::ECHO_SEQNO])
     }
 
+    /// Return the gateway address field (for redirect packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a redirect packet.
+    #[inline]
+    pub fn gateway(&self) -> Ipv4Address {
+        let data = self.buffer.as_ref();
+        Ipv4Address::from_bytes(&data[field::GATEWAY])
+    }
+
+    /// Return the next-hop MTU field (for "fragmentation required" destination
+    /// unreachable packets), see [RFC 1191 § 4].
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a destination unreachable packet.
+    ///
+    /// [RFC 1191 § 4]: https://tools.ietf.org/html/rfc1191#section-4
+    #[inline]
+    pub fn next_hop_mtu(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::NEXT_HOP_MTU])
+    }
+
+    /// Return the length field, counting the octets of the original datagram in 32-bit
+    /// words (for destination unreachable, time exceeded and parameter problem packets),
+    /// see [RFC 4884 § 4.1]. It is zero when the packet has no extensions.
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not an error packet.
+    ///
+    /// [RFC 4884 § 4.1]: https://www.rfc-editor.org/rfc/rfc4884#section-4.1
+    #[inline]
+    pub fn length(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::LENGTH]
+    }
+
     /// Return the header length.
     /// The result depends on the value of the message type field.
     pub fn header_len(&self) -> usize {
@@ -335,6 +401,38 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u16(&mut data[field::ECHO_SEQNO], value)
     }
 
+    /// Set the gateway address field (for redirect packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a redirect packet.
+    #[inline]
+    pub fn set_gateway(&mut self, value: Ipv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::GATEWAY].copy_from_slice(value.as_bytes())
+    }
+
+    /// Set the next-hop MTU field (for "fragmentation required" destination
+    /// unreachable packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a destination unreachable packet.
+    #[inline]
+    pub fn set_next_hop_mtu(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::NEXT_HOP_MTU], value)
+    }
+
+    /// Set the length field (for destination unreachable, time exceeded and parameter
+    /// problem packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not an error packet.
+    #[inline]
+    pub fn set_length(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::LENGTH] = value
+    }
+
     /// Compute and fill in the header checksum.
     pub fn fill_checksum(&mut self) {
         self.set_checksum(0);
@@ -381,12 +479,67 @@ pub enum Repr<'a> {
         reason: DstUnreachable,
         header: Ipv4Repr,
         data: &'a [u8],
+        extensions: IcmpExtensions<'a>,
     },
     TimeExceeded {
         reason: TimeExceeded,
         header: Ipv4Repr,
         data: &'a [u8],
+        extensions: IcmpExtensions<'a>,
     },
+    /// A "fragmentation required, and DF flag set" destination unreachable message,
+    /// with the MTU of the next hop (see [RFC 1191 § 4]).
+    ///
+    /// [RFC 1191 § 4]: https://tools.ietf.org/html/rfc1191#section-4
+    FragRequired {
+        mtu: u16,
+        header: Ipv4Repr,
+        data: &'a [u8],
+        extensions: IcmpExtensions<'a>,
+    },
+    Redirect {
+        reason: Redirect,
+        gateway: Ipv4Address,
+        header: Ipv4Repr,
+        data: &'a [u8],
+    },
+}
+
+/// Parse the header and payload of the datagram quoted in an ICMP error message.
+///
+/// The padding of a datagram followed by extensions is left out.
+fn parse_quoted(data: &[u8], padded: bool) -> Result<(Ipv4Repr, &[u8])> {
+    let ip_packet = Ipv4Packet::new_checked(data)?;
+
+    let payload = if padded {
+        &data[ip_packet.header_len() as usize..ip_packet.total_len() as usize]
+    } else {
+        &data[ip_packet.header_len() as usize..]
+    };
+    // RFC 792 requires exactly eight bytes to be returned.
+    // We allow more, since there isn't a reason not to, but require at least eight.
+    if payload.len() < 8 {
+        return Err(Error);
+    }
+
+    let header = Ipv4Repr {
+        src_addr: ip_packet.src_addr(),
+        dst_addr: ip_packet.dst_addr(),
+        next_header: ip_packet.next_header(),
+        payload_len: payload.len(),
+        hop_limit: ip_packet.hop_limit(),
+        dscp: 0,
+        ecn: 0,
+        options: Ipv4Options::new(ip_packet.options())?,
+    };
+    Ok((header, payload))
+}
+
+/// Split the data of an ICMP error message into the original datagram and the extensions.
+fn split_extensions<'a, T: AsRef<[u8]> + ?Sized>(
+    packet: &Packet<&'a T>,
+) -> (&'a [u8], IcmpExtensions<'a>) {
+    IcmpExtensions::split(packet.data(), packet.length() as usize * 4)
 }
 
 impl<'a> Repr<'a> {
@@ -419,49 +572,48 @@ impl<'a> Repr<'a> {
                 data: packet.data(),
             }),
 
-            (Message::DstUnreachable, code) => {
-                let ip_packet = Ipv4Packet::new_checked(packet.data())?;
-
-                let payload = &packet.data()[ip_packet.header_len() as usize..];
-                // RFC 792 requires exactly eight bytes to be returned.
-                // We allow more, since there isn't a reason not to, but require at least eight.
-                if payload.len() < 8 {
-                    return Err(Error);
-                }
+            (Message::DstUnreachable, code)
+                if DstUnreachable::from(code) == DstUnreachable::FragRequired =>
+            {
+                let (original, extensions) = split_extensions(packet);
+                let (header, data) = parse_quoted(original, !extensions.is_empty())?;
+                Ok(Repr::FragRequired {
+                    mtu: packet.next_hop_mtu(),
+                    header,
+                    data,
+                    extensions,
+                })
+            }
 
+            (Message::DstUnreachable, code) => {
+                let (original, extensions) = split_extensions(packet);
+                let (header, data) = parse_quoted(original, !extensions.is_empty())?;
                 Ok(Repr::DstUnreachable {
                     reason: DstUnreachable::from(code),
-                    header: Ipv4Repr {
-                        src_addr: ip_packet.src_addr(),
-                        dst_addr: ip_packet.dst_addr(),
-                        next_header: ip_packet.next_header(),
-                        payload_len: payload.len(),
-                        hop_limit: ip_packet.hop_limit(),
-                    },
-                    data: payload,
+                    header,
+                    data,
+                    extensions,
                 })
             }
 
             (Message::TimeExceeded, code) => {
-                let ip_packet = Ipv4Packet::new_checked(packet.data())?;
-
-                let payload = &packet.data()[ip_packet.header_len() as usize..];
-                // RFC 792 requires exactly eight bytes to be returned.
-                // We allow more, since there isn't a reason not to, but require at least eight.
-                if payload.len() < 8 {
-                    return Err(Error);
-                }
-
+                let (original, extensions) = split_extensions(packet);
+                let (header, data) = parse_quoted(original, !extensions.is_empty())?;
                 Ok(Repr::TimeExceeded {
                     reason: TimeExceeded::from(code),
-                    header: Ipv4Repr {
-                        src_addr: ip_packet.src_addr(),
-                        dst_addr: ip_packet.dst_addr(),
-                        next_header: ip_packet.next_header(),
-                        payload_len: payload.len(),
-                        hop_limit: ip_packet.hop_limit(),
-                    },
-                    data: payload,
+                    header,
+                    data,
+                    extensions,
+                })
+            }
+
+            (Message::Redirect, code) => {
+                let (header, data) = parse_quoted(packet.data(), false)?;
+                Ok(Repr::Redirect {
+                    reason: Redirect::from(code),
+                    gateway: packet.gateway(),
+                    header,
+                    data,
                 })
             }
 
@@ -475,8 +627,29 @@ impl<'a> Repr<'a> {
             &Repr::EchoRequest { data, .. } | &Repr::EchoReply { data, .. } => {
                 field::ECHO_SEQNO.end + data.len()
             }
-            &Repr::DstUnreachable { header, data, .. }
-            | &Repr::TimeExceeded { header, data, .. } => {
+            &Repr::DstUnreachable {
+                header,
+                data,
+                extensions,
+                ..
+            }
+            | &Repr::TimeExceeded {
+                header,
+                data,
+                extensions,
+                ..
+            }
+            | &Repr::FragRequired {
+                header,
+                data,
+                extensions,
+                ..
+            } => {
+                field::UNUSED.end
+                    + extensions.original_datagram_len(header.buffer_len() + data.len(), 4)
+                    + extensions.buffer_len()
+            }
+            &Repr::Redirect { header, data, .. } => {
                 field::UNUSED.end + header.buffer_len() + data.len()
             }
         }
@@ -488,6 +661,29 @@ impl<'a> Repr<'a> {
     where
         T: AsRef<[u8]> + AsMut<[u8]> + ?Sized,
     {
+        fn emit_original_datagram<T>(
+            packet: &mut Packet<&mut T>,
+            header: Ipv4Repr,
+            data: &[u8],
+            extensions: &IcmpExtensions,
+            checksum_caps: &ChecksumCapabilities,
+        ) where
+            T: AsRef<[u8]> + AsMut<[u8]> + ?Sized,
+        {
+            let len = header.buffer_len() + data.len();
+            let original_len = extensions.original_datagram_len(len, 4);
+            if !extensions.is_empty() {
+                packet.set_length((original_len / 4) as u8);
+            }
+
+            let mut ip_packet = Ipv4Packet::new_unchecked(packet.data_mut());
+            header.emit(&mut ip_packet, checksum_caps);
+            let payload = &mut ip_packet.into_inner()[header.buffer_len()..];
+            payload[..data.len()].copy_from_slice(data);
+            payload[data.len()..original_len - header.buffer_len()].fill(0);
+            extensions.emit(&mut payload[original_len - header.buffer_len()..]);
+        }
+
         packet.set_msg_code(0);
         match *self {
             Repr::EchoRequest {
@@ -520,23 +716,51 @@ impl<'a> Repr<'a> {
                 reason,
                 header,
                 data,
+                extensions,
             } => {
                 packet.set_msg_type(Message::DstUnreachable);
                 packet.set_msg_code(reason.into());
+                packet.buffer.as_mut()[field::UNUSED].fill(0);
 
-                let mut ip_packet = Ipv4Packet::new_unchecked(packet.data_mut());
-                header.emit(&mut ip_packet, checksum_caps);
-                let payload = &mut ip_packet.into_inner()[header.buffer_len()..];
-                payload.copy_from_slice(data)
+                emit_original_datagram(packet, header, data, &extensions, checksum_caps);
             }
 
             Repr::TimeExceeded {
                 reason,
                 header,
                 data,
+                extensions,
             } => {
                 packet.set_msg_type(Message::TimeExceeded);
                 packet.set_msg_code(reason.into());
+                packet.buffer.as_mut()[field::UNUSED].fill(0);
+
+                emit_original_datagram(packet, header, data, &extensions, checksum_caps);
+            }
+
+            Repr::FragRequired {
+                mtu,
+                header,
+                data,
+                extensions,
+            } => {
+                packet.set_msg_type(Message::DstUnreachable);
+                packet.set_msg_code(DstUnreachable::FragRequired.into());
+                packet.buffer.as_mut()[field::UNUSED.start..field::NEXT_HOP_MTU.start].fill(0);
+                packet.set_next_hop_mtu(mtu);
+
+                emit_original_datagram(packet, header, data, &extensions, checksum_caps);
+            }
+
+            Repr::Redirect {
+                reason,
+                gateway,
+                header,
+                data,
+            } => {
+                packet.set_msg_type(Message::Redirect);
+                packet.set_msg_code(reason.into());
+                packet.set_gateway(gateway);
 
                 let mut ip_packet = Ipv4Packet::new_unchecked(packet.data_mut());
                 header.emit(&mut ip_packet, checksum_caps);
@@ -569,6 +793,9 @@ impl<'a, T: AsRef<[u8]> + ?Sized> fmt::Display for Packet<&'a T> {
                     Message::TimeExceeded => {
                         write!(f, " code={:?}", TimeExceeded::from(self.msg_code()))
                     }
+                    Message::Redirect => {
+                        write!(f, " code={:?}", Redirect::from(self.msg_code()))
+                    }
                     _ => write!(f, " code={}", self.msg_code()),
                 }
             }
@@ -607,6 +834,14 @@ impl<'a> fmt::Display for Repr<'a> {
             Repr::TimeExceeded { reason, .. } => {
                 write!(f, "ICMPv4 time exceeded ({reason})")
             }
+            Repr::FragRequired { mtu, .. } => {
+                write!(f, "ICMPv4 fragmentation required (mtu={mtu})")
+            }
+            Repr::Redirect {
+                reason, gateway, ..
+            } => {
+                write!(f, "ICMPv4 redirect ({reason}) gateway={gateway}")
+            }
         }
     }
 }
@@ -626,7 +861,7 @@ impl<T: AsRef<[u8]>> PrettyPrint for Packet<T> {
         write!(f, "{indent}{packet}")?;
 
         match packet.msg_type() {
-            Message::DstUnreachable | Message::TimeExceeded => {
+            Message::DstUnreachable | Message::TimeExceeded | Message::Redirect => {
                 indent.increase(f)?;
                 super::Ipv4Packet::<&[u8]>::pretty_print(&packet.data(), f, indent)
             }
@@ -694,6 +929,120 @@ mod test {
         assert_eq!(&packet.into_inner()[..], &ECHO_PACKET_BYTES[..]);
     }
 
+    fn quoted_header() -> Ipv4Repr {
+        Ipv4Repr {
+            src_addr: Ipv4Address::new(192, 168, 1, 2),
+            dst_addr: Ipv4Address::new(10, 0, 0, 1),
+            next_header: crate::wire::IpProtocol::Udp,
+            payload_len: 8,
+            hop_limit: 63,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
+        }
+    }
+
+    static QUOTED_DATA_BYTES: [u8; 8] = [0x12, 0x34, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00];
+
+    #[test]
+    fn test_frag_required_emit_parse() {
+        let repr = Repr::FragRequired {
+            mtu: 1400,
+            header: quoted_header(),
+            data: &QUOTED_DATA_BYTES,
+            extensions: IcmpExtensions::EMPTY,
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &ChecksumCapabilities::default());
+        assert_eq!(packet.msg_type(), Message::DstUnreachable);
+        assert_eq!(packet.msg_code(), 4);
+        assert_eq!(&packet.as_ref()[4..8], &[0x00, 0x00, 0x05, 0x78]);
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(
+            Repr::parse(&packet, &ChecksumCapabilities::default()),
+            Ok(repr)
+        );
+    }
+
+    #[test]
+    fn test_time_exceeded_extensions_emit_parse() {
+        use crate::wire::{IcmpExtensionObjectRepr, MplsLabelStack};
+
+        let mut objects = [0u8; 8];
+        let labels = [0x00, 0x01, 0x21, 0x01];
+        let extensions = IcmpExtensions::from_objects(
+            &[IcmpExtensionObjectRepr::MplsLabelStack(
+                MplsLabelStack::new(&labels).unwrap(),
+            )],
+            &mut objects,
+        )
+        .unwrap();
+        let repr = Repr::TimeExceeded {
+            reason: TimeExceeded::TtlExpired,
+            header: quoted_header(),
+            data: &QUOTED_DATA_BYTES,
+            extensions,
+        };
+        // The original datagram is padded to 128 octets.
+        assert_eq!(repr.buffer_len(), 8 + 128 + 12);
+
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &ChecksumCapabilities::default());
+        assert_eq!(&packet.as_ref()[4..8], &[0x00, 32, 0x00, 0x00]);
+        assert!(packet.as_ref()[8 + 28..8 + 128].iter().all(|&b| b == 0));
+        assert_eq!(
+            &packet.as_ref()[8 + 128 + 4..],
+            &[0x00, 0x08, 0x01, 0x01, 0x00, 0x01, 0x21, 0x01]
+        );
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(
+            Repr::parse(&packet, &ChecksumCapabilities::default()),
+            Ok(repr)
+        );
+
+        // Without the length field, the extensions are part of the original datagram.
+        bytes[5] = 0;
+        bytes[2..4].fill(0);
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.fill_checksum();
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        match Repr::parse(&packet, &ChecksumCapabilities::default()) {
+            Ok(Repr::TimeExceeded {
+                data, extensions, ..
+            }) => {
+                assert_eq!(data.len(), 128 - 20 + 12);
+                assert!(extensions.is_empty());
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_redirect_emit_parse() {
+        let repr = Repr::Redirect {
+            reason: Redirect::Host,
+            gateway: Ipv4Address::new(192, 168, 1, 254),
+            header: quoted_header(),
+            data: &QUOTED_DATA_BYTES,
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &ChecksumCapabilities::default());
+        assert_eq!(packet.msg_type(), Message::Redirect);
+        assert_eq!(packet.msg_code(), 1);
+        assert_eq!(packet.gateway(), Ipv4Address::new(192, 168, 1, 254));
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(
+            Repr::parse(&packet, &ChecksumCapabilities::default()),
+            Ok(repr)
+        );
+    }
+
     #[test]
     fn test_check_len() {
         let bytes = [0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
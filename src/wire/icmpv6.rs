@@ -4,6 +4,7 @@ use core::{cmp, fmt};
 use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::ip::checksum;
+use crate::wire::IcmpExtensions;
 use crate::wire::MldRepr;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
 use crate::wire::NdiscRepr;
@@ -208,6 +209,7 @@ pub(super) mod field {
     pub const CHECKSUM: Field = 2..4;
 
     pub const UNUSED: Field = 4..8;
+    pub const LENGTH: usize = 4;
     pub const MTU: Field = 4..8;
     pub const POINTER: Field = 4..8;
     pub const ECHO_IDENT: Field = 4..6;
@@ -392,6 +394,17 @@ impl<T: AsRef<[u8]>> Packet<T> {
         NetworkEndian::read_u32(&data[field::POINTER])
     }
 
+    /// Return the length field, counting the octets of the original datagram in 64-bit
+    /// words (for destination unreachable and time exceeded messages), see [RFC 4884 § 4.2].
+    /// It is zero when the message has no extensions.
+    ///
+    /// [RFC 4884 § 4.2]: https://www.rfc-editor.org/rfc/rfc4884#section-4.2
+    #[inline]
+    pub fn length(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::LENGTH]
+    }
+
     /// Return the header length. The result depends on the value of
     /// the message type field.
     pub fn header_len(&self) -> usize {
@@ -468,7 +481,9 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
     #[inline]
     pub fn clear_reserved(&mut self) {
         match self.msg_type() {
-            Message::RouterSolicit
+            Message::DstUnreachable
+            | Message::TimeExceeded
+            | Message::RouterSolicit
             | Message::NeighborSolicit
             | Message::NeighborAdvert
             | Message::Redirect => {
@@ -534,6 +549,17 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u32(&mut data[field::POINTER], value)
     }
 
+    /// Set the length field (for destination unreachable and time exceeded messages).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a destination unreachable or time
+    /// exceeded message.
+    #[inline]
+    pub fn set_length(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::LENGTH] = value
+    }
+
     /// Compute and fill in the header checksum.
     pub fn fill_checksum(&mut self, src_addr: &Ipv6Address, dst_addr: &Ipv6Address) {
         self.set_checksum(0);
@@ -576,6 +602,7 @@ pub enum Repr<'a> {
         reason: DstUnreachable,
         header: Ipv6Repr,
         data: &'a [u8],
+        extensions: IcmpExtensions<'a>,
     },
     PktTooBig {
         mtu: u32,
@@ -586,6 +613,7 @@ pub enum Repr<'a> {
         reason: TimeExceeded,
         header: Ipv6Repr,
         data: &'a [u8],
+        extensions: IcmpExtensions<'a>,
     },
     ParamProblem {
         reason: ParamProblem,
@@ -610,6 +638,14 @@ pub enum Repr<'a> {
     Rpl(RplRepr<'a>),
 }
 
+/// Return the length of the original datagram field of an error message, truncated for the
+/// message to fit the minimum MTU.
+fn original_datagram_len(header: &Ipv6Repr, data: &[u8], extensions: &IcmpExtensions) -> usize {
+    let len = header.buffer_len() + data.len();
+    let max_len = (MAX_ERROR_PACKET_LEN - field::UNUSED.end - extensions.buffer_len()) & !7;
+    extensions.original_datagram_len(cmp::min(len, max_len), 8)
+}
+
 impl<'a> Repr<'a> {
     /// Parse an Internet Control Message Protocol version 6 packet and return
     /// a high-level representation.
@@ -624,26 +660,29 @@ impl<'a> Repr<'a> {
     {
         packet.check_len()?;
 
-        fn create_packet_from_payload<'a, T>(packet: &Packet<&'a T>) -> Result<(&'a [u8], Ipv6Repr)>
-        where
-            T: AsRef<[u8]> + ?Sized,
-        {
+        fn create_packet_from_payload(original: &[u8], padded: bool) -> Result<(&[u8], Ipv6Repr)> {
             // The packet must be truncated to fit the min MTU. Since we don't know the offset of
             // the ICMPv6 header in the L2 frame, we should only check whether the payload's IPv6
             // header is present, the rest is allowed to be truncated.
-            let ip_packet = if packet.payload().len() >= IPV6_HEADER_LEN {
-                Ipv6Packet::new_unchecked(packet.payload())
+            let ip_packet = if original.len() >= IPV6_HEADER_LEN {
+                Ipv6Packet::new_unchecked(original)
             } else {
                 return Err(Error);
             };
 
-            let payload = &packet.payload()[ip_packet.header_len()..];
+            let mut payload = &original[ip_packet.header_len()..];
+            // The padding of a datagram followed by extensions is left out.
+            if padded {
+                payload = &payload[..cmp::min(payload.len(), ip_packet.payload_len().into())];
+            }
             let repr = Ipv6Repr {
                 src_addr: ip_packet.src_addr(),
                 dst_addr: ip_packet.dst_addr(),
                 next_header: ip_packet.next_header(),
                 payload_len: ip_packet.payload_len().into(),
                 hop_limit: ip_packet.hop_limit(),
+                dscp: 0,
+                ecn: 0,
             };
             Ok((payload, repr))
         }
@@ -654,15 +693,18 @@ impl<'a> Repr<'a> {
 
         match (packet.msg_type(), packet.msg_code()) {
             (Message::DstUnreachable, code) => {
-                let (payload, repr) = create_packet_from_payload(packet)?;
+                let (original, extensions) =
+                    IcmpExtensions::split(packet.payload(), packet.length() as usize * 8);
+                let (payload, repr) = create_packet_from_payload(original, !extensions.is_empty())?;
                 Ok(Repr::DstUnreachable {
                     reason: DstUnreachable::from(code),
                     header: repr,
                     data: payload,
+                    extensions,
                 })
             }
             (Message::PktTooBig, 0) => {
-                let (payload, repr) = create_packet_from_payload(packet)?;
+                let (payload, repr) = create_packet_from_payload(packet.payload(), false)?;
                 Ok(Repr::PktTooBig {
                     mtu: packet.pkt_too_big_mtu(),
                     header: repr,
@@ -670,15 +712,18 @@ impl<'a> Repr<'a> {
                 })
             }
             (Message::TimeExceeded, code) => {
-                let (payload, repr) = create_packet_from_payload(packet)?;
+                let (original, extensions) =
+                    IcmpExtensions::split(packet.payload(), packet.length() as usize * 8);
+                let (payload, repr) = create_packet_from_payload(original, !extensions.is_empty())?;
                 Ok(Repr::TimeExceeded {
                     reason: TimeExceeded::from(code),
                     header: repr,
                     data: payload,
+                    extensions,
                 })
             }
             (Message::ParamProblem, code) => {
-                let (payload, repr) = create_packet_from_payload(packet)?;
+                let (payload, repr) = create_packet_from_payload(packet.payload(), false)?;
                 Ok(Repr::ParamProblem {
                     reason: ParamProblem::from(code),
                     pointer: packet.param_problem_ptr(),
@@ -708,6 +753,22 @@ impl<'a> Repr<'a> {
     /// Return the length of a packet that will be emitted from this high-level representation.
     pub fn buffer_len(&self) -> usize {
         match self {
+            &Repr::DstUnreachable {
+                header,
+                data,
+                extensions,
+                ..
+            }
+            | &Repr::TimeExceeded {
+                header,
+                data,
+                extensions,
+                ..
+            } if !extensions.is_empty() => {
+                field::UNUSED.end
+                    + original_datagram_len(&header, data, &extensions)
+                    + extensions.buffer_len()
+            }
             &Repr::DstUnreachable { header, data, .. }
             | &Repr::PktTooBig { header, data, .. }
             | &Repr::TimeExceeded { header, data, .. }
@@ -737,11 +798,20 @@ impl<'a> Repr<'a> {
     ) where
         T: AsRef<[u8]> + AsMut<[u8]> + ?Sized,
     {
-        fn emit_contained_packet<T>(packet: &mut Packet<&mut T>, header: Ipv6Repr, data: &[u8])
-        where
+        fn emit_contained_packet<T>(
+            packet: &mut Packet<&mut T>,
+            header: Ipv6Repr,
+            data: &[u8],
+            extensions: &IcmpExtensions,
+        ) where
             T: AsRef<[u8]> + AsMut<[u8]> + ?Sized,
         {
             let icmp_header_len = packet.header_len();
+            let original_len = original_datagram_len(&header, data, extensions);
+            if !extensions.is_empty() {
+                packet.set_length((original_len / 8) as u8);
+            }
+
             let mut ip_packet = Ipv6Packet::new_unchecked(packet.payload_mut());
             header.emit(&mut ip_packet);
             let payload = &mut ip_packet.into_inner()[header.buffer_len()..];
@@ -751,7 +821,12 @@ impl<'a> Repr<'a> {
                 data.len(),
                 MAX_ERROR_PACKET_LEN - icmp_header_len - IPV6_HEADER_LEN,
             );
+            let payload_len = cmp::min(payload_len, original_len - header.buffer_len());
             payload[..payload_len].copy_from_slice(&data[..payload_len]);
+            if !extensions.is_empty() {
+                payload[payload_len..original_len - header.buffer_len()].fill(0);
+                extensions.emit(&mut payload[original_len - header.buffer_len()..]);
+            }
         }
 
         match *self {
@@ -759,11 +834,13 @@ impl<'a> Repr<'a> {
                 reason,
                 header,
                 data,
+                extensions,
             } => {
                 packet.set_msg_type(Message::DstUnreachable);
                 packet.set_msg_code(reason.into());
+                packet.clear_reserved();
 
-                emit_contained_packet(packet, header, data);
+                emit_contained_packet(packet, header, data, &extensions);
             }
 
             Repr::PktTooBig { mtu, header, data } => {
@@ -771,18 +848,20 @@ impl<'a> Repr<'a> {
                 packet.set_msg_code(0);
                 packet.set_pkt_too_big_mtu(mtu);
 
-                emit_contained_packet(packet, header, data);
+                emit_contained_packet(packet, header, data, &IcmpExtensions::EMPTY);
             }
 
             Repr::TimeExceeded {
                 reason,
                 header,
                 data,
+                extensions,
             } => {
                 packet.set_msg_type(Message::TimeExceeded);
                 packet.set_msg_code(reason.into());
+                packet.clear_reserved();
 
-                emit_contained_packet(packet, header, data);
+                emit_contained_packet(packet, header, data, &extensions);
             }
 
             Repr::ParamProblem {
@@ -795,7 +874,7 @@ impl<'a> Repr<'a> {
                 packet.set_msg_code(reason.into());
                 packet.set_param_problem_ptr(pointer);
 
-                emit_contained_packet(packet, header, data);
+                emit_contained_packet(packet, header, data, &IcmpExtensions::EMPTY);
             }
 
             Repr::EchoRequest {
@@ -899,6 +978,8 @@ mod test {
                 next_header: IpProtocol::Udp,
                 payload_len: 12,
                 hop_limit: 0x40,
+                dscp: 0,
+                ecn: 0,
             },
             data: &PKT_TOO_BIG_UDP_PAYLOAD,
         }
@@ -1021,6 +1102,8 @@ mod test {
                 dst_addr: Default::default(),
                 next_header: IpProtocol::Tcp,
                 hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
                 payload_len: 1280,
             },
             data: &vec![0; 9999],
@@ -1035,6 +1118,8 @@ mod test {
             dst_addr: Default::default(),
             next_header: IpProtocol::Tcp,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
             payload_len: IPV6_MIN_MTU - IPV6_HEADER_LEN,
         };
         let mut ip_packet = Ipv6Packet::new_unchecked(vec![0; IPV6_MIN_MTU]);
@@ -1073,6 +1158,83 @@ mod test {
         assert_eq!(repr1, repr2);
     }
 
+    #[test]
+    fn test_dst_unreachable_extensions_emit_parse() {
+        use crate::wire::{IcmpExtensionObjectRepr, IcmpInterfaceInformation, IcmpInterfaceRole};
+
+        let mut objects = [0u8; 8];
+        let extensions = IcmpExtensions::from_objects(
+            &[IcmpExtensionObjectRepr::InterfaceInformation(
+                IcmpInterfaceInformation {
+                    role: IcmpInterfaceRole::IncomingInterface,
+                    if_index: Some(2),
+                    address: None,
+                    name: None,
+                    mtu: None,
+                },
+            )],
+            &mut objects,
+        )
+        .unwrap();
+        let repr = Repr::DstUnreachable {
+            reason: DstUnreachable::AddrUnreachable,
+            header: Ipv6Repr {
+                src_addr: MOCK_IP_ADDR_1,
+                dst_addr: MOCK_IP_ADDR_2,
+                next_header: IpProtocol::Udp,
+                payload_len: 12,
+                hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
+            },
+            data: &PKT_TOO_BIG_UDP_PAYLOAD,
+            extensions,
+        };
+        // The original datagram is padded to 128 octets.
+        assert_eq!(repr.buffer_len(), 8 + 128 + 12);
+
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(
+            &MOCK_IP_ADDR_1,
+            &MOCK_IP_ADDR_2,
+            &mut packet,
+            &ChecksumCapabilities::default(),
+        );
+        assert_eq!(&packet.as_ref()[4..8], &[16, 0x00, 0x00, 0x00]);
+        assert!(packet.as_ref()[8 + 52..8 + 128].iter().all(|&b| b == 0));
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        let parsed = Repr::parse(
+            &MOCK_IP_ADDR_1,
+            &MOCK_IP_ADDR_2,
+            &packet,
+            &ChecksumCapabilities::default(),
+        );
+        assert_eq!(parsed, Ok(repr));
+    }
+
+    #[test]
+    fn test_extensions_truncated_to_mtu() {
+        let objects = [0x00, 0x08, 0x03, 0x01, 0xaa, 0xaa, 0xaa, 0xaa];
+        let repr = Repr::TimeExceeded {
+            reason: TimeExceeded::HopLimitExceeded,
+            header: Ipv6Repr {
+                src_addr: Default::default(),
+                dst_addr: Default::default(),
+                next_header: IpProtocol::Tcp,
+                hop_limit: 64,
+                dscp: 0,
+                ecn: 0,
+                payload_len: 1280,
+            },
+            data: &vec![0; 9999],
+            extensions: IcmpExtensions::new(&objects).unwrap(),
+        };
+        // The original datagram is a multiple of 64 bits, and leaves room for the extensions.
+        assert_eq!(repr.buffer_len(), 8 + 1216 + 12);
+    }
+
     #[test]
     fn test_truncated_payload_ipv6_header_parse_fails() {
         let repr = too_big_packet_repr();
@@ -4,7 +4,7 @@ use core::fmt;
 use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
 #[cfg(feature = "proto-ipv4")]
-use crate::wire::{Ipv4Address, Ipv4Cidr, Ipv4Packet, Ipv4Repr};
+use crate::wire::{Ipv4Address, Ipv4Cidr, Ipv4Options, Ipv4Packet, Ipv4Repr};
 #[cfg(feature = "proto-ipv6")]
 use crate::wire::{Ipv6Address, Ipv6Cidr, Ipv6Packet, Ipv6Repr};
 
@@ -59,7 +59,8 @@ enum_with_unknown! {
         IpSecAh   = 0x33,
         Icmpv6    = 0x3a,
         Ipv6NoNxt = 0x3b,
-        Ipv6Opts  = 0x3c
+        Ipv6Opts  = 0x3c,
+        UdpLite   = 0x88
     }
 }
 
@@ -78,6 +79,7 @@ impl fmt::Display for Protocol {
             Protocol::Icmpv6 => write!(f, "ICMPv6"),
             Protocol::Ipv6NoNxt => write!(f, "IPv6-NoNxt"),
             Protocol::Ipv6Opts => write!(f, "IPv6-Opts"),
+            Protocol::UdpLite => write!(f, "UDP-Lite"),
             Protocol::Unknown(id) => write!(f, "0x{id:02x}"),
         }
     }
@@ -168,6 +170,16 @@ impl Address {
         }
     }
 
+    /// Query whether the address falls into the "loopback" range.
+    pub fn is_loopback(&self) -> bool {
+        match self {
+            #[cfg(feature = "proto-ipv4")]
+            Address::Ipv4(addr) => addr.is_loopback(),
+            #[cfg(feature = "proto-ipv6")]
+            Address::Ipv6(addr) => addr.is_loopback(),
+        }
+    }
+
     /// If `self` is a CIDR-compatible subnet mask, return `Some(prefix_len)`,
     /// where `prefix_len` is the number of leading zeroes. Return `None` otherwise.
     pub fn prefix_len(&self) -> Option<u8> {
@@ -585,6 +597,9 @@ impl Repr {
                 next_header,
                 payload_len,
                 hop_limit,
+                dscp: 0,
+                ecn: 0,
+                options: Ipv4Options::EMPTY,
             }),
             #[cfg(feature = "proto-ipv6")]
             (Address::Ipv6(src_addr), Address::Ipv6(dst_addr)) => Self::Ipv6(Ipv6Repr {
@@ -593,6 +608,8 @@ impl Repr {
                 next_header,
                 payload_len,
                 hop_limit,
+                dscp: 0,
+                ecn: 0,
             }),
             #[allow(unreachable_patterns)]
             _ => panic!("IP version mismatch: src={src_addr:?} dst={dst_addr:?}"),
@@ -669,6 +686,46 @@ impl Repr {
         }
     }
 
+    /// Return the Differentiated Services Code Point.
+    pub const fn dscp(&self) -> u8 {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(Ipv4Repr { dscp, .. }) => dscp,
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { dscp, .. }) => dscp,
+        }
+    }
+
+    /// Set the Differentiated Services Code Point.
+    pub fn set_dscp(&mut self, value: u8) {
+        match self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(Ipv4Repr { dscp, .. }) => *dscp = value,
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { dscp, .. }) => *dscp = value,
+        }
+    }
+
+    /// Return the Explicit Congestion Notification bits.
+    pub const fn ecn(&self) -> u8 {
+        match *self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(Ipv4Repr { ecn, .. }) => ecn,
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { ecn, .. }) => ecn,
+        }
+    }
+
+    /// Set the Explicit Congestion Notification bits.
+    pub fn set_ecn(&mut self, value: u8) {
+        match self {
+            #[cfg(feature = "proto-ipv4")]
+            Repr::Ipv4(Ipv4Repr { ecn, .. }) => *ecn = value,
+            #[cfg(feature = "proto-ipv6")]
+            Repr::Ipv6(Ipv6Repr { ecn, .. }) => *ecn = value,
+        }
+    }
+
     /// Return the length of a header that will be emitted from this high-level representation.
     pub const fn header_len(&self) -> usize {
         match *self {
@@ -832,7 +889,7 @@ pub fn pretty_print_ip_payload<T: Into<Repr>>(
     use crate::wire::ip::checksum::format_checksum;
     #[cfg(feature = "proto-ipv4")]
     use crate::wire::Icmpv4Packet;
-    use crate::wire::{TcpPacket, TcpRepr, UdpPacket, UdpRepr};
+    use crate::wire::{TcpPacket, TcpRepr, UdpLitePacket, UdpPacket, UdpRepr};
 
     let checksum_caps = ChecksumCapabilities::ignored();
     let repr = ip_repr.into();
@@ -870,6 +927,17 @@ pub fn pretty_print_ip_payload<T: Into<Repr>>(
                 }
             }
         }
+        Protocol::UdpLite => {
+            indent.increase(f)?;
+            match UdpLitePacket::<&[u8]>::new_checked(payload) {
+                Err(err) => write!(f, "{indent}({err})"),
+                Ok(udp_packet) => {
+                    write!(f, "{indent}{udp_packet}")?;
+                    let valid = udp_packet.verify_checksum(&repr.src_addr(), &repr.dst_addr());
+                    format_checksum(f, valid)
+                }
+            }
+        }
         Protocol::Tcp => {
             indent.increase(f)?;
             match TcpPacket::<&[u8]>::new_checked(payload) {
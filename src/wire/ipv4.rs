@@ -1,7 +1,7 @@
 use byteorder::{ByteOrder, NetworkEndian};
 use core::fmt;
 
-use super::{Error, Result};
+use super::{Error, Ipv4Options, Result};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::ip::{checksum, pretty_print_ip_payload};
 
@@ -52,6 +52,9 @@ impl Address {
     /// All multicast-capable routers
     pub const MULTICAST_ALL_ROUTERS: Address = Address([224, 0, 0, 2]);
 
+    /// All IGMPv3-capable multicast routers
+    pub const MULTICAST_ALL_IGMPV3_ROUTERS: Address = Address([224, 0, 0, 22]);
+
     /// Construct an IPv4 address from parts.
     pub const fn new(a0: u8, a1: u8, a2: u8, a3: u8) -> Address {
         Address([a0, a1, a2, a3])
@@ -320,8 +323,8 @@ impl<T: AsRef<[u8]>> Packet<T> {
 
     /// Ensure that no accessor method will panic if called.
     /// Returns `Err(Error)` if the buffer is too short.
-    /// Returns `Err(Error)` if the header length is greater
-    /// than total length.
+    /// Returns `Err(Error)` if the header length is shorter than the fixed header,
+    /// or greater than total length.
     ///
     /// The result of this check is invalidated by calling [set_header_len]
     /// and [set_total_len].
@@ -335,6 +338,8 @@ impl<T: AsRef<[u8]>> Packet<T> {
             Err(Error)
         } else if len < self.header_len() as usize {
             Err(Error)
+        } else if (self.header_len() as usize) < field::DST_ADDR.end {
+            Err(Error)
         } else if self.header_len() as u16 > self.total_len() {
             Err(Error)
         } else if len < self.total_len() as usize {
@@ -470,6 +475,14 @@ impl<T: AsRef<[u8]>> Packet<T> {
 }
 
 impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the options, including their padding.
+    #[inline]
+    pub fn options(&self) -> &'a [u8] {
+        let range = field::DST_ADDR.end..self.header_len() as usize;
+        let data = self.buffer.as_ref();
+        &data[range]
+    }
+
     /// Return a pointer to the payload.
     #[inline]
     pub fn payload(&self) -> &'a [u8] {
@@ -601,6 +614,14 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         self.set_checksum(checksum)
     }
 
+    /// Return a mutable pointer to the options, including their padding.
+    #[inline]
+    pub fn options_mut(&mut self) -> &mut [u8] {
+        let range = field::DST_ADDR.end..self.header_len() as usize;
+        let data = self.buffer.as_mut();
+        &mut data[range]
+    }
+
     /// Return a mutable pointer to the payload.
     #[inline]
     pub fn payload_mut(&mut self) -> &mut [u8] {
@@ -625,6 +646,11 @@ pub struct Repr {
     pub next_header: Protocol,
     pub payload_len: usize,
     pub hop_limit: u8,
+    /// The Differentiated Services Code Point, see RFC 2474.
+    pub dscp: u8,
+    /// The Explicit Congestion Notification bits, see RFC 3168.
+    pub ecn: u8,
+    pub options: Ipv4Options,
 }
 
 impl Repr {
@@ -660,13 +686,15 @@ impl Repr {
             next_header: packet.next_header(),
             payload_len,
             hop_limit: packet.hop_limit(),
+            dscp: packet.dscp(),
+            ecn: packet.ecn(),
+            options: Ipv4Options::new(packet.options())?,
         })
     }
 
     /// Return the length of a header that will be emitted from this high-level representation.
     pub const fn buffer_len(&self) -> usize {
-        // We never emit any options.
-        field::DST_ADDR.end
+        field::DST_ADDR.end + self.options.buffer_len()
     }
 
     /// Emit a high-level representation into an Internet Protocol version 4 packet.
@@ -676,9 +704,9 @@ impl Repr {
         checksum_caps: &ChecksumCapabilities,
     ) {
         packet.set_version(4);
-        packet.set_header_len(field::DST_ADDR.end as u8);
-        packet.set_dscp(0);
-        packet.set_ecn(0);
+        packet.set_header_len(self.buffer_len() as u8);
+        packet.set_dscp(self.dscp);
+        packet.set_ecn(self.ecn);
         let total_len = packet.header_len() as u16 + self.payload_len as u16;
         packet.set_total_len(total_len);
         packet.set_ident(0);
@@ -691,6 +719,12 @@ impl Repr {
         packet.set_src_addr(self.src_addr);
         packet.set_dst_addr(self.dst_addr);
 
+        // Pad the options with End of List options.
+        let options = packet.options_mut();
+        let len = self.options.as_bytes().len();
+        options[..len].copy_from_slice(self.options.as_bytes());
+        options[len..].fill(0);
+
         if checksum_caps.ipv4.tx() {
             packet.fill_checksum();
         } else {
@@ -752,7 +786,11 @@ impl fmt::Display for Repr {
             f,
             "IPv4 src={} dst={} proto={}",
             self.src_addr, self.dst_addr, self.next_header
-        )
+        )?;
+        for opt in self.options.iter().flatten() {
+            write!(f, " ({opt})")?;
+        }
+        Ok(())
     }
 }
 
@@ -900,6 +938,9 @@ pub(crate) mod test {
             next_header: Protocol::Icmp,
             payload_len: 4,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
+            options: Ipv4Options::EMPTY,
         }
     }
 
@@ -941,6 +982,25 @@ pub(crate) mod test {
         assert_eq!(&*packet.into_inner(), &REPR_PACKET_BYTES[..]);
     }
 
+    #[test]
+    fn test_emit_parse_dscp_ecn() {
+        let repr = Repr {
+            dscp: 46,
+            ecn: 0b10,
+            ..packet_repr()
+        };
+        let mut bytes = vec![0xa5; repr.buffer_len() + REPR_PAYLOAD_BYTES.len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &ChecksumCapabilities::default());
+        assert_eq!(packet.dscp(), 46);
+        assert_eq!(packet.ecn(), 0b10);
+        let packet = Packet::new_unchecked(&*packet.into_inner());
+        assert_eq!(
+            Repr::parse(&packet, &ChecksumCapabilities::default()),
+            Ok(repr)
+        );
+    }
+
     #[test]
     fn test_unspecified() {
         assert!(Address::UNSPECIFIED.is_unspecified());
@@ -0,0 +1,733 @@
+use super::{Error, Result};
+
+use core::fmt;
+
+enum_with_unknown! {
+    /// IPv4 Option Type
+    pub enum Type(u8) {
+        /// End of the option list
+        EndOfList         = 0x00,
+        /// No operation, used for alignment
+        NoOperation       = 0x01,
+        /// Record Route
+        RecordRoute       = 0x07,
+        /// Internet Timestamp
+        Timestamp         = 0x44,
+        /// Basic Security
+        Security          = 0x82,
+        /// Loose Source and Record Route
+        LooseSourceRoute  = 0x83,
+        /// Strict Source and Record Route
+        StrictSourceRoute = 0x89,
+        /// Router Alert
+        RouterAlert       = 0x94,
+    }
+}
+
+impl Type {
+    /// Return whether the option is copied into all the fragments of a packet.
+    pub fn is_copied(&self) -> bool {
+        u8::from(*self) & 0x80 != 0
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::EndOfList => write!(f, "End of List"),
+            Type::NoOperation => write!(f, "No Operation"),
+            Type::RecordRoute => write!(f, "Record Route"),
+            Type::Timestamp => write!(f, "Timestamp"),
+            Type::Security => write!(f, "Security"),
+            Type::LooseSourceRoute => write!(f, "Loose Source Route"),
+            Type::StrictSourceRoute => write!(f, "Strict Source Route"),
+            Type::RouterAlert => write!(f, "Router Alert"),
+            Type::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+enum_with_unknown! {
+    /// Contents of an Internet Timestamp option, see [RFC 791 § 3.1].
+    ///
+    /// [RFC 791 § 3.1]: https://www.rfc-editor.org/rfc/rfc791#section-3.1
+    pub enum TimestampFlags(u8) {
+        /// Timestamps only
+        TimestampsOnly = 0,
+        /// Every timestamp is preceded by the address of the host that recorded it
+        WithAddresses  = 1,
+        /// Timestamps recorded by the hosts whose addresses are prespecified
+        Prespecified   = 3,
+    }
+}
+
+/// Maximum length of the options of an IPv4 header.
+pub const MAX_LEN: usize = 40;
+
+/// A read/write wrapper around an IPv4 Option.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ipv4Option<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+// Format of Option
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+- - - - - - - - -
+// |  Option Type  | Option Length |  Option Data
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+- - - - - - - - -
+//
+// The End of List and No Operation options are a single Option Type octet. The Option
+// Length counts all the octets of the option.
+//
+// See https://tools.ietf.org/html/rfc791#section-3.1 for details.
+mod field {
+    #![allow(non_snake_case)]
+
+    use crate::wire::field::*;
+
+    // 8-bit identifier of the type of option.
+    pub const TYPE: usize = 0;
+    // 8-bit unsigned integer. Length of the option, in octets.
+    pub const LENGTH: usize = 1;
+    // Variable-length field. Option-Type-specific data.
+    pub const fn DATA(length: u8) -> Field {
+        2..length as usize
+    }
+}
+
+impl<T: AsRef<[u8]>> Ipv4Option<T> {
+    /// Create a raw octet buffer with an IPv4 Option structure.
+    pub const fn new_unchecked(buffer: T) -> Ipv4Option<T> {
+        Ipv4Option { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Ipv4Option<T>> {
+        let opt = Self::new_unchecked(buffer);
+        opt.check_len()?;
+        Ok(opt)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short, or if the option length is
+    /// shorter than the option type and length fields.
+    ///
+    /// The result of this check is invalidated by calling [set_length].
+    ///
+    /// [set_length]: #method.set_length
+    pub fn check_len(&self) -> Result<()> {
+        let data = self.buffer.as_ref();
+        let len = data.len();
+
+        if data.is_empty() {
+            return Err(Error);
+        }
+
+        if matches!(self.option_type(), Type::EndOfList | Type::NoOperation) {
+            return Ok(());
+        }
+
+        if len <= field::LENGTH {
+            return Err(Error);
+        }
+
+        let length = data[field::LENGTH] as usize;
+        if length <= field::LENGTH || len < length {
+            return Err(Error);
+        }
+
+        Ok(())
+    }
+
+    /// Consume the IPv4 option, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the option type.
+    #[inline]
+    pub fn option_type(&self) -> Type {
+        let data = self.buffer.as_ref();
+        Type::from(data[field::TYPE])
+    }
+
+    /// Return the length of the option, including the type and length fields.
+    ///
+    /// # Panics
+    /// This function panics if this is a single-octet option.
+    #[inline]
+    pub fn length(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::LENGTH]
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Ipv4Option<&'a T> {
+    /// Return the option data.
+    ///
+    /// # Panics
+    /// This function panics if this is a single-octet option.
+    #[inline]
+    pub fn data(&self) -> &'a [u8] {
+        let len = self.length();
+        let data = self.buffer.as_ref();
+        &data[field::DATA(len)]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Ipv4Option<T> {
+    /// Set the option type.
+    #[inline]
+    pub fn set_option_type(&mut self, value: Type) {
+        let data = self.buffer.as_mut();
+        data[field::TYPE] = value.into();
+    }
+
+    /// Set the length of the option, including the type and length fields.
+    ///
+    /// # Panics
+    /// This function panics if this is a single-octet option.
+    #[inline]
+    pub fn set_length(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::LENGTH] = value;
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]> + ?Sized> Ipv4Option<&mut T> {
+    /// Return a mutable pointer to the option data.
+    ///
+    /// # Panics
+    /// This function panics if this is a single-octet option.
+    #[inline]
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        let len = self.length();
+        let data = self.buffer.as_mut();
+        &mut data[field::DATA(len)]
+    }
+}
+
+impl<T: AsRef<[u8]> + ?Sized> fmt::Display for Ipv4Option<&T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Repr::parse(self) {
+            Ok(repr) => write!(f, "{repr}"),
+            Err(err) => {
+                write!(f, "IPv4 Option ({err})")?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A high-level representation of an IPv4 Option.
+///
+/// The route of the source route and Record Route options is a list of IPv4 addresses,
+/// and `pointer` is the offset, from the start of the option and counting from 1, of the
+/// next address to be used or recorded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Repr<'a> {
+    EndOfList,
+    NoOperation,
+    /// Record Route, see [RFC 791 § 3.1](https://www.rfc-editor.org/rfc/rfc791#section-3.1).
+    RecordRoute {
+        pointer: u8,
+        route: &'a [u8],
+    },
+    /// Loose Source and Record Route, see
+    /// [RFC 791 § 3.1](https://www.rfc-editor.org/rfc/rfc791#section-3.1).
+    LooseSourceRoute {
+        pointer: u8,
+        route: &'a [u8],
+    },
+    /// Strict Source and Record Route, see
+    /// [RFC 791 § 3.1](https://www.rfc-editor.org/rfc/rfc791#section-3.1).
+    StrictSourceRoute {
+        pointer: u8,
+        route: &'a [u8],
+    },
+    /// Internet Timestamp, see [RFC 791 § 3.1](https://www.rfc-editor.org/rfc/rfc791#section-3.1).
+    Timestamp {
+        pointer: u8,
+        overflow: u8,
+        flags: TimestampFlags,
+        data: &'a [u8],
+    },
+    /// Basic Security, see [RFC 1108 § 2](https://www.rfc-editor.org/rfc/rfc1108#section-2).
+    Security {
+        classification: u8,
+        protection_authority: &'a [u8],
+    },
+    /// Router Alert, see [RFC 2113 § 2.1](https://www.rfc-editor.org/rfc/rfc2113#section-2.1).
+    /// The value 0 asks routers to examine the packet.
+    RouterAlert(u16),
+    Unknown {
+        type_: Type,
+        data: &'a [u8],
+    },
+}
+
+impl<'a> Repr<'a> {
+    /// Parse an IPv4 Option and return a high-level representation.
+    pub fn parse<T>(opt: &Ipv4Option<&'a T>) -> Result<Repr<'a>>
+    where
+        T: AsRef<[u8]> + ?Sized,
+    {
+        opt.check_len()?;
+        match opt.option_type() {
+            Type::EndOfList => Ok(Repr::EndOfList),
+            Type::NoOperation => Ok(Repr::NoOperation),
+            type_ @ (Type::RecordRoute | Type::LooseSourceRoute | Type::StrictSourceRoute) => {
+                let data = opt.data();
+                if data.is_empty() || data[0] < 4 || (data.len() - 1) % 4 != 0 {
+                    return Err(Error);
+                }
+                let (pointer, route) = (data[0], &data[1..]);
+                Ok(match type_ {
+                    Type::RecordRoute => Repr::RecordRoute { pointer, route },
+                    Type::LooseSourceRoute => Repr::LooseSourceRoute { pointer, route },
+                    _ => Repr::StrictSourceRoute { pointer, route },
+                })
+            }
+            Type::Timestamp => {
+                let data = opt.data();
+                if data.len() < 2 || data[0] < 5 {
+                    return Err(Error);
+                }
+                Ok(Repr::Timestamp {
+                    pointer: data[0],
+                    overflow: data[1] >> 4,
+                    flags: TimestampFlags::from(data[1] & 0x0f),
+                    data: &data[2..],
+                })
+            }
+            Type::Security => {
+                let data = opt.data();
+                if data.is_empty() {
+                    return Err(Error);
+                }
+                Ok(Repr::Security {
+                    classification: data[0],
+                    protection_authority: &data[1..],
+                })
+            }
+            Type::RouterAlert => match opt.data() {
+                [a, b] => Ok(Repr::RouterAlert(u16::from_be_bytes([*a, *b]))),
+                _ => Err(Error),
+            },
+            type_ @ Type::Unknown(_) => Ok(Repr::Unknown {
+                type_,
+                data: opt.data(),
+            }),
+        }
+    }
+
+    /// Return the type of the option.
+    pub const fn option_type(&self) -> Type {
+        match *self {
+            Repr::EndOfList => Type::EndOfList,
+            Repr::NoOperation => Type::NoOperation,
+            Repr::RecordRoute { .. } => Type::RecordRoute,
+            Repr::LooseSourceRoute { .. } => Type::LooseSourceRoute,
+            Repr::StrictSourceRoute { .. } => Type::StrictSourceRoute,
+            Repr::Timestamp { .. } => Type::Timestamp,
+            Repr::Security { .. } => Type::Security,
+            Repr::RouterAlert(_) => Type::RouterAlert,
+            Repr::Unknown { type_, .. } => type_,
+        }
+    }
+
+    /// Return the length of an option that will be emitted from this high-level representation.
+    pub const fn buffer_len(&self) -> usize {
+        match *self {
+            Repr::EndOfList | Repr::NoOperation => 1,
+            Repr::RecordRoute { route, .. }
+            | Repr::LooseSourceRoute { route, .. }
+            | Repr::StrictSourceRoute { route, .. } => 3 + route.len(),
+            Repr::Timestamp { data, .. } => 4 + data.len(),
+            Repr::Security {
+                protection_authority,
+                ..
+            } => 3 + protection_authority.len(),
+            Repr::RouterAlert(_) => 4,
+            Repr::Unknown { data, .. } => 2 + data.len(),
+        }
+    }
+
+    /// Emit a high-level representation into an IPv4 Option.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]> + ?Sized>(&self, opt: &mut Ipv4Option<&'a mut T>) {
+        opt.set_option_type(self.option_type());
+        if self.buffer_len() == 1 {
+            return;
+        }
+        opt.set_length(self.buffer_len() as u8);
+
+        let data = opt.data_mut();
+        match *self {
+            Repr::EndOfList | Repr::NoOperation => unreachable!(),
+            Repr::RecordRoute { pointer, route }
+            | Repr::LooseSourceRoute { pointer, route }
+            | Repr::StrictSourceRoute { pointer, route } => {
+                data[0] = pointer;
+                data[1..].copy_from_slice(route);
+            }
+            Repr::Timestamp {
+                pointer,
+                overflow,
+                flags,
+                data: timestamps,
+            } => {
+                data[0] = pointer;
+                data[1] = (overflow << 4) | (u8::from(flags) & 0x0f);
+                data[2..].copy_from_slice(timestamps);
+            }
+            Repr::Security {
+                classification,
+                protection_authority,
+            } => {
+                data[0] = classification;
+                data[1..].copy_from_slice(protection_authority);
+            }
+            Repr::RouterAlert(value) => data.copy_from_slice(&value.to_be_bytes()),
+            Repr::Unknown { data: value, .. } => data.copy_from_slice(value),
+        }
+    }
+
+    /// Return whether this is a source route option with addresses left to visit.
+    pub fn is_pending_source_route(&self) -> bool {
+        match *self {
+            Repr::LooseSourceRoute { pointer, route }
+            | Repr::StrictSourceRoute { pointer, route } => (pointer as usize) <= route.len() + 3,
+            _ => false,
+        }
+    }
+}
+
+/// An iterator for IPv4 options.
+///
+/// The iteration stops after the End of List option.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ipv4OptionsIterator<'a> {
+    pos: usize,
+    data: &'a [u8],
+    done: bool,
+}
+
+impl<'a> Ipv4OptionsIterator<'a> {
+    /// Create a new `Ipv4OptionsIterator`, used to iterate over the options contained in
+    /// an IPv4 header.
+    pub fn new(data: &'a [u8]) -> Ipv4OptionsIterator<'a> {
+        Ipv4OptionsIterator {
+            pos: 0,
+            data,
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Ipv4OptionsIterator<'a> {
+    type Item = Result<Repr<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() || self.done {
+            return None;
+        }
+
+        let result =
+            Ipv4Option::new_checked(&self.data[self.pos..]).and_then(|opt| Repr::parse(&opt));
+        match result {
+            Ok(repr) => {
+                self.pos += repr.buffer_len();
+                self.done = repr == Repr::EndOfList;
+            }
+            // Do not continue after an error.
+            Err(_) => self.done = true,
+        }
+        Some(result)
+    }
+}
+
+/// The options of an IPv4 header.
+///
+/// The options are stored in their wire format, so that [`Ipv4Repr`] does not borrow the
+/// packet it was parsed from.
+///
+/// [`Ipv4Repr`]: crate::wire::Ipv4Repr
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Options {
+    buffer: [u8; MAX_LEN],
+    len: u8,
+}
+
+impl Options {
+    /// No options.
+    pub const EMPTY: Options = Options {
+        buffer: [0; MAX_LEN],
+        len: 0,
+    };
+
+    /// Create the options from their wire format. The End of List option and the padding
+    /// after it are left out.
+    ///
+    /// Returns `Err(Error)` if the options are too long or malformed.
+    pub fn new(data: &[u8]) -> Result<Options> {
+        if data.len() > MAX_LEN {
+            return Err(Error);
+        }
+        let mut len = 0;
+        for opt in Ipv4OptionsIterator::new(data) {
+            match opt? {
+                Repr::EndOfList => break,
+                opt => len += opt.buffer_len(),
+            }
+        }
+
+        let mut options = Options::EMPTY;
+        options.buffer[..len].copy_from_slice(&data[..len]);
+        options.len = len as u8;
+        Ok(options)
+    }
+
+    /// Append an option.
+    ///
+    /// Returns `Err(Error)` if the option does not fit in an IPv4 header.
+    pub fn push(&mut self, repr: &Repr) -> Result<()> {
+        let len = self.len as usize;
+        if len + repr.buffer_len() > MAX_LEN {
+            return Err(Error);
+        }
+        repr.emit(&mut Ipv4Option::new_unchecked(
+            &mut self.buffer[len..len + repr.buffer_len()],
+        ));
+        self.len += repr.buffer_len() as u8;
+        Ok(())
+    }
+
+    /// Return the options in their wire format, without the padding.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.len as usize]
+    }
+
+    /// Return whether there are no options.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the options.
+    pub fn iter(&self) -> Ipv4OptionsIterator<'_> {
+        Ipv4OptionsIterator::new(self.as_bytes())
+    }
+
+    /// Return the length of the options in an IPv4 header, padded to a multiple of 4 octets.
+    pub const fn buffer_len(&self) -> usize {
+        (self.len as usize + 3) & !3
+    }
+
+    /// Return the value of the Router Alert option, if any.
+    pub fn router_alert(&self) -> Option<u16> {
+        self.iter().find_map(|opt| match opt {
+            Ok(Repr::RouterAlert(value)) => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Return whether there is a source route option with addresses left to visit.
+    pub fn has_pending_source_route(&self) -> bool {
+        self.iter()
+            .any(|opt| opt.map_or(false, |opt| opt.is_pending_source_route()))
+    }
+
+    /// Return the options that are copied into all the fragments of a packet.
+    pub fn copied(&self) -> Options {
+        let mut options = Options::EMPTY;
+        for opt in self.iter().flatten() {
+            if opt.option_type().is_copied() {
+                // NOTE(unwrap): the copied options are a subset of the options.
+                options.push(&opt).unwrap();
+            }
+        }
+        options
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::EMPTY
+    }
+}
+
+impl<'a> fmt::Display for Repr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "IPv4 Option {}", self.option_type())?;
+        match *self {
+            Repr::RecordRoute { pointer, route }
+            | Repr::LooseSourceRoute { pointer, route }
+            | Repr::StrictSourceRoute { pointer, route } => {
+                write!(f, " pointer={pointer} len={}", route.len())
+            }
+            Repr::Timestamp { pointer, flags, .. } => {
+                write!(f, " pointer={pointer} flags={}", u8::from(flags))
+            }
+            Repr::Security { classification, .. } => {
+                write!(f, " classification={classification:#x}")
+            }
+            Repr::RouterAlert(value) => write!(f, " value={value}"),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static RECORD_ROUTE_BYTES: [u8; 11] = [
+        0x07, 0x0b, 0x08, 0xc0, 0xa8, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00,
+    ];
+    static ROUTER_ALERT_BYTES: [u8; 4] = [0x94, 0x04, 0x00, 0x00];
+    static TIMESTAMP_BYTES: [u8; 8] = [0x44, 0x08, 0x05, 0x10, 0x00, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn test_check_len() {
+        // empty buffer
+        assert_eq!(Err(Error), Ipv4Option::new_unchecked(&[][..]).check_len());
+        // single-octet options
+        assert_eq!(Ok(()), Ipv4Option::new_unchecked(&[0x00]).check_len());
+        assert_eq!(Ok(()), Ipv4Option::new_unchecked(&[0x01]).check_len());
+        // missing length
+        assert_eq!(Err(Error), Ipv4Option::new_unchecked(&[0x94]).check_len());
+        // length too short
+        assert_eq!(
+            Err(Error),
+            Ipv4Option::new_unchecked(&[0x94, 0x01]).check_len()
+        );
+        // truncated data
+        assert_eq!(
+            Err(Error),
+            Ipv4Option::new_unchecked(&ROUTER_ALERT_BYTES[..3]).check_len()
+        );
+        assert_eq!(
+            Ok(()),
+            Ipv4Option::new_unchecked(&ROUTER_ALERT_BYTES).check_len()
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let opt = Ipv4Option::new_checked(&RECORD_ROUTE_BYTES[..]).unwrap();
+        assert_eq!(
+            Repr::parse(&opt),
+            Ok(Repr::RecordRoute {
+                pointer: 8,
+                route: &RECORD_ROUTE_BYTES[3..]
+            })
+        );
+
+        let opt = Ipv4Option::new_checked(&ROUTER_ALERT_BYTES[..]).unwrap();
+        assert_eq!(Repr::parse(&opt), Ok(Repr::RouterAlert(0)));
+
+        let opt = Ipv4Option::new_checked(&TIMESTAMP_BYTES[..]).unwrap();
+        assert_eq!(
+            Repr::parse(&opt),
+            Ok(Repr::Timestamp {
+                pointer: 5,
+                overflow: 1,
+                flags: TimestampFlags::TimestampsOnly,
+                data: &TIMESTAMP_BYTES[4..]
+            })
+        );
+
+        // A pointer inside the option header.
+        let bytes = [0x83, 0x07, 0x03, 0xc0, 0xa8, 0x01, 0x01];
+        let opt = Ipv4Option::new_checked(&bytes[..]).unwrap();
+        assert_eq!(Repr::parse(&opt), Err(Error));
+        // A route that is not a list of addresses.
+        let bytes = [0x83, 0x06, 0x04, 0xc0, 0xa8, 0x01];
+        let opt = Ipv4Option::new_checked(&bytes[..]).unwrap();
+        assert_eq!(Repr::parse(&opt), Err(Error));
+    }
+
+    #[test]
+    fn test_emit() {
+        let repr = Repr::RecordRoute {
+            pointer: 8,
+            route: &RECORD_ROUTE_BYTES[3..],
+        };
+        let mut bytes = [0u8; 11];
+        repr.emit(&mut Ipv4Option::new_unchecked(&mut bytes[..]));
+        assert_eq!(bytes, RECORD_ROUTE_BYTES);
+
+        let mut bytes = [0u8; 4];
+        Repr::RouterAlert(0).emit(&mut Ipv4Option::new_unchecked(&mut bytes[..]));
+        assert_eq!(bytes, ROUTER_ALERT_BYTES);
+    }
+
+    #[test]
+    fn test_source_route() {
+        let route = [192, 168, 1, 1, 192, 168, 1, 2];
+        let pending = Repr::LooseSourceRoute {
+            pointer: 8,
+            route: &route,
+        };
+        assert!(pending.is_pending_source_route());
+        let done = Repr::StrictSourceRoute {
+            pointer: 12,
+            route: &route,
+        };
+        assert!(!done.is_pending_source_route());
+        assert!(!Repr::RecordRoute {
+            pointer: 4,
+            route: &route
+        }
+        .is_pending_source_route());
+    }
+
+    #[test]
+    fn test_options() {
+        let mut options = Options::EMPTY;
+        options.push(&Repr::RouterAlert(0)).unwrap();
+        options.push(&Repr::NoOperation).unwrap();
+        assert_eq!(options.as_bytes(), &[0x94, 0x04, 0x00, 0x00, 0x01]);
+        assert_eq!(options.buffer_len(), 8);
+        assert_eq!(options.router_alert(), Some(0));
+        assert!(!options.has_pending_source_route());
+
+        let options = Options::new(&RECORD_ROUTE_BYTES).unwrap();
+        assert_eq!(options.router_alert(), None);
+        // The padding is left out.
+        let mut bytes = [0u8; 12];
+        bytes[..11].copy_from_slice(&RECORD_ROUTE_BYTES);
+        assert_eq!(Options::new(&bytes), Ok(options));
+        // Record Route is only in the first fragment.
+        assert!(options.copied().is_empty());
+
+        let mut options = Options::EMPTY;
+        options
+            .push(&Repr::Unknown {
+                type_: Type::Unknown(0x99),
+                data: &[0; 36],
+            })
+            .unwrap();
+        assert_eq!(options.push(&Repr::RouterAlert(0)), Err(Error));
+
+        // Malformed options.
+        assert_eq!(Options::new(&RECORD_ROUTE_BYTES[..10]), Err(Error));
+        assert_eq!(Options::new(&[0x01; 41]), Err(Error));
+    }
+
+    #[test]
+    fn test_iterator_end_of_list() {
+        let bytes = [0x01, 0x00, 0xff, 0xff];
+        let mut iter = Ipv4OptionsIterator::new(&bytes);
+        assert_eq!(iter.next(), Some(Ok(Repr::NoOperation)));
+        assert_eq!(iter.next(), Some(Ok(Repr::EndOfList)));
+        assert_eq!(iter.next(), None);
+    }
+}
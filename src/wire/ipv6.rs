@@ -87,6 +87,14 @@ impl Address {
         0x02,
     ]);
 
+    /// The link-local [all MLDv2-capable routers multicast address].
+    ///
+    /// [all MLDv2-capable routers multicast address]: https://www.rfc-editor.org/rfc/rfc3810#section-11
+    pub const LINK_LOCAL_ALL_MLDV2_ROUTERS: Address = Address([
+        0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x16,
+    ]);
+
     /// The link-local [all RPL nodes multicast address].
     ///
     /// [all RPL nodes multicast address]: https://www.rfc-editor.org/rfc/rfc6550.html#section-20.19
@@ -807,6 +815,10 @@ pub struct Repr {
     pub payload_len: usize,
     /// The 8-bit hop limit field.
     pub hop_limit: u8,
+    /// The Differentiated Services Code Point of the traffic class, see RFC 2474.
+    pub dscp: u8,
+    /// The Explicit Congestion Notification bits of the traffic class, see RFC 3168.
+    pub ecn: u8,
 }
 
 impl Repr {
@@ -823,6 +835,8 @@ impl Repr {
             next_header: packet.next_header(),
             payload_len: packet.payload_len() as usize,
             hop_limit: packet.hop_limit(),
+            dscp: packet.traffic_class() >> 2,
+            ecn: packet.traffic_class() & 0x03,
         })
     }
 
@@ -837,7 +851,7 @@ impl Repr {
         // Make no assumptions about the original state of the packet buffer.
         // Make sure to set every byte.
         packet.set_version(6);
-        packet.set_traffic_class(0);
+        packet.set_traffic_class((self.dscp << 2) | (self.ecn & 0x03));
         packet.set_flow_label(0);
         packet.set_payload_len(self.payload_len as u16);
         packet.set_hop_limit(self.hop_limit);
@@ -1325,6 +1339,8 @@ pub(crate) mod test {
             next_header: Protocol::Udp,
             payload_len: 12,
             hop_limit: 64,
+            dscp: 0,
+            ecn: 0,
         }
     }
 
@@ -1457,6 +1473,21 @@ pub(crate) mod test {
         assert_eq!(&*packet.into_inner(), &REPR_PACKET_BYTES[..]);
     }
 
+    #[test]
+    fn test_repr_emit_parse_traffic_class() {
+        let repr = Repr {
+            dscp: 46,
+            ecn: 0b01,
+            ..packet_repr()
+        };
+        let mut bytes = vec![0xff; repr.buffer_len() + REPR_PAYLOAD_BYTES.len()];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet);
+        assert_eq!(packet.traffic_class(), 0xb9);
+        let packet = Packet::new_unchecked(&*packet.into_inner());
+        assert_eq!(Repr::parse(&packet), Ok(repr));
+    }
+
     #[test]
     fn test_pretty_print() {
         assert_eq!(
@@ -236,6 +236,13 @@ impl<T: AsRef<[u8]>> AddressRecord<T> {
         let data = self.buffer.as_ref();
         Ipv6Address::from_bytes(&data[field::RECORD_MCAST_ADDR])
     }
+
+    /// Return the length of the record, including its sources and auxiliary data.
+    pub fn record_len(&self) -> usize {
+        field::RECORD_MCAST_ADDR.end
+            + self.num_srcs() as usize * 16
+            + self.aux_data_len() as usize * 4
+    }
 }
 
 impl<'a, T: AsRef<[u8]> + ?Sized> AddressRecord<&'a T> {
@@ -494,6 +501,7 @@ mod test {
         assert_eq!(addr_rcrd.aux_data_len(), 0x00);
         assert_eq!(addr_rcrd.num_srcs(), 0x01);
         assert_eq!(addr_rcrd.mcast_addr(), Ipv6Address::LINK_LOCAL_ALL_NODES);
+        assert_eq!(addr_rcrd.record_len(), 36);
         assert_eq!(
             Ipv6Address::from_bytes(addr_rcrd.payload()),
             Ipv6Address::LINK_LOCAL_ALL_ROUTERS
@@ -53,6 +53,9 @@ let repr = Ipv4Repr {
     next_header: IpProtocol::Tcp,
     payload_len: 10,
     hop_limit:   64,
+    dscp:        0,
+    ecn:         0,
+    options:     Ipv4Options::EMPTY,
 };
 let mut buffer = vec![0; repr.buffer_len() + repr.payload_len];
 { // emission
@@ -87,6 +90,8 @@ pub(crate) mod dns;
 mod ethernet;
 #[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
 mod icmp;
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+mod icmpext;
 #[cfg(feature = "proto-ipv4")]
 mod icmpv4;
 #[cfg(feature = "proto-ipv6")]
@@ -98,6 +103,8 @@ mod igmp;
 pub(crate) mod ip;
 #[cfg(feature = "proto-ipv4")]
 pub(crate) mod ipv4;
+#[cfg(feature = "proto-ipv4")]
+mod ipv4option;
 #[cfg(feature = "proto-ipv6")]
 pub(crate) mod ipv6;
 #[cfg(feature = "proto-ipv6")]
@@ -128,6 +135,7 @@ mod rpl;
 mod sixlowpan;
 mod tcp;
 mod udp;
+mod udplite;
 
 #[cfg(feature = "proto-ipsec-ah")]
 mod ipsec_ah;
@@ -190,6 +198,12 @@ pub use self::ipv4::{
     Repr as Ipv4Repr, HEADER_LEN as IPV4_HEADER_LEN, MIN_MTU as IPV4_MIN_MTU,
 };
 
+#[cfg(feature = "proto-ipv4")]
+pub use self::ipv4option::{
+    Ipv4Option, Ipv4OptionsIterator, Options as Ipv4Options, Repr as Ipv4OptionRepr,
+    TimestampFlags as Ipv4TimestampFlags, Type as Ipv4OptionType, MAX_LEN as IPV4_OPTIONS_MAX_LEN,
+};
+
 #[cfg(feature = "proto-ipv6")]
 pub(crate) use self::ipv6::MulticastScope as Ipv6MulticastScope;
 #[cfg(feature = "proto-ipv6")]
@@ -208,7 +222,9 @@ pub use self::ipv6option::{
 pub use self::ipv6ext_header::{Header as Ipv6ExtHeader, Repr as Ipv6ExtHeaderRepr};
 
 #[cfg(feature = "proto-ipv6")]
-pub use self::ipv6fragment::{Header as Ipv6FragmentHeader, Repr as Ipv6FragmentRepr};
+pub use self::ipv6fragment::{
+    Header as Ipv6FragmentHeader, Key as Ipv6FragKey, Repr as Ipv6FragmentRepr,
+};
 
 #[cfg(feature = "proto-ipv6")]
 pub use self::ipv6hbh::{Header as Ipv6HopByHopHeader, Repr as Ipv6HopByHopRepr};
@@ -226,7 +242,10 @@ pub use self::icmpv4::{
 };
 
 #[cfg(feature = "proto-igmp")]
-pub use self::igmp::{IgmpVersion, Packet as IgmpPacket, Repr as IgmpRepr};
+pub use self::igmp::{
+    GroupRecord as IgmpGroupRecord, IgmpVersion, Packet as IgmpPacket,
+    RecordType as IgmpRecordType, Repr as IgmpRepr,
+};
 
 #[cfg(feature = "proto-ipv6")]
 pub use self::icmpv6::{
@@ -237,6 +256,15 @@ pub use self::icmpv6::{
 #[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
 pub use self::icmp::Repr as IcmpRepr;
 
+#[cfg(any(feature = "proto-ipv4", feature = "proto-ipv6"))]
+pub use self::icmpext::{
+    ClassNum as IcmpExtensionClass, ExtensionObject as IcmpExtensionObject,
+    ExtensionObjectsIterator as IcmpExtensionObjectsIterator, Extensions as IcmpExtensions,
+    InterfaceInformation as IcmpInterfaceInformation, InterfaceRole as IcmpInterfaceRole,
+    MplsLabel, MplsLabelStack, ObjectRepr as IcmpExtensionObjectRepr,
+    MIN_ORIGINAL_DATAGRAM_LEN as ICMP_EXTENSIONS_MIN_ORIGINAL_DATAGRAM_LEN,
+};
+
 #[cfg(all(
     feature = "proto-ipv6",
     any(feature = "medium-ethernet", feature = "medium-ieee802154")
@@ -256,10 +284,16 @@ pub use self::ndiscoption::{
 };
 
 #[cfg(feature = "proto-ipv6")]
-pub use self::mld::{AddressRecord as MldAddressRecord, Repr as MldRepr};
+pub use self::mld::{
+    AddressRecord as MldAddressRecord, RecordType as MldRecordType, Repr as MldRepr,
+};
 
 pub use self::udp::{Packet as UdpPacket, Repr as UdpRepr, HEADER_LEN as UDP_HEADER_LEN};
 
+pub use self::udplite::{
+    Packet as UdpLitePacket, Repr as UdpLiteRepr, HEADER_LEN as UDPLITE_HEADER_LEN,
+};
+
 pub use self::tcp::{
     Control as TcpControl, Packet as TcpPacket, Repr as TcpRepr, SeqNumber as TcpSeqNumber,
     TcpOption, HEADER_LEN as TCP_HEADER_LEN,